@@ -0,0 +1,19 @@
+use std::path::Path;
+use std::process::Command;
+
+// git revision of the build for the provenance of the outputs, with -dirty for uncommitted changes of tracked files
+// and empty outside of a git checkout
+fn main() {
+    let git = |args: &[&str]| Command::new("git").args(args).output().ok().filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    let hash = match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(hash) => if git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty()) { format!("{}-dirty", hash) } else { hash },
+        None => String::new(),
+    };
+    println!("cargo:rustc-env=MCLJ_GIT_HASH={}", hash);
+    // commits and checkouts change the index, edits the sources
+    println!("cargo:rerun-if-changed=src");
+    for file in [".git/HEAD", ".git/index"] {
+        if Path::new(file).exists() { println!("cargo:rerun-if-changed={}", file); }
+    }
+}
@@ -0,0 +1,56 @@
+/* C interface of the mclj Lennard Jones Monte Carlo library, see src/ffi.rs.
+ * Link against libmclj (cargo build --release builds target/release/libmclj.so). */
+#ifndef MCLJ_H
+#define MCLJ_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* run parameters, initialize with mclj_params_default() */
+typedef struct MCLJParams {
+    size_t num_particles;
+    double density;
+    double temperature;
+    double cutoff;
+    double displacement;
+    double vacuum_slab;
+    uint64_t recompute_interval;
+    int sweeps;    /* one step is num_particles attempted moves */
+    int tailcorr;
+    int shift;
+} MCLJParams;
+
+/* opaque handle of a system */
+typedef struct MCLJSystem MCLJSystem;
+
+MCLJParams mclj_params_default(void);
+
+/* returns NULL for invalid parameters */
+MCLJSystem *mclj_system_new(const MCLJParams *params);
+void mclj_system_free(MCLJSystem *system);
+
+/* functions returning int give 0 on success and -1 on invalid arguments */
+int mclj_system_step(MCLJSystem *system, uint64_t steps);
+int mclj_system_set_temperature(MCLJSystem *system, double temperature);
+int mclj_system_set_displacement(MCLJSystem *system, double displacement);
+
+/* NaN (or 0 particles) for a NULL system */
+double mclj_system_energy(const MCLJSystem *system);
+double mclj_system_virial(const MCLJSystem *system);
+double mclj_system_pressure(const MCLJSystem *system);
+double mclj_system_acceptance(const MCLJSystem *system);
+size_t mclj_system_num_particles(const MCLJSystem *system);
+
+/* box_size needs room for 3 doubles, positions for 3 * num_particles (x0 y0 z0 x1 ...) */
+int mclj_system_box(const MCLJSystem *system, double *box_size);
+int mclj_system_positions(const MCLJSystem *system, double *positions);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1,11 +1,11 @@
-mod trajectory;
-use trajectory::*;
-mod energy;
-use energy::*;
+extern crate mclj;
+use mclj::trajectory::*;
+use mclj::error::exit_with;
+use mclj::energy::*;
 use std::env;
 
-const LJ_EPS : f64 = 1.0;
-const LJ_SIG : f64 = 1.0;
+use mclj::{LJ_EPS, LJ_SIG};
+use mclj::Coordinate;
 
 const AVG_OUTPUT_INTERVAL : usize = 10;
 const SLAB_NUM : usize = 200;
@@ -25,11 +25,11 @@ fn main() {
     }
 
     // open file and skip to requested position
-    let mut trj_reader = TrjReader::new(&filename);
-    if skip > 0 { trj_reader.skip(skip) };
+    let mut trj_reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { trj_reader.skip(skip).unwrap_or_else(exit_with) };
 
     // read first trajectory and system params
-    let mut frame = trj_reader.next_frame();
+    let mut frame = trj_reader.next_frame().unwrap_or_else(exit_with);
     let volume = frame.box_x * frame.box_y * frame.box_z;
     let density = frame.num_particles as f64 / volume;
     let num_particles = frame.num_particles;
@@ -55,15 +55,15 @@ fn main() {
         frame_count += 1;
 
         for i in 0..num_particles {
-            let slab_i = get_slab_number_for_position(frame.rz[i], slab_height);
+            let slab_i = get_slab_number_for_position(frame.rz[i].to_f64(), slab_height);
             slab_particles_sum[slab_i-1] += 1;
             for j in i+1..num_particles {
                 // distance
-                let dist_sqrt = get_particle_distance_squared(frame.rx[i], frame.ry[i], frame.rz[i], frame.rx[j], frame.ry[j], frame.rz[j], frame.box_x, frame.box_y, frame.box_z, box_half_x, box_half_y, box_half_z);
+                let dist_sqrt = get_particle_distance_squared(frame.rx[i].to_f64(), frame.ry[i].to_f64(), frame.rz[i].to_f64(), frame.rx[j].to_f64(), frame.ry[j].to_f64(), frame.rz[j].to_f64(), frame.box_x, frame.box_y, frame.box_z, box_half_x, box_half_y, box_half_z);
                 let dist = dist_sqrt.sqrt();
-                let dx = get_distance_with_pbc(frame.rx[i], frame.rx[j], frame.box_x, box_half_x);
-                let dy = get_distance_with_pbc(frame.ry[i], frame.ry[j], frame.box_y, box_half_y);
-                let dz = get_distance_with_pbc(frame.rz[i], frame.rz[j], frame.box_z, box_half_z);
+                let dx = get_distance_with_pbc(frame.rx[i].to_f64(), frame.rx[j].to_f64(), frame.box_x, box_half_x);
+                let dy = get_distance_with_pbc(frame.ry[i].to_f64(), frame.ry[j].to_f64(), frame.box_y, box_half_y);
+                let dz = get_distance_with_pbc(frame.rz[i].to_f64(), frame.rz[j].to_f64(), frame.box_z, box_half_z);
 
                 // calculate virial tensor
                 let virial = eval_virial(dist, LJ_EPS, LJ_SIG);
@@ -71,9 +71,9 @@ fn main() {
                 let virial_z = (dz * dz) / dist * virial;
 
                 // calculate slab distribution
-                let slab_j = get_slab_number_for_position(frame.rz[j], slab_height);
-                let first_slab_index = get_first_slab_for_trace(frame.rz[i], frame.rz[j], slab_height) - 1;
-                let last_slab_index = get_last_slab_for_trace(frame.rz[i], frame.rz[j], slab_height) - 1 ;
+                let slab_j = get_slab_number_for_position(frame.rz[j].to_f64(), slab_height);
+                let first_slab_index = get_first_slab_for_trace(frame.rz[i].to_f64(), frame.rz[j].to_f64(), slab_height) - 1;
+                let last_slab_index = get_last_slab_for_trace(frame.rz[i].to_f64(), frame.rz[j].to_f64(), slab_height) - 1 ;
                 let num_slabs = last_slab_index - first_slab_index + 1;
                 // println!("{} {} {}", first_slab_index, last_slab_index, num_slabs);
                 let virial_xy_partial = virial_xy / num_slabs as f64;
@@ -121,7 +121,11 @@ fn main() {
         // }
 
         // read next frame
-        if !trj_reader.update_with_next(&mut frame) { break }
+        match trj_reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
     }
 
 }
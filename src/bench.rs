@@ -0,0 +1,104 @@
+extern crate rand;
+extern crate mclj;
+extern crate argparse;
+use std::hint::black_box;
+use std::time::Instant;
+use rand::{Rng, SeedableRng, StdRng};
+use argparse::{ArgumentParser, Store};
+use mclj::energy::{get_particle_energy, get_particle_energy_only};
+use mclj::system::*;
+use mclj::Step;
+
+/// Standard workloads: name, particles and density
+const WORKLOADS : [(&str, usize, f64); 6] = [
+    ("dense", 500, 0.8),
+    ("dilute", 500, 0.05),
+    ("dense", 5000, 0.8),
+    ("dilute", 5000, 0.05),
+    ("dense", 50000, 0.8),
+    ("dilute", 50000, 0.05),
+];
+
+const BENCH_SEED : usize = 42;
+const BENCH_CUTOFF : f64 = 3.0;
+
+// untimed sweeps from the random start before the measurements, at the fixed displacement of all measurements
+const WARMUP_SWEEPS : Step = 5;
+
+// moves (energy evaluations) between checks of the elapsed time
+const BATCH : usize = 100;
+
+/// Rate of the repeated calls of work within at least the given seconds, returns calls per second
+fn measure<F: FnMut()>(seconds: f64, mut work: F) -> f64 {
+    let start = Instant::now();
+    let mut calls = 0;
+    loop {
+        for _ in 0..BATCH {
+            work();
+        }
+        calls += BATCH;
+        let elapsed = start.elapsed().as_secs_f64();
+        if elapsed >= seconds {
+            return calls as f64 / elapsed;
+        }
+    }
+}
+
+#[test]
+fn test_measure() {
+    let mut calls = 0;
+    let rate = measure(0.01, || calls += 1);
+    assert!(calls >= BATCH && calls % BATCH == 0, "{}", calls);
+    assert!(rate > 0.0);
+}
+
+fn main() {
+    let mut seconds : f64 = 2.0;
+    let mut max_particles : usize = 50000;
+    let mut warmup : Step = WARMUP_SWEEPS;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Measures the move and energy evaluation throughput of fixed dense and dilute workloads.");
+        ap.refer(&mut seconds)
+            .add_option(&["-t", "--time"], Store, "Seconds per measurement");
+        ap.refer(&mut max_particles)
+            .add_option(&["-p", "--max-particles"], Store, "Skip workloads with more particles");
+        ap.refer(&mut warmup)
+            .add_option(&["-w", "--warmup"], Store, "Untimed sweeps from the random start before the measurements");
+        ap.parse_args_or_exit();
+    }
+
+    println!("# after {} untimed warm-up sweeps of every workload", warmup);
+    println!("{:<8} {:>8} {:>8} {:>14} {:>12} {:>16} {:>16} {:>10}", "workload", "N", "density", "moves/s", "acceptance", "pair evals/s", "energy only/s", "setup s");
+    for &(name, num_particles, density) in WORKLOADS.iter().filter(|w| w.1 <= max_particles) {
+        let params = Params { num_particles: num_particles, density: density, cutoff: BENCH_CUTOFF, sweeps: true, scale: false, ..Params::default() };
+        let mut rng : StdRng = SeedableRng::from_seed(&[BENCH_SEED][..]);
+        let start = Instant::now();
+        let mut system = System::new(params, &mut rng);
+        let setup = start.elapsed().as_secs_f64();
+
+        // the measurements start from the configuration relaxed from the overlaps of the random start, the
+        // displacement stays fixed
+        system.equilibrate(warmup, &mut rng);
+        let mut accepted = 0;
+        let mut moves = 0;
+        let moves_per_second = measure(seconds, || {
+            if system.trial_move(&mut rng) { accepted += 1; }
+            moves += 1;
+        });
+
+        // particle energies alone, each of them evaluates N - 1 pairs
+        let evaluations_per_second = measure(seconds, || {
+            let i = rng.gen_range(0, num_particles);
+            black_box(get_particle_energy(&system.rx, &system.ry, &system.rz, i, num_particles, system.l_x, system.l_y, system.l_z, system.cutoff_squared, system.e_shift, system.f_shift, system.switch_squared));
+        });
+        // and without the virial (the trial moves of --lazy-virial)
+        let energy_only_per_second = measure(seconds, || {
+            let i = rng.gen_range(0, num_particles);
+            black_box(get_particle_energy_only(&system.rx, &system.ry, &system.rz, i, num_particles, system.l_x, system.l_y, system.l_z, system.cutoff_squared, system.e_shift, system.f_shift, system.switch_squared));
+        });
+
+        println!("{:<8} {:>8} {:>8} {:>14.0} {:>11.1}% {:>16.3e} {:>16.3e} {:>10.3}", name, num_particles, density, moves_per_second,
+                 accepted as f64 / moves as f64 * 100.0, evaluations_per_second * (num_particles - 1) as f64, energy_only_per_second * (num_particles - 1) as f64, setup);
+    }
+}
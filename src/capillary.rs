@@ -0,0 +1,80 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store};
+use mclj::error::exit_with;
+use mclj::interface::{fit_capillary_tension, get_height_spectrum, IntrinsicSurface};
+use mclj::trajectory::*;
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut skip : usize = 0;
+    let mut probe_radius : f64 = 1.0;
+    let mut grid : usize = 16;
+    let mut q_max : f64 = 1.0;
+    let mut temperature : f64 = 0.0;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Capillary wave spectrum of the intrinsic surfaces of a slab normal to z and the surface tension of the fit of kT / (A gamma q²), independent of the pressure tensor route.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Trajectory");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first frames, e.g. the equilibration");
+        ap.refer(&mut probe_radius)
+            .add_option(&["--probe"], Store, "Radius of the probe sphere of the surface");
+        ap.refer(&mut grid)
+            .add_option(&["--grid"], Store, "Height map lines per box length in x and y");
+        ap.refer(&mut q_max)
+            .add_option(&["--qmax"], Store, "Largest wave number of the fit, larger ones see the structure of the liquid");
+        ap.refer(&mut temperature)
+            .add_option(&["-t", "--temperature"], Store, "Temperature, by default the one of the frames");
+        ap.parse_args_or_exit();
+    }
+    if grid < 2 || probe_radius.is_nan() || probe_radius <= 0.0 || q_max.is_nan() || q_max <= 0.0 {
+        eprintln!("Error: the grid needs at least 2 lines, probe and qmax have to be positive.");
+        std::process::exit(1);
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { reader.skip(skip).unwrap_or_else(exit_with); }
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+    if temperature == 0.0 { temperature = frame.temperature; }
+    if temperature.is_nan() || temperature <= 0.0 {
+        eprintln!("Error: the frames have no temperature, use --temperature.");
+        std::process::exit(1);
+    }
+    let (l_x, l_y) = (frame.box_x, frame.box_y);
+
+    // spectra of both surfaces of every frame
+    let mut spectrum : Vec<(f64, f64)> = vec![];
+    let mut surfaces = 0;
+    loop {
+        let surface = IntrinsicSurface::new(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, frame.box_x, frame.box_y, frame.box_z, probe_radius, grid);
+        for heights in [&surface.top_heights, &surface.bottom_heights].iter() {
+            let modes = get_height_spectrum(heights, grid, l_x, l_y);
+            if spectrum.is_empty() { spectrum = modes.iter().map(|m| (m.0, 0.0)).collect(); }
+            for (sum, mode) in spectrum.iter_mut().zip(modes) { sum.1 += mode.1; }
+            surfaces += 1;
+        }
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+    for mode in spectrum.iter_mut() { mode.1 /= surfaces as f64; }
+
+    let area = l_x * l_y;
+    let tension = fit_capillary_tension(&spectrum, temperature, area, q_max);
+    println!("# {} surfaces, fitted surface tension {:.5} (modes up to q = {})", surfaces, tension, q_max);
+
+    // modes of the same |q| averaged
+    spectrum.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    println!("# q    <|h_q|²>    kT / (A gamma q²)");
+    let mut k = 0;
+    while k < spectrum.len() {
+        let q = spectrum[k].0;
+        let same : Vec<f64> = spectrum[k..].iter().take_while(|m| (m.0 - q).abs() < 1e-9).map(|m| m.1).collect();
+        println!("{:.5}\t{:.6e}\t{:.6e}", q, same.iter().sum::<f64>() / same.len() as f64, temperature / (area * tension * q * q));
+        k += same.len();
+    }
+}
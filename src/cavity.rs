@@ -0,0 +1,64 @@
+extern crate mclj;
+extern crate argparse;
+extern crate rand;
+use argparse::{ArgumentParser, Store};
+use rand::Rng;
+use mclj::error::exit_with;
+use mclj::observables::{get_cavity_probabilities, get_nearest_distance};
+use mclj::statistics::{get_mean, get_standard_error};
+use mclj::trajectory::*;
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut skip : usize = 0;
+    let mut insertions : usize = 10000;
+    let mut r_max : f64 = 1.5;
+    let mut bins : usize = 30;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Probability to insert a hard test sphere of radius r into the frames of a trajectory (cavity statistics) and the reversible work -T ln P0(r) to open the cavity.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Trajectory");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first frames, e.g. the equilibration");
+        ap.refer(&mut insertions)
+            .add_option(&["-n", "--insertions"], Store, "Random insertion points per frame");
+        ap.refer(&mut r_max)
+            .add_option(&["--rmax"], Store, "Largest test sphere radius");
+        ap.refer(&mut bins)
+            .add_option(&["--bins"], Store, "Number of radii up to rmax");
+        ap.parse_args_or_exit();
+    }
+    if insertions == 0 || bins == 0 || r_max.is_nan() || r_max <= 0.0 {
+        eprintln!("Error: insertions, bins and rmax have to be positive.");
+        std::process::exit(1);
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { reader.skip(skip).unwrap_or_else(exit_with); }
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+    let mut rng = rand::thread_rng();
+
+    // probabilities of every frame, their scatter gives the errors
+    let mut frames : Vec<Vec<f64>> = vec![];
+    loop {
+        let distances : Vec<f64> = (0..insertions).map(|_| {
+            let (x, y, z) = (frame.box_x * rng.gen::<f64>(), frame.box_y * rng.gen::<f64>(), frame.box_z * rng.gen::<f64>());
+            get_nearest_distance(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, x, y, z, frame.box_x, frame.box_y, frame.box_z)
+        }).collect();
+        frames.push(get_cavity_probabilities(&distances, r_max, bins));
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+
+    println!("# {} frames with {} insertions each, T = {}", frames.len(), insertions, frame.temperature);
+    println!("# r    P0(r)    error    -T ln P0(r)");
+    for k in 0..bins {
+        let values : Vec<f64> = frames.iter().map(|p| p[k]).collect();
+        let p0 = get_mean(&values);
+        println!("{:.4}\t{:.6e}\t{:.3e}\t{}", (k + 1) as f64 * r_max / bins as f64, p0, get_standard_error(&values), -frame.temperature * p0.ln());
+    }
+}
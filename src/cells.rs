@@ -0,0 +1,148 @@
+//! Cell lists: the particles binned into a periodic grid of cells at least as wide as a search radius
+
+use energy::get_particle_distance_squared;
+use {Coordinate, Real};
+
+/// Particles of every cell of a grid over the box, so the particles within radius of a point are in its cell and the
+/// 26 cells around it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CellList {
+    pub radius: f64,
+    dims: [usize; 3],
+    widths: [f64; 3],
+    // shift of the grid against the box, a position p is binned as p + offset (periodic)
+    #[serde(default)]
+    offset: [f64; 3],
+    cells: Vec<Vec<usize>>,
+    cell_of: Vec<usize>,
+}
+
+impl CellList {
+    pub fn new(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, radius: f64) -> CellList {
+        let dims = [((l_x / radius) as usize).max(1), ((l_y / radius) as usize).max(1), ((l_z / radius) as usize).max(1)];
+        return CellList::with_grid(rx, ry, rz, num_particles, l_x, l_y, l_z, radius, dims, [0.0; 3]);
+    }
+
+    /// Cell list of a grid of dims cells shifted by offset (between 0 and a cell width), the cells have to be at least
+    /// as wide as the radius
+    pub fn with_grid(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, radius: f64, dims: [usize; 3], offset: [f64; 3]) -> CellList {
+        let mut cells = CellList { radius: radius, dims: dims, widths: [l_x / dims[0] as f64, l_y / dims[1] as f64, l_z / dims[2] as f64], offset: offset,
+                                   cells: vec![vec![]; dims[0] * dims[1] * dims[2]], cell_of: vec![0; num_particles] };
+        for i in 0..num_particles {
+            let cell = cells.get_cell(rx[i].to_f64(), ry[i].to_f64(), rz[i].to_f64());
+            cells.cells[cell].push(i);
+            cells.cell_of[i] = cell;
+        }
+        return cells;
+    }
+
+    /// Cell coordinates of a position in the box
+    pub fn get_coordinates(&self, x: f64, y: f64, z: f64) -> [usize; 3] {
+        let position = [x, y, z];
+        let mut coordinates = [0; 3];
+        for k in 0..3 {
+            let length = self.widths[k] * self.dims[k] as f64;
+            let p = position[k] + self.offset[k];
+            let p = if p >= length { p - length } else { p };
+            coordinates[k] = ((p / self.widths[k]).max(0.0) as usize).min(self.dims[k] - 1);
+        }
+        return coordinates;
+    }
+
+    /// Index of the cell of cell coordinates
+    pub fn get_index(&self, c: [usize; 3]) -> usize {
+        return (c[0] * self.dims[1] + c[1]) * self.dims[2] + c[2];
+    }
+
+    fn get_cell(&self, x: f64, y: f64, z: f64) -> usize {
+        return self.get_index(self.get_coordinates(x, y, z));
+    }
+
+    /// Number of cells along x, y and z
+    pub fn dims(&self) -> [usize; 3] {
+        return self.dims;
+    }
+
+    /// Particles of the cell of index cell
+    pub fn particles(&self, cell: usize) -> &[usize] {
+        return &self.cells[cell];
+    }
+
+    /// Moves particle index to the cell of its new position
+    pub fn update(&mut self, index: usize, x: f64, y: f64, z: f64) {
+        let cell = self.get_cell(x, y, z);
+        let old = self.cell_of[index];
+        if cell == old { return; }
+        self.cells[old].retain(|&i| i != index);
+        self.cells[cell].push(index);
+        self.cell_of[index] = cell;
+    }
+
+    // the cell of x y z and the cells around it, each once also in grids of less than three cells
+    fn get_cells_around(&self, x: f64, y: f64, z: f64) -> Vec<usize> {
+        return self.get_cells_around_cell(self.get_coordinates(x, y, z));
+    }
+
+    /// The cell of cell coordinates c and the cells around it, each once also in grids of less than three cells
+    pub fn get_cells_around_cell(&self, c: [usize; 3]) -> Vec<usize> {
+        let around = |k: usize| -> Vec<usize> { if self.dims[k] < 3 { (0..self.dims[k]).collect() } else { vec![(c[k] + self.dims[k] - 1) % self.dims[k], c[k], (c[k] + 1) % self.dims[k]] } };
+        let mut cells = Vec::with_capacity(27);
+        for &a in &around(0) {
+            for &b in &around(1) {
+                for &d in &around(2) {
+                    cells.push((a * self.dims[1] + b) * self.dims[2] + d);
+                }
+            }
+        }
+        return cells;
+    }
+
+    /// Particles in the cells around x y z, a superset of the particles within the radius
+    pub fn get_candidates(&self, x: f64, y: f64, z: f64) -> Vec<usize> {
+        return self.get_cells_around(x, y, z).iter().flat_map(|&cell| self.cells[cell].iter().cloned()).collect();
+    }
+
+    /// True if a particle other than index is closer than the radius to x y z
+    pub fn has_overlap(&self, rx: &[Real], ry: &[Real], rz: &[Real], index: usize, x: f64, y: f64, z: f64, l_x: f64, l_y: f64, l_z: f64) -> bool {
+        let radius_squared = self.radius * self.radius;
+        for cell in self.get_cells_around(x, y, z) {
+            for &j in &self.cells[cell] {
+                if j != index && get_particle_distance_squared(rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(), x, y, z, l_x, l_y, l_z, l_x / 2.0, l_y / 2.0, l_z / 2.0) < radius_squared {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+}
+
+#[test]
+fn test_cell_list() {
+    let (mut rx, ry, rz) = (vec![0.2, 5.0, 9.9], vec![5.0, 5.0, 5.0], vec![5.0, 5.0, 5.0]);
+    let mut cells = CellList::new(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0, 0.8);
+    // across the periodic boundary
+    assert!(cells.has_overlap(&rx, &ry, &rz, 0, 0.2, 5.0, 5.0, 10.0, 10.0, 10.0));
+    assert!(!cells.has_overlap(&rx, &ry, &rz, 1, 5.0, 5.0, 5.0, 10.0, 10.0, 10.0));
+    assert!(cells.has_overlap(&rx, &ry, &rz, 1, 0.5, 5.0, 5.0, 10.0, 10.0, 10.0));
+    // particle 0 moved next to particle 1
+    rx[0] = 5.5;
+    cells.update(0, 5.5, 5.0, 5.0);
+    assert!(cells.has_overlap(&rx, &ry, &rz, 1, 5.0, 5.0, 5.0, 10.0, 10.0, 10.0));
+    assert!(!cells.has_overlap(&rx, &ry, &rz, 2, 9.9, 5.0, 5.0, 10.0, 10.0, 10.0));
+
+    // two cells along x, the neighbor across the boundary is in the other one
+    let (rx, ry, rz) = (vec![0.1, 1.7], vec![5.0, 5.0], vec![5.0, 5.0]);
+    let cells = CellList::new(&rx, &ry, &rz, 2, 1.8, 10.0, 10.0, 0.8);
+    assert!(cells.has_overlap(&rx, &ry, &rz, 0, 0.1, 5.0, 5.0, 1.8, 10.0, 10.0));
+    let mut candidates = cells.get_candidates(0.1, 5.0, 5.0);
+    candidates.sort();
+    assert_eq!(vec![0, 1], candidates);
+
+    // a grid shifted by half a cell bins across the periodic boundary
+    let (rx, ry, rz) = (vec![0.1, 9.6, 4.0], vec![5.0, 5.0, 5.0], vec![5.0, 5.0, 5.0]);
+    let cells = CellList::with_grid(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0, 2.5, [4, 4, 4], [1.25, 0.0, 0.0]);
+    assert_eq!(cells.get_coordinates(0.1, 5.0, 5.0), cells.get_coordinates(9.6, 5.0, 5.0));
+    assert_eq!([0, 2, 2], cells.get_coordinates(9.6, 5.0, 5.0));
+    assert_eq!([2, 2, 2], cells.get_coordinates(4.0, 5.0, 5.0));
+    assert_eq!(&[0, 1], cells.particles(cells.get_index([0, 2, 2])));
+}
@@ -0,0 +1,189 @@
+//! Checkerboard domain decomposition of the trial moves: the cells of a randomly shifted grid at least as wide as the
+//! cutoff get eight colors by the parities of their coordinates. Particles only move within their cell, so two cells of
+//! a color are always a full cell (at least the cutoff) apart and their moves are independent. A sweep moves the colors
+//! one after the other in random order, the cells of each color in parallel threads.
+
+use rand::{Rng, SeedableRng, StdRng};
+use std::thread;
+use cells::CellList;
+use energy::{eval_truncated_pair_energy, get_particle_distance_squared};
+use moves::Proposal;
+use {Coordinate, Real, Step};
+
+/// Colors of the cells, by the parities of their x, y and z coordinates
+pub const COLORS : usize = 8;
+
+/// Cells along a box length: the even number of the widest cells of at least the cutoff, two cells of a color are
+/// then never neighbors across the periodic boundary. 0 if the box is shorter than two cutoffs.
+pub fn get_cell_count(length: f64, cutoff: f64) -> usize {
+    return (length / cutoff) as usize / 2 * 2;
+}
+
+#[test]
+fn test_get_cell_count() {
+    assert_eq!(4, get_cell_count(10.0, 2.5));
+    assert_eq!(2, get_cell_count(7.4, 2.5));
+    assert_eq!(0, get_cell_count(4.9, 2.5));
+}
+
+/// Pair potential and box of the moves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PairSystem {
+    pub l_x: f64,
+    pub l_y: f64,
+    pub l_z: f64,
+    pub cutoff_squared: f64,
+    pub e_shift: f64,
+    pub f_shift: f64,
+    pub switch_squared: f64,
+    pub beta: f64,
+    pub displacement: f64,
+}
+
+/// Result of the moves of the cells of a color
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorMoves {
+    /// Moved particles and their new positions
+    pub positions: Vec<(usize, Real, Real, Real)>,
+    pub d_energy: f64,
+    pub d_virial: f64,
+    pub attempts: Step,
+    pub accepted: Step,
+    /// Sum of the magnitudes of the particle energies and virials of the accepted moves (see Counters::energy_turnover)
+    pub turnover: f64,
+}
+
+impl ColorMoves {
+    fn add(&mut self, other: ColorMoves) {
+        self.positions.extend(other.positions);
+        self.d_energy += other.d_energy;
+        self.d_virial += other.d_virial;
+        self.attempts += other.attempts;
+        self.accepted += other.accepted;
+        self.turnover += other.turnover;
+    }
+}
+
+/// Grid of a sweep, shifted by a random offset so the particles get across the cell boundaries over the sweeps
+pub fn get_sweep_grid<R: Rng>(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, pair: &PairSystem, rng: &mut R) -> CellList {
+    let cutoff = pair.cutoff_squared.sqrt();
+    let dims = [get_cell_count(pair.l_x, cutoff), get_cell_count(pair.l_y, cutoff), get_cell_count(pair.l_z, cutoff)];
+    let offset = [rng.gen::<f64>() * pair.l_x / dims[0] as f64, rng.gen::<f64>() * pair.l_y / dims[1] as f64, rng.gen::<f64>() * pair.l_z / dims[2] as f64];
+    return CellList::with_grid(rx, ry, rz, num_particles, pair.l_x, pair.l_y, pair.l_z, cutoff, dims, offset);
+}
+
+/// Trial moves of the particles of the cells of color on threads threads, as many moves per cell as it has particles.
+/// The random numbers of a cell come from its own generator seeded from rng, so the moves do not depend on the number
+/// of threads. The positions stay unchanged, the result has the new ones.
+pub fn move_color<R: Rng>(rx: &[Real], ry: &[Real], rz: &[Real], grid: &CellList, color: usize, pair: &PairSystem, proposal: &Proposal, threads: usize, rng: &mut R) -> ColorMoves {
+    let dims = grid.dims();
+    let mut cells = vec![];
+    for a in 0..dims[0] {
+        for b in 0..dims[1] {
+            for c in 0..dims[2] {
+                if (a % 2) * 4 + (b % 2) * 2 + c % 2 == color && !grid.particles(grid.get_index([a, b, c])).is_empty() {
+                    cells.push(([a, b, c], [rng.gen::<usize>(), rng.gen::<usize>()]));
+                }
+            }
+        }
+    }
+    let mut result = ColorMoves::default();
+    let chunk_size = cells.len().div_ceil(threads.max(1)).max(1);
+    thread::scope(|scope| {
+        let workers : Vec<_> = cells.chunks(chunk_size).map(|chunk| scope.spawn(move || {
+            return chunk.iter().map(|&(cell, seed)| {
+                let mut cell_rng : StdRng = SeedableRng::from_seed(&seed[..]);
+                return move_cell(rx, ry, rz, grid, cell, pair, proposal, &mut cell_rng);
+            }).collect::<Vec<_>>();
+        })).collect();
+        // summed in the order of the cells, so also the rounding does not depend on the threads
+        for worker in workers {
+            for moves in worker.join().expect("checkerboard worker panicked") { result.add(moves); }
+        }
+    });
+    return result;
+}
+
+// trial moves of the particles of one cell, moves out of the cell are rejected
+fn move_cell<R: Rng>(rx: &[Real], ry: &[Real], rz: &[Real], grid: &CellList, cell: [usize; 3], pair: &PairSystem, proposal: &Proposal, rng: &mut R) -> ColorMoves {
+    let own = grid.particles(grid.get_index(cell)).to_vec();
+    let index = grid.get_index(cell);
+    let neighbors : Vec<usize> = grid.get_cells_around_cell(cell).iter().filter(|&&other| other != index).flat_map(|&other| grid.particles(other).iter().cloned()).collect();
+    let mut positions : Vec<(Real, Real, Real)> = own.iter().map(|&i| (rx[i], ry[i], rz[i])).collect();
+    let (l_x, l_y, l_z) = (pair.l_x, pair.l_y, pair.l_z);
+
+    // energy and virial of own particle k at x y z with the others of the cell and the frozen neighbor cells
+    let particle_energy = |positions: &[(Real, Real, Real)], k: usize, x: f64, y: f64, z: f64| -> (f64, f64) {
+        let (mut energy, mut virial) = (0.0, 0.0);
+        let mut add = |px: Real, py: Real, pz: Real| {
+            let dist_squared = get_particle_distance_squared(px.to_f64(), py.to_f64(), pz.to_f64(), x, y, z, l_x, l_y, l_z, l_x / 2.0, l_y / 2.0, l_z / 2.0);
+            if dist_squared < pair.cutoff_squared {
+                let (e, v) = eval_truncated_pair_energy(dist_squared, pair.e_shift, pair.f_shift, pair.cutoff_squared, pair.switch_squared);
+                energy += e;
+                virial += v;
+            }
+        };
+        for (other, &(px, py, pz)) in positions.iter().enumerate() {
+            if other != k { add(px, py, pz); }
+        }
+        for &j in &neighbors { add(rx[j], ry[j], rz[j]); }
+        return (energy, virial);
+    };
+
+    let mut moves = ColorMoves { attempts: own.len() as Step, ..ColorMoves::default() };
+    for _ in 0..own.len() {
+        let k = rng.gen_range(0, own.len());
+        let (old_x, old_y, old_z) = positions[k];
+        let trial = proposal.sample(pair.displacement, rng);
+        let wrap = |p: f64, l: f64| if p < 0.0 { p + l } else if p >= l { p - l } else { p };
+        let new = (Real::from_f64(wrap(old_x.to_f64() + trial[0], l_x)), Real::from_f64(wrap(old_y.to_f64() + trial[1], l_y)), Real::from_f64(wrap(old_z.to_f64() + trial[2], l_z)));
+        if grid.get_coordinates(new.0.to_f64(), new.1.to_f64(), new.2.to_f64()) != cell { continue; }
+        let (old_energy, old_virial) = particle_energy(&positions, k, old_x.to_f64(), old_y.to_f64(), old_z.to_f64());
+        let (new_energy, new_virial) = particle_energy(&positions, k, new.0.to_f64(), new.1.to_f64(), new.2.to_f64());
+        let d_e = new_energy - old_energy;
+        if d_e < 0.0 || rng.gen::<f64>() < (-pair.beta * d_e).exp() {
+            positions[k] = new;
+            moves.d_energy += d_e;
+            moves.d_virial += new_virial - old_virial;
+            moves.accepted += 1;
+            moves.turnover += old_energy.abs() + new_energy.abs() + old_virial.abs() + new_virial.abs();
+        }
+    }
+    moves.positions = own.iter().zip(positions.iter()).map(|(&i, &(x, y, z))| (i, x, y, z)).collect();
+    return moves;
+}
+
+#[test]
+fn test_move_color() {
+    use energy::get_total_energy;
+    // a relaxed lattice fills the cells evenly
+    let (n, l) = (216, 6.5);
+    let coordinate = |i: usize| Real::from_f64((i as f64 + 0.5) * l / 6.0);
+    let rx : Vec<Real> = (0..n).map(|i| coordinate(i % 6)).collect();
+    let ry : Vec<Real> = (0..n).map(|i| coordinate(i / 6 % 6)).collect();
+    let rz : Vec<Real> = (0..n).map(|i| coordinate(i / 36)).collect();
+    let pair = PairSystem { l_x: l, l_y: l, l_z: l, cutoff_squared: 2.5 * 2.5, e_shift: 0.0, f_shift: 0.0, switch_squared: 0.0, beta: 1.0, displacement: 0.2 };
+    let (energy, virial) = get_total_energy(&rx, &ry, &rz, n, l, l, l, pair.cutoff_squared, 0.0, 0.0, 0.0, 0.0);
+
+    let mut rng : StdRng = SeedableRng::from_seed(&[7][..]);
+    let grid = get_sweep_grid(&rx, &ry, &rz, n, &pair, &mut rng);
+    assert_eq!([2, 2, 2], grid.dims());
+    let mut on_one = rng;
+    // the same moves on one and on four threads
+    let moves = move_color(&rx, &ry, &rz, &grid, 5, &pair, &Proposal::Cube, 4, &mut rng);
+    assert_eq!(moves, move_color(&rx, &ry, &rz, &grid, 5, &pair, &Proposal::Cube, 1, &mut on_one));
+    assert_eq!(grid.particles(grid.get_index([1, 0, 1])).len() as Step, moves.attempts);
+    assert!(moves.accepted > 0);
+
+    // the changes add up to the recalculated energy and virial
+    let (mut rx, mut ry, mut rz) = (rx, ry, rz);
+    for &(i, x, y, z) in &moves.positions {
+        rx[i] = x;
+        ry[i] = y;
+        rz[i] = z;
+        assert_eq!(grid.get_index([1, 0, 1]), grid.get_index(grid.get_coordinates(x.to_f64(), y.to_f64(), z.to_f64())));
+    }
+    let (new_energy, new_virial) = get_total_energy(&rx, &ry, &rz, n, l, l, l, pair.cutoff_squared, 0.0, 0.0, 0.0, 0.0);
+    assert!( (new_energy - energy - moves.d_energy).abs() < 1e-6 * energy.abs(), "{} {} {}", new_energy, energy, moves.d_energy);
+    assert!( (new_virial - virial - moves.d_virial).abs() < 1e-6 * virial.abs(), "{} {} {}", new_virial, virial, moves.d_virial);
+}
@@ -0,0 +1,46 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store};
+use mclj::error::exit_with;
+use mclj::report::RunResults;
+
+fn main() {
+    let mut first : String = String::new();
+    let mut second : String = String::new();
+    let mut tolerance : f64 = 3.0;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Compares the results of two runs (<output>.results.json): the parameters that differ and the averages that differ by more than the tolerance in combined errors. The exit code is 1 if any average differs by more than the tolerance in combined errors, differing parameters alone do not change it.");
+        ap.refer(&mut first)
+            .add_argument("first", Store, "Results of the first run").required();
+        ap.refer(&mut second)
+            .add_argument("second", Store, "Results of the second run").required();
+        ap.refer(&mut tolerance)
+            .add_option(&["--tolerance"], Store, "Significant difference in combined errors");
+        ap.parse_args_or_exit();
+    }
+    let first_results = RunResults::load(&first).unwrap_or_else(exit_with);
+    let second_results = RunResults::load(&second).unwrap_or_else(exit_with);
+
+    let differences = first_results.get_parameter_differences(&second_results);
+    println!("# {} differing parameters", differences.len());
+    for (name, a, b) in &differences {
+        println!("{}\t{}\t{}", name, a, b);
+    }
+
+    let comparison = first_results.compare(&second_results);
+    let significant = comparison.iter().filter(|c| c.3.abs() > tolerance).count();
+    println!();
+    println!("# {} of {} common averages differ by more than {} combined errors", significant, comparison.len(), tolerance);
+    println!("# observable    first    second    difference in errors");
+    for (name, a, b, z) in &comparison {
+        println!("{}\t{}\t{}\t{:.2}{}", name, a, b, z, if z.abs() > tolerance { "\t*" } else { "" });
+    }
+    for (name, _, _) in first_results.observables.iter().filter(|o| !comparison.iter().any(|c| c.0 == o.0)) {
+        println!("{}\tonly in {}", name, first);
+    }
+    for (name, _, _) in second_results.observables.iter().filter(|o| !comparison.iter().any(|c| c.0 == o.0)) {
+        println!("{}\tonly in {}", name, second);
+    }
+    if significant > 0 { std::process::exit(1); }
+}
@@ -1,5 +1,7 @@
-mod trajectory;
-use trajectory::*;
+extern crate mclj;
+use mclj::trajectory::*;
+use mclj::error::exit_with;
+use mclj::Coordinate;
 use std::env;
 
 fn main() {
@@ -19,12 +21,12 @@ fn main() {
         }
     }
 
-    let mut trj_reader = TrjReader::new(&filename);
+    let mut trj_reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
 
     // skip some frames
     println!("# Skipping {} frames.", skip_frames);
-    trj_reader.skip(skip_frames);
-    let mut frame = trj_reader.next_frame();
+    trj_reader.skip(skip_frames).unwrap_or_else(exit_with);
+    let mut frame = trj_reader.next_frame().unwrap_or_else(exit_with);
     println!("# Done.");
 
     let slab_height = frame.box_z / slabs as f64;
@@ -37,12 +39,14 @@ fn main() {
         frame_count += 1;
 
         for i in 0..frame.num_particles {
-            let slab_no : usize = get_slab_number_for_position(frame.rz[i], slab_height);
+            let slab_no : usize = get_slab_number_for_position(frame.rz[i].to_f64(), slab_height);
             slab_particles_sum[slab_no-1] += 1.0;
         }
 
-        if !trj_reader.update_with_next(&mut frame) {
-            break;
+        match trj_reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
         }
     }
 
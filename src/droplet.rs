@@ -0,0 +1,75 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store};
+use mclj::error::exit_with;
+use mclj::interface::{get_laplace_tension, RadialProfile};
+use mclj::trajectory::*;
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut skip : usize = 0;
+    let mut r_max : f64 = 0.0;
+    let mut bins : usize = 100;
+    let mut core : f64 = 2.0;
+    let mut vapor : f64 = 0.0;
+    let mut temperature : f64 = 0.0;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Radial density and normal pressure profiles of a droplet, its equimolar radius and the surface tension and Tolman length from the Laplace pressure.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Trajectory");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first frames, e.g. the equilibration");
+        ap.refer(&mut r_max)
+            .add_option(&["--rmax"], Store, "Radius of the profiles, by default half the smallest box length");
+        ap.refer(&mut bins)
+            .add_option(&["--bins"], Store, "Shells of the profiles");
+        ap.refer(&mut core)
+            .add_option(&["--core"], Store, "Radius of the core of the droplet, its liquid density and internal pressure");
+        ap.refer(&mut vapor)
+            .add_option(&["--vapor"], Store, "Radius beyond which the vapor density and external pressure are taken, by default 3/4 of rmax");
+        ap.refer(&mut temperature)
+            .add_option(&["-t", "--temperature"], Store, "Temperature, by default the one of the frames");
+        ap.parse_args_or_exit();
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { reader.skip(skip).unwrap_or_else(exit_with); }
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+    if temperature == 0.0 { temperature = frame.temperature; }
+    if r_max == 0.0 { r_max = frame.box_x.min(frame.box_y).min(frame.box_z) / 2.0; }
+    if vapor == 0.0 { vapor = 0.75 * r_max; }
+    if temperature.is_nan() || temperature <= 0.0 {
+        eprintln!("Error: the frames have no temperature, use --temperature.");
+        std::process::exit(1);
+    }
+    if bins == 0 || r_max.is_nan() || r_max <= 0.0 || core.is_nan() || core <= 0.0 || vapor.is_nan() || vapor <= core || vapor >= r_max {
+        eprintln!("Error: bins and rmax have to be positive and core < vapor < rmax.");
+        std::process::exit(1);
+    }
+
+    let mut profile = RadialProfile::new(r_max, bins);
+    loop {
+        profile.add(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, frame.box_x, frame.box_y, frame.box_z, frame.lj_cutoff * frame.lj_cutoff);
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+
+    let (liquid, p_in) = profile.get_region(0.0, core, temperature);
+    let (gas, p_out) = profile.get_region(vapor, r_max, temperature);
+    let equimolar = profile.get_equimolar_radius(liquid, gas);
+    let pressure = profile.get_normal_pressure(temperature);
+    let (tension, tension_radius) = get_laplace_tension(&pressure, profile.width, p_in, p_out);
+    println!("# {} frames", profile.frames);
+    println!("# liquid density {:.5}, vapor density {:.5}, equimolar radius {:.4}", liquid, gas, equimolar);
+    println!("# internal pressure {:.5}, external pressure {:.5}, Laplace pressure {:.5}", p_in, p_out, p_in - p_out);
+    println!("# surface tension at the equimolar radius {:.5}", (p_in - p_out) * equimolar / 2.0);
+    println!("# surface of tension: radius {:.4}, surface tension {:.5}, Tolman length {:.4}", tension_radius, tension, equimolar - tension_radius);
+    println!("# r    density    normal pressure");
+    for (k, density) in profile.get_density().iter().enumerate() {
+        println!("{:.4}\t{:.6}\t{:.6}", (k as f64 + 0.5) * profile.width, density, pressure[k]);
+    }
+}
@@ -1,46 +1,325 @@
  #![allow(dead_code)]
 
+use {Coordinate, Real};
+
+/// Sum with the rounding errors of the additions collected separately (Neumaier's variant of Kahan summation), for the
+/// totals over all pairs where a plain sum loses digits to the growing total or to large overlap terms
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    pub fn add(&mut self, value: f64) {
+        let sum = self.sum + value;
+        // the low order digits lost by the smaller of the two terms
+        self.compensation += if self.sum.abs() >= value.abs() { (self.sum - sum) + value } else { (value - sum) + self.sum };
+        self.sum = sum;
+    }
+
+    pub fn value(&self) -> f64 {
+        return self.sum + self.compensation;
+    }
+}
+
+#[test]
+fn test_compensated_sum() {
+    let mut sum = CompensatedSum::default();
+    let mut plain = 0.0;
+    for value in [1e16, 1.0, -1e16].iter().chain([0.1; 1000].iter()) {
+        sum.add(*value);
+        plain += *value;
+    }
+    assert!( (sum.value() - 101.0).abs() < 1e-12, "{}", sum.value());
+    assert!( (plain - 101.0).abs() > 0.5);
+}
+
 /// Calculates the total energy and virial of a system containing num_particles with coords rx,ry,rz
-/// of size l_x, l_y, l_z and given cutoff + corrections
-pub fn get_total_energy(rx: &[f64], ry: &[f64], rz: &[f64], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_corr: f64, e_shift: f64) -> (f64, f64) {
-    let mut energy = 0.0;
-    let mut virial = 0.0;
+/// of size l_x, l_y, l_z and given cutoff + corrections, switched between switch_squared and the cutoff if switch_squared > 0
+pub fn get_total_energy(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_corr: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> (f64, f64) {
+    let mut energy = CompensatedSum::default();
+    let mut virial = CompensatedSum::default();
     let hl_x = l_x / 2.0;
     let hl_y = l_y / 2.0;
     let hl_z = l_z / 2.0;
     for i in 0..num_particles {
         for j in i+1..num_particles {
-            let dist_squared = get_particle_distance_squared(rx[i], ry[i],rz[i],rx[j],ry[j],rz[j], l_x, l_y, l_z, hl_x, hl_y, hl_z);
+            let dist_squared = get_particle_distance_squared(rx[i].to_f64(), ry[i].to_f64(), rz[i].to_f64(), rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(), l_x, l_y, l_z, hl_x, hl_y, hl_z);
             if dist_squared < cutoff_squared {
-                let (e,v) = eval_pair_energy(dist_squared, e_shift);
+                let (e,v) = eval_truncated_pair_energy(dist_squared, e_shift, f_shift, cutoff_squared, switch_squared);
+                energy.add(e);
+                virial.add(v);
+            }
+        }
+    }
+    energy.add(num_particles as f64 * e_corr);
+    return (energy.value(), virial.value());
+}
+
+/// Calculates the particle energy and virial for particle at p_index in system containing num_particles with coords rx,ry,rz
+/// of size l_x, l_y, l_z and given cutoff + corrections, switched between switch_squared and the cutoff if switch_squared > 0
+pub fn get_particle_energy(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> (f64, f64) {
+    return dispatch_particle_energy(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift, f_shift, switch_squared, true);
+}
+
+/// Particle energy like get_particle_energy without the virial, for trial moves that only need the energy difference
+pub fn get_particle_energy_only(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> f64 {
+    return dispatch_particle_energy(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift, f_shift, switch_squared, false).0;
+}
+
+// kernel of the particle energy for the truncation and the cpu, the virial is 0 without with_virial
+fn dispatch_particle_energy(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64, with_virial: bool) -> (f64, f64) {
+    #[cfg(all(feature = "simd", target_arch = "x86_64", not(feature = "f32")))]
+    {
+        // the avx kernels only do the (shifted) sharp cutoff, the virial costs them nothing measurable
+        if f_shift == 0.0 && switch_squared <= 0.0 && is_x86_feature_detected!("avx") {
+            let (energy, virial) = unsafe { ::simd::get_particle_energy_avx(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift) };
+            return (energy, if with_virial { virial } else { 0.0 });
+        }
+    }
+    // the plain pair energy keeps the kernel free of the branches of the other truncations
+    return match (f_shift == 0.0 && switch_squared <= 0.0, with_virial) {
+        (true, true) => get_particle_energy_chunked(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, |d| eval_pair_energy(d, e_shift)),
+        (true, false) => get_particle_energy_chunked(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, |d| (eval_pair_energy(d, e_shift).0, 0.0)),
+        (false, true) => get_particle_energy_chunked(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, |d| eval_truncated_pair_energy(d, e_shift, f_shift, cutoff_squared, switch_squared)),
+        (false, false) => get_particle_energy_chunked(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, |d| (eval_truncated_pair_energy(d, e_shift, f_shift, cutoff_squared, switch_squared).0, 0.0)),
+    };
+}
+
+/// Width of the chunks of neighbor coordinates in the particle energy
+pub const LANES : usize = 4;
+
+/// Particle energy and virial with the neighbors processed in chunks of LANES without branches, which the
+/// compiler vectorizes. Pairs outside of the cutoff are evaluated anyway and masked out. pair_energy gives energy and virial of a
+/// pair from the squared distance.
+pub fn get_particle_energy_chunked<F: Fn(f64) -> (f64, f64)>(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, pair_energy: F) -> (f64, f64) {
+    let (x, y, z) = (rx[p_index].to_f64(), ry[p_index].to_f64(), rz[p_index].to_f64());
+    let (hl_x, hl_y, hl_z) = (l_x / 2.0, l_y / 2.0, l_z / 2.0);
+
+    // contribution of neighbor j at dx/dy/dz, zero outside of the cutoff and for the particle itself
+    let pair = |j: usize, dx: f64, dy: f64, dz: f64| -> (f64, f64) {
+        let dx = if dx > hl_x { dx - l_x } else if dx < -hl_x { dx + l_x } else { dx };
+        let dy = if dy > hl_y { dy - l_y } else if dy < -hl_y { dy + l_y } else { dy };
+        let dz = if dz > hl_z { dz - l_z } else if dz < -hl_z { dz + l_z } else { dz };
+        let dist_squared = dx*dx + dy*dy + dz*dz;
+        let (e, v) = pair_energy(dist_squared);
+        let inside = dist_squared < cutoff_squared && j != p_index;
+        return (if inside { e } else { 0.0 }, if inside { v } else { 0.0 });
+    };
+
+    let mut energy = [0.0; LANES];
+    let mut virial = [0.0; LANES];
+    let (rx, ry, rz) = (&rx[..num_particles], &ry[..num_particles], &rz[..num_particles]);
+    for (chunk, ((cx, cy), cz)) in rx.chunks_exact(LANES).zip(ry.chunks_exact(LANES)).zip(rz.chunks_exact(LANES)).enumerate() {
+        for k in 0..LANES {
+            let (e, v) = pair(chunk * LANES + k, cx[k].to_f64() - x, cy[k].to_f64() - y, cz[k].to_f64() - z);
+            energy[k] += e;
+            virial[k] += v;
+        }
+    }
+    let (mut energy, mut virial) : (f64, f64) = (energy.iter().sum(), virial.iter().sum());
+    for j in num_particles / LANES * LANES..num_particles {
+        let (e, v) = pair(j, rx[j].to_f64() - x, ry[j].to_f64() - y, rz[j].to_f64() - z);
+        energy += e;
+        virial += v;
+    }
+    return (energy, virial);
+}
+
+#[test]
+fn test_get_particle_energy() {
+    // pseudo random positions, some pairs beyond the cutoff
+    let n = 37;
+    let l = 4.0;
+    let coordinate = |i: usize, k: usize| Real::from_f64(((i * 7919 + k * 104729) % 1000) as f64 / 1000.0 * l);
+    let rx : Vec<Real> = (0..n).map(|i| coordinate(i, 1)).collect();
+    let ry : Vec<Real> = (0..n).map(|i| coordinate(i, 2)).collect();
+    let rz : Vec<Real> = (0..n).map(|i| coordinate(i, 3)).collect();
+    for &p in &[0, 5, 17, 36] {
+        let (mut energy, mut virial) = (0.0, 0.0);
+        for j in 0..n {
+            if j == p { continue; }
+            let dist_squared = get_particle_distance_squared(rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(), rx[p].to_f64(), ry[p].to_f64(), rz[p].to_f64(), l, l, l, l / 2.0, l / 2.0, l / 2.0);
+            if dist_squared < 2.25 {
+                let (e, v) = eval_pair_energy(dist_squared, 0.1);
                 energy += e;
                 virial += v;
             }
         }
+        let (e, v) = get_particle_energy(&rx, &ry, &rz, p, n, l, l, l, 2.25, 0.1, 0.0, 0.0);
+        assert!( (e - energy).abs() < 1e-9 * energy.abs().max(1.0), "{} {}", e, energy);
+        assert!( (v - virial).abs() < 1e-9 * virial.abs().max(1.0), "{} {}", v, virial);
+        assert_eq!(e, get_particle_energy_only(&rx, &ry, &rz, p, n, l, l, l, 2.25, 0.1, 0.0, 0.0));
+
+        // switched between 1.0 and the cutoff
+        let (mut energy, mut virial) = (0.0, 0.0);
+        for j in 0..n {
+            if j == p { continue; }
+            let dist_squared = get_particle_distance_squared(rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(), rx[p].to_f64(), ry[p].to_f64(), rz[p].to_f64(), l, l, l, l / 2.0, l / 2.0, l / 2.0);
+            if dist_squared < 2.25 {
+                let (e, v) = eval_truncated_pair_energy(dist_squared, 0.0, 0.0, 2.25, 1.0);
+                energy += e;
+                virial += v;
+            }
+        }
+        let (e, v) = get_particle_energy(&rx, &ry, &rz, p, n, l, l, l, 2.25, 0.0, 0.0, 1.0);
+        assert!( (e - energy).abs() < 1e-9 * energy.abs().max(1.0), "{} {}", e, energy);
+        assert!( (v - virial).abs() < 1e-9 * virial.abs().max(1.0), "{} {}", v, virial);
+        assert_eq!(e, get_particle_energy_only(&rx, &ry, &rz, p, n, l, l, l, 2.25, 0.0, 0.0, 1.0));
     }
-    energy += num_particles as f64 * e_corr;
-    return (energy, virial);
 }
 
-/// Calculates the particle energy and virial for particle at p_index in system containing num_particles with coords rx,ry,rz
-/// of size l_x, l_y, l_z and given cutoff + corrections
-pub fn get_particle_energy(rx: &[f64], ry: &[f64], rz: &[f64], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64) -> (f64, f64) {
-    let mut energy = 0.0;
-    let mut virial = 0.0;
-    let hl_x = l_x / 2.0;
-    let hl_y = l_y / 2.0;
-    let hl_z = l_z / 2.0;
+/// Particle energy and virial like get_particle_energy, or None as soon as the energy is certainly above limit: the sum
+/// so far plus the lowest pair energy (-epsilon and the shifts) for every remaining particle exceeds it. A trial move
+/// that needs its energy below limit is then rejected without the remaining pairs.
+pub fn get_particle_energy_bounded(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64, limit: f64) -> Option<(f64, f64)> {
+    let (x, y, z) = (rx[p_index].to_f64(), ry[p_index].to_f64(), rz[p_index].to_f64());
+    let pair_min = -::LJ_EPS - e_shift.abs() - f_shift.abs() * cutoff_squared.sqrt();
+    let (mut energy, mut virial) = (0.0, 0.0);
+    for j in (0..num_particles).filter(|&j| j != p_index) {
+        let dist_squared = get_particle_distance_squared(rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(), x, y, z, l_x, l_y, l_z, l_x / 2.0, l_y / 2.0, l_z / 2.0);
+        if dist_squared >= cutoff_squared { continue; }
+        let (e, v) = eval_truncated_pair_energy(dist_squared, e_shift, f_shift, cutoff_squared, switch_squared);
+        energy += e;
+        virial += v;
+        // only repulsive pairs raise the bound
+        if e > 0.0 && energy + pair_min * (num_particles - 1 - j) as f64 > limit { return None; }
+    }
+    return Some((energy, virial));
+}
+
+#[test]
+fn test_get_particle_energy_bounded() {
+    let n = 37;
+    let l = 4.0;
+    let coordinate = |i: usize, k: usize| Real::from_f64(((i * 7919 + k * 104729) % 1000) as f64 / 1000.0 * l);
+    let mut rx : Vec<Real> = (0..n).map(|i| coordinate(i, 1)).collect();
+    let mut ry : Vec<Real> = (0..n).map(|i| coordinate(i, 2)).collect();
+    let rz : Vec<Real> = (0..n).map(|i| coordinate(i, 3)).collect();
+    let (e, v) = get_particle_energy(&rx, &ry, &rz, 5, n, l, l, l, 2.25, 0.1, 0.0, 0.0);
+    let (e_bounded, v_bounded) = get_particle_energy_bounded(&rx, &ry, &rz, 5, n, l, l, l, 2.25, 0.1, 0.0, 0.0, e + 0.001).unwrap();
+    assert!( (e - e_bounded).abs() < 1e-9 * e.abs().max(1.0) && (v - v_bounded).abs() < 1e-9 * v.abs().max(1.0), "{} {}", e, e_bounded);
+    // below the limit the bound is only a shortcut, the energy is the same without it
+    if let Some((e_bounded, _)) = get_particle_energy_bounded(&rx, &ry, &rz, 5, n, l, l, l, 2.25, 0.1, 0.0, 0.0, e - 0.001) {
+        assert!( (e - e_bounded).abs() < 1e-9 * e.abs().max(1.0));
+    }
+
+    // an overlap with particle 0 stops the sum right away
+    let mut rz = rz;
+    rx[5] = rx[0];
+    ry[5] = Real::from_f64(ry[0].to_f64() + 0.3);
+    rz[5] = rz[0];
+    assert!(get_particle_energy_bounded(&rx, &ry, &rz, 5, n, l, l, l, 2.25, 0.1, 0.0, 0.0, 100.0).is_none());
+}
+
+/// Finds the first particle with a non-finite position or particle energy, if any
+pub fn find_non_finite_particle(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> Option<usize> {
     for i in 0..num_particles {
-        if i == p_index { continue; }
+        if !(rx[i].is_finite() && ry[i].is_finite() && rz[i].is_finite()) { return Some(i); }
+    }
+    for i in 0..num_particles {
+        let (e, v) = get_particle_energy(rx, ry, rz, i, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift, f_shift, switch_squared);
+        if !(e.is_finite() && v.is_finite()) { return Some(i); }
+    }
+    return None;
+}
 
-        let dist_squared = get_particle_distance_squared(rx[i], ry[i], rz[i], rx[p_index], ry[p_index], rz[p_index], l_x, l_y, l_z, hl_x, hl_y, hl_z);
-        if dist_squared < cutoff_squared {
-            let (e,v) = eval_pair_energy(dist_squared, e_shift);
-            energy += e;
-            virial += v;
+#[test]
+fn test_find_non_finite_particle() {
+    let rx = vec![1.0, 2.0, 3.0];
+    let ry = vec![1.0, 1.0, 1.0];
+    let mut rz = vec![1.0, 1.0, 1.0];
+    assert_eq!(None, find_non_finite_particle(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0, 9.0, 0.0, 0.0, 0.0));
+
+    rz[2] = Real::NAN;
+    assert_eq!(Some(2), find_non_finite_particle(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0, 9.0, 0.0, 0.0, 0.0));
+
+    // two particles on top of each other
+    let rz = vec![1.0, 1.0, 1.0];
+    let rx = vec![1.0, 2.0, 2.0];
+    assert_eq!(Some(1), find_non_finite_particle(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0, 9.0, 0.0, 0.0, 0.0));
+}
+
+/// Energy of every particle, half of its pair energies so that they add up to the total energy without tail corrections
+pub fn get_particle_energies(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> Vec<f64> {
+    return (0..num_particles).map(|i| get_particle_energy(rx, ry, rz, i, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift, f_shift, switch_squared).0 / 2.0).collect();
+}
+
+/// Repulsive (r^-12) and attractive (r^-6) parts of the plain lj pair energies within the cutoff. The shift or the
+/// switching function and the tail corrections make up the rest of the total energy.
+pub fn get_energy_decomposition(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64) -> (f64, f64) {
+    let mut repulsive = CompensatedSum::default();
+    let mut attractive = CompensatedSum::default();
+    let (hl_x, hl_y, hl_z) = (l_x / 2.0, l_y / 2.0, l_z / 2.0);
+    for i in 0..num_particles {
+        for j in i+1..num_particles {
+            let dist_squared = get_particle_distance_squared(rx[i].to_f64(), ry[i].to_f64(), rz[i].to_f64(), rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(), l_x, l_y, l_z, hl_x, hl_y, hl_z);
+            if dist_squared < cutoff_squared {
+                let r6 = ::LJ_SIG/(dist_squared * dist_squared * dist_squared);
+                repulsive.add(4.0 * ::LJ_EPS * r6 * r6);
+                attractive.add(-4.0 * ::LJ_EPS * r6);
+            }
         }
     }
-    return (energy, virial);
+    return (repulsive.value(), attractive.value());
+}
+
+#[test]
+fn test_get_energy_decomposition() {
+    let rx = vec![1.0, 2.1, 3.3, 7.0];
+    let ry = vec![1.0, 1.0, 1.5, 1.0];
+    let rz = vec![1.0, 1.0, 1.0, 1.0];
+    let (energy, _) = get_total_energy(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0, 0.0, 0.0, 0.0, 0.0);
+    let (repulsive, attractive) = get_energy_decomposition(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0);
+    assert!(repulsive > 0.0 && attractive < 0.0);
+    assert!( (repulsive + attractive - energy).abs() < 0.00001, "{} {} {}", repulsive, attractive, energy);
+
+    // the particle energies add up to the shifted total energy
+    let (energy, _) = get_total_energy(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0, 0.0, 0.01, 0.0, 0.0);
+    let sum : f64 = get_particle_energies(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0, 0.01, 0.0, 0.0).iter().sum();
+    assert!( (sum - energy).abs() < 0.00001, "{} {}", sum, energy);
+}
+
+/// Virial tensor of every particle, half of r_a r_b w(r) / r² summed over its pairs with the pair virial w of the
+/// truncated potential, as xx yy zz xy xz yz. The traces add up to the virial of get_total_energy and divided by a
+/// volume they are the configurational part of the local pressure tensor.
+pub fn get_particle_virials(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> Vec<[f64; 6]> {
+    let mut virials = vec![[0.0; 6]; num_particles];
+    let (hl_x, hl_y, hl_z) = (l_x / 2.0, l_y / 2.0, l_z / 2.0);
+    for i in 0..num_particles {
+        for j in i+1..num_particles {
+            let dx = get_displacement_with_pbc(rx[i].to_f64(), rx[j].to_f64(), l_x, hl_x);
+            let dy = get_displacement_with_pbc(ry[i].to_f64(), ry[j].to_f64(), l_y, hl_y);
+            let dz = get_displacement_with_pbc(rz[i].to_f64(), rz[j].to_f64(), l_z, hl_z);
+            let dist_squared = dx*dx + dy*dy + dz*dz;
+            if dist_squared < cutoff_squared {
+                let (_, w) = eval_truncated_pair_energy(dist_squared, e_shift, f_shift, cutoff_squared, switch_squared);
+                let f = 0.5 * w / dist_squared;
+                let pair = [f*dx*dx, f*dy*dy, f*dz*dz, f*dx*dy, f*dx*dz, f*dy*dz];
+                for k in 0..6 {
+                    virials[i][k] += pair[k];
+                    virials[j][k] += pair[k];
+                }
+            }
+        }
+    }
+    return virials;
+}
+
+#[test]
+fn test_get_particle_virials() {
+    let rx = vec![1.0, 2.1, 3.3, 9.5];
+    let ry = vec![1.0, 1.0, 1.5, 1.0];
+    let rz = vec![1.0, 1.0, 1.0, 1.3];
+    let (_, virial) = get_total_energy(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0, 0.0, 0.01, 0.0, 0.0);
+    let virials = get_particle_virials(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0, 0.01, 0.0, 0.0);
+    let trace : f64 = virials.iter().map(|w| w[0] + w[1] + w[2]).sum();
+    assert!( (trace - virial).abs() < 0.00001, "{} {}", trace, virial);
+    // particles 0 and 1 only interact along x
+    let single = get_particle_virials(&rx[..2], &ry[..2], &rz[..2], 2, 10.0, 10.0, 10.0, 9.0, 0.01, 0.0, 0.0);
+    assert!(single[0][1].abs() < 0.00001 && single[0][3].abs() < 0.00001);
+    assert_eq!(single[0], single[1]);
 }
 
 // squared distance between 2 particles regarding the minimum image convention
@@ -53,7 +332,7 @@ pub fn get_particle_distance_squared(x1: f64,y1: f64,z1: f64,x2: f64,y2: f64,z2:
         else if dx < -hl_x { dx += l_x }
     if dy > hl_y { dy -= l_y}
         else if dy < -hl_y{ dy += l_y }
-    if dz > hl_z { dz -= l_y }
+    if dz > hl_z { dz -= l_z }
         else if dz < -hl_z { dz += l_z}
     return dx*dx + dy*dy + dz*dz;
 }
@@ -66,6 +345,27 @@ pub fn get_distance_with_pbc(x1: f64, x2: f64, length: f64, half_length: f64) ->
     return d;
 }
 
+/// Scalar product of two vectors, e.g. of separations and particle axes
+pub fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+// signed one dimensional distance x1 - x2 with applied minimum image convention
+pub fn get_displacement_with_pbc(x1: f64, x2: f64, length: f64, half_length: f64) -> f64 {
+    let mut d = x1 - x2;
+    if d > half_length { d -= length }
+    else if d < -half_length { d += length }
+    return d;
+}
+
+#[test]
+fn test_get_displacement_with_pbc() {
+    assert!( (get_displacement_with_pbc(3.0, 1.0, 10.0, 5.0) - 2.0).abs() < 0.00001);
+    assert!( (get_displacement_with_pbc(1.0, 3.0, 10.0, 5.0) - -2.0).abs() < 0.00001);
+    assert!( (get_displacement_with_pbc(9.0, 1.0, 10.0, 5.0) - -2.0).abs() < 0.00001);
+    assert!( (get_displacement_with_pbc(1.0, 9.0, 10.0, 5.0) - 2.0).abs() < 0.00001);
+}
+
 #[test]
 fn test_get_particle_distance_squared() {
     let (x1, y1, z1) = (0.0, 0.0, 0.0);
@@ -99,6 +399,75 @@ pub fn eval_pair_energy(dist_squared: f64, e_shift: f64) -> (f64, f64) {
     return (energy, virial);
 }
 
+/// Polynomial switching function of the squared distance, 1 up to switch_squared and going smoothly to 0 at
+/// cutoff_squared with vanishing slope at both ends. Returns the factor and r times its derivative for the virial.
+pub fn eval_switch(dist_squared: f64, cutoff_squared: f64, switch_squared: f64) -> (f64, f64) {
+    if dist_squared <= switch_squared || switch_squared <= 0.0 {
+        return (1.0, 0.0);
+    }
+    let denominator = (cutoff_squared - switch_squared).powi(3);
+    let factor = (cutoff_squared - dist_squared).powi(2) * (cutoff_squared + 2.0 * dist_squared - 3.0 * switch_squared) / denominator;
+    let r_derivative = 12.0 * dist_squared * (cutoff_squared - dist_squared) * (switch_squared - dist_squared) / denominator;
+    return (factor, r_derivative);
+}
+
+#[test]
+fn test_eval_switch() {
+    assert_eq!((1.0, 0.0), eval_switch(4.0, 9.0, 0.0));
+    assert_eq!((1.0, 0.0), eval_switch(4.0, 9.0, 6.25));
+    let (s, _) = eval_switch(9.0, 9.0, 6.25);
+    assert!(s.abs() < 0.00001, "{}", s);
+
+    // r dS/dr against the numerical derivative
+    let r = 2.8;
+    let h = 0.000001;
+    let numerical = r * (eval_switch((r+h)*(r+h), 9.0, 6.25).0 - eval_switch((r-h)*(r-h), 9.0, 6.25).0) / (2.0*h);
+    let analytical = eval_switch(r*r, 9.0, 6.25).1;
+    assert!( (numerical - analytical).abs() < 0.0001, "{} {}", numerical, analytical);
+}
+
+/// Shifts of the force-shifted potential U(r) - U(rc) - (r - rc) U'(rc) at the cutoff: the energy shift U(rc) - rc U'(rc)
+/// and the force shift -U'(rc) for eval_truncated_pair_energy
+pub fn get_force_shifts(cutoff: f64) -> (f64, f64) {
+    let (energy, virial) = eval_pair_energy(cutoff * cutoff, 0.0);
+    let f_shift = virial / cutoff;
+    return (energy + cutoff * f_shift, f_shift);
+}
+
+/// Pair energy and virial like eval_pair_energy, with the linear term r f_shift of the force-shifted potential if f_shift != 0 and
+/// multiplied with the switching function if switch_squared > 0
+pub fn eval_truncated_pair_energy(dist_squared: f64, e_shift: f64, f_shift: f64, cutoff_squared: f64, switch_squared: f64) -> (f64, f64) {
+    let (mut energy, mut virial) = eval_pair_energy(dist_squared, e_shift);
+    if f_shift != 0.0 {
+        let r = dist_squared.sqrt();
+        energy += r * f_shift;
+        virial -= r * f_shift;
+    }
+    let (factor, r_derivative) = eval_switch(dist_squared, cutoff_squared, switch_squared);
+    // virial -r dU/dr of the product U S
+    return (energy * factor, virial * factor - energy * r_derivative);
+}
+
+#[test]
+fn test_eval_truncated_pair_energy() {
+    // continuous at the cutoff, virial is -r dU/dr
+    let e = eval_truncated_pair_energy(9.0 - 1e-9, 0.0, 0.0, 9.0, 4.0).0;
+    assert!(e.abs() < 0.00001, "{}", e);
+    let r = 2.5;
+    let h = 0.000001;
+    let numerical = -r * (eval_truncated_pair_energy((r+h)*(r+h), 0.0, 0.0, 9.0, 4.0).0 - eval_truncated_pair_energy((r-h)*(r-h), 0.0, 0.0, 9.0, 4.0).0) / (2.0*h);
+    let analytical = eval_truncated_pair_energy(r*r, 0.0, 0.0, 9.0, 4.0).1;
+    assert!( (numerical - analytical).abs() < 0.0001, "{} {}", numerical, analytical);
+
+    // force-shifted: energy and force vanish at the cutoff
+    let (e_shift, f_shift) = get_force_shifts(3.0);
+    let (e, v) = eval_truncated_pair_energy(9.0, e_shift, f_shift, 9.0, 0.0);
+    assert!(e.abs() < 1e-12 && v.abs() < 1e-12, "{} {}", e, v);
+    let numerical = -r * (eval_truncated_pair_energy((r+h)*(r+h), e_shift, f_shift, 9.0, 0.0).0 - eval_truncated_pair_energy((r-h)*(r-h), e_shift, f_shift, 9.0, 0.0).0) / (2.0*h);
+    let analytical = eval_truncated_pair_energy(r*r, e_shift, f_shift, 9.0, 0.0).1;
+    assert!( (numerical - analytical).abs() < 0.0001, "{} {}", numerical, analytical);
+}
+
 #[test]
 fn test_eval_pair_energy() {
     let e = eval_pair_energy(1.0, 0.0).0;
@@ -126,3 +495,138 @@ fn test_eval_virial() {
     let expected = 1.1580288;
     assert!( (result - expected).abs() < 0.0001, "{}", result );
 }
+
+/// calculate the lj force between two particles divided by their distance from given square distance.
+/// Multiply with the distance vector r_i - r_j to get the force on particle i. The energy shift is
+/// a constant and does not change the force.
+pub fn eval_pair_force(dist_squared: f64) -> f64 {
+    let r6 = ::LJ_SIG/(dist_squared * dist_squared * dist_squared);
+    let r62 = r6*r6;
+    return 48.0 * ::LJ_EPS * ( r62 - 0.5 * r6 ) / dist_squared;
+}
+
+#[test]
+fn test_eval_pair_force() {
+    // force vanishes in the potential minimum at 2^(1/6)
+    let f = eval_pair_force(2.0_f64.powf(1.0/3.0));
+    assert!( f.abs() < 0.00001, "{}", f);
+
+    // compare against the numerical derivative of the pair energy
+    let r = 1.3;
+    let h = 0.000001;
+    let numerical = -(eval_pair_energy((r+h)*(r+h), 0.0).0 - eval_pair_energy((r-h)*(r-h), 0.0).0) / (2.0*h);
+    let analytical = eval_pair_force(r*r) * r;
+    assert!( (numerical - analytical).abs() < 0.0001, "{} {}", numerical, analytical);
+}
+
+/// Pair force divided by the distance like eval_pair_force, of the truncation of eval_truncated_pair_energy: with the
+/// force shift if f_shift != 0 and the derivative of the switching function if switch_squared > 0
+pub fn eval_truncated_pair_force(dist_squared: f64, e_shift: f64, f_shift: f64, cutoff_squared: f64, switch_squared: f64) -> f64 {
+    if f_shift == 0.0 && switch_squared <= 0.0 { return eval_pair_force(dist_squared); }
+    // the virial is -r dU/dr, the force over the distance -dU/dr / r
+    return eval_truncated_pair_energy(dist_squared, e_shift, f_shift, cutoff_squared, switch_squared).1 / dist_squared;
+}
+
+/// Calculates the lj force acting on the particle at p_index in a system containing num_particles with coords rx,ry,rz
+/// of size l_x, l_y, l_z and given cutoff, truncated like get_particle_energy. Returns the force components (f_x, f_y, f_z)
+pub fn get_particle_forces(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> (f64, f64, f64) {
+    let (mut f_x, mut f_y, mut f_z) = (0.0, 0.0, 0.0);
+    let hl_x = l_x / 2.0;
+    let hl_y = l_y / 2.0;
+    let hl_z = l_z / 2.0;
+    for i in 0..num_particles {
+        if i == p_index { continue; }
+
+        let dx = get_displacement_with_pbc(rx[p_index].to_f64(), rx[i].to_f64(), l_x, hl_x);
+        let dy = get_displacement_with_pbc(ry[p_index].to_f64(), ry[i].to_f64(), l_y, hl_y);
+        let dz = get_displacement_with_pbc(rz[p_index].to_f64(), rz[i].to_f64(), l_z, hl_z);
+        let dist_squared = dx*dx + dy*dy + dz*dz;
+        if dist_squared < cutoff_squared {
+            let f = eval_truncated_pair_force(dist_squared, e_shift, f_shift, cutoff_squared, switch_squared);
+            f_x += f * dx;
+            f_y += f * dy;
+            f_z += f * dz;
+        }
+    }
+    return (f_x, f_y, f_z);
+}
+
+/// Calculates the lj forces acting on all particles of a system containing num_particles with coords rx,ry,rz
+/// of size l_x, l_y, l_z and given cutoff, truncated like get_total_energy. Returns the force components as vectors (f_x, f_y, f_z)
+pub fn get_total_forces(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64, f_shift: f64, switch_squared: f64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let mut f_x = vec![0.0; num_particles];
+    let mut f_y = vec![0.0; num_particles];
+    let mut f_z = vec![0.0; num_particles];
+    let hl_x = l_x / 2.0;
+    let hl_y = l_y / 2.0;
+    let hl_z = l_z / 2.0;
+    for i in 0..num_particles {
+        for j in i+1..num_particles {
+            let dx = get_displacement_with_pbc(rx[i].to_f64(), rx[j].to_f64(), l_x, hl_x);
+            let dy = get_displacement_with_pbc(ry[i].to_f64(), ry[j].to_f64(), l_y, hl_y);
+            let dz = get_displacement_with_pbc(rz[i].to_f64(), rz[j].to_f64(), l_z, hl_z);
+            let dist_squared = dx*dx + dy*dy + dz*dz;
+            if dist_squared < cutoff_squared {
+                // newtons third law: the same force acts on j in opposite direction
+                let f = eval_truncated_pair_force(dist_squared, e_shift, f_shift, cutoff_squared, switch_squared);
+                f_x[i] += f * dx;
+                f_y[i] += f * dy;
+                f_z[i] += f * dz;
+                f_x[j] -= f * dx;
+                f_y[j] -= f * dy;
+                f_z[j] -= f * dz;
+            }
+        }
+    }
+    return (f_x, f_y, f_z);
+}
+
+#[test]
+fn test_get_total_forces() {
+    let rx = vec![0.5, 1.6, 9.8];
+    let ry = vec![0.5, 0.7, 0.4];
+    let rz = vec![0.5, 0.4, 0.6];
+    let (f_x, f_y, f_z) = get_total_forces(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0, 9.0, 0.0, 0.0, 0.0);
+
+    // total force is zero and particle forces match the total force calculation
+    assert!( (f_x[0] + f_x[1] + f_x[2]).abs() < 0.00001);
+    assert!( (f_y[0] + f_y[1] + f_y[2]).abs() < 0.00001);
+    assert!( (f_z[0] + f_z[1] + f_z[2]).abs() < 0.00001);
+    for i in 0..3 {
+        let (fx, fy, fz) = get_particle_forces(&rx, &ry, &rz, i, 3, 10.0, 10.0, 10.0, 9.0, 0.0, 0.0, 0.0);
+        assert!( (fx - f_x[i]).abs() < 0.00001 && (fy - f_y[i]).abs() < 0.00001 && (fz - f_z[i]).abs() < 0.00001);
+    }
+
+    // particle 0 and 2 interact through the periodic boundary and are pushed apart
+    assert!(f_x[2] < 0.0);
+}
+
+#[test]
+fn test_truncated_forces() {
+    // pairs inside the switching radius, in the switching region (2.55) and through the periodic boundary, coordinates
+    // and steps of 1/1024 are exact also in single precision
+    let rx : Vec<Real> = vec![1.0, 2.125, 1.0, 9.5];
+    let ry : Vec<Real> = vec![1.0, 1.25, 3.5, 1.0];
+    let rz : Vec<Real> = vec![1.0, 1.0, 1.5, 1.25];
+    let h = 1.0 / 1024.0;
+    let (force_e_shift, force_f_shift) = get_force_shifts(3.0);
+    // shifted, force-shifted and switched from 2
+    for &(e_shift, f_shift, switch_squared) in &[(eval_pair_energy(9.0, 0.0).0, 0.0, 0.0), (force_e_shift, force_f_shift, 0.0), (0.0, 0.0, 4.0)] {
+        let (f_x, f_y, f_z) = get_total_forces(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 9.0, e_shift, f_shift, switch_squared);
+        for i in 0..4 {
+            let forces = [f_x[i], f_y[i], f_z[i]];
+            let (fx, fy, fz) = get_particle_forces(&rx, &ry, &rz, i, 4, 10.0, 10.0, 10.0, 9.0, e_shift, f_shift, switch_squared);
+            for k in 0..3 {
+                // -dU/dr_i of the central difference of the energy of particle i
+                let energy = |d: f64| {
+                    let mut r = [rx.clone(), ry.clone(), rz.clone()];
+                    r[k][i] = Real::from_f64(r[k][i].to_f64() + d);
+                    return get_particle_energy(&r[0], &r[1], &r[2], i, 4, 10.0, 10.0, 10.0, 9.0, e_shift, f_shift, switch_squared).0;
+                };
+                let numerical = -(energy(h) - energy(-h)) / (2.0 * h);
+                assert!( (numerical - forces[k]).abs() < 1e-3 * numerical.abs().max(1.0), "{} {} {} {}", i, k, numerical, forces[k]);
+                assert!( ([fx, fy, fz][k] - forces[k]).abs() < 1e-9 * forces[k].abs().max(1.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Equation of state of the full lj fluid: the modified Benedict-Webb-Rubin fit of Johnson, Zollweg and Gubbins (1993),
+//! fitted to simulations between T = 0.7 and 6 up to the solid, meaningless inside the liquid-vapor coexistence region
+
+// the 32 parameters x_i and gamma of table 10
+const X : [f64; 32] = [
+    0.8623085097507421, 2.976218765822098, -8.402230115796038, 0.1054136629203555, -0.8564583828174598,
+    1.582759470107601, 0.7639421948305453, 1.753173414312048, 2.798291772190376e3, -4.839422026085766e-2,
+    0.9963265197721935, -3.698000291272493e1, 2.084012299434647e1, 8.305402124717285e1, -9.574799715203068e2,
+    -1.477746229234994e2, 6.398607852471505e1, 1.603993673294834e1, 6.805916615864377e1, -2.791293578795945e3,
+    -6.245128304568454, -8.11683610495841e3, 1.488735559561229e1, -1.059346754655084e4, -1.131607632802822e2,
+    -8.867771540418822e3, -3.986982844450543e1, -4.689270299917261e3, 2.593535277438717e2, -2.694523589434903e3,
+    -7.218487631550215e2, 1.721802063863269e2,
+];
+const GAMMA : f64 = 3.0;
+
+/// Temperatures the equation of state was fitted to
+pub const EOS_TEMPERATURES : (f64, f64) = (0.7, 6.0);
+
+// the density integrals G_i of the exponential terms
+fn get_g(density: f64) -> [f64; 6] {
+    let f = (-GAMMA * density * density).exp();
+    let mut g = [(1.0 - f) / (2.0 * GAMMA); 6];
+    for i in 1..6 {
+        g[i] = -(f * density.powi(2 * i as i32) - 2.0 * i as f64 * g[i - 1]) / (2.0 * GAMMA);
+    }
+    return g;
+}
+
+/// Excess helmholtz free energy per particle of the full lj fluid
+pub fn get_excess_free_energy(temperature: f64, density: f64) -> f64 {
+    let (a, b) = get_coefficients(temperature);
+    let g = get_g(density);
+    return (0..8).map(|i| a[i] * density.powi(i as i32 + 1) / (i + 1) as f64).sum::<f64>() + (0..6).map(|i| b[i] * g[i]).sum::<f64>();
+}
+
+/// Configurational energy per particle and pressure of the full lj fluid
+pub fn get_johnson_eos(temperature: f64, density: f64) -> (f64, f64) {
+    let t = temperature;
+    let x = |i: usize| X[i - 1];
+    let c = [x(2) * t.sqrt() / 2.0 + x(3) + 2.0 * x(4) / t + 3.0 * x(5) / t.powi(2), x(7) + 2.0 * x(8) / t + 3.0 * x(9) / t.powi(2), x(11) + 2.0 * x(12) / t, x(13),
+             2.0 * x(14) / t + 3.0 * x(15) / t.powi(2), 2.0 * x(16) / t, 2.0 * x(17) / t + 3.0 * x(18) / t.powi(2), 3.0 * x(19) / t.powi(2)];
+    let d = [3.0 * x(20) / t.powi(2) + 4.0 * x(21) / t.powi(3), 3.0 * x(22) / t.powi(2) + 5.0 * x(23) / t.powi(4), 3.0 * x(24) / t.powi(2) + 4.0 * x(25) / t.powi(3),
+             3.0 * x(26) / t.powi(2) + 5.0 * x(27) / t.powi(4), 3.0 * x(28) / t.powi(2) + 4.0 * x(29) / t.powi(3),
+             3.0 * x(30) / t.powi(2) + 4.0 * x(31) / t.powi(3) + 5.0 * x(32) / t.powi(4)];
+    let g = get_g(density);
+    let energy = (0..8).map(|i| c[i] * density.powi(i as i32 + 1) / (i + 1) as f64).sum::<f64>() + (0..6).map(|i| d[i] * g[i]).sum::<f64>();
+
+    let (a, b) = get_coefficients(temperature);
+    let f = (-GAMMA * density * density).exp();
+    let pressure = density * t + (0..8).map(|i| a[i] * density.powi(i as i32 + 2)).sum::<f64>() + f * (0..6).map(|i| b[i] * density.powi(2 * i as i32 + 3)).sum::<f64>();
+    return (energy, pressure);
+}
+
+// the temperature dependent coefficients a_i and b_i of the pressure
+fn get_coefficients(t: f64) -> ([f64; 8], [f64; 6]) {
+    let x = |i: usize| X[i - 1];
+    let a = [x(1) * t + x(2) * t.sqrt() + x(3) + x(4) / t + x(5) / t.powi(2), x(6) * t + x(7) + x(8) / t + x(9) / t.powi(2), x(10) * t + x(11) + x(12) / t, x(13),
+             x(14) / t + x(15) / t.powi(2), x(16) / t, x(17) / t + x(18) / t.powi(2), x(19) / t.powi(2)];
+    let b = [x(20) / t.powi(2) + x(21) / t.powi(3), x(22) / t.powi(2) + x(23) / t.powi(4), x(24) / t.powi(2) + x(25) / t.powi(3),
+             x(26) / t.powi(2) + x(27) / t.powi(4), x(28) / t.powi(2) + x(29) / t.powi(3), x(30) / t.powi(2) + x(31) / t.powi(3) + x(32) / t.powi(4)];
+    return (a, b);
+}
+
+#[test]
+fn test_johnson_eos() {
+    // energy and pressure are the derivatives of the free energy: u = d(A/T)/d(1/T) and P = rho T + rho² dA/drho
+    for &(t, rho) in [(1.0, 0.8), (2.0, 0.5), (0.8, 0.05)].iter() {
+        let (energy, pressure) = get_johnson_eos(t, rho);
+        let h = 1e-5;
+        let beta_a = |beta: f64| get_excess_free_energy(1.0 / beta, rho) * beta;
+        assert!( (energy - (beta_a(1.0 / t + h) - beta_a(1.0 / t - h)) / (2.0 * h)).abs() < 1e-5, "{} {}", t, rho);
+        let d_a = (get_excess_free_energy(t, rho + h) - get_excess_free_energy(t, rho - h)) / (2.0 * h);
+        assert!( (pressure - rho * t - rho * rho * d_a).abs() < 1e-5, "{} {}", t, rho);
+    }
+    // the ideal gas at low density
+    let (energy, pressure) = get_johnson_eos(2.0, 1e-6);
+    assert!(energy.abs() < 1e-4 && (pressure / 2e-6 - 1.0).abs() < 1e-4);
+    // liquid near the triple point, about -6.0 and close to zero pressure
+    let (energy, pressure) = get_johnson_eos(0.72, 0.84);
+    assert!( (energy + 6.0).abs() < 0.2 && pressure.abs() < 0.3, "{} {}", energy, pressure);
+}
+
+/// Line of a simulated value next to the equation of state, with the deviation in standard errors if the error is known
+pub fn format_comparison(name: &str, simulated: f64, error: f64, eos: f64) -> String {
+    let deviation = simulated - eos;
+    let errors = if error.is_finite() && error > 0.0 { format!(", {:.1} standard errors", deviation / error) } else { String::new() };
+    return format!("{}: {:.4} (EOS {:.4}, deviation {:+.4}{})", name, simulated, eos, deviation, errors);
+}
+
+#[test]
+fn test_format_comparison() {
+    assert_eq!("Pressure: 1.0387 (EOS 1.0318, deviation +0.0069, 0.3 standard errors)", format_comparison("Pressure", 1.0387, 0.022, 1.0318));
+    assert_eq!("Energy per particle: -5.5000 (EOS -5.5235, deviation +0.0235)", format_comparison("Energy per particle", -5.5, f64::NAN, -5.5235));
+}
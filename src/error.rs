@@ -0,0 +1,48 @@
+use std::fmt;
+use std::io;
+
+/// Errors of the trajectory and file handling
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be opened or created
+    FileNotFound(String, io::Error),
+    /// Reading or writing failed
+    Io(io::Error),
+    /// A frame in the trajectory could not be parsed (line number and reason)
+    MalformedFrame(usize, String),
+    /// There is no further frame in the trajectory
+    EndOfTrajectory,
+    /// A state could not be serialized or deserialized
+    Serialization(String),
+    /// A figure could not be drawn
+    Plot(String),
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::FileNotFound(ref filename, ref e) => write!(f, "could not open {}: {}", filename, e),
+            Error::Io(ref e) => write!(f, "i/o error: {}", e),
+            Error::MalformedFrame(line, ref reason) => write!(f, "malformed frame in line {}: {}", line, reason),
+            Error::EndOfTrajectory => write!(f, "no further frame in trajectory"),
+            Error::Serialization(ref reason) => write!(f, "serialization failed: {}", reason),
+            Error::Plot(ref reason) => write!(f, "plotting failed: {}", reason),
+        }
+    }
+}
+
+impl ::std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// Prints the error to stderr and exits, for the command line tools
+pub fn exit_with<T>(error: Error) -> T {
+    eprintln!("Error: {}", error);
+    ::std::process::exit(1);
+}
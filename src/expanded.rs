@@ -0,0 +1,162 @@
+//! Expanded ensembles: ladders of states with learned weights and the coupling of a tagged particle (the solute) to the others
+
+use rand::Rng;
+use multicanonical::FLATNESS;
+use statistics::Statistics;
+use Step;
+
+/// Solute particle of the expanded ensemble
+pub const SOLUTE : usize = 0;
+
+/// Soft-core parameter alpha of the coupled pair energies
+pub const SOFT_CORE_ALPHA : f64 = 0.5;
+
+/// Squared soft-core distance of a solute pair at coupling lambda, (r^6 + alpha (1 - lambda) sigma^6)^(1/3). The coupled
+/// pair energy is lambda times the pair energy at this distance (Beutler et al.), which stays finite at overlaps with
+/// weak couplings unlike the linear scaling of the energy.
+pub fn get_soft_core_distance_squared(dist_squared: f64, lambda: f64) -> f64 {
+    return (dist_squared.powi(3) + SOFT_CORE_ALPHA * (1.0 - lambda) * ::LJ_SIG.powi(6)).cbrt();
+}
+
+#[test]
+fn test_get_soft_core_distance_squared() {
+    assert!( (get_soft_core_distance_squared(1.44, 1.0) - 1.44).abs() < 0.00001);
+    assert!( (get_soft_core_distance_squared(0.0, 0.0) - 0.5f64.cbrt()).abs() < 0.00001);
+}
+
+/// Weights of a ladder of states that a simulation walks through, e.g. the couplings of the expanded ensemble or the
+/// temperatures of simulated tempering. Moves go to the neighboring states and are accepted with
+/// min(1, exp(-d(beta U) + w_new - w_old)). While learning (the equilibration) every visit lowers the weight of the state
+/// by ln_f (Wang-Landau) so that all states are visited evenly. The reduced free energy of state k is w_k - ln P_k up to
+/// a constant.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StateLadder {
+    pub state: usize,
+    pub weights: Vec<f64>,
+    pub histogram: Vec<Step>,
+    pub ln_f: f64,
+    pub learning: bool,
+    pub attempts: Step,
+    pub accepted: Step,
+}
+
+impl StateLadder {
+    pub fn new(states: usize, state: usize) -> StateLadder {
+        return StateLadder { state: state, weights: vec![0.0; states], histogram: vec![0; states], ln_f: 1.0, learning: true, attempts: 0, accepted: 0 };
+    }
+
+    /// One of the neighboring states at random, None beyond the ends of the ladder
+    pub fn propose<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+        let new = if rng.gen::<bool>() { self.state + 1 } else { self.state.wrapping_sub(1) };
+        return if new < self.weights.len() { Some(new) } else { None };
+    }
+
+    /// Metropolis step to the proposed state with the change of the reduced energy beta U, then counts the visit of the
+    /// current state. Returns true if the move was accepted.
+    pub fn step<R: Rng>(&mut self, new: Option<usize>, d_reduced_energy: f64, rng: &mut R) -> bool {
+        self.attempts += 1;
+        let accepted = match new {
+            Some(new) => rng.gen::<f64>() < (-d_reduced_energy + self.weights[new] - self.weights[self.state]).exp(),
+            None => false,
+        };
+        if accepted {
+            self.state = new.unwrap();
+            self.accepted += 1;
+        }
+        self.histogram[self.state] += 1;
+        if self.learning { self.weights[self.state] -= self.ln_f; }
+        return accepted;
+    }
+
+    /// Halves ln f and starts a new histogram if all states were visited evenly, returns true then
+    pub fn update_modification_factor(&mut self) -> bool {
+        let mean = self.histogram.iter().sum::<Step>() as f64 / self.histogram.len() as f64;
+        if mean == 0.0 || self.histogram.iter().any(|&h| (h as f64) < FLATNESS * mean) { return false; }
+        self.ln_f /= 2.0;
+        self.histogram = vec![0; self.histogram.len()];
+        return true;
+    }
+
+    /// Ends the learning stage, the weights stay fixed and the histogram starts over for the sampling
+    pub fn freeze(&mut self) {
+        self.learning = false;
+        self.histogram = vec![0; self.histogram.len()];
+    }
+
+    /// Reduced free energies of the states relative to the first, NaN for states that were not visited
+    pub fn get_free_energies(&self) -> Vec<f64> {
+        let free_energy = |k: usize| if self.histogram[k] > 0 { self.weights[k] - (self.histogram[k] as f64).ln() } else { f64::NAN };
+        return (0..self.weights.len()).map(|k| free_energy(k) - free_energy(0)).collect();
+    }
+}
+
+#[test]
+fn test_state_ladder() {
+    let mut rng = ::rand::thread_rng();
+    let mut ladder = StateLadder::new(5, 4);
+    // rejected beyond the end, the visit still counts
+    assert!(!ladder.step(None, 0.0, &mut rng));
+    assert_eq!(-1.0, ladder.weights[4]);
+    assert!(!ladder.update_modification_factor());
+    for state in (0..4).rev() {
+        assert!(ladder.step(Some(state), -1.0, &mut rng));
+    }
+    assert_eq!((5, 4), (ladder.attempts, ladder.accepted));
+    assert!(ladder.update_modification_factor());
+    assert_eq!(0.5, ladder.ln_f);
+    let new = ladder.propose(&mut rng);
+    assert!(new.is_none() || new == Some(1));
+
+    // states visited in proportion to exp(w_k - beta F_k) give back the free energies
+    ladder.freeze();
+    ladder.weights = vec![0.0, 1.0, 2.0, 3.0, 4.0];
+    ladder.histogram = vec![100, 100, 200, 400, 100];
+    let free_energies = ladder.get_free_energies();
+    assert!( (free_energies[2] - (2.0 - 2f64.ln())).abs() < 0.00001, "{:?}", free_energies);
+    assert!( (free_energies[4] - 4.0).abs() < 0.00001, "{:?}", free_energies);
+}
+
+/// Couplings lambda from 0 (ideal particle) to 1 (full lj particle) of the soft-core pair energies of the solute
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExpandedEnsemble {
+    pub lambdas: Vec<f64>,
+    pub ladder: StateLadder,
+    // insertion factors exp(-beta U) of the full solute energy in the decoupled state, a widom estimate of the same free energy
+    #[serde(default)]
+    pub widom: Statistics,
+}
+
+impl ExpandedEnsemble {
+    /// Evenly spaced states between 0 and 1, starting from the full coupling
+    pub fn new(states: usize) -> ExpandedEnsemble {
+        return ExpandedEnsemble {
+            lambdas: (0..states).map(|k| k as f64 / (states - 1) as f64).collect(),
+            ladder: StateLadder::new(states, states - 1),
+            widom: Statistics::default(),
+        };
+    }
+
+    pub fn lambda(&self) -> f64 {
+        return self.lambdas[self.ladder.state];
+    }
+
+    /// Ends the learning stage, the widom average starts over for the sampling
+    pub fn freeze(&mut self) {
+        self.ladder.freeze();
+        self.widom.clear();
+    }
+
+    /// Widom estimate of the free energy of the full coupling in kT, -ln <exp(-beta U)> of the decoupled solute
+    pub fn get_widom_free_energy(&self) -> f64 {
+        return -self.widom.mean().ln();
+    }
+}
+
+#[test]
+fn test_expanded_ensemble() {
+    let mut expanded = ExpandedEnsemble::new(5);
+    assert_eq!(vec![0.0, 0.25, 0.5, 0.75, 1.0], expanded.lambdas);
+    assert_eq!(1.0, expanded.lambda());
+    expanded.widom = Statistics::from_sum(2.0 * ::std::f64::consts::E, 2);
+    assert!( (expanded.get_widom_free_energy() + 1.0).abs() < 0.00001);
+}
@@ -0,0 +1,253 @@
+//! C interface to embed the sampler in other codes, declared in include/mclj.h
+use std::ptr;
+use std::slice;
+use rand::StdRng;
+use system::{Params, System};
+use Coordinate;
+
+/// Run parameters passed from C, initialize with mclj_params_default
+#[repr(C)]
+pub struct MCLJParams {
+    pub num_particles: usize,
+    pub density: f64,
+    pub temperature: f64,
+    pub cutoff: f64,
+    pub displacement: f64,
+    pub vacuum_slab: f64,
+    pub recompute_interval: u64,
+    pub sweeps: i32,
+    pub tailcorr: i32,
+    pub shift: i32,
+}
+
+/// Opaque handle of a system and its random number generator
+pub struct MCLJSystem {
+    system: System,
+    rng: StdRng,
+}
+
+/// Default run parameters of mc
+#[no_mangle]
+pub extern "C" fn mclj_params_default() -> MCLJParams {
+    let params = Params::default();
+    return MCLJParams {
+        num_particles: params.num_particles,
+        density: params.density,
+        temperature: params.temperature,
+        cutoff: params.cutoff,
+        displacement: params.displacement,
+        vacuum_slab: params.vacuum_slab,
+        recompute_interval: params.recompute_interval,
+        sweeps: params.sweeps as i32,
+        tailcorr: params.tailcorr as i32,
+        shift: params.shift as i32,
+    };
+}
+
+/// Creates a system with randomly placed particles, returns NULL for invalid parameters
+///
+/// # Safety
+/// params has to point to a valid MCLJParams
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_new(params: *const MCLJParams) -> *mut MCLJSystem {
+    let params = match params.as_ref() {
+        Some(params) => params,
+        None => return ptr::null_mut(),
+    };
+    let params = Params {
+        num_particles: params.num_particles,
+        density: params.density,
+        temperature: params.temperature,
+        cutoff: params.cutoff,
+        displacement: params.displacement,
+        vacuum_slab: params.vacuum_slab,
+        recompute_interval: params.recompute_interval,
+        sweeps: params.sweeps != 0,
+        tailcorr: params.tailcorr != 0,
+        shift: params.shift != 0,
+        ..Params::default()
+    };
+    if !params.validate().is_empty() {
+        return ptr::null_mut();
+    }
+    let mut rng = match StdRng::new() {
+        Ok(rng) => rng,
+        Err(_) => return ptr::null_mut(),
+    };
+    let system = System::new(params, &mut rng);
+    return Box::into_raw(Box::new(MCLJSystem { system: system, rng: rng }));
+}
+
+/// Frees a system created by mclj_system_new
+///
+/// # Safety
+/// system has to be NULL or a pointer returned by mclj_system_new that was not freed before
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_free(system: *mut MCLJSystem) {
+    if !system.is_null() {
+        drop(Box::from_raw(system));
+    }
+}
+
+/// Runs steps steps (or sweeps), returns 0 on success and -1 for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_step(system: *mut MCLJSystem, steps: u64) -> i32 {
+    return match system.as_mut() {
+        Some(s) => { s.system.run(steps, &mut s.rng); 0 },
+        None => -1,
+    };
+}
+
+/// Changes the temperature of a running system, returns 0 on success and -1 for a NULL system or a non positive temperature
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_set_temperature(system: *mut MCLJSystem, temperature: f64) -> i32 {
+    let s = match system.as_mut() {
+        Some(s) if temperature > 0.0 => s,
+        _ => return -1,
+    };
+    s.system.set_temperature(temperature);
+    return 0;
+}
+
+/// Sets the maximum displacement (limited to half the box), returns 0 on success and -1 for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_set_displacement(system: *mut MCLJSystem, displacement: f64) -> i32 {
+    return match system.as_mut() {
+        Some(s) if displacement > 0.0 => { s.system.displacement = displacement.min(s.system.max_displacement); 0 },
+        _ => -1,
+    };
+}
+
+/// Current energy of the system, NaN for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_energy(system: *const MCLJSystem) -> f64 {
+    return system.as_ref().map_or(f64::NAN, |s| s.system.energy);
+}
+
+/// Current virial of the system, NaN for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_virial(system: *const MCLJSystem) -> f64 {
+    return system.as_ref().map_or(f64::NAN, |s| s.system.virial);
+}
+
+/// Current pressure of the system, NaN for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_pressure(system: *const MCLJSystem) -> f64 {
+    return system.as_ref().map_or(f64::NAN, |s| s.system.pressure());
+}
+
+/// Acceptance ratio since creation or the last reset, NaN for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_acceptance(system: *const MCLJSystem) -> f64 {
+    return system.as_ref().map_or(f64::NAN, |s| {
+        let tries = s.system.counters.step_counter * s.system.moves_per_step();
+        s.system.counters.accept_counter as f64 / tries as f64
+    });
+}
+
+/// Number of particles, 0 for a NULL system
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_num_particles(system: *const MCLJSystem) -> usize {
+    return system.as_ref().map_or(0, |s| s.system.num_particles);
+}
+
+/// Copies the box lengths to box_size[3], returns 0 on success and -1 for NULL pointers
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new, box_size NULL or writable for 3 doubles
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_box(system: *const MCLJSystem, box_size: *mut f64) -> i32 {
+    let s = match system.as_ref() {
+        Some(s) if !box_size.is_null() => s,
+        _ => return -1,
+    };
+    let out = slice::from_raw_parts_mut(box_size, 3);
+    out.copy_from_slice(&[s.system.l_x, s.system.l_y, s.system.l_z]);
+    return 0;
+}
+
+/// Copies the positions as x0 y0 z0 x1 ... to positions[3 * num_particles], returns 0 on success and -1 for NULL pointers
+///
+/// # Safety
+/// system has to be NULL or a valid pointer returned by mclj_system_new, positions NULL or writable for 3 * num_particles doubles
+#[no_mangle]
+pub unsafe extern "C" fn mclj_system_positions(system: *const MCLJSystem, positions: *mut f64) -> i32 {
+    let s = match system.as_ref() {
+        Some(s) if !positions.is_null() => s,
+        _ => return -1,
+    };
+    let out = slice::from_raw_parts_mut(positions, 3 * s.system.num_particles);
+    for i in 0..s.system.num_particles {
+        out[3 * i] = s.system.rx[i].to_f64();
+        out[3 * i + 1] = s.system.ry[i].to_f64();
+        out[3 * i + 2] = s.system.rz[i].to_f64();
+    }
+    return 0;
+}
+
+#[test]
+fn test_ffi_system() {
+    let mut params = mclj_params_default();
+    params.num_particles = 100;
+    params.cutoff = 2.0;
+    params.sweeps = 1;
+    unsafe {
+        let system = mclj_system_new(&params);
+        assert!(!system.is_null());
+        assert_eq!(0, mclj_system_step(system, 10));
+        assert!(mclj_system_energy(system).is_finite());
+        assert!(mclj_system_acceptance(system) > 0.0);
+        assert_eq!(0, mclj_system_set_temperature(system, 1.2));
+        assert_eq!(-1, mclj_system_set_temperature(system, 0.0));
+
+        let n = mclj_system_num_particles(system);
+        let mut positions = vec![0.0; 3 * n];
+        let mut box_size = [0.0; 3];
+        assert_eq!(0, mclj_system_positions(system, positions.as_mut_ptr()));
+        assert_eq!(0, mclj_system_box(system, box_size.as_mut_ptr()));
+        assert!(positions.iter().all(|&x| x >= 0.0 && x < box_size[0]));
+        mclj_system_free(system);
+
+        // invalid parameters and null handles
+        params.density = -1.0;
+        assert!(mclj_system_new(&params).is_null());
+        assert_eq!(-1, mclj_system_step(ptr::null_mut(), 1));
+        assert!(mclj_system_energy(ptr::null()).is_nan());
+    }
+}
+
+#[test]
+fn test_ffi_header() {
+    // every exported function is declared in the header
+    let header = include_str!("../include/mclj.h");
+    for line in include_str!("ffi.rs").lines() {
+        if let Some(start) = line.find("extern \"C\" fn ") {
+            let name = line[start + 14..].split('(').next().unwrap();
+            assert!(header.contains(&format!("{}(", name)), "{} missing in include/mclj.h", name);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+extern crate rand;
+extern crate mclj;
+extern crate argparse;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use argparse::{ArgumentParser, Store};
+use mclj::statistics::get_linear_fit;
+use mclj::system::*;
+use mclj::Step;
+
+/// Parses a , separated list of system sizes, None for an empty list or a size below 2
+fn parse_sizes(list: &str) -> Option<Vec<usize>> {
+    let sizes : Option<Vec<usize>> = list.split(',').map(|size| size.trim().parse::<usize>().ok().filter(|&n| n > 1)).collect();
+    return sizes.filter(|sizes| !sizes.is_empty());
+}
+
+#[test]
+fn test_parse_sizes() {
+    assert_eq!(Some(vec![256, 512, 1024]), parse_sizes("256, 512,1024"));
+    assert_eq!(None, parse_sizes("256,,512"));
+    assert_eq!(None, parse_sizes("1"));
+}
+
+fn main() {
+    let mut sizes : String = "256,512,1024,2048".to_string();
+    let mut density : f64 = 0.8;
+    let mut temperature : f64 = 1.0;
+    let mut cutoff : f64 = 3.0;
+    let mut eq_sweeps : Step = 2000;
+    let mut sample_sweeps : Step = 10000;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut seed : usize = 0;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Runs one state point at several system sizes and extrapolates energy and pressure linearly in 1/N to the infinite system.");
+        ap.refer(&mut sizes)
+            .add_option(&["-p", "--particles"], Store, "Comma separated list of system sizes");
+        ap.refer(&mut density)
+            .add_option(&["-d", "--density"], Store, "Density in reduced units");
+        ap.refer(&mut temperature)
+            .add_option(&["-t", "--temperature"], Store, "Temperature in reduced units");
+        ap.refer(&mut cutoff)
+            .add_option(&["--cutoff"], Store, "Cutoff radius in sigma, the same for all sizes (with tail corrections)");
+        ap.refer(&mut eq_sweeps)
+            .add_option(&["-m", "--nminimsweeps"], Store, "Equilibration sweeps per size");
+        ap.refer(&mut sample_sweeps)
+            .add_option(&["-n", "--nsweeps"], Store, "Sampling sweeps per size");
+        ap.refer(&mut threads)
+            .add_option(&["-j", "--threads"], Store, "Number of parallel runs (1=sequential)");
+        ap.refer(&mut seed)
+            .add_option(&["--seed"], Store, "Master seed, every size runs on its own stream of it (0=seeded from the os)");
+        ap.parse_args_or_exit();
+    }
+    let sizes = parse_sizes(&sizes).unwrap_or_else(|| {
+        eprintln!("Error: invalid list of system sizes '{}'.", sizes);
+        std::process::exit(1);
+    });
+    if sample_sweeps < SAMPLE_BLOCKS {
+        eprintln!("Error: at least {} sampling sweeps are needed for the error estimates.", SAMPLE_BLOCKS);
+        std::process::exit(1);
+    }
+    let params : Vec<Params> = sizes.iter().map(|&num_particles| Params {
+        num_particles: num_particles, density: density, temperature: temperature, cutoff: cutoff,
+        shift: false, tailcorr: true, sweeps: true, eq_steps: eq_sweeps, sample_steps: sample_sweeps,
+        ..Params::default()
+    }).collect();
+    // the smallest box limits the cutoff, check all sizes before running any
+    let errors : Vec<String> = sizes.iter().zip(params.iter()).flat_map(|(n, params)| params.validate().into_iter().map(move |error| format!("N = {}: {}", n, error))).collect();
+    if !errors.is_empty() {
+        for error in &errors { eprintln!("Error at {}", error); }
+        std::process::exit(1);
+    }
+    eprintln!("Running T = {}, rho = {} at {} sizes on {} threads", temperature, density, sizes.len(), threads);
+
+    let queue : Arc<Mutex<VecDeque<usize>>> = Arc::new(Mutex::new((0..sizes.len()).collect()));
+    let results : Arc<Mutex<Vec<Option<Averages>>>> = Arc::new(Mutex::new(vec![None; sizes.len()]));
+    let params = Arc::new(params);
+    let workers : Vec<thread::JoinHandle<()>> = (0..threads.max(1)).map(|_| {
+        let (queue, results, params) = (queue.clone(), results.clone(), params.clone());
+        thread::spawn(move || loop {
+            let index = match queue.lock().unwrap().pop_front() {
+                Some(index) => index,
+                None => break,
+            };
+            let mut rng = get_stream_rng(seed, StreamDomain::FiniteSize, index as u64);
+            let mut system = System::new(params[index].clone(), &mut rng);
+            system.equilibrate(eq_sweeps, &mut rng);
+            let averages = system.sample(sample_sweeps, &mut rng);
+            eprintln!("Done N = {}", params[index].num_particles);
+            results.lock().unwrap()[index] = Some(averages);
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().expect("worker panicked");
+    }
+
+    let results = results.lock().unwrap();
+    let averages : Vec<&Averages> = results.iter().map(|averages| averages.as_ref().expect("missing system size")).collect();
+    let inverse : Vec<f64> = sizes.iter().map(|&n| 1.0 / n as f64).collect();
+    let energy : Vec<f64> = averages.iter().zip(sizes.iter()).map(|(a, &n)| a.energy / n as f64).collect();
+    let energy_error : Vec<f64> = averages.iter().zip(sizes.iter()).map(|(a, &n)| a.energy_error / n as f64).collect();
+    let pressure : Vec<f64> = averages.iter().map(|a| a.pressure).collect();
+    let pressure_error : Vec<f64> = averages.iter().map(|a| a.pressure_error).collect();
+
+    println!("# T = {}, rho = {}, cutoff {} with tail corrections", temperature, density, cutoff);
+    println!("{:>6} {:>10} {:>24} {:>24}", "N", "1/N", "U/N", "p");
+    for i in 0..sizes.len() {
+        println!("{:>6} {:>10.6} {:>24} {:>24}", sizes[i], inverse[i],
+                 format!("{:.5} +- {:.5}", energy[i], energy_error[i]), format!("{:.5} +- {:.5}", pressure[i], pressure_error[i]));
+    }
+    if sizes.len() < 2 {
+        println!("# at least two sizes are needed for the extrapolation");
+        return;
+    }
+    // weighted by the block errors, the intercept is the value of the infinite system
+    for &(name, values, errors) in [("U/N", &energy, &energy_error), ("p", &pressure, &pressure_error)].iter() {
+        let (intercept, slope, intercept_error, slope_error) = get_linear_fit(&inverse, values, errors);
+        println!("# {} extrapolated to 1/N -> 0: {:.5} +- {:.5}, slope {:.3} +- {:.3}", name, intercept, intercept_error, slope, slope_error);
+    }
+}
@@ -0,0 +1,113 @@
+//! Gay-Berne pair energies of uniaxial ellipsoids along the body z axis of their orientations
+
+use energy::{dot, get_displacement_with_pbc, CompensatedSum};
+use orientation::{get_axis, Quaternion};
+use {Coordinate, Real};
+
+/// Exponents mu and nu of the orientation dependent well depth (the common GB(kappa, kappa', 2, 1))
+pub const GB_MU : i32 = 2;
+pub const GB_NU : f64 = 1.0;
+
+/// Smallest reduced distance of a pair, deeper overlaps of a random start count as this one and have no virial
+pub const GB_MIN_RHO : f64 = 0.2;
+
+/// Gay-Berne potential of ellipsoids with the length to breadth ratio kappa and the ratio kappa' of the side by side to
+/// the end to end well depth. sigma and epsilon of the lj units are the breadth and the side by side cross well depth.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GayBerne {
+    pub kappa: f64,
+    pub kappa_prime: f64,
+    chi: f64,
+    chi_prime: f64,
+}
+
+impl GayBerne {
+    pub fn new(kappa: f64, kappa_prime: f64) -> GayBerne {
+        let root = kappa_prime.powf(1.0 / GB_MU as f64);
+        return GayBerne { kappa: kappa, kappa_prime: kappa_prime, chi: (kappa * kappa - 1.0) / (kappa * kappa + 1.0), chi_prime: (root - 1.0) / (root + 1.0) };
+    }
+
+    /// Energy and virial of a pair at separation r (from 2 to 1) with the axes u1 and u2. The range sigma and the well
+    /// depth epsilon depend on the orientations, the energy is 4 epsilon (rho^-12 - rho^-6) of rho = (r - sigma + sigma_s) / sigma_s.
+    /// The virial -dU/ds of the pair scaled by s is 4 epsilon (12 rho^-12 - 6 rho^-6) r / (rho sigma_s).
+    pub fn eval_pair_energy(&self, r: [f64; 3], dist_squared: f64, u1: [f64; 3], u2: [f64; 3]) -> (f64, f64) {
+        let dist = dist_squared.sqrt();
+        let (r1, r2, u12) = (dot(r, u1) / dist, dot(r, u2) / dist, dot(u1, u2));
+        let anisotropy = |chi: f64| 1.0 - chi / 2.0 * ((r1 + r2).powi(2) / (1.0 + chi * u12) + (r1 - r2).powi(2) / (1.0 - chi * u12));
+        let sigma = ::LJ_SIG / anisotropy(self.chi).sqrt();
+        let epsilon = ::LJ_EPS * (1.0 - self.chi * self.chi * u12 * u12).powf(-0.5 * GB_NU) * anisotropy(self.chi_prime).powi(GB_MU);
+        let rho = (dist - sigma + ::LJ_SIG) / ::LJ_SIG;
+        if rho < GB_MIN_RHO { return (4.0 * epsilon * (GB_MIN_RHO.powi(-12) - GB_MIN_RHO.powi(-6)), 0.0); }
+        let rho6 = rho.powi(-6);
+        return (4.0 * epsilon * (rho6 * rho6 - rho6), 4.0 * epsilon * (12.0 * rho6 * rho6 - 6.0 * rho6) * dist / rho / ::LJ_SIG);
+    }
+
+    // energy and virial of the pair i j if it is within the cutoff
+    fn eval_particles(&self, rx: &[Real], ry: &[Real], rz: &[Real], orientations: &[Quaternion], i: usize, j: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64) -> (f64, f64) {
+        let r = [get_displacement_with_pbc(rx[i].to_f64(), rx[j].to_f64(), l_x, l_x / 2.0),
+                 get_displacement_with_pbc(ry[i].to_f64(), ry[j].to_f64(), l_y, l_y / 2.0),
+                 get_displacement_with_pbc(rz[i].to_f64(), rz[j].to_f64(), l_z, l_z / 2.0)];
+        let dist_squared = dot(r, r);
+        if dist_squared >= cutoff_squared { return (0.0, 0.0); }
+        return self.eval_pair_energy(r, dist_squared, get_axis(orientations[i]), get_axis(orientations[j]));
+    }
+
+    /// Energy and virial of particle p_index with all others within the cutoff
+    pub fn get_particle_energy(&self, rx: &[Real], ry: &[Real], rz: &[Real], orientations: &[Quaternion], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64) -> (f64, f64) {
+        let (mut energy, mut virial) = (0.0, 0.0);
+        for j in (0..num_particles).filter(|&j| j != p_index) {
+            let (e, v) = self.eval_particles(rx, ry, rz, orientations, p_index, j, l_x, l_y, l_z, cutoff_squared);
+            energy += e;
+            virial += v;
+        }
+        return (energy, virial);
+    }
+
+    /// Total energy and virial of all pairs within the cutoff
+    pub fn get_total_energy(&self, rx: &[Real], ry: &[Real], rz: &[Real], orientations: &[Quaternion], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64) -> (f64, f64) {
+        let (mut energy, mut virial) = (CompensatedSum::default(), CompensatedSum::default());
+        for i in 0..num_particles {
+            for j in i+1..num_particles {
+                let (e, v) = self.eval_particles(rx, ry, rz, orientations, i, j, l_x, l_y, l_z, cutoff_squared);
+                energy.add(e);
+                virial.add(v);
+            }
+        }
+        return (energy.value(), virial.value());
+    }
+}
+
+#[test]
+fn test_gay_berne_pair_energy() {
+    // spheres are lj particles
+    let spheres = GayBerne::new(1.0, 1.0);
+    let (e, v) = spheres.eval_pair_energy([1.2, 0.0, 0.0], 1.44, [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]);
+    let (e_lj, v_lj) = ::energy::eval_pair_energy(1.44, 0.0);
+    assert!( (e - e_lj).abs() < 0.00001 && (v - v_lj).abs() < 0.00001, "{} {} {} {}", e, e_lj, v, v_lj);
+
+    // contact of the ends at kappa sigma and of the sides at sigma, wells kappa' times deeper side by side
+    let gb = GayBerne::new(3.0, 5.0);
+    let z = [0.0, 0.0, 1.0];
+    assert!(gb.eval_pair_energy([0.0, 0.0, 3.0], 9.0, z, z).0.abs() < 0.00001);
+    assert!(gb.eval_pair_energy([1.0, 0.0, 0.0], 1.0, z, z).0.abs() < 0.00001);
+    let minimum = 2f64.powf(1.0 / 6.0) - 1.0;
+    let (end, _) = gb.eval_pair_energy([0.0, 0.0, 3.0 + minimum], (3.0 + minimum).powi(2), z, z);
+    let (side, _) = gb.eval_pair_energy([1.0 + minimum, 0.0, 0.0], (1.0 + minimum).powi(2), z, z);
+    assert!( (side / end - 5.0).abs() < 0.00001, "{} {}", side, end);
+
+    // finite at overlaps
+    assert!(gb.eval_pair_energy([0.0, 0.0, 0.5], 0.25, z, z).0.is_finite());
+}
+
+#[test]
+fn test_gay_berne_particle_energy() {
+    let (rx, ry, rz) = (vec![1.0, 2.2, 1.0], vec![1.0, 1.0, 5.0], vec![1.0, 1.0, 1.0]);
+    let orientations = vec![::orientation::IDENTITY; 3];
+    let gb = GayBerne::new(3.0, 5.0);
+    let (e0, v0) = gb.get_particle_energy(&rx, &ry, &rz, &orientations, 0, 3, 10.0, 10.0, 10.0, 16.0);
+    let (e1, _) = gb.get_particle_energy(&rx, &ry, &rz, &orientations, 1, 3, 10.0, 10.0, 10.0, 16.0);
+    let (total, virial) = gb.get_total_energy(&rx, &ry, &rz, &orientations, 3, 10.0, 10.0, 10.0, 16.0);
+    // the third particle is beyond the cutoff of both
+    assert!( (e0 - e1).abs() < 0.00001 && (total - e0).abs() < 0.00001 && (virial - v0).abs() < 0.00001);
+    assert!(e0 < 0.0);
+}
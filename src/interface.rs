@@ -0,0 +1,295 @@
+//! Interfaces: intrinsic surfaces of liquid slabs normal to z and the radial profiles of droplets
+
+use {Coordinate, Real};
+use energy::{get_displacement_with_pbc, get_particle_virials};
+use observables::{get_cluster_ids, NEIGHBOR_RADIUS};
+
+/// Radius of the particles seen by the probe sphere
+pub const PARTICLE_RADIUS : f64 = 0.5;
+
+/// Surface particles of both sides of a slab found by a probe sphere that moves along z from the vapor on every line
+/// of a grid x grid mesh and stops at the first liquid particle it touches (ITIM). The particle heights are relative
+/// to the center of the slab, the upper surface is at positive heights.
+pub struct IntrinsicSurface {
+    pub grid: usize,
+    pub l_x: f64,
+    pub l_y: f64,
+    pub center: f64, // z of the slab center
+    pub top: Vec<usize>, // particle touched first on every line from above, row by row in y
+    pub bottom: Vec<usize>,
+    pub top_heights: Vec<f64>,
+    pub bottom_heights: Vec<f64>,
+}
+
+/// Height of z above center in a periodic box of length l_z, between -l_z/2 and l_z/2
+fn get_height(z: f64, center: f64, l_z: f64) -> f64 {
+    return get_displacement_with_pbc(z, center, l_z, l_z / 2.0);
+}
+
+/// Mean of periodic coordinates in [0, l), the mean angle on a circle of circumference l
+fn get_circular_mean<I: Iterator<Item = f64>>(values: I, l: f64) -> f64 {
+    let (s, c) = values.map(|v| v / l * 2.0 * ::std::f64::consts::PI).fold((0.0, 0.0), |(s, c), angle| (s + angle.sin(), c + angle.cos()));
+    return (s.atan2(c) / (2.0 * ::std::f64::consts::PI) * l + l) % l;
+}
+
+/// Center along z of the liquid slab, the circular mean of the largest cluster
+pub fn get_slab_center(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64) -> f64 {
+    let clusters = get_cluster_ids(rx, ry, rz, num_particles, l_x, l_y, l_z, NEIGHBOR_RADIUS);
+    return get_circular_mean((0..num_particles).filter(|&i| clusters[i] == 0).map(|i| rz[i].to_f64()), l_z);
+}
+
+impl IntrinsicSurface {
+    /// Finds the surfaces of the largest cluster of a configuration with a probe sphere of probe_radius
+    pub fn new(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, probe_radius: f64, grid: usize) -> IntrinsicSurface {
+        let clusters = get_cluster_ids(rx, ry, rz, num_particles, l_x, l_y, l_z, NEIGHBOR_RADIUS);
+        let liquid : Vec<usize> = (0..num_particles).filter(|&i| clusters[i] == 0).collect();
+
+        // the slab may cross the periodic boundary in z
+        let center = get_circular_mean(liquid.iter().map(|&i| rz[i].to_f64()), l_z);
+
+        let contact = probe_radius + PARTICLE_RADIUS;
+        let mut surface = IntrinsicSurface { grid: grid, l_x: l_x, l_y: l_y, center: center, top: vec![], bottom: vec![], top_heights: vec![], bottom_heights: vec![] };
+        for line in 0..grid * grid {
+            let x = (line % grid) as f64 * l_x / grid as f64;
+            let y = (line / grid) as f64 * l_y / grid as f64;
+            let mut top = (f64::NEG_INFINITY, usize::MAX);
+            let mut bottom = (f64::INFINITY, usize::MAX);
+            for &i in &liquid {
+                let dx = get_displacement_with_pbc(rx[i].to_f64(), x, l_x, l_x / 2.0);
+                let dy = get_displacement_with_pbc(ry[i].to_f64(), y, l_y, l_y / 2.0);
+                let lateral = dx * dx + dy * dy;
+                if lateral >= contact * contact { continue; }
+                let height = get_height(rz[i].to_f64(), center, l_z);
+                let reach = (contact * contact - lateral).sqrt();
+                if height + reach > top.0 { top = (height + reach, i); }
+                if height - reach < bottom.0 { bottom = (height - reach, i); }
+            }
+            // lines through holes of the liquid belong to no particle
+            surface.top.push(top.1);
+            surface.bottom.push(bottom.1);
+            surface.top_heights.push(if top.1 == usize::MAX { f64::NAN } else { get_height(rz[top.1].to_f64(), center, l_z) });
+            surface.bottom_heights.push(if bottom.1 == usize::MAX { f64::NAN } else { get_height(rz[bottom.1].to_f64(), center, l_z) });
+        }
+        return surface;
+    }
+
+    /// Distinct surface particles of the upper and the lower surface
+    pub fn get_surface_particles(&self) -> (Vec<usize>, Vec<usize>) {
+        let unique = |lines: &[usize]| {
+            let mut particles : Vec<usize> = lines.iter().cloned().filter(|&i| i != usize::MAX).collect();
+            particles.sort();
+            particles.dedup();
+            particles
+        };
+        return (unique(&self.top), unique(&self.bottom));
+    }
+
+    // grid line closest to x, y
+    fn get_line(&self, x: f64, y: f64) -> usize {
+        let column = ((x / self.l_x * self.grid as f64).round() as usize) % self.grid;
+        let row = ((y / self.l_y * self.grid as f64).round() as usize) % self.grid;
+        return row * self.grid + column;
+    }
+
+    /// Distances of a particle to the upper and the lower surface, the heights of the surface particles on the grid
+    /// line closest to it. Positive distances point into the vapor.
+    pub fn get_intrinsic_distances(&self, x: f64, y: f64, z: f64, l_z: f64) -> (f64, f64) {
+        let line = self.get_line(x, y);
+        let height = get_height(z, self.center, l_z);
+        return (height - self.top_heights[line], self.bottom_heights[line] - height);
+    }
+}
+
+#[test]
+fn test_intrinsic_surface() {
+    // a simple cubic slab of 4 x 4 x 5 particles at spacing 1 in a box of height 20, one particle on top
+    let (mut rx, mut ry, mut rz) = (vec![], vec![], vec![]);
+    for i in 0..80 {
+        rx.push(Real::from_f64((i % 4) as f64 + 0.5));
+        ry.push(Real::from_f64((i / 4 % 4) as f64 + 0.5));
+        rz.push(Real::from_f64((i / 16) as f64 + 8.0));
+    }
+    rx.push(Real::from_f64(1.5));
+    ry.push(Real::from_f64(1.5));
+    rz.push(Real::from_f64(13.0));
+    let surface = IntrinsicSurface::new(&rx, &ry, &rz, 81, 4.0, 4.0, 20.0, 0.5, 8);
+    assert!( (surface.center - 10.04).abs() < 0.01, "{}", surface.center);
+
+    let (top, bottom) = surface.get_surface_particles();
+    assert!(top.contains(&80) && top.len() > 1 && top.iter().all(|&i| i >= 64), "{:?}", top);
+    assert_eq!(16, bottom.len());
+    assert!(bottom.iter().all(|&i| i < 16), "{:?}", bottom);
+
+    // a particle in the middle of the slab is below the upper surface and above the lower one
+    let (above_top, below_bottom) = surface.get_intrinsic_distances(3.5, 3.5, 10.0, 20.0);
+    assert!( (above_top + 2.0).abs() < 0.00001, "{}", above_top);
+    assert!( (below_bottom + 2.0).abs() < 0.00001, "{}", below_bottom);
+}
+
+/// Power spectrum |h_q|² of the modes q != 0 of a periodic height map of grid x grid lines over an l_x x l_y area, with
+/// h_q = 1/grid² sum h(r) exp(-i q r) so that capillary waves give <|h_q|²> = kT / (A gamma q²). Lines without a
+/// height (NaN) get the mean height. Returns |q| and |h_q|² of every mode.
+pub fn get_height_spectrum(heights: &[f64], grid: usize, l_x: f64, l_y: f64) -> Vec<(f64, f64)> {
+    let known : Vec<f64> = heights.iter().cloned().filter(|h| h.is_finite()).collect();
+    let mean = known.iter().sum::<f64>() / known.len().max(1) as f64;
+    let h : Vec<f64> = heights.iter().map(|&h| if h.is_finite() { h - mean } else { 0.0 }).collect();
+    let signed = |m: usize| if m > grid / 2 { m as f64 - grid as f64 } else { m as f64 };
+    let mut spectrum = vec![];
+    for mode in 1..grid * grid {
+        let (qx, qy) = (2.0 * ::std::f64::consts::PI * signed(mode % grid) / l_x, 2.0 * ::std::f64::consts::PI * signed(mode / grid) / l_y);
+        let (mut re, mut im) = (0.0, 0.0);
+        for (line, height) in h.iter().enumerate() {
+            let phase = qx * (line % grid) as f64 * l_x / grid as f64 + qy * (line / grid) as f64 * l_y / grid as f64;
+            re += height * phase.cos();
+            im -= height * phase.sin();
+        }
+        let norm = (grid * grid) as f64;
+        spectrum.push(((qx * qx + qy * qy).sqrt(), (re * re + im * im) / (norm * norm)));
+    }
+    return spectrum;
+}
+
+/// Surface tension from the fit of kT / (A gamma q²) to the averaged spectrum of the modes with |q| <= q_max, a
+/// least squares fit of 1/<|h_q|²> = A gamma q² / kT through the origin
+pub fn fit_capillary_tension(spectrum: &[(f64, f64)], temperature: f64, area: f64, q_max: f64) -> f64 {
+    let (mut xy, mut xx) = (0.0, 0.0);
+    for &(q, power) in spectrum.iter().filter(|m| m.0 <= q_max && m.1 > 0.0) {
+        xy += q * q / power;
+        xx += q.powi(4);
+    }
+    return xy / xx * temperature / area;
+}
+
+#[test]
+fn test_capillary_spectrum() {
+    // a single wave of amplitude 0.4 along x has |h_q|² = 0.04 at q = ±2 pi / l_x
+    let (grid, l) = (8, 10.0);
+    let heights : Vec<f64> = (0..grid * grid).map(|line| 1.0 + 0.4 * (2.0 * ::std::f64::consts::PI * (line % grid) as f64 / grid as f64).cos()).collect();
+    let spectrum = get_height_spectrum(&heights, grid, l, l);
+    assert_eq!(63, spectrum.len());
+    let q = 2.0 * ::std::f64::consts::PI / l;
+    let excited : Vec<&(f64, f64)> = spectrum.iter().filter(|m| m.1 > 0.00001).collect();
+    assert_eq!(2, excited.len());
+    assert!(excited.iter().all(|m| (m.0 - q).abs() < 0.00001 && (m.1 - 0.04).abs() < 0.00001), "{:?}", excited);
+
+    // an ideal capillary spectrum gives back its tension
+    let ideal : Vec<(f64, f64)> = spectrum.iter().map(|&(k, _)| (k, 0.9 / (100.0 * 0.5 * k * k))).collect();
+    assert!( (fit_capillary_tension(&ideal, 0.9, 100.0, 2.0) - 0.5).abs() < 0.00001);
+}
+
+/// Particle counts and radial virials in spherical shells of width around the center of a droplet (the largest
+/// cluster), summed over frames. The pair virials are split half and half between the particles, so the normal pressure
+/// of a shell is rho kT plus the radial components r W r of its particles over the shell volume.
+pub struct RadialProfile {
+    pub width: f64,
+    pub frames: usize,
+    pub counts: Vec<f64>,
+    pub virials: Vec<f64>,
+}
+
+impl RadialProfile {
+    pub fn new(r_max: f64, bins: usize) -> RadialProfile {
+        return RadialProfile { width: r_max / bins as f64, frames: 0, counts: vec![0.0; bins], virials: vec![0.0; bins] };
+    }
+
+    /// Adds a configuration with the pair virials within the cutoff, returns the droplet center
+    pub fn add(&mut self, rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64) -> [f64; 3] {
+        let clusters = get_cluster_ids(rx, ry, rz, num_particles, l_x, l_y, l_z, NEIGHBOR_RADIUS);
+        let liquid : Vec<usize> = (0..num_particles).filter(|&i| clusters[i] == 0).collect();
+        let center = [get_circular_mean(liquid.iter().map(|&i| rx[i].to_f64()), l_x),
+                      get_circular_mean(liquid.iter().map(|&i| ry[i].to_f64()), l_y),
+                      get_circular_mean(liquid.iter().map(|&i| rz[i].to_f64()), l_z)];
+        let virials = get_particle_virials(rx, ry, rz, num_particles, l_x, l_y, l_z, cutoff_squared, 0.0, 0.0, 0.0);
+        for i in 0..num_particles {
+            let d = [get_displacement_with_pbc(rx[i].to_f64(), center[0], l_x, l_x / 2.0),
+                     get_displacement_with_pbc(ry[i].to_f64(), center[1], l_y, l_y / 2.0),
+                     get_displacement_with_pbc(rz[i].to_f64(), center[2], l_z, l_z / 2.0)];
+            let r = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+            let bin = (r / self.width) as usize;
+            if bin >= self.counts.len() { continue; }
+            self.counts[bin] += 1.0;
+            if r > 0.0 {
+                let w = virials[i];
+                let radial = w[0] * d[0] * d[0] + w[1] * d[1] * d[1] + w[2] * d[2] * d[2] + 2.0 * (w[3] * d[0] * d[1] + w[4] * d[0] * d[2] + w[5] * d[1] * d[2]);
+                self.virials[bin] += radial / (r * r);
+            }
+        }
+        self.frames += 1;
+        return center;
+    }
+
+    fn get_shell_volume(&self, bin: usize) -> f64 {
+        return 4.0 / 3.0 * ::std::f64::consts::PI * self.width.powi(3) * ((bin + 1).pow(3) - bin.pow(3)) as f64;
+    }
+
+    pub fn get_density(&self) -> Vec<f64> {
+        return (0..self.counts.len()).map(|k| self.counts[k] / (self.frames as f64 * self.get_shell_volume(k))).collect();
+    }
+
+    pub fn get_normal_pressure(&self, temperature: f64) -> Vec<f64> {
+        return (0..self.counts.len()).map(|k| (self.counts[k] * temperature + self.virials[k]) / (self.frames as f64 * self.get_shell_volume(k))).collect();
+    }
+
+    /// Density and normal pressure of all shells between r_min and r_max together
+    pub fn get_region(&self, r_min: f64, r_max: f64, temperature: f64) -> (f64, f64) {
+        let (mut volume, mut count, mut virial) = (0.0, 0.0, 0.0);
+        for k in (0..self.counts.len()).filter(|&k| (k as f64 + 0.5) * self.width >= r_min && (k as f64 + 0.5) * self.width < r_max) {
+            volume += self.frames as f64 * self.get_shell_volume(k);
+            count += self.counts[k];
+            virial += self.virials[k];
+        }
+        return (count / volume, (count * temperature + virial) / volume);
+    }
+
+    /// Radius of the sphere of the liquid density that holds the same excess of particles over the vapor density
+    pub fn get_equimolar_radius(&self, liquid: f64, vapor: f64) -> f64 {
+        let density = self.get_density();
+        let excess : f64 = (0..density.len()).map(|k| (density[k] - vapor) * self.get_shell_volume(k)).sum();
+        return (excess / (liquid - vapor) * 3.0 / (4.0 * ::std::f64::consts::PI)).cbrt();
+    }
+}
+
+#[test]
+fn test_radial_profile() {
+    // a lattice droplet across the corner of the box
+    let (l, radius) = (12.0, 3.2);
+    let (mut rx, mut ry, mut rz) = (vec![], vec![], vec![]);
+    for i in 0..343 {
+        let d = [(i % 7) as f64 - 3.0, (i / 7 % 7) as f64 - 3.0, (i / 49) as f64 - 3.0];
+        if d[0] * d[0] + d[1] * d[1] + d[2] * d[2] > radius * radius { continue; }
+        rx.push(::Real::from_f64((d[0] + 0.5 + l) % l));
+        ry.push(::Real::from_f64((d[1] + 0.5 + l) % l));
+        rz.push(::Real::from_f64((d[2] + 0.5 + l) % l));
+    }
+    let n = rx.len();
+    let mut profile = RadialProfile::new(6.0, 60);
+    let center = profile.add(&rx, &ry, &rz, n, l, l, l, 6.25);
+    for k in 0..3 {
+        assert!( (center[k] - 0.5).abs() < 0.00001, "{:?}", center);
+    }
+    assert_eq!(n as f64, profile.counts.iter().sum::<f64>());
+    let expected = (3.0 * n as f64 / (4.0 * ::std::f64::consts::PI)).cbrt();
+    assert!( (profile.get_equimolar_radius(1.0, 0.0) - expected).abs() < 0.00001, "{}", profile.get_equimolar_radius(1.0, 0.0));
+    let (density, pressure) = profile.get_region(0.0, 6.0, 1.0);
+    assert!( (density * 4.0 / 3.0 * ::std::f64::consts::PI * 216.0 - n as f64).abs() < 0.00001, "{}", density);
+    assert!(pressure.is_finite());
+}
+
+/// Surface tension and radius of the surface of tension of a droplet from the normal pressure profile in shells of
+/// width (mechanical route): gamma_s³ = -dp²/8 int r³ dp_N/dr dr and R_s = 2 gamma_s / dp with dp = p_in - p_out.
+/// The integral is taken by parts against p_out so the noise of the profile is not differentiated.
+pub fn get_laplace_tension(normal_pressure: &[f64], width: f64, p_in: f64, p_out: f64) -> (f64, f64) {
+    let dp = p_in - p_out;
+    let integral : f64 = normal_pressure.iter().enumerate().map(|(k, p)| 3.0 * ((k as f64 + 0.5) * width).powi(2) * (p - p_out) * width).sum();
+    let tension = (dp * dp / 8.0 * integral).cbrt();
+    return (tension, 2.0 * tension / dp);
+}
+
+#[test]
+fn test_get_laplace_tension() {
+    // a step of the pressure at 3 gives the Laplace equation dp = 2 gamma / R
+    let pressure : Vec<f64> = (0..1000).map(|k| if (k as f64 + 0.5) * 0.01 < 3.0 { 0.1 } else { 0.02 }).collect();
+    let (tension, radius) = get_laplace_tension(&pressure, 0.01, 0.1, 0.02);
+    assert!( (radius - 3.0).abs() < 0.0001, "{}", radius);
+    assert!( (tension - 0.12).abs() < 0.0001, "{}", tension);
+}
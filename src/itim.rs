@@ -0,0 +1,88 @@
+extern crate mclj;
+extern crate argparse;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use argparse::{ArgumentParser, Store};
+use mclj::Coordinate;
+use mclj::error::{exit_with, Error};
+use mclj::interface::IntrinsicSurface;
+use mclj::statistics::{get_mean, get_standard_error};
+use mclj::trajectory::*;
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut skip : usize = 0;
+    let mut probe_radius : f64 = 1.0;
+    let mut grid : usize = 20;
+    let mut range : f64 = 6.0;
+    let mut bins : usize = 120;
+    let mut counts_file : String = String::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Identifies the surface particles of a liquid slab normal to z with a probe sphere (ITIM) and prints the density profile relative to the intrinsic surface.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Trajectory");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first frames, e.g. the equilibration");
+        ap.refer(&mut probe_radius)
+            .add_option(&["--probe"], Store, "Radius of the probe sphere");
+        ap.refer(&mut grid)
+            .add_option(&["--grid"], Store, "Probe lines per box length in x and y");
+        ap.refer(&mut range)
+            .add_option(&["--range"], Store, "Largest distance to the surface of the profile");
+        ap.refer(&mut bins)
+            .add_option(&["--bins"], Store, "Number of profile bins");
+        ap.refer(&mut counts_file)
+            .add_option(&["--counts"], Store, "Write the number of surface particles of every frame to this file");
+        ap.parse_args_or_exit();
+    }
+    if grid == 0 || bins == 0 || probe_radius.is_nan() || probe_radius <= 0.0 || range.is_nan() || range <= 0.0 {
+        eprintln!("Error: probe, grid, range and bins have to be positive.");
+        std::process::exit(1);
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { reader.skip(skip).unwrap_or_else(exit_with); }
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+    let mut counts = if counts_file.is_empty() { None } else {
+        let mut file = BufWriter::new(File::create(&counts_file).map_err(|why| Error::FileNotFound(counts_file.clone(), why)).unwrap_or_else(exit_with));
+        writeln!(file, "# frame top bottom").map_err(Error::from).unwrap_or_else(exit_with);
+        Some(file)
+    };
+
+    // both surfaces add to the profile, negative distances are in the liquid
+    let width = 2.0 * range / bins as f64;
+    let mut profile = vec![0.0; bins];
+    let mut surface_counts = vec![];
+    let mut area = 0.0;
+    loop {
+        let surface = IntrinsicSurface::new(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, frame.box_x, frame.box_y, frame.box_z, probe_radius, grid);
+        let (top, bottom) = surface.get_surface_particles();
+        surface_counts.push((top.len() + bottom.len()) as f64 / 2.0);
+        if let Some(ref mut counts) = counts {
+            writeln!(counts, "{} {} {}", surface_counts.len() - 1, top.len(), bottom.len()).map_err(Error::from).unwrap_or_else(exit_with);
+        }
+        for i in 0..frame.num_particles {
+            let (above_top, below_bottom) = surface.get_intrinsic_distances(frame.rx[i].to_f64(), frame.ry[i].to_f64(), frame.rz[i].to_f64(), frame.box_z);
+            for distance in [above_top, below_bottom].iter() {
+                let bin = ((distance + range) / width).floor();
+                if bin >= 0.0 && (bin as usize) < bins { profile[bin as usize] += 1.0; }
+            }
+        }
+        area += 2.0 * frame.box_x * frame.box_y;
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+    if let Some(ref mut counts) = counts { counts.flush().map_err(Error::from).unwrap_or_else(exit_with); }
+
+    println!("# {} frames, surface particles per surface {:.2} +/- {:.2} ({:.4} per area)", surface_counts.len(), get_mean(&surface_counts), get_standard_error(&surface_counts),
+             get_mean(&surface_counts) * surface_counts.len() as f64 * 2.0 / area);
+    println!("# distance to the surface (positive in the vapor)    density");
+    for (k, count) in profile.iter().enumerate() {
+        println!("{:.4}\t{:.6}", -range + (k as f64 + 0.5) * width, count / (area * width));
+    }
+}
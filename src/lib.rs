@@ -0,0 +1,89 @@
+//! Lennard Jones Monte Carlo simulation of simple liquids and tools to analyse the trajectories
+
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate plotters;
+// the pyo3 and wasm-bindgen macros refer to ::core
+#[cfg(any(feature = "python", feature = "wasm"))]
+extern crate core;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "python")]
+extern crate numpy;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(all(feature = "mmap", unix))]
+extern crate libc;
+
+// LJ params
+pub const LJ_EPS : f64 = 1.0;
+pub const LJ_SIG : f64 = 1.0;
+
+/// Float type of the particle coordinates, f32 with the f32 feature. Everything derived from them is f64.
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(feature = "f32")]
+pub type Real = f32;
+
+/// Conversions between the coordinate type and f64
+pub trait Coordinate: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+/// Counts of steps and moves and the step intervals, 64 bits on every target as usize only has 32 on wasm32. Debug
+/// builds panic on an overflow.
+pub type Step = u64;
+
+impl Coordinate for f64 {
+    fn to_f64(self) -> f64 { return self; }
+    fn from_f64(value: f64) -> f64 { return value; }
+}
+
+impl Coordinate for f32 {
+    fn to_f64(self) -> f64 { return self as f64; }
+    fn from_f64(value: f64) -> f32 { return value as f32; }
+}
+
+pub mod cells;
+pub mod checkerboard;
+pub mod energy;
+pub mod eos;
+pub mod ffi;
+pub mod error;
+pub mod expanded;
+pub mod gay_berne;
+pub mod interface;
+pub mod move_log;
+pub mod moves;
+pub mod multicanonical;
+#[cfg(feature = "netcdf")]
+pub mod netcdf;
+pub mod observables;
+pub mod orientation;
+pub mod outdir;
+pub mod plots;
+pub mod provenance;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod report;
+pub mod restraints;
+pub mod reweighting;
+pub mod schedule;
+pub mod server;
+#[cfg(all(feature = "simd", target_arch = "x86_64", not(feature = "f32")))]
+pub mod simd;
+pub mod statistics;
+pub mod stream;
+pub mod system;
+pub mod tempering;
+pub mod three_body;
+pub mod trajectory;
+pub mod tracking;
+pub mod tui;
+pub mod units;
+#[cfg(feature = "wasm")]
+pub mod wasm;
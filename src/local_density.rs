@@ -0,0 +1,77 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store};
+use mclj::Coordinate;
+use mclj::error::exit_with;
+use mclj::observables::{get_histogram, get_local_densities, get_voronoi_volumes, NEIGHBOR_RADIUS};
+use mclj::trajectory::*;
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut skip : usize = 0;
+    let mut radius : f64 = NEIGHBOR_RADIUS;
+    let mut max_density : f64 = 2.0;
+    let mut bins : usize = 50;
+    let mut z_min : f64 = f64::NEG_INFINITY;
+    let mut z_max : f64 = f64::INFINITY;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Histograms of the local densities of the particles in a trajectory, from the neighbors within a radius and from the inverse voronoi volumes.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Trajectory");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first frames, e.g. the equilibration");
+        ap.refer(&mut radius)
+            .add_option(&["-r", "--radius"], Store, "Radius of the neighbor sphere");
+        ap.refer(&mut max_density)
+            .add_option(&["--max"], Store, "Largest density of the histograms");
+        ap.refer(&mut bins)
+            .add_option(&["--bins"], Store, "Number of histogram bins");
+        ap.refer(&mut z_min)
+            .add_option(&["--zmin"], Store, "Only particles above this z, e.g. to look at an interface");
+        ap.refer(&mut z_max)
+            .add_option(&["--zmax"], Store, "Only particles below this z");
+        ap.parse_args_or_exit();
+    }
+    if bins == 0 || radius.is_nan() || radius <= 0.0 || max_density.is_nan() || max_density <= 0.0 {
+        eprintln!("Error: radius, max and bins have to be positive.");
+        std::process::exit(1);
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { reader.skip(skip).unwrap_or_else(exit_with); }
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+
+    let mut neighbor_densities = vec![];
+    let mut voronoi_densities = vec![];
+    let mut frames = 0;
+    loop {
+        let n = frame.num_particles;
+        let local = get_local_densities(&frame.rx, &frame.ry, &frame.rz, n, frame.box_x, frame.box_y, frame.box_z, radius);
+        let volumes = get_voronoi_volumes(&frame.rx, &frame.ry, &frame.rz, n, frame.box_x, frame.box_y, frame.box_z);
+        for i in (0..n).filter(|&i| frame.rz[i].to_f64() >= z_min && frame.rz[i].to_f64() < z_max) {
+            neighbor_densities.push(local[i]);
+            voronoi_densities.push(1.0 / volumes[i]);
+        }
+        frames += 1;
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+    if neighbor_densities.is_empty() {
+        eprintln!("Error: no particles between z = {} and {}.", z_min, z_max);
+        std::process::exit(1);
+    }
+
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    println!("# {} frames, {} particle samples, mean densities {:.4} (neighbors within {}) and {:.4} (voronoi)",
+             frames, neighbor_densities.len(), mean(&neighbor_densities), radius, mean(&voronoi_densities));
+    println!("# density    P(neighbors)    P(voronoi)");
+    let neighbors = get_histogram(&neighbor_densities, max_density, bins);
+    let voronoi = get_histogram(&voronoi_densities, max_density, bins);
+    for k in 0..bins {
+        println!("{:.4}\t{:.6}\t{:.6}", (k as f64 + 0.5) * max_density / bins as f64, neighbors[k], voronoi[k]);
+    }
+}
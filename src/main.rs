@@ -2,28 +2,65 @@
 
 extern crate rand;
 use rand::Rng;
-use rand::distributions::{IndependentSample, Range};
-mod energy;
-use energy::*;
+extern crate mclj;
+use mclj::{LJ_EPS, LJ_SIG, Real, Step};
+use mclj::energy::*;
+use mclj::eos::{format_comparison, get_johnson_eos, EOS_TEMPERATURES};
+use mclj::error::exit_with;
+use std::fs::File;
+use std::io::BufWriter;
 use std::io::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 extern crate argparse;
 use argparse::{ArgumentParser, Store, StoreFalse, StoreTrue};
-mod trajectory;
-use trajectory::*;
+use mclj::moves::Proposal;
+use mclj::trajectory::*;
+use mclj::tracking::{parse_indices, TaggedParticles};
+use mclj::move_log::MoveLog;
+#[cfg(feature = "netcdf")]
+use mclj::netcdf::NetCdfTrajectory;
+use mclj::units::Units;
+use mclj::schedule::{get_protocol_schedule, get_stage_starts, parse_protocol, parse_schedule};
+use mclj::system::*;
+use mclj::server::*;
+use mclj::stream::Publisher;
+use mclj::tui::{render_profile, Dashboard};
+use mclj::observables::get_density_profile;
+use mclj::orientation::{get_nematic_order, parse_direction};
+use mclj::outdir::{create_run_directory, format_timestamp, write_config, Manifest};
+use mclj::plots::line_plot_svg;
+use mclj::provenance::Provenance;
+use mclj::report::{Report, Results, RunResults, Scope};
+use mclj::statistics::{get_mean, get_standard_error};
 
-// LJ params
-const LJ_EPS : f64 = 1.0;
-const LJ_SIG : f64 = 1.0;
+const EQUILIBRATION_OUTPUT_INTERVAL : Step = 5000;
+const SAMPLING_OUTPUT_INTERVAL : Step = 5000;
 
- // intended acceptance rate = 33%
-const TRIES_INTENDED : f64 = 3.0;
+// intervals in sweeps if one step is a sweep of num_particles moves
+const SWEEP_OUTPUT_INTERVAL : Step = 10;
 
-// factor for auto displacement scaling
-const DISP_SCALE_FACTOR : f64 = 0.1;
-const SCALE_INTERVAL : usize = 5000;
+// density profile printout in slab mode
+const PROFILE_OUTPUT_INTERVAL : Step = 50000;
+const SWEEP_PROFILE_OUTPUT_INTERVAL : Step = 100;
+const PROFILE_BINS : usize = 12;
+const PROFILE_WIDTH : usize = 40;
 
-const EQUILIBRATION_OUTPUT_INTERVAL : usize = 5000;
-const SAMPLING_OUTPUT_INTERVAL : usize = 5000;
+// number of points of the summary plots and bins of their density profile
+const PLOT_POINTS : Step = 2000;
+const PLOT_PROFILE_BINS : usize = 50;
+
+// blocks for the error estimates of the report
+const REPORT_BLOCKS : Step = 20;
+
+// seconds between redraws of the terminal dashboard
+const TUI_REFRESH : f64 = 0.25;
+
+// steps (sweeps) between updates of the control server
+const SERVE_INTERVAL : Step = 1000;
+const SWEEP_SERVE_INTERVAL : Step = 1;
 
 // easy printing to stderr
 macro_rules! println_stderr(
@@ -40,97 +77,415 @@ fn main() {
     println_stderr!("################################################################");
     println_stderr!("");
 
-    /** Definition of default run parameters **/
-    let mut eq_steps  = 1000000;
-    let mut sample_steps = 100000;
+    /* Definition of default run parameters */
+    let mut params = Params::default();
 
-    let mut num_particles: usize = 512;
-    let mut density = 0.7;
-    let mut temperature = 0.9;
+    // a preset replaces the defaults before the other options override them
+    let args : Vec<String> = std::env::args().collect();
+    if let Some(k) = args.iter().position(|arg| arg == "--preset") {
+        let name = args.get(k + 1).cloned().unwrap_or_default();
+        params = Params::preset(&name).unwrap_or_else(|| {
+            println_stderr!("Error: Unknown preset {}, expected one of {}.", name, PRESETS.iter().map(|p| p.0).collect::<Vec<&str>>().join(", "));
+            std::process::exit(1);
+        });
+        println_stderr!("Preset {}: {}", name, PRESETS.iter().find(|p| p.0 == name).unwrap().1);
+    }
 
-    let mut cutoff = 3.0;
+    // only print the derived setup and exit
+    let mut DRY_RUN : bool = false;
 
-    let mut TAILCORR : bool = true;
-    let mut SHIFT: bool = true;
+    // address of the http control server, empty for none
+    let mut SERVE : String = String::new();
 
-    let mut displacement = 0.1; // max particle displacement in one dimension
-    let mut SCALE: bool = true; // switch for displacement scaling
+    // address to publish frames and observables on, empty for none
+    let mut STREAM : String = String::new();
 
-    // scale factor in z for vaccuum space
-    let mut vacuum_slab = 0.0;
+    // address of the prometheus metrics endpoint, empty for none
+    let mut METRICS : String = String::new();
 
-    // output config
-    let mut output_prefix = "montecarlo".to_string(); // .xyz will be append
-    let mut output_interval : i64 = 100;
-    let mut output_minim : bool = false;
+    // terminal dashboard instead of the progress lines
+    let mut TUI : bool = false;
 
-    // parse cmd line arguments and override defaults
-    parse_cmd_args(&mut sample_steps, &mut eq_steps, &mut num_particles,
-                   &mut density, &mut temperature,
-                   &mut cutoff, &mut displacement, &mut SCALE, &mut TAILCORR, &mut SHIFT,
-                   &mut output_prefix, &mut output_interval, &mut output_minim,
-                   &mut vacuum_slab);
+    // write summary svg plots at the end of the run
+    let mut PLOTS : bool = false;
+
+    // html report file, empty for none
+    let mut REPORT : String = String::new();
+
+    // independent walkers of the same state point
+    let mut REPLICAS : usize = 1;
+
+    // checkpoint to continue, empty for a new run
+    let mut RESTART : String = String::new();
+
+    // wall time after which the run stops with a checkpoint (e.g. 23h), empty for none
+    let mut MAX_WALLTIME : String = String::new();
+
+    // seconds between flushes of the buffered trajectory
+    let mut FLUSH_INTERVAL : f64 = 60.0;
+
+    // particles whose unwrapped coordinates are written every sweep (e.g. 0-9,42), empty for none
+    let mut TAG : String = String::new();
+
+    // binary log of every trial move for debugging
+    let mut MOVE_LOG : bool = false;
 
+    // temperature in kelvin of the units, 0 for the reduced temperature
+    let mut KELVIN : f64 = 0.0;
 
-    /** Initialize the system **/
-    let beta = 1.0/temperature;
+    // number of first trial moves explained on the terminal, 0 for none
+    let mut TEACH : usize = 0;
 
-    let mut volume = (num_particles as f64)/ density;
-    let length  = volume.cbrt();
-    let (l_x, l_y, mut l_z) = (length, length, length);
+    // also write the trajectory frames as pdb models
+    let mut PDB : bool = false;
 
-    let cutoff_squared = cutoff * cutoff;
-    let max_displacement = length / 2.0; // displacement wont be scaled over that
+    // also write the trajectory frames to an amber netcdf trajectory (netcdf feature)
+    let mut NETCDF : bool = false;
 
-    // initialize randomness - TODO seed?
-    let mut rng = rand::thread_rng();
-    let particle_range = Range::new(0, num_particles);
+    // pause, inspect and steer the run with commands on stdin
+    let mut INTERACTIVE : bool = false;
+
+    // protocol file of the stages of the run, empty for none
+    let mut PROTOCOL : String = String::new();
+
+    // directory the timestamped run directory of all outputs is created in, empty for files by prefix in the cwd
+    let mut OUTDIR : String = String::new();
+
+    // steps between the trajectory frames or -1 for the last frame only, empty for the interval of the parameters
+    let mut OSTEPS : String = String::new();
+
+    // parse cmd line arguments and override defaults
+    parse_cmd_args(&mut params, &mut DRY_RUN, &mut SERVE, &mut STREAM, &mut METRICS, &mut TUI, &mut PLOTS, &mut REPORT, &mut REPLICAS, &mut RESTART, &mut MAX_WALLTIME, &mut FLUSH_INTERVAL, &mut TAG, &mut MOVE_LOG, &mut KELVIN, &mut TEACH, &mut INTERACTIVE, &mut PDB, &mut NETCDF, &mut PROTOCOL, &mut OUTDIR, &mut OSTEPS);
+
+    // the output interval of the command line, an invalid one is reported with the other errors
+    let output_interval = if OSTEPS.is_empty() { Ok(params.output_interval) } else { parse_output_interval(&OSTEPS) };
+    params.output_interval = output_interval.clone().unwrap_or(params.output_interval);
+
+    // a temperature in kelvin replaces the reduced one
+    let units = Units::parse(&params.units);
+    if KELVIN > 0.0 {
+        if let Some(units) = units { params.temperature = units.reduced_temperature(KELVIN); }
+    }
+
+    // a protocol becomes the schedule, the stages but the last are the equilibration and the last the sampling
+    let scheduled = !params.schedule.is_empty();
+    let protocol = if PROTOCOL.is_empty() { vec![] } else {
+        let stages = std::fs::read_to_string(&PROTOCOL).map_err(|why| why.to_string()).and_then(|text| parse_protocol(&text)).unwrap_or_else(|message| {
+            println_stderr!("Error: invalid protocol {}: {}", PROTOCOL, message);
+            std::process::exit(1);
+        });
+        params.schedule = get_protocol_schedule(&stages);
+        params.sample_steps = stages[stages.len() - 1].steps;
+        params.eq_steps = stages.iter().map(|stage| stage.steps).sum::<Step>() - params.sample_steps;
+        stages
+    };
+    let stage_starts = get_stage_starts(&protocol);
+
+    // reject nonsensical parameters before doing anything
+    let mut errors = params.validate();
+    if !PROTOCOL.is_empty() && scheduled {
+        errors.push("A protocol replaces the schedule, they can not be combined.".to_string());
+    }
+    if !PROTOCOL.is_empty() && !RESTART.is_empty() {
+        errors.push("A restart continues the schedule of its checkpoint, the protocol can not be given again.".to_string());
+    }
+    if KELVIN.is_nan() || KELVIN < 0.0 || (KELVIN > 0.0 && params.units.is_empty()) {
+        errors.push("A temperature in kelvin needs the units (e.g. --units argon).".to_string());
+    }
+    if REPLICAS == 0 {
+        errors.push("Number of replicas has to be at least 1.".to_string());
+    }
+    if let Err(message) = output_interval {
+        errors.push(format!("Invalid output interval {}: {}.", OSTEPS, message));
+    }
+    let max_walltime = if MAX_WALLTIME.is_empty() { None } else { parse_duration(&MAX_WALLTIME) };
+    if !MAX_WALLTIME.is_empty() && max_walltime.is_none() {
+        errors.push(format!("Invalid wall time {}, expected e.g. 3600, 90m or 23h.", MAX_WALLTIME));
+    }
+    if FLUSH_INTERVAL < 0.0 {
+        errors.push("Flush interval has to be positive or 0.".to_string());
+    }
+    let tagged_indices = if TAG.is_empty() { vec![] } else { parse_indices(&TAG, params.num_particles).unwrap_or_default() };
+    if !TAG.is_empty() && tagged_indices.is_empty() {
+        errors.push(format!("Invalid tagged particles {}, expected indices below the number of particles like 0-9,42.", TAG));
+    }
+    if params.checkerboard_threads > 0 && (!TAG.is_empty() || MOVE_LOG || TEACH > 0) {
+        errors.push("The checkerboard decomposition can not be combined with tagged particles, the move log or the teaching mode.".to_string());
+    }
+    if REPLICAS > 1 && !RESTART.is_empty() {
+        errors.push("Replicas can not be restarted from a single checkpoint.".to_string());
+    }
+    if !OUTDIR.is_empty() && !RESTART.is_empty() {
+        errors.push("A restart continues in the run directory of its checkpoint, the output directory can not be given again.".to_string());
+    }
+    if !errors.is_empty() {
+        for error in &errors {
+            println_stderr!("Error: {}", error);
+        }
+        std::process::exit(1);
+    }
 
-    // randomly place particles in the box
-    let mut rx : Vec<f64> = vec![];
-    let mut ry : Vec<f64> = vec![];
-    let mut rz : Vec<f64> = vec![];
-    loop {
-        rx.push(l_x * rng.gen::<f64>());
-        ry.push(l_y * rng.gen::<f64>());
-        rz.push(l_z * rng.gen::<f64>());
-        if rx.len() == num_particles { break; }
+    // an unseeded run draws its master seed from the os, so the seed of its provenance repeats it
+    if params.seed == 0 && RESTART.is_empty() {
+        params.seed = 1 + rand::OsRng::new().expect("no random source for the seed").gen::<u32>() as usize;
+        println_stderr!("Master seed {} drawn from the os, --seed {} repeats the run", params.seed, params.seed);
     }
 
-    // scale box in z for vacuum space and move particles in the middle of the box
-    if vacuum_slab > 0.0 {
-        let scale = vacuum_slab + 1.0;
-        l_z *= scale;
-        volume *= scale;
-        density /= scale;
-        let move_z = l_z/scale*vacuum_slab/2.0;
-        for i in 0..num_particles {
-            rz[i] += move_z;
+    // a new run directory of every run collects the outputs under the file name of the prefix, with an echo of the
+    // effective parameters, relative report paths are placed in it as well
+    let start_time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    if !OUTDIR.is_empty() && !DRY_RUN {
+        let name = Path::new(&params.output_prefix).file_name().map_or("montecarlo".to_string(), |name| name.to_string_lossy().to_string());
+        let directory = create_run_directory(&OUTDIR, &name, start_time).unwrap_or_else(exit_with);
+        params.output_prefix = Path::new(&directory).join(&name).to_string_lossy().to_string();
+        if !REPORT.is_empty() && Path::new(&REPORT).is_relative() {
+            REPORT = Path::new(&directory).join(&REPORT).to_string_lossy().to_string();
         }
+        params.output_directory = directory;
+        write_config(&params.output_directory, &params).unwrap_or_else(exit_with);
+        println_stderr!("Outputs are written to the run directory {}", params.output_directory);
+    }
+
+    /* Initialize the system */
+    // initialize randomness, a seeded restart continues on the stream of its first step
+    let (mut rng, mut system) = if RESTART.is_empty() {
+        let mut rng = get_stream_rng(params.seed, StreamDomain::Run, 0);
+        let system = System::new(params, &mut rng);
+        (rng, system)
+    } else {
+        let system = System::load_checkpoint(&RESTART).unwrap_or_else(exit_with);
+        println_stderr!("Restarting {} at step {}", RESTART, system.counters.step);
+        (get_stream_rng(system.params.seed, StreamDomain::Restart, system.counters.step), system)
+    };
+    let num_particles = system.num_particles;
+    let first_step = system.counters.step;
+
+    let eq_steps = system.params.eq_steps;
+    let sample_steps = system.params.sample_steps;
+    let output_prefix = system.params.output_prefix.clone();
+    let mut output_interval = system.params.output_interval;
+    let output_minim = system.params.output_minim;
+    let recompute_interval = system.params.recompute_interval;
+    let recompute_time = system.params.recompute_time;
+    let SWEEPS = system.params.sweeps;
+    let SCALE = system.params.scale;
+
+    // all step based intervals count sweeps in sweep mode
+    let moves_per_step = system.moves_per_step();
+    let scale_interval = system.scale_interval();
+    let log_interval = system.params.log_interval;
+    let eq_output_interval = if log_interval > 0 { log_interval } else if SWEEPS { SWEEP_OUTPUT_INTERVAL } else { EQUILIBRATION_OUTPUT_INTERVAL };
+    let sampling_output_interval = if log_interval > 0 { log_interval } else if SWEEPS { SWEEP_OUTPUT_INTERVAL } else { SAMPLING_OUTPUT_INTERVAL };
+    let serve_interval = if SWEEPS { SWEEP_SERVE_INTERVAL } else { SERVE_INTERVAL };
+    let analysis_interval = system.params.analysis_interval;
+    let profile_output_interval = if analysis_interval > 0 { analysis_interval } else if SWEEPS { SWEEP_PROFILE_OUTPUT_INTERVAL } else { PROFILE_OUTPUT_INTERVAL };
+
+    println_stderr!("Particles: {}, Density: {}, Temperature: {}", num_particles, system.density, system.params.temperature);
+    println_stderr!("System volume: {:8.3}, Dimensions {:.3}/{:.3}/{:.3}", system.volume, system.l_x, system.l_y, system.l_z);
+    println_stderr!("Minimization {}: {}, Sampling {}: {}", if SWEEPS { "sweeps" } else { "steps" }, eq_steps, if SWEEPS { "sweeps" } else { "steps" }, sample_steps);
+    println_stderr!("LJ params eps: {}, sigma: {}, cutoff: {}", LJ_EPS, LJ_SIG, system.params.cutoff);
+    if let Some(units) = Units::parse(&system.params.units) {
+        println_stderr!("Real units eps/kB: {} K, sigma: {} nm, temperature: {:.2} K, density: {:.3} kg/m3, cutoff: {:.3} nm",
+                        units.eps, units.sig, units.temperature(system.params.temperature), units.mass_density(system.density), units.length(system.params.cutoff));
+    }
+    println_stderr!("Tailcorr: {:8.3}, Shift: {:8.3}, Pressurecorr: {:8.3}", system.e_corr, system.e_shift, system.p_corr);
+    if system.params.force_shift { println_stderr!("Force-shifted potential, force shift: {:8.3}{}", system.f_shift, if system.params.tailcorr { ", tail corrections ignored" } else { "" }); }
+    if system.switch_squared > 0.0 { println_stderr!("Switching function between {} and the cutoff", system.params.switch_radius); }
+    if system.params.drift_tolerance > 0.0 { println_stderr!("Drift tolerance: {:e}{}", system.params.drift_tolerance, if system.params.drift_abort { " (abort)" } else { "" }); }
+    if system.params.force_bias { println_stderr!("Force-bias moves with lambda: {}", system.params.fb_lambda); }
+    if system.params.early_rejection { println_stderr!("Early rejection of trial moves against the pre-drawn acceptance threshold"); }
+    if system.params.lazy_virial { println_stderr!("Virial and pressure only at the full recalculations every {} steps", system.params.recompute_interval); }
+    if system.params.checkerboard_threads > 0 { println_stderr!("Checkerboard decomposition of the sweeps on {} threads", system.params.checkerboard_threads); }
+    if system.params.overlap_radius > 0.0 { println_stderr!("Hard core at {}: closer trial positions are rejected by a cell list scan", system.params.overlap_radius); }
+    if system.proposal != Proposal::Cube { println_stderr!("Trial displacements from the {:?} proposal", system.proposal); }
+    if system.params.rotations { println_stderr!("Rotation moves of the particle orientations after every displacement move"); }
+    if system.params.three_body_nu != 0.0 { println_stderr!("Axilrod-Teller three-body term with nu {} within {}", system.params.three_body_nu, system.params.three_body_cutoff); }
+    if system.params.field_strength != 0.0 {
+        println_stderr!("Dipoles {} along the particle axes in the field {} along {}", system.params.dipole_moment, system.params.field_strength, if system.params.field_direction.is_empty() { "0,0,1" } else { &system.params.field_direction });
+    }
+    if let Some(ref restraints) = system.restraints { println_stderr!("{} restraints in the energy: {}", restraints.restraints.len(), system.params.restraints); }
+    if let Some(ref gay_berne) = system.gay_berne { println_stderr!("Gay-Berne ellipsoids with aspect ratio {} and well depth ratio {}, cut without shift and tail corrections", gay_berne.kappa, gay_berne.kappa_prime); }
+    if system.params.multicanonical_bins > 0 {
+        println_stderr!("Multicanonical sampling of the energies per particle between {} and {} in {} bins, Wang-Landau during the equilibration", system.params.multicanonical_min, system.params.multicanonical_max, system.params.multicanonical_bins);
+    }
+    if system.params.expanded_states > 0 { println_stderr!("Expanded ensemble of particle 0 over {} couplings, one lambda move per step, Wang-Landau during the equilibration", system.params.expanded_states); }
+    if system.params.tempering_states > 0 {
+        println_stderr!("Simulated tempering over {} temperatures between {} and {}, one temperature move per step, Wang-Landau during the equilibration", system.params.tempering_states, system.params.tempering_min, system.params.tempering_max);
+    }
+    if system.params.umbrella_spring > 0.0 { println_stderr!("Umbrella bias on the largest cluster: center {}, spring {}", system.params.umbrella_center, system.params.umbrella_spring); }
+    if system.params.recenter_interval > 0 { println_stderr!("Liquid slab recentered every {} steps, the shifts are written to {}.recenter.dat", system.params.recenter_interval, output_prefix); }
+    if system.params.interface_factor != 1.0 && !system.pref_sampling {
+        println_stderr!("Preferential interface sampling requires a vacuum slab and is ignored.");
     }
 
-    // calculation of shift and tailcorrections
-    let e_shift = if SHIFT { 4.0 * LJ_EPS * ( (LJ_SIG/cutoff).powi(12) - (LJ_SIG/cutoff).powi(6) ) } else { 0.0 };
-    let e_corr = if TAILCORR { 8.0/3.0*std::f64::consts::PI*density*LJ_EPS*LJ_SIG.powi(3)*((1.0/3.0*(LJ_SIG/cutoff).powi(9)) - (LJ_SIG/cutoff).powi(3)) } else { 0.0 };
-    let p_corr = if TAILCORR { 16.0/3.0*std::f64::consts::PI*density.powi(2)*LJ_EPS*LJ_SIG.powi(3)*((2.0/3.0*(LJ_SIG/cutoff).powi(9)) - (LJ_SIG/cutoff).powi(3)) } else { 0.0 };
+    if DRY_RUN {
+        // coordinates and selection weights plus the text trajectory frames
+        let memory = num_particles * (3 * std::mem::size_of::<Real>() + std::mem::size_of::<f64>());
+        let frames = match output_interval { Some(interval) => (sample_steps + if output_minim { eq_steps } else { 0 }) / interval + 1, None => 1 };
+        let frame_size = 60 * (num_particles as Step + 1);
+        println_stderr!("Cutoff squared: {:.3}, Max displacement: {:.3}, Beta: {:.3}", system.cutoff_squared, system.max_displacement, system.beta);
+        println_stderr!("Moves per step: {}, Total moves: {}", moves_per_step, (eq_steps + sample_steps) * moves_per_step);
+        println_stderr!("Estimated memory: {:.3} MB, Estimated trajectory size: {:.3} MB ({} frames)",
+                        memory as f64 / 1e6, (frames * frame_size) as f64 / 1e6, frames);
+        println_stderr!("Initial energy: {:.3}, Initial virial: {:.3}", system.energy, system.virial);
+        println_stderr!("Dry run, exiting.");
+        return;
+    }
 
-    println_stderr!("Particles: {}, Density: {}, Temperature: {}", num_particles, density, temperature);
-    println_stderr!("System volume: {:8.3}, Dimensions {:.3}/{:.3}/{:.3}", volume, l_x, l_y, l_z);
-    println_stderr!("Minimization steps: {}, Sampling steps: {}", eq_steps, sample_steps);
-    println_stderr!("LJ params eps: {}, sigma: {}, cutoff: {}", LJ_EPS, LJ_SIG, cutoff);
-    println_stderr!("Tailcorr: {:8.3}, Shift: {:8.3}, Pressurecorr: {:8.3}", e_corr, e_shift, p_corr);
+    // the manifest of the run directory lists its files at the start and the end of the run, a restart updates the one
+    // of its first run
+    let output_directory = system.params.output_directory.clone();
+    let mut manifest = if output_directory.is_empty() { None } else {
+        let mut manifest = match Manifest::load(&output_directory) {
+            Ok(manifest) if !RESTART.is_empty() => Manifest { command: args.clone(), status: "running".to_string(), ..manifest },
+            _ => Manifest::new(&args, &format_timestamp(start_time)),
+        };
+        manifest.write(&output_directory).unwrap_or_else(exit_with);
+        Some(manifest)
+    };
 
-    // energy and average sums
-    let (mut energy, mut virial) = get_total_energy(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, cutoff_squared, e_corr, e_shift);
-    let mut energy_sum = 0.0;
-    let mut virial_sum = 0.0;
-    let mut step_counter = 0;
-    let mut accept_counter = 0;
+    // provenance of this start or restart for the checkpoints, the results json and the trajectory headers
+    let provenance = Provenance::new(&args, system.params.seed, start_time);
+    system.provenance.push(provenance.clone());
 
+    if REPLICAS > 1 {
+        run_replicas(&system.params, REPLICAS, &provenance);
+        if let Some(ref mut manifest) = manifest { manifest.finish("finished", &output_directory).unwrap_or_else(exit_with); }
+        return;
+    }
 
-    // prepare and write first trajectory frame
-    let mut trajectory : XYZTrajectory = XYZTrajectory::new(&format!("{}.xyz", output_prefix));
-    if output_minim { trajectory.write(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, temperature, LJ_EPS, LJ_SIG, cutoff, true); }
+    let mut last_recompute = Instant::now();
+
+    // prepare and write first trajectory frame, restarts continue the trajectory after the frames up to their checkpoint
+    let trajectory_file = format!("{}.xyz", output_prefix);
+    let mut trajectory : XYZTrajectory = if first_step > 0 {
+        let (trajectory, dropped) = XYZTrajectory::resume(&trajectory_file, first_step).unwrap_or_else(exit_with);
+        if dropped > 0 { println_stderr!("Dropped {} frames after step {} from {}, they are written again", dropped, first_step, trajectory_file); }
+        trajectory
+    } else { XYZTrajectory::new(&trajectory_file).unwrap_or_else(exit_with) };
+    trajectory.flush_interval = FLUSH_INTERVAL;
+    trajectory.header = provenance.to_key_values(&system.params);
+    let mut frames_written = 0;
+    if output_minim && first_step == 0 {
+        system.write_frame(&mut trajectory, 0).unwrap_or_else(exit_with);
+        frames_written += 1;
+    }
+
+    // pdb snapshots in Å, sigma is 1 nm without units like in the gro file, a restart starts a separate file
+    let pdb_length_unit = 10.0 * Units::parse(&system.params.units).map_or(1.0, |units| units.sig);
+    let pdb_element = if system.params.units == "argon" { "Ar" } else { "X" };
+    let mut pdb = if !PDB { None } else {
+        let pdb_file = if first_step > 0 { format!("{}.{}.pdb", output_prefix, first_step) } else { format!("{}.pdb", output_prefix) };
+        let mut pdb = PdbWriter::new(&pdb_file, pdb_length_unit, pdb_element).unwrap_or_else(exit_with);
+        pdb.remark(&provenance.summary()).unwrap_or_else(exit_with);
+        Some(pdb)
+    };
+    if let Some(ref mut pdb) = pdb {
+        if output_minim && first_step == 0 { pdb.write(&system.frame(0)).unwrap_or_else(exit_with); }
+    }
+
+    // amber netcdf trajectory with the lengths of the pdb snapshots
+    #[cfg(feature = "netcdf")]
+    let mut netcdf = if !NETCDF { None } else {
+        let netcdf_file = if first_step > 0 { format!("{}.{}.nc", output_prefix, first_step) } else { format!("{}.nc", output_prefix) };
+        Some(NetCdfTrajectory::new(&netcdf_file, num_particles, pdb_length_unit, &provenance.summary()).unwrap_or_else(exit_with))
+    };
+    #[cfg(feature = "netcdf")]
+    if let Some(ref mut netcdf) = netcdf {
+        if output_minim && first_step == 0 { netcdf.write(&system.frame(0)).unwrap_or_else(exit_with); }
+    }
+    #[cfg(not(feature = "netcdf"))]
+    if NETCDF {
+        println_stderr!("Netcdf trajectories need the netcdf feature: cargo build --release --features netcdf");
+        std::process::exit(1);
+    }
+
+    // tracking of tagged particles, a restart starts new unwrapped coordinates in a separate file
+    let mut tagged = if tagged_indices.is_empty() { None } else {
+        if tagged_indices.iter().any(|&i| i >= num_particles) {
+            println_stderr!("Error: tagged particles {} do not exist in the restarted system of {} particles.", TAG, num_particles);
+            std::process::exit(1);
+        }
+        let tagged_file = if first_step > 0 { format!("{}.tagged.{}.dat", output_prefix, first_step) } else { format!("{}.tagged.dat", output_prefix) };
+        println_stderr!("Tracking {} tagged particles in {}", tagged_indices.len(), tagged_file);
+        Some(TaggedParticles::new(&tagged_file, tagged_indices, &system).unwrap_or_else(exit_with))
+    };
+
+    // move log for debugging, a restart starts a separate file
+    let mut move_log = if !MOVE_LOG { None } else {
+        let move_log_file = if first_step > 0 { format!("{}.moves.{}.bin", output_prefix, first_step) } else { format!("{}.moves.bin", output_prefix) };
+        println_stderr!("Logging every trial move to {}", move_log_file);
+        Some(MoveLog::new(&move_log_file).unwrap_or_else(exit_with))
+    };
+    let sweep_steps = (num_particles as Step / moves_per_step).max(1);
+
+    // start the control server
+    let control = if SERVE.is_empty() { None } else {
+        let control = Arc::new(Mutex::new(Control { output_interval: output_interval, ..Control::default() }));
+        let address = serve(&SERVE, control.clone()).unwrap_or_else(exit_with);
+        println_stderr!("Control server listening on http://{}", address);
+        Some(control)
+    };
+
+    // console commands read from stdin
+    let console = if INTERACTIVE {
+        println_stderr!("Interactive console on stdin: {}", CONSOLE_HELP);
+        Some(read_commands())
+    } else { None };
+
+    // start publishing to stream clients
+    let publisher = if STREAM.is_empty() { None } else {
+        let publisher = Publisher::bind(&STREAM).unwrap_or_else(exit_with);
+        println_stderr!("Streaming frames and observables to clients of {}", publisher.local_addr());
+        Some(publisher)
+    };
+
+    // start the metrics endpoint, the run is labeled by its output prefix
+    let metrics = if METRICS.is_empty() { None } else {
+        let metrics = Arc::new(Mutex::new(Metrics::default()));
+        let address = serve_metrics(&METRICS, metrics.clone(), output_prefix.clone()).unwrap_or_else(exit_with);
+        println_stderr!("Metrics available at http://{}/metrics", address);
+        Some(metrics)
+    };
+    let mut last_metrics = (Instant::now(), 0);
+
+    // series for the summary plots
+    let plot_interval = if analysis_interval > 0 { analysis_interval } else { ((eq_steps + sample_steps) / PLOT_POINTS).max(1) };
+    let mut energy_series : Vec<(f64, f64)> = vec![];
+    let mut acceptance_series : Vec<(f64, f64)> = vec![];
+    let mut profile_sum = vec![0.0; PLOT_PROFILE_BINS];
+    let mut profile_count = 0;
+    let record_series = PLOTS || !REPORT.is_empty();
+
+    // block averages of the sampling for error estimates, and the repulsive, attractive and three-body energies and the
+    // surface tension of slabs sampled at the full recalculations, a restart continues those of its checkpoint
+    let mut slab = system.params.vacuum_slab > 0.0;
+    let pressure_block_size = sample_steps / system.pressure_interval() / REPORT_BLOCKS;
+    let new_sampling = || SamplingBlocks::new(sample_steps / REPORT_BLOCKS, pressure_block_size, sample_steps / recompute_interval.max(1) / REPORT_BLOCKS);
+    let mut sampling = system.counters.sampling.take().unwrap_or_else(new_sampling);
+
+    // step, shift and total shift of every recentering of the slab, written as they happen and flushed with the
+    // checkpoints, a restart continues the lines before its checkpoint
+    let recenter_file = format!("{}.recenter.dat", output_prefix);
+    let mut recenter_log = if system.params.recenter_interval == 0 { None } else {
+        let mut log = "# step, shift along z, total shift of the run (trajectory frames and profiles are recentered)\n".to_string();
+        if first_step > 0 {
+            for line in std::fs::read_to_string(&recenter_file).unwrap_or_default().lines() {
+                if line.split_whitespace().next().and_then(|step| step.parse::<Step>().ok()).is_some_and(|step| step < first_step) {
+                    log += line;
+                    log.push('\n');
+                }
+            }
+        }
+        let mut file = BufWriter::new(File::create(&recenter_file).unwrap_or_else(|e| exit_with(e.into())));
+        file.write_all(log.as_bytes()).unwrap_or_else(|e| exit_with(e.into()));
+        Some(file)
+    };
+    let field_direction = parse_direction(&system.params.field_direction).unwrap_or([0.0, 0.0, 1.0]);
+    let start = Instant::now();
+
+    let mut dashboard = Dashboard::default();
+    let mut last_redraw = Instant::now();
 
 
     println_stderr!("");
@@ -143,58 +498,132 @@ fn main() {
     // START OF METROPOLIS
     /*****************************************************************************************/
 
-    for step in 0..eq_steps+sample_steps {
-
-        // select rnd particle
-        let rnd_index = particle_range.ind_sample(&mut rng);
-
-        // store old position
-        let oldX = rx[rnd_index];
-        let oldY = ry[rnd_index];
-        let oldZ = rz[rnd_index];
-
-        // old particle energy
-        let (old_particle_energy, old_particle_virial) = get_particle_energy(&rx, &ry, &rz, rnd_index, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift);
-
-        // rnd displacement and PBC
-        rx[rnd_index] += ( rng.gen::<f64>() - 0.5 ) * displacement;
-        ry[rnd_index] += ( rng.gen::<f64>() - 0.5 ) * displacement;
-        rz[rnd_index] += ( rng.gen::<f64>() - 0.5 ) * displacement;
-        if rx[rnd_index] < 0.0 { rx[rnd_index] += l_x }
-        if rx[rnd_index] >= l_x { rx[rnd_index] -= l_x }
-        if ry[rnd_index] < 0.0 { ry[rnd_index] += l_y }
-        if ry[rnd_index] >= l_y { ry[rnd_index] -= l_y }
-        if rz[rnd_index] < 0.0 { rz[rnd_index] += l_z }
-        if rz[rnd_index] >= l_z { rz[rnd_index] -= l_z }
-
-        // calculate energy difference
-        let (new_particle_energy, new_particle_virial) = get_particle_energy(&rx, &ry, &rz, rnd_index, num_particles, l_x, l_y, l_z, cutoff_squared, e_shift);
-
-        let dE = new_particle_energy - old_particle_energy;
-
-        // acceptance rule
-        if dE < 0.0 || rng.gen::<f64>() < (-beta * dE).exp() {
-            accept_counter += 1;
-            energy += dE;
-            virial += new_particle_virial - old_particle_virial;
-
-            // recalculate total energy every 1000 steps to account for rounding errors in particle energy function
-            if step % 10000 == 0 {
-                let (e, v) = get_total_energy(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, cutoff_squared, e_corr, e_shift);
-                energy = e;
-                virial = v;
+    let mut teach_count = 0;
+    let schedule = parse_schedule(&system.params.schedule).unwrap_or_default();
+    let mut restart_averages : Option<Step> = None;
+    for step in first_step..eq_steps+sample_steps {
+        system.counters.step = step;
+
+        if let Some(k) = stage_starts.iter().position(|&start| start == step) {
+            println_stderr!("Stage {} ({} of {}) for {} steps from step {}", protocol[k].name, k + 1, protocol.len(), protocol[k].steps, step);
+        }
+
+        // scheduled parameter changes, the averages restart after the re-equilibration
+        for &(_, change) in schedule.iter().filter(|&&(at, _)| at == step) {
+            if let Err(message) = change.apply(&mut system) {
+                println_stderr!("Scheduled change to {} at step {} failed: {}", change, step, message);
+                std::process::exit(1);
             }
+            println_stderr!("Changed to {} at step {}, averages restart at step {}", change, step, step + system.params.schedule_equilibration);
+            restart_averages = Some(step + system.params.schedule_equilibration);
+            slab = system.params.vacuum_slab > 0.0;
+        }
+        if restart_averages.is_some_and(|at| at <= step) {
+            println_stderr!("Averages restarted at step {}", step);
+            restart_averages = None;
+            system.reset_averages();
+            sampling = new_sampling();
+            profile_sum = vec![0.0; PLOT_PROFILE_BINS];
+            profile_count = 0;
+        }
+
+        // locate the interfaces and update selection weights, frozen during sampling
+        if system.pref_sampling && step <= eq_steps && step % scale_interval == 0 {
+            system.update_interfaces();
+            if step == eq_steps {
+                println_stderr!("Interfaces for preferential sampling at z = {:.3} and {:.3}", system.interfaces.0, system.interfaces.1);
+            }
+        }
+
+        // attempted single particle moves per reported step (one sweep = num_particles moves), by the checkerboard
+        // decomposition all at once
+        if system.params.checkerboard_threads > 0 {
+            system.checkerboard_sweep(&mut rng);
+            for _ in 0..moves_per_step { system.rotation_move(&mut rng); }
         } else {
-            // restore old positions if move is rejected
-            rx[rnd_index] = oldX;
-            ry[rnd_index] = oldY;
-            rz[rnd_index] = oldZ;
+            for _ in 0..moves_per_step {
+                let energy_before = system.energy;
+                let accepted = system.trial_move(&mut rng);
+                if teach_count < TEACH {
+                    teach_count += 1;
+                    println_stderr!("Move {}: {}", teach_count, system.last_trial.describe(energy_before, system.params.temperature));
+                }
+                if let Some(ref mut tagged) = tagged { tagged.record_move(&system, accepted); }
+                if let Some(ref mut move_log) = move_log { move_log.write(&system.last_trial).unwrap_or_else(exit_with); }
+                if step < eq_steps { system.record_trial_displacement(); }
+                system.rotation_move(&mut rng);
+            }
+        }
+        system.lambda_move(&mut rng);
+        system.tempering_move(&mut rng);
+
+        // translate the drifting slab back to the box center, logged for the analyses of the trajectory
+        if system.params.recenter_interval > 0 && step % system.params.recenter_interval == 0 {
+            let shift = system.recenter_slab();
+            if let Some(ref mut log) = recenter_log { writeln!(log, "{} {} {}", step, shift, system.slab_shift).unwrap_or_else(|e| exit_with(e.into())); }
         }
 
-        // update average sums
-        step_counter += 1;
-        energy_sum += energy;
-        virial_sum += virial;
+        // recalculate total energy to account for rounding errors in particle energy function and track the drift
+        // always at the end of the equilibration, the overlaps of a random start leave rounding errors in the running energy
+        if step == eq_steps || (recompute_interval > 0 && step % recompute_interval == 0) || (recompute_time > 0.0 && last_recompute.elapsed().as_secs_f64() > recompute_time) {
+            let (e, v) = system.total_energy();
+
+            // sanity watchdog: abort instead of averaging garbage
+            if !(system.energy.is_finite() && system.virial.is_finite() && e.is_finite() && v.is_finite() && system.displacement.is_finite()) {
+                println_stderr!("");
+                println_stderr!("Non-finite values detected at step {}: energy {} (recomputed {}), virial {} (recomputed {}), displacement {}",
+                                step, system.energy, e, system.virial, v, system.displacement);
+                match find_non_finite_particle(&system.rx, &system.ry, &system.rz, num_particles, system.l_x, system.l_y, system.l_z, system.cutoff_squared, system.e_shift, system.f_shift, system.switch_squared) {
+                    Some(i) => println_stderr!("Offending particle {} at position {} {} {}", i, system.rx[i], system.ry[i], system.rz[i]),
+                    None => println_stderr!("No single offending particle found."),
+                }
+                abort_run(&system, &mut trajectory, &mut tagged, &mut move_log, &mut recenter_log, &mut manifest, &output_directory,
+                          " (for inspection, its non-finite values are null and it can not be restarted)");
+            }
+
+            // exactness check of the running values, catches bookkeeping errors of the moves
+            if let Some(message) = system.check_drift(e, v) {
+                println_stderr!("");
+                println_stderr!("{}", message);
+                if system.params.drift_abort {
+                    abort_run(&system, &mut trajectory, &mut tagged, &mut move_log, &mut recenter_log, &mut manifest, &output_directory, "");
+                }
+            }
+
+            system.apply_recomputed_energy(e, v);
+            last_recompute = Instant::now();
+            if step > eq_steps {
+                let (repulsive, attractive) = system.get_energy_decomposition();
+                sampling.repulsive_energy.push(repulsive);
+                sampling.attractive_energy.push(attractive);
+                sampling.three_body_energy.push(system.get_three_body_energy().0);
+                if slab { sampling.tension.push(system.surface_tension()); }
+            }
+        }
+
+        // update average sums once per reported step
+        system.counters.step_counter += 1;
+        system.counters.energy.push(system.energy);
+        if system.samples_pressure() { system.counters.virial.push(system.virial); }
+        system.record_cluster_size();
+        system.record_multicanonical();
+        system.record_tempering();
+
+        // wang-landau: a flat histogram halves the modification factor
+        if step < eq_steps && step % scale_interval == 0 {
+            system.update_weights();
+        }
+
+        if step > eq_steps {
+            sampling.energy.push(system.energy);
+            if system.params.rotations { sampling.nematic.push(get_nematic_order(&system.orientations).0); }
+            if system.params.dipole_moment != 0.0 {
+                let polarization = system.get_polarization();
+                sampling.polarization.push((0..3).map(|k| polarization[k] * field_direction[k]).sum());
+            }
+            if system.restraints.is_some() { sampling.restraint.push(system.get_restraint_energy()); }
+            if system.samples_pressure() { sampling.pressure.push(system.pressure()); }
+        }
 
         // reset average sums for sampling
         if step == eq_steps {
@@ -203,61 +632,297 @@ fn main() {
             println_stderr!("##########################  Sampling  ##########################");
             println_stderr!("################################################################");
             println_stderr!("");
-            step_counter = 0;
-            accept_counter = 0;
-            energy_sum = 0.0;
-            virial_sum = 0.0;
+            println_stderr!("Displacement frozen at {} for sampling", system.displacement);
+            if system.params.rotations { println_stderr!("Maximum rotation angle frozen at {} for sampling", system.max_angle); }
+            println_stderr!("Max energy drift during equilibration: {:e}", system.counters.energy_drift_max);
+
+            // acceptance by trial displacement length over the equilibration, shows what the adaptive scaling balances
+            let acceptance = system.get_displacement_acceptance();
+            if !acceptance.is_empty() {
+                let mut table = "# trial displacement length (bin center), attempted moves, acceptance ratio\n".to_string();
+                println_stderr!("Acceptance by trial displacement length during equilibration:");
+                for &(length, attempts, ratio) in acceptance.iter().filter(|a| a.1 > 0) {
+                    table += &format!("{} {} {}\n", length, attempts, ratio);
+                    println_stderr!("    {:5.3} {:>5.1}% {:>10} moves", length, ratio * 100.0, attempts);
+                }
+                std::fs::write(format!("{}.acceptance.dat", output_prefix), table).unwrap_or_else(|e| exit_with(e.into()));
+                println_stderr!("Acceptance histogram written to {}.acceptance.dat", output_prefix);
+            }
+            let counters = &mut system.counters;
+            counters.energy_drift_max = 0.0;
+            counters.virial_drift_max = 0.0;
+            counters.energy_drift_sum = 0.0;
+            counters.step_counter = 0;
+            counters.accept_counter = 0;
+            counters.energy.clear();
+            counters.virial.clear();
+            counters.cluster_histogram.clear();
+            counters.rotation_attempts = 0;
+            counters.rotation_accepted = 0;
+            counters.early_rejections = 0;
+            counters.overlap_rejections = 0;
+            system.freeze_weights();
+            if let Some(ref multicanonical) = system.multicanonical { println_stderr!("Multicanonical weights fixed with ln f = {:e}", multicanonical.ln_f); }
+            if let Some(ref expanded) = system.expanded { println_stderr!("Expanded ensemble weights fixed with ln f = {:e}", expanded.ladder.ln_f); }
+            if let Some(ref tempering) = system.tempering { println_stderr!("Simulated tempering weights fixed with ln f = {:e}", tempering.ladder.ln_f); }
         }
 
         // Everything below here is not part of the metropolis sampling (extras)
 
-        // print some output during equilibration
-        if step < eq_steps && step_counter % EQUILIBRATION_OUTPUT_INTERVAL == 0 && step != 0 {
-            let tries_per_step : f64 = step_counter as f64 /accept_counter as f64;
+        // print some output during equilibration, the averages since the last displacement update
+        let step_counter = system.counters.step_counter;
+        if !TUI && step < eq_steps && step.is_multiple_of(eq_output_interval) && step != 0 {
+            let tries_per_step : f64 = (step_counter * moves_per_step) as f64 /system.counters.accept_counter as f64;
             let acceptance_rate = 1.0/tries_per_step * 100.0;
-            let avg_energy = energy_sum / step_counter as f64;
-            let avg_virial = virial_sum / step_counter as f64;
-            println_stderr!("Eq {:<10} Energy: {:<30.3} Virial: {:<30.3} Accept.: {:<4.1}%   dr: {:.3}", step, avg_energy, avg_virial, acceptance_rate, displacement);
+            let avg_energy = system.counters.energy.mean();
+            let avg_virial = system.counters.virial.mean();
+            println_stderr!("Eq {:<10} Energy: {:<30.3} Virial: {:<30.3} Accept.: {:<4.1}%   dr: {:.3}", step, avg_energy, avg_virial, acceptance_rate, system.displacement);
+        }
+
+        // displacement adaptation during equilibration and the re-equilibration after a change for good acceptance ratios
+        if SCALE && (step < eq_steps || restart_averages.is_some()) && step % scale_interval == 0 {
+            system.adapt_displacement();
         }
 
-        // displacement scaling during equilibration for good acceptance ratios
-        if SCALE && step < eq_steps && step % SCALE_INTERVAL == 0 {
-            let tries_per_step : f64 = step_counter as f64 /accept_counter as f64;
+        // print some output during sampling
+        if !TUI && step > eq_steps && step_counter.is_multiple_of(sampling_output_interval) {
+            println_stderr!("Step  {:<10} Energy: {:<30.3} Max drift: {:.3e}", step_counter, system.energy, system.counters.energy_drift_max);
+        }
 
-            // will increase the max displacement if the acceptance rate is too high and vice versa
-            let scale_factor = (TRIES_INTENDED/tries_per_step * DISP_SCALE_FACTOR).abs();
-            if tries_per_step < TRIES_INTENDED - 0.2 && displacement < max_displacement {
-                displacement += displacement * scale_factor;
-            } else if tries_per_step > TRIES_INTENDED + 0.2 && displacement > 0.0 {
-                displacement -= displacement * scale_factor;
+        // write trajectory, the frame is the configuration after the moves of this step
+        if output_interval.is_some_and(|interval| step.is_multiple_of(interval)) && (step > eq_steps || output_minim) {
+            system.write_frame(&mut trajectory, step + 1).unwrap_or_else(exit_with);
+            frames_written += 1;
+            if let Some(ref mut pdb) = pdb { pdb.write(&system.frame(step + 1)).unwrap_or_else(exit_with); }
+            #[cfg(feature = "netcdf")]
+            if let Some(ref mut netcdf) = netcdf { netcdf.write(&system.frame(step + 1)).unwrap_or_else(exit_with); }
+            if let Some(ref publisher) = publisher {
+                if publisher.has_clients() { publisher.publish("frame", &system.frame(step + 1)).unwrap_or_else(exit_with); }
             }
-            step_counter = 0;
-            accept_counter = 0;
-            energy_sum = 0.0;
         }
 
-        // print some output during sampling
-        if step > eq_steps && step_counter % SAMPLING_OUTPUT_INTERVAL == 0 {
-            println_stderr!("Step  {:<10} Energy: {:<30.3}", step_counter, energy);
+        // unwrapped coordinates of the tagged particles once per sweep
+        if let Some(ref mut tagged) = tagged {
+            if (step + 1).is_multiple_of(sweep_steps) {
+                tagged.write((step + 1) / sweep_steps).unwrap_or_else(exit_with);
+            }
+        }
+
+        // show the density profile of a slab, makes drifting, split or evaporated slabs obvious
+        if !TUI && system.params.vacuum_slab > 0.0 && step % profile_output_interval == 0 && step != 0 {
+            let profile = get_density_profile(&system.rz, num_particles, system.l_x, system.l_y, system.l_z, PROFILE_BINS);
+            println_stderr!("Density profile rho(z) at step {}:", step);
+            eprint!("{}", render_profile(&profile, system.l_z, PROFILE_WIDTH));
+        }
+
+        // record the series for the summary plots
+        if record_series && step % plot_interval == 0 {
+            energy_series.push((step as f64, system.energy / num_particles as f64));
+            acceptance_series.push((step as f64, system.counters.accept_counter as f64 / (system.counters.step_counter * moves_per_step) as f64));
+            if step >= eq_steps && system.params.vacuum_slab > 0.0 {
+                let profile = get_density_profile(&system.rz, num_particles, system.l_x, system.l_y, system.l_z, PLOT_PROFILE_BINS);
+                for (sum, density) in profile_sum.iter_mut().zip(profile) { *sum += density; }
+                profile_count += 1;
+            }
+        }
+
+        // redraw the terminal dashboard
+        if TUI && step % serve_interval == 0 && last_redraw.elapsed().as_secs_f64() > TUI_REFRESH {
+            dashboard.push(&system, system.counters.accept_counter as f64 / (system.counters.step_counter * moves_per_step) as f64);
+            let phase = if step < eq_steps { "Equilibration" } else { "Sampling" };
+            eprint!("{}", dashboard.render(&system, step, eq_steps + sample_steps, phase));
+            last_redraw = Instant::now();
+        }
+
+        // update the runtime metrics
+        if let Some(ref metrics) = metrics {
+            if step % serve_interval == 0 {
+                let elapsed = last_metrics.0.elapsed().as_secs_f64();
+                let mut metrics = metrics.lock().unwrap();
+                *metrics = Metrics {
+                    steps: step,
+                    steps_per_second: if elapsed > 0.0 { (step - last_metrics.1) as f64 / elapsed } else { metrics.steps_per_second },
+                    acceptance: system.counters.accept_counter as f64 / (system.counters.step_counter * moves_per_step) as f64,
+                    energy: system.energy,
+                    pressure: system.pressure(),
+                    displacement: system.displacement,
+                    frames_written: frames_written,
+                };
+                last_metrics = (Instant::now(), step);
+            }
+        }
+
+        // stream the observables
+        if let Some(ref publisher) = publisher {
+            if step % serve_interval == 0 && publisher.has_clients() {
+                let status = get_status(&system, step, eq_steps + sample_steps, output_interval, false);
+                publisher.publish("observables", &status).unwrap_or_else(exit_with);
+            }
+        }
+
+        // publish observables and the latest frame, follow the commands of the control server
+        if let Some(ref control) = control {
+            if step % serve_interval == 0 {
+                let stop = loop {
+                    let mut control = control.lock().unwrap();
+                    let paused = control.paused;
+                    control.status = get_status(&system, step, eq_steps + sample_steps, output_interval, paused);
+                    control.frame = Some(system.frame(step + 1));
+                    output_interval = control.output_interval;
+                    if !paused { break control.stop; }
+                    drop(control);
+                    thread::sleep(Duration::from_millis(100));
+                };
+                if stop {
+                    println_stderr!("Stopped by the control server at step {}", step);
+                    break;
+                }
+            }
+        }
+
+        // follow the commands of the interactive console, a paused run waits for the next command
+        if let Some(ref console) = console {
+            if step % serve_interval == 0 {
+                let (mut paused, mut stop) = (false, false);
+                while let Some(command) = if paused { console.recv().ok() } else { console.try_recv().ok() } {
+                    match command {
+                        Command::Pause => { paused = true; println_stderr!("Paused at step {}", step); },
+                        Command::Resume => { paused = false; println_stderr!("Resumed at step {}", step); },
+                        Command::Stop => { stop = true; break; },
+                        Command::Help => println_stderr!("{}", CONSOLE_HELP),
+                        Command::Status => {
+                            let phase = if step < eq_steps { "equilibration" } else { "sampling" };
+                            println_stderr!("Step {} of {} ({}): energy {:.4}, pressure {:.4}, density {:.4}, temperature {}, acceptance {:.1}%, dr {:.3}",
+                                            step, eq_steps + sample_steps, phase, system.energy, system.pressure(), system.density, system.params.temperature,
+                                            system.counters.accept_counter as f64 / (system.counters.step_counter * moves_per_step) as f64 * 100.0, system.displacement);
+                        },
+                        Command::Profile(bins) => {
+                            let profile = get_density_profile(&system.rz, num_particles, system.l_x, system.l_y, system.l_z, bins.unwrap_or(PROFILE_BINS));
+                            println_stderr!("Density profile rho(z) at step {}:", step);
+                            eprint!("{}", render_profile(&profile, system.l_z, PROFILE_WIDTH));
+                        },
+                        Command::OutputInterval(interval) => {
+                            output_interval = interval;
+                            if let Some(ref control) = control { control.lock().unwrap().output_interval = interval; }
+                            match interval {
+                                Some(interval) => println_stderr!("Output interval {} from step {}", interval, step),
+                                None => println_stderr!("Only the last frame is written from step {}", step),
+                            }
+                        },
+                        Command::Temperature(_) if system.tempering.is_some() => println_stderr!("The temperature is sampled by the simulated tempering"),
+                        // the averages of the old temperature are discarded
+                        Command::Temperature(temperature) => {
+                            println_stderr!("Temperature changed from {} to {} at step {}, averages restart at step {}", system.params.temperature, temperature,
+                                            step, step + system.params.schedule_equilibration);
+                            system.set_temperature(temperature);
+                            restart_averages = Some(step + system.params.schedule_equilibration);
+                        },
+                    }
+                }
+                if stop {
+                    println_stderr!("Stopped from the console at step {}", step);
+                    break;
+                }
+            }
+        }
+
+        // periodic checkpoint for the restart after a crash, with everything written so far on disk
+        let checkpoint_interval = system.params.checkpoint_interval;
+        if checkpoint_interval > 0 && (step + 1) % checkpoint_interval == 0 && step + 1 < eq_steps + sample_steps {
+            trajectory.flush().unwrap_or_else(exit_with);
+            if let Some(ref mut tagged) = tagged { tagged.flush().unwrap_or_else(exit_with); }
+            if let Some(ref mut move_log) = move_log { move_log.flush().unwrap_or_else(exit_with); }
+            if let Some(ref mut log) = recenter_log { log.flush().unwrap_or_else(|e| exit_with(e.into())); }
+            system.counters.step = step + 1;
+            system.counters.sampling = Some(sampling.clone());
+            system.save_checkpoint(&format!("{}.checkpoint.json", output_prefix)).unwrap_or_else(exit_with);
+            system.counters.step = step;
         }
 
-        // write trajectory
-        if step as i64 % output_interval == 0 {
-            if step > eq_steps || output_minim {
-                trajectory.write(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, temperature, LJ_EPS, LJ_SIG, cutoff, true);
+        // stop before the scheduler kills the job, the final checkpoint continues the run
+        if let Some(limit) = max_walltime {
+            if start.elapsed().as_secs_f64() > limit {
+                println_stderr!("Wall time limit reached at step {}, the results are partial. Continue with --restart {}.checkpoint.json", step, output_prefix);
+                break;
             }
         }
     }
+    system.counters.step += 1;
 
     // END OF METROPOLIS
     /*****************************************************************************************/
     println_stderr!("Done sampling!");
 
-    let final_energy = energy_sum/step_counter as f64;
+    let counters = system.counters.clone();
+    let step_counter = counters.step_counter;
+    let final_energy = counters.energy.mean();
     let particle_energy = final_energy / num_particles as f64;
-    let final_virial = virial_sum / 3.0 / step_counter as f64 / volume;
-    let pressure = virial_sum / 3.0 / step_counter as f64 / volume + density * temperature + p_corr;
-    let final_acceptance_rate = 1.0/((accept_counter as f64)/(step_counter as f64)) * 100.0;
+    let final_virial = counters.virial.mean() / 3.0 / system.volume;
+    let pressure = counters.virial.mean() / 3.0 / system.volume + system.density * system.params.temperature + system.p_corr;
+    let final_acceptance_rate = 1.0/((counters.accept_counter as f64)/((step_counter * moves_per_step) as f64)) * 100.0;
+    // the final configuration if there was no recalculation during the sampling
+    if sampling.repulsive_energy.count == 0 {
+        let (repulsive, attractive) = system.get_energy_decomposition();
+        sampling.repulsive_energy.push(repulsive);
+        sampling.attractive_energy.push(attractive);
+        sampling.three_body_energy.push(system.get_three_body_energy().0);
+    }
+    let n = num_particles as f64;
+
+    // observables of this kind of run, the pressure of a slab mixes the liquid and the vapor and would be meaningless
+    let mut results = Results::default();
+    results.add("Energy", final_energy, sampling.energy.error(), Scope::All);
+    results.add("Energy per particle", particle_energy, sampling.energy.error() / n, Scope::All);
+    if system.gay_berne.is_none() {
+        results.add("Repulsive energy per particle (r^-12 within cutoff)", sampling.repulsive_energy.mean() / n, f64::NAN, Scope::All);
+        results.add("Attractive energy per particle (r^-6 within cutoff)", sampling.attractive_energy.mean() / n, f64::NAN, Scope::All);
+    }
+    if system.params.three_body_nu != 0.0 {
+        results.add("Three-body energy per particle (Axilrod-Teller)", sampling.three_body_energy.mean() / n, f64::NAN, Scope::All);
+    }
+    if system.restraints.is_some() {
+        results.add("Restraint energy (included in the energy)", sampling.restraint.mean(), sampling.restraint.error(), Scope::All);
+    }
+    results.add("Virial", final_virial, f64::NAN, Scope::All);
+    results.add("Pressure", pressure, sampling.pressure.error(), Scope::Bulk);
+    // the residual properties of equation of state comparisons, the energy is configurational already
+    let ideal_pressure = system.density * system.params.temperature;
+    results.add("Excess internal energy per particle", particle_energy, sampling.energy.error() / n, Scope::All);
+    results.add("Excess pressure (P - rho T)", pressure - ideal_pressure, sampling.pressure.error(), Scope::Bulk);
+    results.add("Compressibility factor Z = P/(rho T)", pressure / ideal_pressure, sampling.pressure.error() / ideal_pressure, Scope::Bulk);
+    results.add("Surface tension (without tail corrections)", sampling.tension.mean(), sampling.tension.error(), Scope::Slab);
+    if let Some(ref expanded) = system.expanded {
+        // excess chemical potential within the cutoff, the tail correction is twice the one of the energy per particle
+        let temperature = system.params.temperature;
+        results.add("Solvation free energy (expanded ensemble, within cutoff)", expanded.ladder.get_free_energies().last().unwrap() * temperature, f64::NAN, Scope::Bulk);
+        results.add("Solvation free energy (widom insertions of the decoupled state)", expanded.get_widom_free_energy() * temperature, f64::NAN, Scope::Bulk);
+        results.add("Lambda move acceptance", expanded.ladder.accepted as f64 / expanded.ladder.attempts as f64, f64::NAN, Scope::All);
+    }
+    if system.params.rotations {
+        results.add("Nematic order parameter P2", sampling.nematic.mean(), sampling.nematic.error(), Scope::All);
+        let (_, director) = get_nematic_order(&system.orientations);
+        println_stderr!("Director of the final configuration: {} {} {}", director[0], director[1], director[2]);
+        if system.params.dipole_moment != 0.0 {
+            results.add("Polarization along the field", sampling.polarization.mean(), sampling.polarization.error(), Scope::All);
+            results.add("Mean alignment of the dipoles with the field", sampling.polarization.mean() / system.density / system.params.dipole_moment, sampling.polarization.error() / system.density / system.params.dipole_moment, Scope::All);
+        }
+        results.add("Rotation acceptance", system.counters.rotation_accepted as f64 / system.counters.rotation_attempts as f64, f64::NAN, Scope::All);
+    }
+    if system.params.overlap_radius > 0.0 {
+        results.add("Overlap rejections per trial move", system.counters.overlap_rejections as f64 / (step_counter * moves_per_step) as f64, f64::NAN, Scope::All);
+    }
+    if system.params.early_rejection {
+        results.add("Early rejections per trial move", system.counters.early_rejections as f64 / (step_counter * moves_per_step) as f64, f64::NAN, Scope::All);
+    }
+    if let Some(ref tempering) = system.tempering {
+        // the plain averages above mix all temperatures of the ladder
+        results.add("Temperature move acceptance", tempering.ladder.accepted as f64 / tempering.ladder.attempts as f64, f64::NAN, Scope::All);
+    }
+    if let Some((energy, pressure)) = system.get_multicanonical_averages() {
+        // the plain averages above are those of the flat energy distribution
+        results.add("Energy per particle reweighted from the multicanonical sampling", energy / n, f64::NAN, Scope::All);
+        results.add("Pressure reweighted from the multicanonical sampling", pressure, f64::NAN, Scope::Bulk);
+    }
 
     println_stderr!("");
     println_stderr!("################################################################");
@@ -290,67 +955,547 @@ P-Correction: {}
 Tries: {}
 Accepted: {}
 Acceptance: {:.2}%
-Energy: {}
-Energy per particle: {}
-Virial: {}
-Pressure: {}",
+{}
+
+# Drift between running and recomputed values
+Max energy drift: {:e}
+Accumulated energy drift: {:e}
+Max virial drift: {:e}",
          eq_steps, sample_steps,
-        LJ_EPS, LJ_SIG, cutoff,
-        num_particles, density, temperature, volume, l_x, l_y, l_z, displacement,
-        e_corr, e_shift, p_corr,
-        step_counter, accept_counter, final_acceptance_rate, final_energy, particle_energy, final_virial, pressure);
+        LJ_EPS, LJ_SIG, system.params.cutoff,
+        num_particles, system.density, system.params.temperature, system.volume, system.l_x, system.l_y, system.l_z, system.displacement,
+        system.e_corr, system.e_shift, system.p_corr,
+        step_counter * moves_per_step, counters.accept_counter, final_acceptance_rate, results.to_text(slab),
+        counters.energy_drift_max, counters.energy_drift_sum, counters.virial_drift_max);
+
+    // comparison with the equation of state of the full potential, for unbiased plain lj pairs with its forces within the cutoff
+    let temperature = system.params.temperature;
+    let plain = system.gay_berne.is_none() && system.params.three_body_nu == 0.0 && system.restraints.is_none() && system.params.dipole_moment == 0.0 && system.params.expanded_states == 0
+                && system.params.tempering_states == 0 && system.params.multicanonical_bins == 0 && system.params.umbrella_spring == 0.0 && !system.params.force_shift && system.switch_squared == 0.0;
+    if plain && !slab && (EOS_TEMPERATURES.0..=EOS_TEMPERATURES.1).contains(&temperature) {
+        let (e_tail, p_tail) = get_tail_corrections(system.params.cutoff, system.density, 0.0);
+        // the shift only changes the energy, the lj energy within the cutoff of the decomposition is not shifted
+        let (energy, energy_error) = if system.params.shift { ((sampling.repulsive_energy.mean() + sampling.attractive_energy.mean()) / n + e_tail, f64::NAN) }
+                                     else { (particle_energy - system.e_corr + e_tail, sampling.energy.error() / n) };
+        let (eos_energy, eos_pressure) = get_johnson_eos(temperature, system.density);
+        println!("\n# Equation of state of the full potential (Johnson, Zollweg and Gubbins 1993, only meaningful outside the coexistence region)");
+        println!("{}", format_comparison("Energy per particle (with tail correction)", energy, energy_error, eos_energy));
+        println!("{}", format_comparison("Pressure (with tail correction)", pressure - system.p_corr + p_tail, sampling.pressure.error(), eos_pressure));
+    }
+
+    if let Some(units) = Units::parse(&system.params.units) {
+        println!("\n# Real units (eps/kB = {} K, sigma = {} nm, {} g/mol)", units.eps, units.sig, units.mass);
+        println!("Temperature: {} K", units.temperature(system.params.temperature));
+        println!("Density: {} 1/nm3, {} kg/m3", units.number_density(system.density), units.mass_density(system.density));
+        println!("Box dimension: {:.4}/{:.4}/{:.4} nm", units.length(system.l_x), units.length(system.l_y), units.length(system.l_z));
+        for (name, mean, error) in results.select(slab) {
+            if let Some((factor, unit)) = get_real_unit(&name, &units) {
+                if error.is_finite() { println!("{}: {} +/- {:.3e} {}", name, factor * mean, factor * error, unit); } else { println!("{}: {} {}", name, factor * mean, unit); }
+            }
+        }
+    }
+
+    // averages with the parameters for the compare tool
+    let mut selected = results.select(slab);
+    selected.push(("Acceptance".to_string(), counters.accept_counter as f64 / (step_counter * moves_per_step) as f64, f64::NAN));
+    RunResults { provenance: system.provenance.clone(), ..RunResults::new(&system.params, &selected) }.save(&format!("{}.results.json", output_prefix)).unwrap_or_else(exit_with);
+
+    system.write_frame(&mut trajectory, system.counters.step).unwrap_or_else(exit_with);
+    trajectory.flush().unwrap_or_else(exit_with);
+    if let Some(ref mut tagged) = tagged { tagged.flush().unwrap_or_else(exit_with); }
+    if let Some(ref mut move_log) = move_log { move_log.flush().unwrap_or_else(exit_with); }
+    system.counters.sampling = Some(sampling.clone());
+    system.save_checkpoint(&format!("{}.checkpoint.json", output_prefix)).unwrap_or_else(exit_with);
+
+    // final configuration on its own for chained runs, the gro file in nm of the units (sigma without)
+    let mut final_frame = XYZTrajectory::new(&format!("{}.final.xyz", output_prefix)).unwrap_or_else(exit_with);
+    final_frame.header = provenance.to_key_values(&system.params);
+    system.write_frame(&mut final_frame, system.counters.step).unwrap_or_else(exit_with);
+    final_frame.flush().unwrap_or_else(exit_with);
+    let length_unit = Units::parse(&system.params.units).map_or(1.0, |units| units.sig);
+    write_gro(&format!("{}.final.gro", output_prefix), &system.frame(system.counters.step), length_unit).unwrap_or_else(exit_with);
+    let mut final_pdb = PdbWriter::new(&format!("{}.final.pdb", output_prefix), pdb_length_unit, pdb_element).unwrap_or_else(exit_with);
+    final_pdb.remark(&provenance.summary()).unwrap_or_else(exit_with);
+    final_pdb.write(&system.frame(system.counters.step)).unwrap_or_else(exit_with);
+    final_pdb.finish().unwrap_or_else(exit_with);
+    if let Some(ref mut pdb) = pdb {
+        pdb.write(&system.frame(system.counters.step)).unwrap_or_else(exit_with);
+        pdb.finish().unwrap_or_else(exit_with);
+    }
+    #[cfg(feature = "netcdf")]
+    if let Some(ref mut netcdf) = netcdf {
+        netcdf.write(&system.frame(system.counters.step)).unwrap_or_else(exit_with);
+        netcdf.flush().unwrap_or_else(exit_with);
+    }
+    println_stderr!("Final configuration written to {}.final.xyz, {}.final.gro and {}.final.pdb", output_prefix, output_prefix, output_prefix);
+
+    // density of states and the canonical distribution of the multicanonical sampling
+    if let Some(ref multicanonical) = system.multicanonical {
+        let distribution = multicanonical.get_canonical_distribution(system.beta);
+        let mut table = "# energy per particle, ln g, samples, canonical probability\n".to_string();
+        for b in 0..multicanonical.ln_g.len() {
+            table += &format!("{} {} {} {:e}\n", (multicanonical.min + (b as f64 + 0.5) * multicanonical.width) / num_particles as f64, multicanonical.ln_g[b], multicanonical.histogram[b], distribution[b]);
+        }
+        std::fs::write(format!("{}.muca.dat", output_prefix), table).unwrap_or_else(|e| exit_with(e.into()));
+        println_stderr!("Density of states written to {}.muca.dat", output_prefix);
+    }
+
+    // free energy profile of the couplings
+    if let Some(ref expanded) = system.expanded {
+        let free_energies = expanded.ladder.get_free_energies();
+        let mut table = "# lambda, weight, samples, free energy in kT\n".to_string();
+        for k in 0..expanded.lambdas.len() {
+            table += &format!("{} {} {} {}\n", expanded.lambdas[k], expanded.ladder.weights[k], expanded.ladder.histogram[k], free_energies[k]);
+        }
+        std::fs::write(format!("{}.expanded.dat", output_prefix), table).unwrap_or_else(|e| exit_with(e.into()));
+        println_stderr!("Free energies of the couplings written to {}.expanded.dat", output_prefix);
+    }
+
+    // averages at every temperature of simulated tempering
+    if let Some(ref tempering) = system.tempering {
+        let free_energies = tempering.ladder.get_free_energies();
+        let mut table = "# temperature, weight, samples, free energy in kT, energy per particle, error, pressure, error (errors of uncorrelated steps)\n".to_string();
+        for k in 0..tempering.temperatures.len() {
+            let (energy, virial) = tempering.get_averages(k);
+            let (energy_error, virial_error) = tempering.get_errors(k);
+            let pressure = system.density * tempering.temperatures[k] + virial / 3.0 / system.volume + system.p_corr;
+            table += &format!("{} {} {} {} {} {:e} {} {:e}\n", tempering.temperatures[k], tempering.ladder.weights[k], tempering.ladder.histogram[k], free_energies[k],
+                              energy / n, energy_error / n, pressure, virial_error / 3.0 / system.volume);
+        }
+        std::fs::write(format!("{}.tempering.dat", output_prefix), table).unwrap_or_else(|e| exit_with(e.into()));
+        println_stderr!("Averages of the temperatures written to {}.tempering.dat", output_prefix);
+    }
+
+    // biased histogram of the largest cluster sizes for the stitching of the umbrella windows
+    if system.params.umbrella_spring > 0.0 {
+        let free_energy = system.get_cluster_free_energy();
+        let mut table = "# cluster size, samples, bias energy, free energy in kT (-ln P - W/kT)\n".to_string();
+        for (n, &samples) in system.counters.cluster_histogram.iter().enumerate().filter(|h| *h.1 > 0) {
+            table += &format!("{} {} {} {}\n", n, samples, system.get_umbrella_bias(n), free_energy[n]);
+        }
+        std::fs::write(format!("{}.umbrella.dat", output_prefix), table).unwrap_or_else(|e| exit_with(e.into()));
+        println_stderr!("Cluster size histogram written to {}.umbrella.dat", output_prefix);
+    }
+
+    if let Some(ref mut log) = recenter_log {
+        log.flush().unwrap_or_else(|e| exit_with(e.into()));
+        println_stderr!("Slab shifts written to {}", recenter_file);
+    }
+
+    // summary figures for the plots and the report
+    let mut figures : Vec<(&str, &str, String)> = vec![];
+    if record_series {
+        let step_label = if SWEEPS { "sweep" } else { "step" };
+        figures.push(("energy", "Energy per particle", line_plot_svg("Energy", step_label, "energy per particle", &energy_series).unwrap_or_else(exit_with)));
+        figures.push(("acceptance", "Acceptance ratio since the last displacement update", line_plot_svg("Acceptance", step_label, "acceptance ratio", &acceptance_series).unwrap_or_else(exit_with)));
+        if profile_count > 0 {
+            let bin_height = system.l_z / PLOT_PROFILE_BINS as f64;
+            let profile : Vec<(f64, f64)> = profile_sum.iter().enumerate()
+                .map(|(i, sum)| ((i as f64 + 0.5) * bin_height, sum / profile_count as f64)).collect();
+            figures.push(("density_z", "Density profile averaged over the sampling", line_plot_svg("Density profile", "z", "density", &profile).unwrap_or_else(exit_with)));
+        }
+    }
+
+    if PLOTS {
+        for &(name, _, ref svg) in &figures {
+            std::fs::write(format!("{}.{}.svg", output_prefix, name), svg).unwrap_or_else(|e| exit_with(e.into()));
+        }
+        println_stderr!("Plots written to {}.*.svg", output_prefix);
+    }
+
+    if !REPORT.is_empty() {
+        let mut report = Report::new(&format!("LJ Monte Carlo: {}", output_prefix), &system.params);
+        report.results = selected;
+        report.diagnostics = vec![
+            ("Blocks x block length".to_string(), format!("{} x {}", sampling.energy.blocks.len(), sampling.energy.block_size)),
+            ("Energy drift between halves of the sampling".to_string(), format!("{:.2} sigma", sampling.energy.drift())),
+            ("Pressure drift between halves of the sampling".to_string(), format!("{:.2} sigma", sampling.pressure.drift())),
+            ("Final displacement".to_string(), format!("{:.5}", system.displacement)),
+            ("Displacement updates".to_string(), format!("{}", counters.scale_updates)),
+            ("Max energy drift (running vs recomputed)".to_string(), format!("{:e}", counters.energy_drift_max)),
+            ("Accumulated energy drift".to_string(), format!("{:e}", counters.energy_drift_sum)),
+            ("Max virial drift".to_string(), format!("{:e}", counters.virial_drift_max)),
+            ("Recalculations above the drift tolerance".to_string(), format!("{}", counters.drift_violations)),
+            ("Box dimensions".to_string(), format!("{:.3}/{:.3}/{:.3}", system.l_x, system.l_y, system.l_z)),
+            ("Energy/pressure tail corrections".to_string(), format!("{:.5}/{:.5}", system.e_corr, system.p_corr)),
+            ("Wall time".to_string(), format!("{:.1} s", start.elapsed().as_secs_f64())),
+        ];
+        report.figures = figures.into_iter().map(|(_, caption, svg)| (caption.to_string(), svg)).collect();
+        report.write(&REPORT).unwrap_or_else(exit_with);
+        println_stderr!("Report written to {}", REPORT);
+    }
+
+    // a run stopped early continues with a restart from its checkpoint
+    if let Some(ref mut manifest) = manifest {
+        manifest.finish(if system.counters.step < eq_steps + sample_steps { "stopped" } else { "finished" }, &output_directory).unwrap_or_else(exit_with);
+        println_stderr!("Manifest written to {}", Path::new(&output_directory).join(mclj::outdir::MANIFEST).display());
+    }
+}
+
+// Factor of an observable of the results to real units and the unit, None for the dimensionless ones
+fn get_real_unit(name: &str, units: &Units) -> Option<(f64, &'static str)> {
+    if name.starts_with("Pressure") || name.starts_with("Excess pressure") { return Some((units.pressure(1.0), "bar")); }
+    if name.starts_with("Surface tension") { return Some((units.tension(1.0), "mN/m")); }
+    if name.starts_with("Virial") || (name.to_lowercase().contains("energy") && !name.contains("per trial move")) {
+        return Some((units.energy(1.0), "kJ/mol"));
+    }
+    return None;
+}
+
+#[test]
+fn test_get_real_unit() {
+    let units = mclj::units::ARGON;
+    assert_eq!("bar", get_real_unit("Pressure", &units).unwrap().1);
+    assert_eq!("kJ/mol", get_real_unit("Solvation free energy (expanded ensemble, within cutoff)", &units).unwrap().1);
+    assert!( (get_real_unit("Energy per particle", &units).unwrap().0 * -5.0 - units.energy(-5.0)).abs() < 1e-12);
+    assert!(get_real_unit("Early rejections per trial move", &units).is_none());
+    assert!(get_real_unit("Nematic order parameter P2", &units).is_none());
+    assert_eq!("bar", get_real_unit("Excess pressure (P - rho T)", &units).unwrap().1);
+    assert!(get_real_unit("Compressibility factor Z = P/(rho T)", &units).is_none());
+}
+
+// Parses a duration in seconds, minutes, hours or days (e.g. 3600, 90m, 23h, 1.5d) into seconds
+fn parse_duration(duration: &str) -> Option<f64> {
+    let duration = duration.trim();
+    let (value, unit) = match duration.chars().last() {
+        Some('s') => (&duration[..duration.len()-1], 1.0),
+        Some('m') => (&duration[..duration.len()-1], 60.0),
+        Some('h') => (&duration[..duration.len()-1], 3600.0),
+        Some('d') => (&duration[..duration.len()-1], 86400.0),
+        _ => (duration, 1.0),
+    };
+    return match value.parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Some(value * unit),
+        _ => None,
+    };
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(Some(3600.0), parse_duration("3600"));
+    assert_eq!(Some(5400.0), parse_duration("90m"));
+    assert_eq!(Some(82800.0), parse_duration("23h"));
+    assert_eq!(Some(129600.0), parse_duration("1.5d"));
+    assert_eq!(Some(10.0), parse_duration("10s"));
+    assert_eq!(None, parse_duration("h"));
+    assert_eq!(None, parse_duration("-1h"));
+    assert_eq!(None, parse_duration("soon"));
+}
+
+// Equilibrates and samples independent replicas in parallel, the errors are the standard errors of the replica averages
+fn run_replicas(params: &Params, replicas: usize, provenance: &Provenance) {
+    println_stderr!("Running {} independent replicas", replicas);
+    let workers : Vec<thread::JoinHandle<(Averages, f64)>> = (0..replicas).map(|i| {
+        let params = params.clone();
+        let provenance = provenance.clone();
+        thread::spawn(move || {
+            // every replica gets its own stream of the seed
+            let mut rng = get_stream_rng(params.seed, StreamDomain::Replica, i as u64);
+            let mut system = System::new(params, &mut rng);
+            system.provenance.push(provenance);
+            system.equilibrate(system.params.eq_steps, &mut rng);
+            let averages = system.sample(system.params.sample_steps, &mut rng);
+            system.save_checkpoint(&format!("{}.replica{}.checkpoint.json", system.params.output_prefix, i)).unwrap_or_else(exit_with);
+            (averages, system.displacement)
+        })
+    }).collect();
+    let results : Vec<(Averages, f64)> = workers.into_iter().map(|w| w.join().expect("replica panicked")).collect();
+
+    let n = params.num_particles as f64;
+    let energies : Vec<f64> = results.iter().map(|r| r.0.energy).collect();
+    let pressures : Vec<f64> = results.iter().map(|r| r.0.pressure).collect();
+    let acceptances : Vec<f64> = results.iter().map(|r| r.0.acceptance).collect();
+    println!(
+"Minimization: {}
+Steps: {}
+Replicas: {}
+
+# Averages over replicas (error: standard error between replicas)
+Energy: {} +- {}
+Energy per particle: {} +- {}
+Pressure: {} +- {}
+Acceptance: {:.2}%
+
+# Replicas (energy per particle, pressure, block error of the pressure, displacement)",
+        params.eq_steps, params.sample_steps, replicas,
+        get_mean(&energies), get_standard_error(&energies),
+        get_mean(&energies) / n, get_standard_error(&energies) / n,
+        get_mean(&pressures), get_standard_error(&pressures),
+        get_mean(&acceptances) * 100.0);
+    for (i, &(ref averages, displacement)) in results.iter().enumerate() {
+        println!("{}: {} {} {} {}", i, averages.energy / n, averages.pressure, averages.pressure_error, displacement);
+    }
+}
+
+// Flushes the trajectory, tagged particles and move log of a run that can not continue, writes the emergency
+// checkpoint {prefix}.emergency.json (with the note after its file name), marks the run directory aborted and exits
+fn abort_run(system: &System, trajectory: &mut XYZTrajectory, tagged: &mut Option<TaggedParticles>, move_log: &mut Option<MoveLog>,
+             recenter_log: &mut Option<BufWriter<File>>, manifest: &mut Option<Manifest>, output_directory: &str, note: &str) -> ! {
+    trajectory.flush().unwrap_or_else(exit_with);
+    if let Some(ref mut tagged) = *tagged { tagged.flush().unwrap_or_else(exit_with); }
+    if let Some(ref mut move_log) = *move_log { move_log.flush().unwrap_or_else(exit_with); }
+    if let Some(ref mut log) = *recenter_log { log.flush().unwrap_or_else(|e| exit_with(e.into())); }
+    let emergency_file = format!("{}.emergency.json", system.params.output_prefix);
+    system.save_checkpoint(&emergency_file).unwrap_or_else(exit_with);
+    println_stderr!("Emergency checkpoint written to {}{}", emergency_file, note);
+    if let Some(ref mut manifest) = *manifest { manifest.finish("aborted", output_directory).unwrap_or_else(exit_with); }
+    std::process::exit(1);
+}
 
-    trajectory.write(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, temperature, LJ_EPS, LJ_SIG, cutoff, true);
+// Observables of the running simulation for the control server and stream clients
+fn get_status(system: &System, step: Step, total_steps: Step, output_interval: Option<Step>, paused: bool) -> Status {
+    return Status {
+        step: step,
+        total_steps: total_steps,
+        sampling: step >= system.params.eq_steps,
+        energy: system.energy,
+        virial: system.virial,
+        pressure: system.pressure(),
+        acceptance: system.counters.accept_counter as f64 / (system.counters.step_counter * system.moves_per_step()) as f64,
+        displacement: system.displacement,
+        output_interval: output_interval,
+        paused: paused,
+    };
 }
 
 // Parse command line arguments
-fn parse_cmd_args(NUM_STEPS: &mut usize, NUM_eq_steps: &mut usize,
-                  NUM_PARTICLES: &mut usize, DENSITY: &mut f64, TEMPERATURE: &mut f64,
-                  CUTOFF: &mut f64, MAX_DISP_START: &mut f64, SCALE: &mut bool, TAILCORR: &mut bool, SHIFT: &mut bool,
-                  OUTPUT_PREFIX: &mut String, OUTPUT_INTERVAL: &mut i64, OUTPUT_MINIM: &mut bool,
-                  VACUUM_SLAB: &mut f64) {
+fn parse_cmd_args(params: &mut Params, DRY_RUN: &mut bool, SERVE: &mut String, STREAM: &mut String, METRICS: &mut String, TUI: &mut bool, PLOTS: &mut bool, REPORT: &mut String, REPLICAS: &mut usize, RESTART: &mut String, MAX_WALLTIME: &mut String, FLUSH_INTERVAL: &mut f64, TAG: &mut String, MOVE_LOG: &mut bool, KELVIN: &mut f64, TEACH: &mut usize, INTERACTIVE: &mut bool, PDB: &mut bool, NETCDF: &mut bool, PROTOCOL: &mut String, OUTDIR: &mut String, OSTEPS: &mut String) {
+    // applied by main before the parsing, only accepted here
+    let mut preset = String::new();
     let mut ap = ArgumentParser::new();
     ap.set_description("LJ MC simulation.");
-    ap.refer(NUM_STEPS)
+    ap.refer(&mut preset)
+        .add_option(&["--preset"], Store,
+                    "Start from the parameters of a common state point (triple, critical, argon85 or slab07), the other options override them");
+    ap.refer(&mut params.sample_steps)
         .add_option(&["-n", "--nsteps"], Store,
                     "Simulation steps: Number of steps for averaging" );
-    ap.refer(NUM_eq_steps)
+    ap.refer(&mut params.eq_steps)
         .add_option(&["-m", "--nminimsteps"], Store,
                     "Minimization steps: Number of steps before averaging starts");
-    ap.refer(NUM_PARTICLES)
+    ap.refer(&mut params.sweeps)
+        .add_option(&["--sweeps"], StoreTrue,
+                    "Count all steps and intervals in sweeps of nparticles attempted moves");
+    ap.refer(&mut params.num_particles)
         .add_option(&["-p", "--nparticles"], Store,
                     "Total number of particles");
-    ap.refer(DENSITY)
+    ap.refer(&mut params.density)
         .add_option(&["-d", "--density"], Store,
                     "Particle density");
-    ap.refer(TEMPERATURE)
+    ap.refer(&mut params.temperature)
         .add_option(&["-t", "--temperature"], Store,
                     "Temperature");
-    ap.refer(CUTOFF)
+    ap.refer(&mut params.cutoff)
         .add_option(&["--cutoff"], Store,
                     "Lennard jones cutoff radius in length of epsilon");
-    ap.refer(MAX_DISP_START)
+    ap.refer(&mut params.force_shift)
+        .add_option(&["--forceshift"], StoreTrue,
+                    "Use the force-shifted potential, energy and force go to zero at the cutoff (no tail corrections)");
+    ap.refer(&mut params.switch_radius)
+        .add_option(&["--switch"], Store,
+                    "Smoothly switch the potential off between this radius and the cutoff instead of shifting it (0=sharp cutoff)");
+    ap.refer(&mut params.recompute_interval)
+        .add_option(&["--recompute"], Store,
+                    "Number of steps between full energy recalculations (0=disabled, default 10000)");
+    ap.refer(&mut params.recompute_time)
+        .add_option(&["--recomputetime"], Store,
+                    "Seconds between full energy recalculations (0=disabled)");
+    ap.refer(&mut params.drift_tolerance)
+        .add_option(&["--drifttol"], Store,
+                    "Warn if the running energy or virial deviate from a recalculation by more than this relative tolerance (0=disabled)");
+    ap.refer(&mut params.drift_abort)
+        .add_option(&["--driftabort"], StoreTrue,
+                    "Abort with an emergency checkpoint instead of warning on a deviation above the drift tolerance");
+    ap.refer(&mut params.displacement)
         .add_option(&["--displacement"], Store,
                     "Displacement per trial move");
-    ap.refer(SCALE)
+    ap.refer(&mut params.scale)
         .add_option(&["--nodisplacementscale"], StoreFalse,
                     "Disable displacement scaling");
-    ap.refer(OUTPUT_PREFIX)
+    ap.refer(&mut params.output_prefix)
         .add_option(&["-o", "--output"], Store,
                     "Output file prefix");
-    ap.refer(OUTPUT_INTERVAL)
+    ap.refer(OUTDIR)
+        .add_option(&["--outdir"], Store,
+                    "Collect all outputs of the run in a new directory <outdir>/<output>-<utc time> with the effective parameters in config.json and a manifest.json of the files");
+    ap.refer(OSTEPS)
         .add_option(&["--osteps"], Store,
                     "Number of steps between writing to the trajectory file. -1 only writes last frame");
-    ap.refer(OUTPUT_MINIM)
+    ap.refer(&mut params.log_interval)
+        .add_option(&["--logsteps"], Store,
+                    "Number of steps between the progress lines (0 for every 5000 steps or 10 sweeps)");
+    ap.refer(&mut params.checkpoint_interval)
+        .add_option(&["--checkpointsteps"], Store,
+                    "Number of steps between checkpoints to restart from (0 only writes the last one)");
+    ap.refer(&mut params.analysis_interval)
+        .add_option(&["--analysissteps"], Store,
+                    "Number of steps between the on-the-fly analyses: slab density profile printout and the series of the summary plots (0 for the defaults)");
+    ap.refer(&mut params.output_minim)
         .add_option(&["--writeminimization"], StoreTrue,
                     "Enables writing of minimization step to trajectory");
-    ap.refer(VACUUM_SLAB)
+    ap.refer(&mut params.particle_columns)
+        .add_option(&["--columns"], Store,
+                    "Per-particle columns of the trajectory frames: comma separated energy, cluster (id of the cluster of neighbors within 1.5 sigma, 0=largest) density (local number density within 1.5 sigma), virial, stress (virial tensor xx yy zz xy xz yz of the particle) and voronoi (volume of the voronoi cell)");
+    ap.refer(&mut params.vacuum_slab)
         .add_option(&["--vacuum"], Store,
                     "Dimension of vacuum space above the intial system relative to the rest of the system (0=no slab, 1=half filled system, 2=thrid filled system ...).");
-    ap.refer(TAILCORR)
+    ap.refer(&mut params.multicanonical_min)
+        .add_option(&["--mucamin"], Store,
+                    "Lowest energy per particle of the multicanonical sampling");
+    ap.refer(&mut params.multicanonical_max)
+        .add_option(&["--mucamax"], Store,
+                    "Highest energy per particle of the multicanonical sampling");
+    ap.refer(&mut params.multicanonical_bins)
+        .add_option(&["--mucabins"], Store,
+                    "Energy bins of the multicanonical sampling, the weights are learned by Wang-Landau during the equilibration and the sampling is reweighted to the temperature, written to <output>.muca.dat (0=disabled)");
+    ap.refer(&mut params.expanded_states)
+        .add_option(&["--expanded"], Store,
+                    "Expanded ensemble over this many couplings lambda of particle 0 between 0 and 1 for the solvation free energy, compared to the widom insertions of the decoupled state and written to <output>.expanded.dat (0=disabled)");
+    ap.refer(&mut params.tempering_min)
+        .add_option(&["--tmin"], Store,
+                    "Lowest temperature of simulated tempering");
+    ap.refer(&mut params.tempering_max)
+        .add_option(&["--tmax"], Store,
+                    "Highest temperature of simulated tempering");
+    ap.refer(&mut params.tempering_states)
+        .add_option(&["--tstates"], Store,
+                    "Geometrically spaced temperatures of simulated tempering, the weights are learned by Wang-Landau during the equilibration and the averages of every temperature are written to <output>.tempering.dat (0=disabled)");
+    ap.refer(&mut params.umbrella_center)
+        .add_option(&["--umbrella"], Store,
+                    "Center of the umbrella bias on the size of the largest cluster of liquid-like particles (5 or more neighbors within 1.5 sigma), the histogram and free energy of the sampled sizes are written to <output>.umbrella.dat");
+    ap.refer(&mut params.umbrella_spring)
+        .add_option(&["--spring"], Store,
+                    "Spring constant of the umbrella bias in epsilon (0=disabled)");
+    ap.refer(&mut params.proposal)
+        .add_option(&["--proposal"], Store,
+                    "Distribution of the trial displacements: cube (uniform per coordinate, default), ball (uniform in the sphere of the displacement as diameter) or gaussian (the variance of the cube, truncated)");
+    ap.refer(&mut params.early_rejection)
+        .add_option(&["--earlyreject"], StoreTrue,
+                    "Stop the pair sum of a trial move once its energy is certainly above the pre-drawn acceptance threshold (overlaps in dense systems), the fraction of early rejections is reported");
+    ap.refer(&mut params.lazy_virial)
+        .add_option(&["--lazy-virial"], StoreTrue,
+                    "Trial moves only evaluate the energy, the virial and the pressure are sampled at the full recalculations every --recompute steps (plain lj pairs)");
+    ap.refer(&mut params.checkerboard_threads)
+        .add_option(&["--checkerboard"], Store,
+                    "Sweeps by checkerboard domain decomposition on this many threads: the particles move within the cells of a randomly shifted grid of the cutoff, the cells of a color in parallel (needs --sweeps, plain lj pairs, 0=disabled)");
+    ap.refer(&mut params.overlap_radius)
+        .add_option(&["--overlap"], Store,
+                    "Hard-core radius below sigma: trial positions closer to any particle are rejected by a cell list scan before the energy, e.g. 0.8 where the lj energy is 43 epsilon, it has to be at least 20 kT (0=disabled)");
+    ap.refer(&mut params.force_bias)
+        .add_option(&["--forcebias"], StoreTrue,
+                    "Enable force-bias trial moves");
+    ap.refer(&mut params.rotations)
+        .add_option(&["--rotations"], StoreTrue,
+                    "Orientation (quaternion) per particle with a random rotation move after every displacement move, the maximum angle is adapted during the equilibration and the orientations are written to the trajectory");
+    ap.refer(&mut params.gb_kappa)
+        .add_option(&["--gbkappa"], Store,
+                    "Gay-Berne ellipsoids of this length to breadth ratio instead of lj spheres, needs --rotations and a cutoff of about kappa + 1 (0=lj)");
+    ap.refer(&mut params.gb_kappa_prime)
+        .add_option(&["--gbkappaprime"], Store,
+                    "Ratio of the side by side to the end to end well depth of the Gay-Berne ellipsoids");
+    ap.refer(&mut params.three_body_nu)
+        .add_option(&["--atnu"], Store,
+                    "Axilrod-Teller triple-dipole coefficient in epsilon sigma^9 added to the pair energies, about 0.073 for argon (0=disabled)");
+    ap.refer(&mut params.three_body_cutoff)
+        .add_option(&["--atcutoff"], Store,
+                    "Cutoff of every pair of the three-body triplets, at most a quarter of the box");
+    ap.refer(&mut params.dipole_moment)
+        .add_option(&["--dipole"], Store,
+                    "Point dipole moment along the particle axes, needs --rotations");
+    ap.refer(&mut params.field_strength)
+        .add_option(&["--field"], Store,
+                    "Strength of the uniform external field coupling to the dipoles by -mu E, the polarization along the field is reported (0=disabled)");
+    ap.refer(&mut params.field_direction)
+        .add_option(&["--fielddir"], Store,
+                    "Direction x,y,z of the external field (default 0,0,1)");
+    ap.refer(&mut params.restraints)
+        .add_option(&["--restraints"], Store,
+                    "Restraints added to the energy, a ; separated list of tether:i:x,y,z:k, distance:i:j:r0:k and slab:w:k (flat-bottom on the slab center), without contribution to the pressure");
+    ap.refer(&mut params.fb_lambda)
+        .add_option(&["--fblambda"], Store,
+                    "Force-bias parameter lambda between 0 and 1 (default 0.5)");
+    ap.refer(&mut params.interface_factor)
+        .add_option(&["--interfacesampling"], Store,
+                    "Select particles near the slab interfaces this many times more often than bulk particles (1=disabled)");
+    ap.refer(&mut params.interface_width)
+        .add_option(&["--interfacewidth"], Store,
+                    "Distance to the interface within which particles are preferentially selected (default 2.0)");
+    ap.refer(&mut params.recenter_interval)
+        .add_option(&["--recenter"], Store,
+                    "Steps between translations of the liquid slab (largest cluster) back to the box center along z against drift, the shifts are written to <output>.recenter.dat (0=disabled)");
+    ap.refer(DRY_RUN)
+        .add_option(&["--dry-run"], StoreTrue,
+                    "Print the derived system setup and exit");
+    ap.refer(SERVE)
+        .add_option(&["--serve"], Store,
+                    "Address (host:port) of a http control server to query and steer the run: GET /status, /frame, POST /pause, /resume, /stop, /output_interval/<steps>");
+    ap.refer(STREAM)
+        .add_option(&["--stream"], Store,
+                    "Address (host:port) to publish trajectory frames and observables on as json lines to every connected tcp client");
+    ap.refer(METRICS)
+        .add_option(&["--metrics"], Store,
+                    "Address (host:port) of a prometheus metrics endpoint at GET /metrics");
+    ap.refer(TUI)
+        .add_option(&["--tui"], StoreTrue,
+                    "Show a terminal dashboard with energy, pressure and acceptance traces and the density profile instead of the progress output");
+    ap.refer(PLOTS)
+        .add_option(&["--plots"], StoreTrue,
+                    "Write svg plots of energy and acceptance vs step (and the density profile in slab mode) at the end of the run");
+    ap.refer(REPORT)
+        .add_option(&["--report"], Store,
+                    "Write a single file html report with parameters, averages with errors, convergence diagnostics and plots");
+    ap.refer(REPLICAS)
+        .add_option(&["--replicas"], Store,
+                    "Run this many independent replicas in parallel threads and estimate errors from their scatter (no trajectory or live outputs)");
+    ap.refer(RESTART)
+        .add_option(&["--restart"], Store,
+                    "Continue the run of a checkpoint, its run parameters replace the given ones and the trajectory is appended after its frames up to the checkpoint");
+    ap.refer(MAX_WALLTIME)
+        .add_option(&["--max-walltime"], Store,
+                    "Stop cleanly with checkpoint and partial results after this wall time (seconds or with unit s/m/h/d, e.g. 23h)");
+    ap.refer(FLUSH_INTERVAL)
+        .add_option(&["--flush-interval"], Store,
+                    "Seconds between flushes of the buffered trajectory, a crash loses at most the frames of this interval (0 flushes every frame)");
+    ap.refer(TAG)
+        .add_option(&["--tag"], Store,
+                    "Write the unwrapped coordinates and move statistics of these particles (e.g. 0-9,42) every sweep to <output>.tagged.dat for the msd tool");
+    ap.refer(&mut params.schedule)
+        .add_option(&["--schedule"], Store,
+                    "Change parameters during the run, a ; separated list of step:temperature:value, step:density:value and step:vacuum:value (e.g. 2000000:temperature:0.8)");
+    ap.refer(PROTOCOL)
+        .add_option(&["--protocol"], Store,
+                    "Run the stages of a protocol file one after the other, a line per stage of name, steps and changes at its start (e.g. cool 1000000 temperature:0.7), the last stage is the sampling");
+    ap.refer(&mut params.schedule_equilibration)
+        .add_option(&["--reequilibration"], Store,
+                    "Steps after a scheduled or interactive change before the averages restart, the displacement adapts in between");
+    ap.refer(INTERACTIVE)
+        .add_option(&["--interactive"], StoreTrue,
+                    "Read commands from stdin to pause, resume or stop the run, show status or density profile and change output interval or temperature (type help)");
+    ap.refer(TEACH)
+        .add_option(&["--teach"], Store,
+                    "Explain the first moves one by one: particle, energies, Boltzmann factor, random number and decision (for tiny systems)");
+    ap.refer(PDB)
+        .add_option(&["--pdb"], StoreTrue,
+                    "Also write the trajectory frames as models of <output>.pdb for programs that only read pdb");
+    ap.refer(NETCDF)
+        .add_option(&["--netcdf"], StoreTrue,
+                    "Also write the trajectory frames to the amber netcdf trajectory <output>.nc (needs the netcdf feature)");
+    ap.refer(MOVE_LOG)
+        .add_option(&["--movelog"], StoreTrue,
+                    "Write every trial move (particle, displacement, energy difference, outcome, random number) to the binary <output>.moves.bin for debugging");
+    ap.refer(&mut params.units)
+        .add_option(&["--units"], Store,
+                    "Also give the results in real units of this substance: argon or e.g. eps=119.8K,sig=3.405A,mass=39.948 (eps in K or kJ/mol, sig in nm or A, mass in g/mol)");
+    ap.refer(KELVIN)
+        .add_option(&["--kelvin"], Store,
+                    "Temperature in kelvin of the units instead of the reduced temperature");
+    ap.refer(&mut params.seed)
+        .add_option(&["--seed"], Store,
+                    "Master seed, runs and replicas of the same seed are reproducible (0=seeded from the os)");
+    ap.refer(&mut params.tailcorr)
         .add_option(&["--notailcorr"], StoreFalse,
                     "Disable tailcorrection");
-    ap.refer(SHIFT)
+    ap.refer(&mut params.shift)
         .add_option(&["--noshift"], StoreFalse,
                     "Disable lj shifting");
     ap.parse_args_or_exit();
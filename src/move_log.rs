@@ -0,0 +1,164 @@
+//! Move log: a compact binary record of every trial move for detailed-balance audits and replays of new move types
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use error::{Error, Result};
+use Step;
+
+// start of every move log, followed by the records
+const MAGIC : &[u8; 8] = b"MCLJMV01";
+
+// bytes per record: step, particle, outcome, displacement, energy difference and random number
+pub const RECORD_SIZE : usize = 8 + 4 + 1 + 3 * 8 + 8 + 8;
+
+/// How a trial move ended
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Outcome {
+    #[default]
+    Rejected,
+    Accepted,
+    /// cut short by the early rejection, the energy difference is not known
+    EarlyRejected,
+    /// into an overlap of the hard-core check, without energy
+    OverlapRejected,
+}
+
+impl Outcome {
+    fn from_byte(byte: u8) -> Option<Outcome> {
+        return [Outcome::Rejected, Outcome::Accepted, Outcome::EarlyRejected, Outcome::OverlapRejected].get(byte as usize).cloned();
+    }
+}
+
+/// One trial move: the particle, its trial displacement, the energy difference and the random number of the
+/// acceptance where the move got to them (e.g. not a downhill move accepted without a random number), NaN in the file
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TrialRecord {
+    pub step: Step,
+    pub index: usize,
+    pub outcome: Outcome,
+    pub displacement: [f64; 3],
+    pub d_e: Option<f64>,
+    pub random: Option<f64>,
+}
+
+impl TrialRecord {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RECORD_SIZE);
+        bytes.extend_from_slice(&self.step.to_le_bytes());
+        bytes.extend_from_slice(&(self.index as u32).to_le_bytes());
+        bytes.push(self.outcome as u8);
+        for value in self.displacement.iter().chain([self.d_e.unwrap_or(f64::NAN), self.random.unwrap_or(f64::NAN)].iter()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        return bytes;
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<TrialRecord> {
+        let float = |k: usize| { let mut b = [0; 8]; b.copy_from_slice(&bytes[13 + 8 * k..21 + 8 * k]); f64::from_le_bytes(b) };
+        let optional = |value: f64| if value.is_nan() { None } else { Some(value) };
+        let mut step = [0; 8];
+        step.copy_from_slice(&bytes[0..8]);
+        let mut index = [0; 4];
+        index.copy_from_slice(&bytes[8..12]);
+        return Some(TrialRecord { step: u64::from_le_bytes(step), index: u32::from_le_bytes(index) as usize, outcome: Outcome::from_byte(bytes[12])?,
+                                  displacement: [float(0), float(1), float(2)], d_e: optional(float(3)), random: optional(float(4)) });
+    }
+}
+
+impl TrialRecord {
+    /// Annotated account of the move in a system of energy before it at temperature, for the teaching mode
+    pub fn describe(&self, energy: f64, temperature: f64) -> String {
+        let d = self.displacement;
+        let mut text = format!("particle {} by ({:.3}, {:.3}, {:.3})", self.index, d[0], d[1], d[2]);
+        let d_e = match (self.outcome, self.d_e) {
+            (Outcome::OverlapRejected, _) => return text + ": into the hard core of another particle, rejected without the energy",
+            (Outcome::EarlyRejected, _) => return text + &format!(": the partial energy already exceeds the threshold of the random number {:.4}, rejected early", self.random.unwrap_or(f64::NAN)),
+            (_, Some(d_e)) => d_e,
+            (_, None) => return text + ": rejected",
+        };
+        let factor = (-d_e / temperature).exp();
+        // the factors of overlaps in scientific notation
+        let shown = if factor != 0.0 && !(1e-3..1e4).contains(&factor) { format!("{:.4e}", factor) } else { format!("{:.4}", factor) };
+        text += &format!(", energy {:.4} -> {:.4} (dE = {:.4}), Boltzmann factor exp(-dE/T) = {}", energy, energy + d_e, d_e, shown);
+        let accepted = self.outcome == Outcome::Accepted;
+        text += &match self.random {
+            None if accepted => " >= 1, downhill: accepted".to_string(),
+            None => ", rejected by a bias of the sampling".to_string(),
+            Some(u) if accepted == (u < factor) => format!(", random number {:.4} {} {}: {}", u, if accepted { "<" } else { ">=" }, shown, if accepted { "accepted" } else { "rejected" }),
+            Some(u) => format!(", random number {:.4}: {} with the corrections of the sampling", u, if accepted { "accepted" } else { "rejected" }),
+        };
+        return text;
+    }
+}
+
+#[test]
+fn test_describe() {
+    let trial = TrialRecord { step: 0, index: 3, outcome: Outcome::Rejected, displacement: [0.1, 0.0, -0.1], d_e: Some(1.0), random: Some(0.5) };
+    assert_eq!("particle 3 by (0.100, 0.000, -0.100), energy -2.0000 -> -1.0000 (dE = 1.0000), Boltzmann factor exp(-dE/T) = 0.3679, random number 0.5000 >= 0.3679: rejected",
+               trial.describe(-2.0, 1.0));
+    let downhill = TrialRecord { outcome: Outcome::Accepted, d_e: Some(-0.5), random: None, ..trial.clone() };
+    assert!(downhill.describe(-2.0, 1.0).ends_with("= 1.6487 >= 1, downhill: accepted"));
+    assert!(TrialRecord { outcome: Outcome::OverlapRejected, ..TrialRecord::default() }.describe(0.0, 1.0).ends_with("rejected without the energy"));
+}
+
+/// Streams the trial records to a file
+pub struct MoveLog {
+    file: BufWriter<File>,
+}
+
+impl MoveLog {
+    pub fn new(filename: &String) -> Result<MoveLog> {
+        let file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let mut file = BufWriter::new(file);
+        file.write_all(MAGIC)?;
+        return Ok(MoveLog { file: file });
+    }
+
+    pub fn write(&mut self, record: &TrialRecord) -> Result<()> {
+        self.file.write_all(&record.to_bytes())?;
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        return Ok(());
+    }
+}
+
+/// Reads all records of a move log, a truncated last record of a crashed run is dropped
+pub fn read_move_log(filename: &String) -> Result<Vec<TrialRecord>> {
+    let file = match File::open(filename) {
+        Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+        Ok(file) => file,
+    };
+    let mut bytes = vec![];
+    BufReader::new(file).read_to_end(&mut bytes)?;
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(Error::MalformedFrame(0, format!("{} is not a move log", filename)));
+    }
+    let mut records = vec![];
+    for (k, chunk) in bytes[MAGIC.len()..].chunks_exact(RECORD_SIZE).enumerate() {
+        records.push(TrialRecord::from_bytes(chunk).ok_or_else(|| Error::MalformedFrame(k + 1, "unknown outcome of a trial move".to_string()))?);
+    }
+    return Ok(records);
+}
+
+#[test]
+fn test_move_log() {
+    let filename = ::std::env::temp_dir().join("mclj_test_move_log.bin").to_str().unwrap().to_string();
+    let records = vec![TrialRecord { step: 3, index: 17, outcome: Outcome::Accepted, displacement: [0.1, -0.05, 0.02], d_e: Some(-0.7), random: None },
+                       TrialRecord { step: 3, index: 2, outcome: Outcome::Rejected, displacement: [0.0, 0.1, -0.1], d_e: Some(2.5), random: Some(0.4) },
+                       TrialRecord { step: 4, index: 5, outcome: Outcome::OverlapRejected, ..TrialRecord::default() }];
+    let mut log = MoveLog::new(&filename).unwrap();
+    for record in &records {
+        log.write(record).unwrap();
+    }
+    log.flush().unwrap();
+    assert_eq!((MAGIC.len() + 3 * RECORD_SIZE) as u64, ::std::fs::metadata(&filename).unwrap().len());
+    assert_eq!(records, read_move_log(&filename).unwrap());
+    ::std::fs::remove_file(&filename).unwrap();
+}
@@ -0,0 +1,252 @@
+#![allow(dead_code)]
+
+use rand::Rng;
+use {Coordinate, Real, Step};
+
+/// Distribution of the uniform trial displacements of width w: uniform in the cube of edge w, uniform in the ball of
+/// diameter w, or gaussian with the variance w²/12 of the cube per component, truncated at ±w. All are symmetric.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Proposal {
+    #[default]
+    Cube,
+    Ball,
+    Gaussian,
+}
+
+impl Proposal {
+    /// Parses cube, ball or gaussian, empty for the cube
+    pub fn parse(name: &str) -> Option<Proposal> {
+        return match name.trim() {
+            "" | "cube" => Some(Proposal::Cube),
+            "ball" => Some(Proposal::Ball),
+            "gaussian" => Some(Proposal::Gaussian),
+            _ => None,
+        };
+    }
+
+    /// Random displacement of width w
+    pub fn sample<R: Rng>(&self, width: f64, rng: &mut R) -> [f64; 3] {
+        let mut d = [0.0; 3];
+        match *self {
+            Proposal::Cube => for d_k in d.iter_mut() { *d_k = (rng.gen::<f64>() - 0.5) * width; },
+            Proposal::Ball => loop {
+                for d_k in d.iter_mut() { *d_k = (rng.gen::<f64>() - 0.5) * width; }
+                if d[0] * d[0] + d[1] * d[1] + d[2] * d[2] <= width * width / 4.0 { break; }
+            },
+            Proposal::Gaussian => for d_k in d.iter_mut() {
+                // box-muller, redrawn beyond the truncation
+                *d_k = loop {
+                    let (u1, u2) = (1.0 - rng.gen::<f64>(), rng.gen::<f64>());
+                    let x = (-2.0 * u1.ln()).sqrt() * (2.0 * ::std::f64::consts::PI * u2).cos() * width / 12f64.sqrt();
+                    if x.abs() <= width { break x; }
+                };
+            },
+        }
+        return d;
+    }
+}
+
+#[test]
+fn test_proposal() {
+    assert_eq!(Some(Proposal::Cube), Proposal::parse(""));
+    assert_eq!(Some(Proposal::Gaussian), Proposal::parse("gaussian"));
+    assert_eq!(None, Proposal::parse("sphere"));
+    let mut rng = ::rand::thread_rng();
+    for &proposal in &[Proposal::Cube, Proposal::Ball, Proposal::Gaussian] {
+        let mut variance = 0.0;
+        for _ in 0..10000 {
+            let d = proposal.sample(0.2, &mut rng);
+            assert!(d.iter().all(|d_k| d_k.abs() <= 0.2));
+            if proposal == Proposal::Ball { assert!(d[0] * d[0] + d[1] * d[1] + d[2] * d[2] <= 0.01); }
+            variance += d[0] * d[0] / 10000.0;
+        }
+        // the ball is narrower than the cube (1/20 against 1/12 of w²), the gaussian about as wide
+        let expected = if proposal == Proposal::Ball { 0.04 / 20.0 } else { 0.04 / 12.0 };
+        assert!( (variance - expected).abs() < 0.1 * expected, "{:?} {}", proposal, variance);
+    }
+}
+
+/// Draws a one dimensional force-bias displacement in [-width/2, width/2] from the
+/// distribution p(d) ~ exp(lambda * beta * force * d). u has to be uniform in [0, 1)
+pub fn sample_force_bias_displacement(force: f64, beta: f64, lambda: f64, width: f64, u: f64) -> f64 {
+    let a = lambda * beta * force;
+    if (a * width).abs() < 1e-8 {
+        return (u - 0.5) * width;
+    }
+    // invert the cumulative distribution, written for a > 0 to avoid overflow
+    let abs_a = a.abs();
+    let d = width / 2.0 + (u + (1.0 - u) * (-abs_a * width).exp()).ln() / abs_a;
+    return if a > 0.0 { d } else { -d };
+}
+
+#[test]
+fn test_sample_force_bias_displacement() {
+    // no force results in the uniform displacement
+    let d = sample_force_bias_displacement(0.0, 1.0, 0.5, 0.2, 0.75);
+    assert!( (d - 0.05).abs() < 0.00001, "{}", d);
+
+    // boundaries of the interval
+    let d = sample_force_bias_displacement(10.0, 1.0, 0.5, 0.2, 0.0);
+    assert!( (d - -0.1).abs() < 0.00001, "{}", d);
+    let d = sample_force_bias_displacement(10.0, 1.0, 0.5, 0.2, 0.99999999);
+    assert!( (d - 0.1).abs() < 0.00001, "{}", d);
+
+    // displacement is biased along the force
+    assert!(sample_force_bias_displacement(50.0, 1.0, 0.5, 0.2, 0.5) > 0.0);
+    assert!(sample_force_bias_displacement(-50.0, 1.0, 0.5, 0.2, 0.5) < 0.0);
+
+    // huge forces must not overflow
+    let d = sample_force_bias_displacement(1e6, 1.0, 0.5, 0.2, 0.5);
+    assert!(d.is_finite() && d <= 0.1, "{}", d);
+}
+
+/// Log of the normalization of the one dimensional force-bias proposal distribution,
+/// ln( integral of exp(lambda * beta * force * d) for d in [-width/2, width/2] )
+pub fn force_bias_log_normalization(force: f64, beta: f64, lambda: f64, width: f64) -> f64 {
+    let abs_a = (lambda * beta * force).abs();
+    if abs_a * width < 1e-8 {
+        return width.ln();
+    }
+    return abs_a * width / 2.0 + (1.0 - (-abs_a * width).exp()).ln() - abs_a.ln();
+}
+
+#[test]
+fn test_force_bias_log_normalization() {
+    let result = force_bias_log_normalization(0.0, 1.0, 0.5, 0.2);
+    assert!( (result - 0.2_f64.ln()).abs() < 0.00001, "{}", result);
+
+    // 2 sinh(a w / 2) / a with a = 2, w = 0.2
+    let expected = (2.0 * (0.2_f64).sinh() / 2.0).ln();
+    let result = force_bias_log_normalization(4.0, 1.0, 0.5, 0.2);
+    assert!( (result - expected).abs() < 0.00001, "{}", result);
+    let result = force_bias_log_normalization(-4.0, 1.0, 0.5, 0.2);
+    assert!( (result - expected).abs() < 0.00001, "{}", result);
+}
+
+/// Log of the proposal correction p(new -> old) / p(old -> new) of a force-bias move with
+/// displacement d, old force f_old and new force f_new (all given as x/y/z arrays)
+pub fn force_bias_log_correction(d: [f64; 3], f_old: [f64; 3], f_new: [f64; 3], beta: f64, lambda: f64, width: f64) -> f64 {
+    let mut log_correction = 0.0;
+    for k in 0..3 {
+        // reverse move displaces by -d starting from the new position
+        log_correction += lambda * beta * (-f_new[k] * d[k] - f_old[k] * d[k]);
+        log_correction += force_bias_log_normalization(f_old[k], beta, lambda, width);
+        log_correction -= force_bias_log_normalization(f_new[k], beta, lambda, width);
+    }
+    return log_correction;
+}
+
+#[test]
+fn test_force_bias_log_correction() {
+    // same force before and after with no displacement is symmetric
+    let result = force_bias_log_correction([0.0; 3], [1.0, 2.0, 3.0], [1.0, 2.0, 3.0], 1.0, 0.5, 0.2);
+    assert!(result.abs() < 0.00001, "{}", result);
+
+    // moving along a constant force is penalized by the reverse move
+    let result = force_bias_log_correction([0.1, 0.0, 0.0], [5.0, 0.0, 0.0], [5.0, 0.0, 0.0], 1.0, 0.5, 0.2);
+    assert!( (result - -0.5).abs() < 0.00001, "{}", result);
+}
+
+/// Estimates the positions (z_lower, z_upper) of the two interfaces of a liquid slab with particle
+/// z coordinates rz in a box of height l_z. The slab center is the periodic mean of all positions and the
+/// interfaces are placed where the density drops below half of the mean density inside the slab.
+pub fn find_slab_interfaces(rz: &[Real], num_particles: usize, l_z: f64, bins: usize) -> (f64, f64) {
+    // periodic center of mass in z
+    let (mut cos_sum, mut sin_sum) = (0.0, 0.0);
+    for i in 0..num_particles {
+        let angle = 2.0 * ::std::f64::consts::PI * rz[i].to_f64() / l_z;
+        cos_sum += angle.cos();
+        sin_sum += angle.sin();
+    }
+    let mut center = sin_sum.atan2(cos_sum) / (2.0 * ::std::f64::consts::PI) * l_z;
+    if center < 0.0 { center += l_z }
+
+    // histogram of the distances to the slab center
+    let hl_z = l_z / 2.0;
+    let bin_width = hl_z / bins as f64;
+    let mut histogram = vec![0; bins];
+    for i in 0..num_particles {
+        let d = ::energy::get_displacement_with_pbc(rz[i].to_f64(), center, l_z, hl_z).abs();
+        let bin = ((d / bin_width) as usize).min(bins - 1);
+        histogram[bin] += 1;
+    }
+    let mut half_thickness = hl_z;
+    let mut inside_count = histogram[0];
+    for bin in 1..bins {
+        if (histogram[bin] as f64) < inside_count as f64 / bin as f64 / 2.0 {
+            half_thickness = bin as f64 * bin_width;
+            break;
+        }
+        inside_count += histogram[bin];
+    }
+
+    let mut z_lower = center - half_thickness;
+    let mut z_upper = center + half_thickness;
+    if z_lower < 0.0 { z_lower += l_z }
+    if z_upper >= l_z { z_upper -= l_z }
+    return (z_lower, z_upper);
+}
+
+#[test]
+fn test_find_slab_interfaces() {
+    // homogeneous slab between 4 and 6 in a box of height 10
+    let rz : Vec<Real> = (0..1000).map(|i| Real::from_f64(4.0 + 2.0 * i as f64 / 1000.0)).collect();
+    let (z_lower, z_upper) = find_slab_interfaces(&rz, 1000, 10.0, 50);
+    assert!( (z_lower - 4.0).abs() < 0.2, "{}", z_lower);
+    assert!( (z_upper - 6.0).abs() < 0.2, "{}", z_upper);
+
+    // slab across the periodic boundary
+    let rz : Vec<Real> = (0..1000).map(|i| Real::from_f64((9.0 + 2.0 * i as f64 / 1000.0) % 10.0)).collect();
+    let (z_lower, z_upper) = find_slab_interfaces(&rz, 1000, 10.0, 50);
+    assert!( (z_lower - 9.0).abs() < 0.2, "{}", z_lower);
+    assert!( (z_upper - 1.0).abs() < 0.2, "{}", z_upper);
+}
+
+/// Selection weight of a particle at z for preferential sampling: particles closer than width to
+/// one of the interfaces are selected factor times more often than the rest
+pub fn get_interface_weight(z: f64, interfaces: (f64, f64), l_z: f64, width: f64, factor: f64) -> f64 {
+    let hl_z = l_z / 2.0;
+    let d_lower = ::energy::get_displacement_with_pbc(z, interfaces.0, l_z, hl_z).abs();
+    let d_upper = ::energy::get_displacement_with_pbc(z, interfaces.1, l_z, hl_z).abs();
+    return if d_lower.min(d_upper) < width { factor } else { 1.0 };
+}
+
+#[test]
+fn test_get_interface_weight() {
+    assert_eq!(5.0, get_interface_weight(4.5, (4.0, 6.0), 10.0, 1.0, 5.0));
+    assert_eq!(1.0, get_interface_weight(5.0, (4.0, 6.0), 10.0, 0.5, 5.0));
+    assert_eq!(5.0, get_interface_weight(9.8, (0.5, 6.0), 10.0, 1.0, 5.0));
+    assert_eq!(1.0, get_interface_weight(2.0, (0.5, 6.0), 10.0, 1.0, 5.0));
+}
+
+/// Robbins-Monro stochastic approximation step for the displacement. The log of the displacement is
+/// moved towards the target acceptance with a gain decaying as gain/(k+1)^decay after the k-th update,
+/// which converges for decay in (0.5, 1].
+pub fn robbins_monro_update(displacement: f64, acceptance: f64, target: f64, k: Step, gain: f64, decay: f64) -> f64 {
+    let gamma = gain / ((k + 1) as f64).powf(decay);
+    return displacement * (gamma * (acceptance - target)).exp();
+}
+
+#[test]
+fn test_robbins_monro_update() {
+    // on target nothing changes
+    let result = robbins_monro_update(0.1, 0.33, 0.33, 0, 1.0, 0.75);
+    assert!( (result - 0.1).abs() < 0.00001, "{}", result);
+
+    // too many accepted moves increase the displacement and vice versa
+    assert!(robbins_monro_update(0.1, 0.5, 0.33, 0, 1.0, 0.75) > 0.1);
+    assert!(robbins_monro_update(0.1, 0.1, 0.33, 0, 1.0, 0.75) < 0.1);
+
+    // gain decays with the number of updates
+    let early = robbins_monro_update(0.1, 0.5, 0.33, 0, 1.0, 0.75);
+    let late = robbins_monro_update(0.1, 0.5, 0.33, 100, 1.0, 0.75);
+    assert!(late < early);
+
+    // a linear acceptance model converges to the target
+    let mut d = 1.0;
+    for k in 0..2000 {
+        let acceptance = (1.0_f64 - d).max(0.0);
+        d = robbins_monro_update(d, acceptance, 0.33, k, 1.0, 0.75);
+    }
+    assert!( (d - 0.67).abs() < 0.01, "{}", d);
+}
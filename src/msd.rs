@@ -0,0 +1,48 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store};
+use mclj::error::exit_with;
+use mclj::tracking::{get_mean_squared_displacement, read_tagged_records};
+use mclj::Step;
+
+fn main() {
+    let mut filename : String = "montecarlo.tagged.dat".to_string();
+    let mut skip : usize = 0;
+    let mut max_lag : usize = 1000;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Mean squared displacement per sweep and move acceptance of the tagged particles of a run with --tag.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Tagged particle file");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first sweeps, e.g. the equilibration");
+        ap.refer(&mut max_lag)
+            .add_option(&["--maxlag"], Store, "Largest lag in recorded sweeps");
+        ap.parse_args_or_exit();
+    }
+
+    let records = read_tagged_records(&filename).unwrap_or_else(exit_with);
+    let first = skip.min(records.sweeps.len());
+    if records.sweeps.len() - first < 2 {
+        eprintln!("Error: {} has less than 2 sweeps after skipping {}.", filename, skip);
+        std::process::exit(1);
+    }
+    println!("# {} tagged particles, {} sweeps from sweep {}", records.indices.len(), records.sweeps.len() - first, records.sweeps[first]);
+
+    let msd = get_mean_squared_displacement(&records.positions[first..], max_lag);
+    let interval = records.sweeps[first + 1] - records.sweeps[first];
+    println!("# lag (sweeps)    MSD");
+    for (lag, value) in msd.iter().enumerate() {
+        println!("{}\t{}", lag as Step * interval, value);
+    }
+
+    // acceptance of the moves within the analysed sweeps
+    let last = records.sweeps.len() - 1;
+    println!();
+    println!("# particle    attempts    accepted    acceptance");
+    for (tag, particle) in records.indices.iter().enumerate() {
+        let attempts = records.attempts[last][tag] - if first > 0 { records.attempts[first - 1][tag] } else { 0 };
+        let accepted = records.accepted[last][tag] - if first > 0 { records.accepted[first - 1][tag] } else { 0 };
+        println!("{}\t{}\t{}\t{}", particle, attempts, accepted, if attempts > 0 { accepted as f64 / attempts as f64 } else { f64::NAN });
+    }
+}
@@ -0,0 +1,135 @@
+//! Multicanonical sampling of a flat energy distribution with the density of states of a Wang-Landau stage
+use statistics::Statistics;
+use Step;
+
+/// The Wang-Landau histogram is flat when its smallest entry is at least this fraction of the mean
+pub const FLATNESS : f64 = 0.8;
+
+/// Estimate ln g(E) of the density of states in bins of the total energy between min and min + bins * width. While
+/// learning (the equilibration) every visit of a bin adds ln_f to it (Wang-Landau), afterwards the estimate is the
+/// fixed bias of the multicanonical sampling, moves are accepted with min(1, g(E_old) / g(E_new)).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Multicanonical {
+    pub min: f64,
+    pub width: f64,
+    pub ln_g: Vec<f64>,
+    pub histogram: Vec<Step>,
+    pub ln_f: f64,
+    pub learning: bool,
+    // per bin of the sampling: the statistics of the canonical weights exp(-beta (E - E_bin)) and of the weighted
+    // energies and virials, their sums are count times mean
+    pub weights: Vec<[Statistics; 3]>,
+}
+
+impl Multicanonical {
+    pub fn new(min: f64, max: f64, bins: usize) -> Multicanonical {
+        return Multicanonical { min: min, width: (max - min) / bins as f64, ln_g: vec![0.0; bins], histogram: vec![0; bins], ln_f: 1.0, learning: true, weights: vec![[Statistics::default(); 3]; bins] };
+    }
+
+    /// Bin of an energy, None outside the range
+    pub fn get_bin(&self, energy: f64) -> Option<usize> {
+        let bin = ((energy - self.min) / self.width).floor();
+        return if bin >= 0.0 && (bin as usize) < self.ln_g.len() { Some(bin as usize) } else { None };
+    }
+
+    /// Counts a visit of the energy, in the learning stage it also raises ln g
+    pub fn visit(&mut self, energy: f64) {
+        if let Some(bin) = self.get_bin(energy) {
+            self.histogram[bin] += 1;
+            if self.learning { self.ln_g[bin] += self.ln_f; }
+        }
+    }
+
+    /// Halves ln f and starts a new histogram if the histogram of the visited bins is flat, returns true then
+    pub fn update_modification_factor(&mut self) -> bool {
+        let visited : Vec<f64> = self.histogram.iter().filter(|&&h| h > 0).map(|&h| h as f64).collect();
+        if visited.len() < 2 { return false; }
+        let mean = visited.iter().sum::<f64>() / visited.len() as f64;
+        if visited.iter().cloned().fold(f64::INFINITY, f64::min) < FLATNESS * mean { return false; }
+        self.ln_f /= 2.0;
+        self.histogram = vec![0; self.histogram.len()];
+        return true;
+    }
+
+    /// Ends the learning stage, ln g stays fixed and the histogram and the statistics start over for the sampling
+    pub fn freeze(&mut self) {
+        self.learning = false;
+        self.histogram = vec![0; self.histogram.len()];
+        self.weights = vec![[Statistics::default(); 3]; self.weights.len()];
+    }
+
+    /// Adds a sample of the energy and virial to the reweighting statistics
+    pub fn record(&mut self, energy: f64, virial: f64, beta: f64) {
+        if let Some(bin) = self.get_bin(energy) {
+            let weight = (-beta * (energy - self.min - bin as f64 * self.width)).exp();
+            self.weights[bin][0].push(weight);
+            self.weights[bin][1].push(weight * energy);
+            self.weights[bin][2].push(weight * virial);
+        }
+    }
+
+    // sum of the samples of statistics k of bin b
+    fn get_sum(&self, b: usize, k: usize) -> f64 {
+        let statistics = &self.weights[b][k];
+        return if statistics.count > 0 { statistics.count as f64 * statistics.mean() } else { 0.0 };
+    }
+
+    // ln of the factors that reweight the sums of every bin to the canonical ensemble, NaN for empty bins
+    fn get_log_weights(&self, beta: f64) -> Vec<f64> {
+        return (0..self.ln_g.len()).map(|b| if self.get_sum(b, 0) > 0.0 { self.ln_g[b] - beta * (self.min + b as f64 * self.width) } else { f64::NAN }).collect();
+    }
+
+    /// Canonical averages of the energy and the virial at beta from the multicanonical samples, NaN without samples
+    pub fn get_canonical_averages(&self, beta: f64) -> (f64, f64) {
+        let log_weights = self.get_log_weights(beta);
+        let max = log_weights.iter().cloned().filter(|w| w.is_finite()).fold(f64::NEG_INFINITY, f64::max);
+        let (mut norm, mut energy, mut virial) = (0.0, 0.0, 0.0);
+        for (b, w) in log_weights.iter().enumerate().filter(|w| w.1.is_finite()) {
+            let factor = (w - max).exp();
+            norm += factor * self.get_sum(b, 0);
+            energy += factor * self.get_sum(b, 1);
+            virial += factor * self.get_sum(b, 2);
+        }
+        return (energy / norm, virial / norm);
+    }
+
+    /// Canonical probabilities of the energy bins at beta from ln g, normalized to one
+    pub fn get_canonical_distribution(&self, beta: f64) -> Vec<f64> {
+        let log_p : Vec<f64> = (0..self.ln_g.len()).map(|b| self.ln_g[b] - beta * (self.min + (b as f64 + 0.5) * self.width)).collect();
+        let max = log_p.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let p : Vec<f64> = log_p.iter().map(|l| (l - max).exp()).collect();
+        let sum : f64 = p.iter().sum();
+        return p.iter().map(|x| x / sum).collect();
+    }
+}
+
+#[test]
+fn test_multicanonical() {
+    let mut multicanonical = Multicanonical::new(-10.0, 0.0, 10);
+    assert_eq!(Some(0), multicanonical.get_bin(-10.0));
+    assert_eq!(Some(9), multicanonical.get_bin(-0.5));
+    assert_eq!(None, multicanonical.get_bin(0.0));
+    assert_eq!(None, multicanonical.get_bin(-10.5));
+
+    // flat after one visit of every bin, then ln f is halved
+    for b in 0..10 { multicanonical.visit(-9.5 + b as f64); }
+    assert!( (multicanonical.ln_g[3] - 1.0).abs() < 0.00001);
+    assert!(multicanonical.update_modification_factor());
+    assert_eq!(0.5, multicanonical.ln_f);
+    multicanonical.visit(-9.5);
+    multicanonical.visit(-9.5);
+    multicanonical.visit(-8.5);
+    assert!(!multicanonical.update_modification_factor());
+
+    // ln g(E) = beta E cancels the canonical weights, two samples at the lower bin edges average to their mean
+    multicanonical.freeze();
+    multicanonical.ln_g = (0..10).map(|b| -10.0 + b as f64).collect();
+    multicanonical.record(-10.0, 1.0, 1.0);
+    multicanonical.record(-1.0, 3.0, 1.0);
+    let (energy, virial) = multicanonical.get_canonical_averages(1.0);
+    assert!( (energy + 5.5).abs() < 0.00001, "{}", energy);
+    assert!( (virial - 2.0).abs() < 0.00001, "{}", virial);
+    let distribution = multicanonical.get_canonical_distribution(1.0);
+    assert!( (distribution.iter().sum::<f64>() - 1.0).abs() < 0.00001);
+    assert!( (distribution[0] - 0.1).abs() < 0.00001, "{}", distribution[0]);
+}
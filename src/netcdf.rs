@@ -0,0 +1,202 @@
+//! Amber NetCDF trajectories: NetCDF 3 files (64-bit offsets) of the AMBER convention, read with random access by
+//! MDAnalysis, ParmEd, cpptraj and VMD. Written without the netcdf library, the format is simple enough.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufWriter, SeekFrom};
+use error::{Error, Result};
+use trajectory::Frame;
+use Coordinate;
+
+const NC_DIMENSION : u32 = 0x0A;
+const NC_VARIABLE : u32 = 0x0B;
+const NC_ATTRIBUTE : u32 = 0x0C;
+const NC_CHAR : u32 = 2;
+const NC_FLOAT : u32 = 5;
+const NC_DOUBLE : u32 = 6;
+
+// dimensions of the convention: frame (unlimited), spatial, atom, cell_spatial, cell_angular and label
+const FRAME : u32 = 0;
+const SPATIAL : u32 = 1;
+const ATOM : u32 = 2;
+const CELL_SPATIAL : u32 = 3;
+const CELL_ANGULAR : u32 = 4;
+const LABEL : u32 = 5;
+
+// position of the number of records in the header
+const NUMRECS_OFFSET : u64 = 4;
+
+/// Header in the big endian encoding of the format
+#[derive(Default)]
+struct Header {
+    bytes: Vec<u8>,
+}
+
+impl Header {
+    fn int(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Bytes padded with zeros to a multiple of 4
+    fn padded(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+        self.bytes.resize(self.bytes.len() + (4 - bytes.len() % 4) % 4, 0);
+    }
+
+    fn name(&mut self, name: &str) {
+        self.int(name.len() as u32);
+        self.padded(name.as_bytes());
+    }
+
+    fn text_attributes(&mut self, attributes: &[(&str, &str)]) {
+        if attributes.is_empty() { return self.bytes.extend_from_slice(&[0; 8]); }
+        self.int(NC_ATTRIBUTE);
+        self.int(attributes.len() as u32);
+        for &(name, value) in attributes {
+            self.name(name);
+            self.int(NC_CHAR);
+            self.int(value.len() as u32);
+            self.padded(value.as_bytes());
+        }
+    }
+}
+
+/// Variable of the header with its dimensions, units and the bytes per frame (per file without the frame dimension)
+struct Variable {
+    name: &'static str,
+    dimensions: &'static [u32],
+    units: Option<&'static str>,
+    kind: u32,
+    size: u64,
+}
+
+/// Writes frames of a fixed number of particles to an Amber NetCDF trajectory, lengths in Å with length_unit Å per
+/// sigma and the sweeps of the frames as time. A title (e.g. the provenance of the run) is the global title attribute.
+pub struct NetCdfTrajectory {
+    file: BufWriter<File>,
+    num_particles: usize,
+    frames: u32,
+    length_unit: f64,
+}
+
+impl NetCdfTrajectory {
+    pub fn new(filename: &String, num_particles: usize, length_unit: f64, title: &str) -> Result<NetCdfTrajectory> {
+        let file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let mut file = BufWriter::new(file);
+        // the header length does not depend on the offsets in it
+        let length = NetCdfTrajectory::header(num_particles, 0, title).bytes.len() as u64;
+        file.write_all(&NetCdfTrajectory::header(num_particles, length, title).bytes)?;
+        // labels of the non-record variables spatial, cell_spatial and cell_angular
+        file.write_all(b"xyz\0abc\0alphabeta gamma\0")?;
+        return Ok(NetCdfTrajectory { file: file, num_particles: num_particles, frames: 0, length_unit: length_unit });
+    }
+
+    /// Header of the file with the data starting at offset
+    fn header(num_particles: usize, offset: u64, title: &str) -> Header {
+        let mut header = Header::default();
+        header.bytes.extend_from_slice(b"CDF\x02");
+        header.int(0);
+        header.int(NC_DIMENSION);
+        let dimensions = [("frame", 0), ("spatial", 3), ("atom", num_particles as u32), ("cell_spatial", 3), ("cell_angular", 3), ("label", 5)];
+        header.int(dimensions.len() as u32);
+        for &(name, length) in dimensions.iter() {
+            header.name(name);
+            header.int(length);
+        }
+        let mut attributes = vec![("Conventions", "AMBER"), ("ConventionVersion", "1.0"), ("program", "mclj"), ("programVersion", env!("CARGO_PKG_VERSION"))];
+        if !title.is_empty() { attributes.push(("title", title)); }
+        header.text_attributes(&attributes);
+
+        // the non-record variables first, then a record of every variable along frame per frame
+        let variables = [
+            Variable { name: "spatial", dimensions: &[SPATIAL], units: None, kind: NC_CHAR, size: 4 },
+            Variable { name: "cell_spatial", dimensions: &[CELL_SPATIAL], units: None, kind: NC_CHAR, size: 4 },
+            Variable { name: "cell_angular", dimensions: &[CELL_ANGULAR, LABEL], units: None, kind: NC_CHAR, size: 16 },
+            Variable { name: "time", dimensions: &[FRAME], units: Some("picosecond"), kind: NC_FLOAT, size: 4 },
+            Variable { name: "coordinates", dimensions: &[FRAME, ATOM, SPATIAL], units: Some("angstrom"), kind: NC_FLOAT, size: 12 * num_particles as u64 },
+            Variable { name: "cell_lengths", dimensions: &[FRAME, CELL_SPATIAL], units: Some("angstrom"), kind: NC_DOUBLE, size: 24 },
+            Variable { name: "cell_angles", dimensions: &[FRAME, CELL_ANGULAR], units: Some("degree"), kind: NC_DOUBLE, size: 24 },
+        ];
+        header.int(NC_VARIABLE);
+        header.int(variables.len() as u32);
+        let mut begin = offset;
+        for variable in variables.iter() {
+            header.name(variable.name);
+            header.int(variable.dimensions.len() as u32);
+            for &dimension in variable.dimensions { header.int(dimension); }
+            match variable.units {
+                Some(units) => header.text_attributes(&[("units", units)]),
+                None => header.text_attributes(&[]),
+            }
+            header.int(variable.kind);
+            header.int(variable.size as u32);
+            header.bytes.extend_from_slice(&begin.to_be_bytes());
+            begin += variable.size;
+        }
+        return header;
+    }
+
+    /// Appends a frame and updates the number of frames in the header
+    pub fn write(&mut self, frame: &Frame) -> Result<()> {
+        if frame.num_particles != self.num_particles {
+            return Err(Error::MalformedFrame(self.frames as usize, format!("{} particles in a trajectory of {}", frame.num_particles, self.num_particles)));
+        }
+        let unit = self.length_unit;
+        let mut record = Vec::with_capacity(52 + 12 * self.num_particles);
+        record.extend_from_slice(&(frame.sweep.unwrap_or(self.frames as f64) as f32).to_be_bytes());
+        for i in 0..self.num_particles {
+            for &coordinate in [frame.rx[i], frame.ry[i], frame.rz[i]].iter() {
+                record.extend_from_slice(&((coordinate.to_f64() * unit) as f32).to_be_bytes());
+            }
+        }
+        for &value in [frame.box_x * unit, frame.box_y * unit, frame.box_z * unit, 90.0, 90.0, 90.0].iter() {
+            record.extend_from_slice(&value.to_be_bytes());
+        }
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&record)?;
+        self.frames += 1;
+        self.file.seek(SeekFrom::Start(NUMRECS_OFFSET))?;
+        self.file.write_all(&self.frames.to_be_bytes())?;
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        return Ok(());
+    }
+}
+
+#[test]
+fn test_netcdf_trajectory() {
+    let filename = ::std::env::temp_dir().join("mclj_test_netcdf.nc").to_str().unwrap().to_string();
+    let frame = Frame { rx: vec![1.0, 2.5], ry: vec![3.0, 4.0], rz: vec![5.0, 6.0], num_particles: 2, box_x: 10.0, box_y: 10.0, box_z: 20.0, temperature: 0.9,
+                        lj_eps: 1.0, lj_sig: 1.0, lj_cutoff: 3.0, ensemble: "NVT".to_string(), step: Some(100), sweep: Some(0.5) };
+    let mut trajectory = NetCdfTrajectory::new(&filename, 2, 3.405, "mclj test run").unwrap();
+    trajectory.write(&frame).unwrap();
+    trajectory.write(&Frame { rx: vec![1.5, 2.5], ..frame.clone() }).unwrap();
+    assert!(trajectory.write(&Frame { num_particles: 3, ..frame.clone() }).is_err());
+    trajectory.flush().unwrap();
+
+    let bytes = ::std::fs::read(&filename).unwrap();
+    let int = |offset: usize| u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+    let float = |offset: usize| f32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+    assert_eq!(b"CDF\x02", &bytes[0..4]);
+    assert_eq!(2, int(4));
+    // header, the labels and two records of time, coordinates and box
+    let header = NetCdfTrajectory::header(2, 0, "mclj test run").bytes.len();
+    assert!(bytes[..header].windows(13).any(|title| title == b"mclj test run"));
+    let record = 4 + 12 * 2 + 48;
+    assert_eq!(header + 24 + 2 * record, bytes.len());
+    assert_eq!(b"alphabeta gamma", &bytes[header + 8..header + 23]);
+    let second = header + 24 + record;
+    assert_eq!(0.5, float(second));
+    assert_eq!((1.5 * 3.405) as f32, float(second + 4));
+    assert_eq!((6.0 * 3.405) as f32, float(second + 4 + 5 * 4));
+    let mut box_x = [0; 8];
+    box_x.copy_from_slice(&bytes[second + 28..second + 36]);
+    assert_eq!(34.05, f64::from_be_bytes(box_x));
+    ::std::fs::remove_file(&filename).unwrap();
+}
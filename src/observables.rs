@@ -0,0 +1,396 @@
+//! Observables computed from a configuration
+
+use {Coordinate, Real};
+use energy::{get_displacement_with_pbc, get_particle_distance_squared};
+
+/// Number density in bins slabs along z of a box with dimensions l_x, l_y, l_z
+pub fn get_density_profile(rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, bins: usize) -> Vec<f64> {
+    let bin_height = l_z / bins as f64;
+    let bin_volume = l_x * l_y * bin_height;
+    let mut profile = vec![0.0; bins];
+    for i in 0..num_particles {
+        // particles exactly on the upper boundary belong to the last bin
+        let bin = ((rz[i].to_f64() / bin_height) as usize).min(bins - 1);
+        profile[bin] += 1.0 / bin_volume;
+    }
+    return profile;
+}
+
+#[test]
+fn test_get_density_profile() {
+    let rz = vec![0.5, 1.5, 1.6, 3.9, 4.0];
+    let profile = get_density_profile(&rz, 5, 2.0, 1.0, 4.0, 4);
+    assert_eq!(4, profile.len());
+    assert!( (profile[0] - 0.5).abs() < 0.00001, "{}", profile[0]);
+    assert!( (profile[1] - 1.0).abs() < 0.00001, "{}", profile[1]);
+    assert!( (profile[2] - 0.0).abs() < 0.00001, "{}", profile[2]);
+    assert!( (profile[3] - 1.0).abs() < 0.00001, "{}", profile[3]);
+
+    // density integrates to the number of particles
+    let sum : f64 = profile.iter().sum();
+    assert!( (sum * 2.0 - 5.0).abs() < 0.00001, "{}", sum);
+}
+
+/// Neighbor distance of the cluster and local density columns, about the first minimum of g(r) of the liquid
+pub const NEIGHBOR_RADIUS : f64 = 1.5;
+
+/// Per-particle observables that can be written as extra trajectory columns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParticleColumn {
+    Energy,
+    Cluster,
+    LocalDensity,
+    Virial,
+    Stress,
+    VoronoiVolume,
+}
+
+impl ParticleColumn {
+    /// Parses a comma separated list of energy, cluster, density, virial, stress and voronoi, empty for no columns
+    pub fn parse_list(list: &str) -> Option<Vec<ParticleColumn>> {
+        let mut columns = vec![];
+        for name in list.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+            columns.push(match name {
+                "energy" => ParticleColumn::Energy,
+                "cluster" => ParticleColumn::Cluster,
+                "density" => ParticleColumn::LocalDensity,
+                "virial" => ParticleColumn::Virial,
+                "stress" => ParticleColumn::Stress,
+                "voronoi" => ParticleColumn::VoronoiVolume,
+                _ => return None,
+            });
+        }
+        return Some(columns);
+    }
+
+    /// extended xyz property of the column
+    pub fn property(&self) -> &'static str {
+        return match *self {
+            ParticleColumn::Energy => "energy:R:1",
+            ParticleColumn::Cluster => "cluster:I:1",
+            ParticleColumn::LocalDensity => "local_density:R:1",
+            ParticleColumn::Virial => "virial:R:1",
+            ParticleColumn::Stress => "stress:R:6",
+            ParticleColumn::VoronoiVolume => "voronoi_volume:R:1",
+        };
+    }
+}
+
+#[test]
+fn test_parse_particle_columns() {
+    assert_eq!(Some(vec![ParticleColumn::Energy, ParticleColumn::LocalDensity]), ParticleColumn::parse_list("energy, density"));
+    assert_eq!(Some(vec![]), ParticleColumn::parse_list(""));
+    assert_eq!(Some(vec![ParticleColumn::Stress]), ParticleColumn::parse_list("stress"));
+    assert_eq!(None, ParticleColumn::parse_list("energy,charge"));
+}
+
+// indices of the neighbors of every particle within radius
+fn get_neighbors(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, radius: f64) -> Vec<Vec<usize>> {
+    let mut neighbors = vec![vec![]; num_particles];
+    for i in 0..num_particles {
+        for j in i+1..num_particles {
+            let d2 = get_particle_distance_squared(rx[i].to_f64(), ry[i].to_f64(), rz[i].to_f64(), rx[j].to_f64(), ry[j].to_f64(), rz[j].to_f64(),
+                                                   l_x, l_y, l_z, l_x / 2.0, l_y / 2.0, l_z / 2.0);
+            if d2 < radius * radius {
+                neighbors[i].push(j);
+                neighbors[j].push(i);
+            }
+        }
+    }
+    return neighbors;
+}
+
+/// Cluster of every particle, particles closer than radius belong to the same cluster. The clusters are numbered
+/// from 0 by decreasing size, so 0 is the largest (e.g. the liquid of a two phase system).
+pub fn get_cluster_ids(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, radius: f64) -> Vec<usize> {
+    let neighbors = get_neighbors(rx, ry, rz, num_particles, l_x, l_y, l_z, radius);
+    let mut ids = vec![usize::MAX; num_particles];
+    let mut sizes = vec![];
+    for start in 0..num_particles {
+        if ids[start] != usize::MAX { continue; }
+        let cluster = sizes.len();
+        let mut stack = vec![start];
+        ids[start] = cluster;
+        let mut size = 0;
+        while let Some(i) = stack.pop() {
+            size += 1;
+            for &j in &neighbors[i] {
+                if ids[j] == usize::MAX {
+                    ids[j] = cluster;
+                    stack.push(j);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    // renumber by size, ties in order of the first particle
+    let mut order : Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].cmp(&sizes[a]).then(a.cmp(&b)));
+    let mut rank = vec![0; sizes.len()];
+    for (r, &cluster) in order.iter().enumerate() { rank[cluster] = r; }
+    return ids.iter().map(|&id| rank[id]).collect();
+}
+
+#[test]
+fn test_get_cluster_ids() {
+    // a pair across the periodic boundary, a single particle and a chain of three
+    let rx = vec![0.2, 9.8, 5.0, 3.0, 4.0, 5.0].into_iter().map(Real::from_f64).collect::<Vec<Real>>();
+    let ry = vec![0.0, 0.0, 5.0, 2.0, 2.0, 2.0].into_iter().map(Real::from_f64).collect::<Vec<Real>>();
+    let rz = vec![0.0; 6].into_iter().map(Real::from_f64).collect::<Vec<Real>>();
+    assert_eq!(vec![1, 1, 2, 0, 0, 0], get_cluster_ids(&rx, &ry, &rz, 6, 10.0, 10.0, 10.0, 1.5));
+}
+
+/// Neighbors within NEIGHBOR_RADIUS of a liquid-like particle in a vapor (ten Wolde and Frenkel)
+pub const LIQUID_NEIGHBORS : usize = 5;
+
+/// Size of the largest cluster of liquid-like particles, i.e. with at least min_neighbors neighbors within radius.
+/// Liquid-like particles closer than radius belong to the same cluster.
+pub fn get_largest_liquid_cluster(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, radius: f64, min_neighbors: usize) -> usize {
+    let neighbors = get_neighbors(rx, ry, rz, num_particles, l_x, l_y, l_z, radius);
+    let liquid : Vec<bool> = neighbors.iter().map(|n| n.len() >= min_neighbors).collect();
+    let mut visited = vec![false; num_particles];
+    let mut largest = 0;
+    for start in (0..num_particles).filter(|&i| liquid[i]) {
+        if visited[start] { continue; }
+        visited[start] = true;
+        let mut stack = vec![start];
+        let mut size = 0;
+        while let Some(i) = stack.pop() {
+            size += 1;
+            for &j in &neighbors[i] {
+                if liquid[j] && !visited[j] {
+                    visited[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        largest = largest.max(size);
+    }
+    return largest;
+}
+
+#[test]
+fn test_get_largest_liquid_cluster() {
+    // a 3x3x3 lattice cube across the boundary and a dimer. With the nearest neighbors only the center and the face
+    // centers have 5 or more, the corners have 3.
+    let (mut rx, mut ry, mut rz) = (vec![], vec![], vec![]);
+    for i in 0..27 {
+        rx.push(Real::from_f64(((i % 3) as f64 + 9.0) % 10.0));
+        ry.push(Real::from_f64((i / 3 % 3) as f64 + 2.0));
+        rz.push(Real::from_f64((i / 9) as f64 + 2.0));
+    }
+    rx.extend(vec![Real::from_f64(6.0), Real::from_f64(6.0)]);
+    ry.extend(vec![Real::from_f64(7.0), Real::from_f64(8.0)]);
+    rz.extend(vec![Real::from_f64(7.0), Real::from_f64(7.0)]);
+    assert_eq!(7, get_largest_liquid_cluster(&rx, &ry, &rz, 29, 10.0, 10.0, 10.0, 1.2, LIQUID_NEIGHBORS));
+    assert_eq!(27, get_largest_liquid_cluster(&rx, &ry, &rz, 29, 10.0, 10.0, 10.0, 1.2, 3));
+    assert_eq!(0, get_largest_liquid_cluster(&rx, &ry, &rz, 29, 10.0, 10.0, 10.0, 0.5, 1));
+}
+
+/// Number density in the sphere of radius around every particle, the particle itself not counted
+pub fn get_local_densities(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, radius: f64) -> Vec<f64> {
+    let volume = 4.0 / 3.0 * ::std::f64::consts::PI * radius.powi(3);
+    return get_neighbors(rx, ry, rz, num_particles, l_x, l_y, l_z, radius).iter().map(|n| n.len() as f64 / volume).collect();
+}
+
+#[test]
+fn test_get_local_densities() {
+    let rx = vec![1.0, 2.0, 3.0, 8.0].into_iter().map(Real::from_f64).collect::<Vec<Real>>();
+    let r0 = vec![0.0; 4].into_iter().map(Real::from_f64).collect::<Vec<Real>>();
+    let densities = get_local_densities(&rx, &r0, &r0, 4, 10.0, 10.0, 10.0, 1.5);
+    let volume = 4.0 / 3.0 * ::std::f64::consts::PI * 3.375;
+    assert!( (densities[1] - 2.0 / volume).abs() < 0.00001, "{}", densities[1]);
+    assert!( (densities[0] - 1.0 / volume).abs() < 0.00001, "{}", densities[0]);
+    assert_eq!(0.0, densities[3]);
+}
+
+/// Distance of the point x, y, z to the nearest particle, infinite without particles
+pub fn get_nearest_distance(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, x: f64, y: f64, z: f64, l_x: f64, l_y: f64, l_z: f64) -> f64 {
+    let mut nearest = f64::INFINITY;
+    for i in 0..num_particles {
+        let d2 = get_particle_distance_squared(x, y, z, rx[i].to_f64(), ry[i].to_f64(), rz[i].to_f64(), l_x, l_y, l_z, l_x / 2.0, l_y / 2.0, l_z / 2.0);
+        nearest = nearest.min(d2);
+    }
+    return nearest.sqrt();
+}
+
+#[test]
+fn test_get_nearest_distance() {
+    let rx = vec![1.0, 9.0];
+    let r0 = vec![5.0, 5.0];
+    assert!( (get_nearest_distance(&rx, &r0, &r0, 2, 0.0, 5.0, 5.0, 10.0, 10.0, 10.0) - 1.0).abs() < 0.00001);
+    assert!( (get_nearest_distance(&rx, &r0, &r0, 2, 5.0, 5.0, 8.0, 10.0, 10.0, 10.0) - 5.0).abs() < 0.00001);
+    assert!(get_nearest_distance(&rx, &r0, &r0, 0, 5.0, 5.0, 8.0, 10.0, 10.0, 10.0).is_infinite());
+}
+
+/// Probability that a hard test sphere of radius r_k = (k + 1) r_max / bins fits into the configuration without
+/// touching a particle center, from the nearest particle distances of random insertion points
+pub fn get_cavity_probabilities(nearest_distances: &[f64], r_max: f64, bins: usize) -> Vec<f64> {
+    return (0..bins).map(|k| {
+        let r = (k + 1) as f64 * r_max / bins as f64;
+        nearest_distances.iter().filter(|&&d| d > r).count() as f64 / nearest_distances.len() as f64
+    }).collect();
+}
+
+#[test]
+fn test_get_cavity_probabilities() {
+    let probabilities = get_cavity_probabilities(&[0.3, 0.6, 0.8, 1.2], 1.0, 4);
+    assert_eq!(vec![1.0, 0.75, 0.5, 0.25], probabilities);
+}
+
+// tolerance of the polyhedron clipping relative to the box
+const VORONOI_EPS : f64 = 1e-10;
+
+// keeps the part of the convex polyhedron of faces on the side p.n <= d of a plane
+fn clip_polyhedron(faces: &mut Vec<Vec<[f64; 3]>>, n: [f64; 3], d: f64) {
+    let side = |p: &[f64; 3]| p[0]*n[0] + p[1]*n[1] + p[2]*n[2] - d;
+    let mut cap : Vec<[f64; 3]> = vec![];
+    for face in faces.iter_mut() {
+        let mut clipped = vec![];
+        for k in 0..face.len() {
+            let (a, b) = (face[k], face[(k + 1) % face.len()]);
+            let (sa, sb) = (side(&a), side(&b));
+            // vertices on the plane belong to both the face and the cut
+            if sa <= VORONOI_EPS { clipped.push(a); }
+            if sa.abs() <= VORONOI_EPS { cap.push(a); }
+            if (sa < -VORONOI_EPS && sb > VORONOI_EPS) || (sa > VORONOI_EPS && sb < -VORONOI_EPS) {
+                let t = sa / (sa - sb);
+                let p = [a[0] + t*(b[0] - a[0]), a[1] + t*(b[1] - a[1]), a[2] + t*(b[2] - a[2])];
+                clipped.push(p);
+                cap.push(p);
+            }
+        }
+        *face = clipped;
+    }
+    faces.retain(|face| face.len() >= 3);
+
+    // the cut edges form the new face, ordered by their angle around its center
+    cap.dedup_by(|a, b| (0..3).all(|k| (a[k] - b[k]).abs() < VORONOI_EPS));
+    let mut unique : Vec<[f64; 3]> = vec![];
+    for p in cap {
+        if !unique.iter().any(|q| (0..3).all(|k| (p[k] - q[k]).abs() < VORONOI_EPS)) { unique.push(p); }
+    }
+    if unique.len() < 3 { return; }
+    let center = (0..3).map(|k| unique.iter().map(|p| p[k]).sum::<f64>() / unique.len() as f64).collect::<Vec<f64>>();
+    let u = [unique[0][0] - center[0], unique[0][1] - center[1], unique[0][2] - center[2]];
+    let v = [n[1]*u[2] - n[2]*u[1], n[2]*u[0] - n[0]*u[2], n[0]*u[1] - n[1]*u[0]];
+    let angle = |p: &[f64; 3]| {
+        let w = [p[0] - center[0], p[1] - center[1], p[2] - center[2]];
+        (w[0]*v[0] + w[1]*v[1] + w[2]*v[2]).atan2(w[0]*u[0] + w[1]*u[1] + w[2]*u[2])
+    };
+    unique.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+    faces.push(unique);
+}
+
+/// Volume of the Voronoi cell of every particle in the periodic box, the region closer to it than to all other
+/// particles. The cells start as the box around the particle and are cut by the bisecting planes of the neighbors
+/// in order of their distance until the next neighbor is too far to reach the cell. Cells reaching further than a
+/// quarter of the box (e.g. in a vapor) are cut again with all periodic images of the neighbors.
+pub fn get_voronoi_volumes(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64) -> Vec<f64> {
+    let (hx, hy, hz) = (l_x / 2.0, l_y / 2.0, l_z / 2.0);
+    let mut volumes = vec![0.0; num_particles];
+    for i in 0..num_particles {
+        let minimum_images : Vec<[f64; 3]> = (0..num_particles).filter(|&j| j != i).map(|j| [
+            get_displacement_with_pbc(rx[j].to_f64(), rx[i].to_f64(), l_x, hx),
+            get_displacement_with_pbc(ry[j].to_f64(), ry[i].to_f64(), l_y, hy),
+            get_displacement_with_pbc(rz[j].to_f64(), rz[i].to_f64(), l_z, hz)]).collect();
+        let (faces, reach) = get_voronoi_cell(minimum_images.clone(), hx, hy, hz);
+        let faces = if reach.sqrt() <= hx.min(hy).min(hz) / 2.0 { faces } else {
+            let mut images = vec![];
+            for d in &minimum_images {
+                for shift in 0..27 {
+                    let (sx, sy, sz) = ((shift % 3) as f64 - 1.0, (shift / 3 % 3) as f64 - 1.0, (shift / 9) as f64 - 1.0);
+                    images.push([d[0] + sx * l_x, d[1] + sy * l_y, d[2] + sz * l_z]);
+                }
+            }
+            // the own images bound the cell like the box
+            get_voronoi_cell(images, hx, hy, hz).0
+        };
+
+        // the particle is inside its cell, so the tetrahedra to the face triangles add up to its volume
+        for face in &faces {
+            for k in 1..face.len() - 1 {
+                let (a, b, c) = (face[0], face[k], face[k + 1]);
+                let det = a[0]*(b[1]*c[2] - b[2]*c[1]) - a[1]*(b[0]*c[2] - b[2]*c[0]) + a[2]*(b[0]*c[1] - b[1]*c[0]);
+                volumes[i] += det.abs() / 6.0;
+            }
+        }
+    }
+    return volumes;
+}
+
+// faces of the voronoi cell of a particle at the origin with neighbors at the given positions and the squared
+// distance of its farthest vertex, starting from the box of half lengths hx, hy, hz
+fn get_voronoi_cell(mut neighbors: Vec<[f64; 3]>, hx: f64, hy: f64, hz: f64) -> (Vec<Vec<[f64; 3]>>, f64) {
+    let norm = |d: &[f64; 3]| d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+    neighbors.sort_by(|a, b| norm(a).partial_cmp(&norm(b)).unwrap());
+
+    let corner = |x: f64, y: f64, z: f64| [x * hx, y * hy, z * hz];
+    let mut faces = vec![
+        vec![corner(-1.0, -1.0, -1.0), corner(-1.0, 1.0, -1.0), corner(1.0, 1.0, -1.0), corner(1.0, -1.0, -1.0)],
+        vec![corner(-1.0, -1.0, 1.0), corner(1.0, -1.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)],
+        vec![corner(-1.0, -1.0, -1.0), corner(1.0, -1.0, -1.0), corner(1.0, -1.0, 1.0), corner(-1.0, -1.0, 1.0)],
+        vec![corner(-1.0, 1.0, -1.0), corner(-1.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), corner(1.0, 1.0, -1.0)],
+        vec![corner(-1.0, -1.0, -1.0), corner(-1.0, -1.0, 1.0), corner(-1.0, 1.0, 1.0), corner(-1.0, 1.0, -1.0)],
+        vec![corner(1.0, -1.0, -1.0), corner(1.0, 1.0, -1.0), corner(1.0, 1.0, 1.0), corner(1.0, -1.0, 1.0)],
+    ];
+    let mut reach = norm(&[hx, hy, hz]);
+    for d in neighbors {
+        // a neighbor cuts the cell only if half its distance is below the farthest vertex
+        let d2 = norm(&d);
+        if d2 / 4.0 > reach { break; }
+        clip_polyhedron(&mut faces, d, d2 / 2.0);
+        reach = faces.iter().flat_map(|face| face.iter()).map(&norm).fold(0.0, f64::max);
+    }
+    return (faces, reach);
+}
+
+#[test]
+fn test_get_voronoi_volumes() {
+    // simple cubic lattice of spacing 2, every cell is a cube of volume 8
+    let mut r : Vec<Vec<Real>> = vec![vec![]; 3];
+    for i in 0..27 {
+        r[0].push(Real::from_f64((i % 3) as f64 * 2.0 + 0.5));
+        r[1].push(Real::from_f64((i / 3 % 3) as f64 * 2.0 + 0.5));
+        r[2].push(Real::from_f64((i / 9) as f64 * 2.0 + 0.5));
+    }
+    for volume in get_voronoi_volumes(&r[0], &r[1], &r[2], 27, 6.0, 6.0, 6.0) {
+        assert!( (volume - 8.0).abs() < 0.00001, "{}", volume);
+    }
+
+    // random particles fill the box
+    use rand::Rng;
+    let mut rng = ::rand::thread_rng();
+    let mut coordinates = || (0..100).map(|_| Real::from_f64(rng.gen::<f64>() * 5.0)).collect::<Vec<Real>>();
+    let (rx, ry, rz) = (coordinates(), coordinates(), coordinates());
+    let volumes = get_voronoi_volumes(&rx, &ry, &rz, 100, 5.0, 5.0, 5.0);
+    let total : f64 = volumes.iter().sum();
+    assert!(volumes.iter().all(|&v| v > 0.0));
+    assert!( (total - 125.0).abs() < 0.0001, "{}", total);
+
+    // a few particles in a large box need the periodic images
+    let (rx, ry, rz) = (vec![1.0, 2.0, 7.0], vec![1.0, 8.0, 4.0], vec![1.0, 5.0, 9.0]);
+    let total : f64 = get_voronoi_volumes(&rx, &ry, &rz, 3, 10.0, 10.0, 10.0).iter().sum();
+    assert!( (total - 1000.0).abs() < 0.0001, "{}", total);
+}
+
+/// Normalized histogram of values between 0 and max in bins, values beyond max are left out
+pub fn get_histogram(values: &[f64], max: f64, bins: usize) -> Vec<f64> {
+    let width = max / bins as f64;
+    let mut histogram = vec![0.0; bins];
+    for &value in values {
+        let bin = (value / width) as usize;
+        if value >= 0.0 && bin < bins { histogram[bin] += 1.0 / (values.len() as f64 * width); }
+    }
+    return histogram;
+}
+
+#[test]
+fn test_get_histogram() {
+    // 5 values in bins of width 0.5, the last one beyond max
+    let histogram = get_histogram(&[0.1, 1.2, 1.3, 1.7, 5.0], 2.0, 4);
+    for (h, expected) in histogram.iter().zip([0.4, 0.0, 0.8, 0.4].iter()) {
+        assert!( (h - expected).abs() < 0.00001, "{:?}", histogram);
+    }
+}
@@ -0,0 +1,168 @@
+//! Orientations of the particles as unit quaternions and their random rotations
+
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// Unit quaternion w x y z of the rotation from the body frame to the box frame
+pub type Quaternion = [f64; 4];
+
+pub const IDENTITY : Quaternion = [1.0, 0.0, 0.0, 0.0];
+
+/// Product ab of two quaternions, the rotation b followed by a
+pub fn multiply(a: Quaternion, b: Quaternion) -> Quaternion {
+    return [a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+            a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+            a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+            a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0]];
+}
+
+/// Quaternion scaled to unit length against the rounding errors of repeated products
+pub fn normalize(q: Quaternion) -> Quaternion {
+    let norm = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+    return [q[0] / norm, q[1] / norm, q[2] / norm, q[3] / norm];
+}
+
+/// Rotation by angle around a unit axis
+pub fn from_axis_angle(axis: [f64; 3], angle: f64) -> Quaternion {
+    let s = (angle / 2.0).sin();
+    return [(angle / 2.0).cos(), axis[0] * s, axis[1] * s, axis[2] * s];
+}
+
+/// Vector v of the body frame rotated into the box frame
+pub fn rotate(q: Quaternion, v: [f64; 3]) -> [f64; 3] {
+    let r = multiply(multiply(q, [0.0, v[0], v[1], v[2]]), [q[0], -q[1], -q[2], -q[3]]);
+    return [r[1], r[2], r[3]];
+}
+
+/// Symmetry axis of a particle, the body z axis in the box frame
+pub fn get_axis(q: Quaternion) -> [f64; 3] {
+    return rotate(q, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn test_quaternion_rotation() {
+    let q = from_axis_angle([1.0, 0.0, 0.0], PI / 2.0);
+    let axis = get_axis(q);
+    assert!( (axis[0]).abs() < 0.00001 && (axis[1] + 1.0).abs() < 0.00001 && axis[2].abs() < 0.00001, "{:?}", axis);
+    // two quarter turns are a half turn
+    let axis = get_axis(multiply(q, q));
+    assert!( (axis[2] + 1.0).abs() < 0.00001, "{:?}", axis);
+    assert_eq!(IDENTITY, multiply(IDENTITY, IDENTITY));
+    let n = normalize([2.0, 0.0, 0.0, 0.0]);
+    assert_eq!(IDENTITY, n);
+}
+
+/// Uniformly distributed orientation (Shoemake)
+pub fn random_orientation<R: Rng>(rng: &mut R) -> Quaternion {
+    let (u1, u2, u3) = (rng.gen::<f64>(), 2.0 * PI * rng.gen::<f64>(), 2.0 * PI * rng.gen::<f64>());
+    return [(1.0 - u1).sqrt() * u2.sin(), (1.0 - u1).sqrt() * u2.cos(), u1.sqrt() * u3.sin(), u1.sqrt() * u3.cos()];
+}
+
+/// Rotation around a random axis by an angle uniform in [-max_angle, max_angle], the rotation and its inverse are
+/// equally likely so the proposals are symmetric
+pub fn random_rotation<R: Rng>(rng: &mut R, max_angle: f64) -> Quaternion {
+    let cos_theta = 2.0 * rng.gen::<f64>() - 1.0;
+    let phi = 2.0 * PI * rng.gen::<f64>();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let axis = [sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta];
+    return from_axis_angle(axis, max_angle * (2.0 * rng.gen::<f64>() - 1.0));
+}
+
+#[test]
+fn test_random_rotation() {
+    let mut rng = ::rand::thread_rng();
+    for _ in 0..100 {
+        let q = random_orientation(&mut rng);
+        assert!( (q.iter().map(|c| c * c).sum::<f64>() - 1.0).abs() < 0.00001);
+        // the axis tilts by at most the angle of the rotation
+        let axis = get_axis(multiply(random_rotation(&mut rng, 0.1), q));
+        let old = get_axis(q);
+        assert!((0..3).map(|k| axis[k] * old[k]).sum::<f64>() >= 0.1f64.cos() - 0.00001);
+    }
+}
+
+// eigenvalues and the eigenvectors (columns) of a symmetric matrix by cyclic jacobi rotations
+fn get_symmetric_eigensystem(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..50 {
+        if a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2) < 1e-30 { break; }
+        for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 { continue; }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for k in 0..3 {
+                let (kp, kq) = (a[k][p], a[k][q]);
+                a[k][p] = c * kp - s * kq;
+                a[k][q] = s * kp + c * kq;
+            }
+            for k in 0..3 {
+                let (pk, qk) = (a[p][k], a[q][k]);
+                a[p][k] = c * pk - s * qk;
+                a[q][k] = s * pk + c * qk;
+            }
+            for row in v.iter_mut() {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+        }
+    }
+    return ([a[0][0], a[1][1], a[2][2]], v);
+}
+
+/// Nematic order parameter P2 and the director, the largest eigenvalue and its eigenvector of the order tensor
+/// Q = <3/2 u u - 1/2 I> of the particle axes u. P2 is 1 for parallel axes and about 0 for isotropic ones.
+pub fn get_nematic_order(orientations: &[Quaternion]) -> (f64, [f64; 3]) {
+    let mut q = [[0.0; 3]; 3];
+    for &orientation in orientations {
+        let u = get_axis(orientation);
+        for a in 0..3 {
+            for b in 0..3 {
+                q[a][b] += 1.5 * u[a] * u[b] / orientations.len() as f64;
+            }
+        }
+    }
+    for (a, row) in q.iter_mut().enumerate() {
+        row[a] -= 0.5;
+    }
+    let (values, vectors) = get_symmetric_eigensystem(q);
+    let mut largest = 0;
+    for k in 1..3 {
+        if values[k] > values[largest] { largest = k; }
+    }
+    return (values[largest], [vectors[0][largest], vectors[1][largest], vectors[2][largest]]);
+}
+
+#[test]
+fn test_get_nematic_order() {
+    // axes along x with a small spread, director along x up to the sign
+    let orientations = vec![from_axis_angle([0.0, 1.0, 0.0], PI / 2.0), from_axis_angle([0.0, 1.0, 0.0], PI / 2.0 + 0.1), from_axis_angle([0.0, 1.0, 0.0], -PI / 2.0)];
+    let (p2, director) = get_nematic_order(&orientations);
+    assert!(p2 > 0.98 && p2 < 1.0, "{}", p2);
+    assert!(director[0].abs() > 0.99, "{:?}", director);
+
+    let mut rng = ::rand::thread_rng();
+    let orientations : Vec<Quaternion> = (0..10000).map(|_| random_orientation(&mut rng)).collect();
+    let (p2, _) = get_nematic_order(&orientations);
+    assert!(p2 < 0.05, "{}", p2);
+}
+
+/// Parses a direction x,y,z to a unit vector, None for malformed or zero vectors
+pub fn parse_direction(list: &str) -> Option<[f64; 3]> {
+    let components = list.split(',').map(|c| c.trim().parse::<f64>().ok()).collect::<Option<Vec<f64>>>()?;
+    if components.len() != 3 { return None; }
+    let norm = components.iter().map(|c| c * c).sum::<f64>().sqrt();
+    if !(norm.is_finite() && norm > 0.0) { return None; }
+    return Some([components[0] / norm, components[1] / norm, components[2] / norm]);
+}
+
+#[test]
+fn test_parse_direction() {
+    assert_eq!(Some([0.0, 0.0, 1.0]), parse_direction("0,0,2"));
+    assert_eq!(Some([0.6, -0.8, 0.0]), parse_direction("3, -4, 0"));
+    assert_eq!(None, parse_direction("0,0,0"));
+    assert_eq!(None, parse_direction("1,0"));
+    assert_eq!(None, parse_direction("x,0,1"));
+}
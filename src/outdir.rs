@@ -0,0 +1,141 @@
+use error::{Error, Result};
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use system::Params;
+
+/// Name of the manifest file in a run directory
+pub const MANIFEST : &str = "manifest.json";
+
+/// Name of the echo of the effective run parameters in a run directory
+pub const CONFIG : &str = "config.json";
+
+/// Date and time in UTC of seconds since the unix epoch: year, month, day, hour, minute and second
+pub fn get_utc_date(seconds: u64) -> (i64, u64, u64, u64, u64, u64) {
+    // civil date of the days since the epoch (Howard Hinnant's days_from_civil inverted), eras of 400 years
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u64;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u64;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let time = seconds % 86400;
+    return (year, month, day, time / 3600, time / 60 % 60, time % 60);
+}
+
+/// Compact UTC timestamp of seconds since the unix epoch for file names, e.g. 20261014-153012
+pub fn format_timestamp(seconds: u64) -> String {
+    let (year, month, day, hour, minute, second) = get_utc_date(seconds);
+    return format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, hour, minute, second);
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!("19700101-000000", format_timestamp(0));
+    assert_eq!("20000229-235959", format_timestamp(951868799));
+    assert_eq!("20000301-000000", format_timestamp(951868800));
+    assert_eq!("20261014-153012", format_timestamp(1791991812));
+}
+
+/// Creates the run directory <outdir>/<name>-<timestamp> and returns its path, runs started in the same second get
+/// the suffixes -2, -3, ...
+pub fn create_run_directory(outdir: &str, name: &str, seconds: u64) -> Result<String> {
+    fs::create_dir_all(outdir).map_err(|why| Error::FileNotFound(outdir.to_string(), why))?;
+    let base = Path::new(outdir).join(format!("{}-{}", name, format_timestamp(seconds)));
+    for k in 1.. {
+        let directory = if k == 1 { base.to_string_lossy().to_string() } else { format!("{}-{}", base.to_string_lossy(), k) };
+        match fs::create_dir(&directory) {
+            Ok(()) => return Ok(directory),
+            Err(ref why) if why.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(why) => return Err(Error::FileNotFound(directory, why)),
+        }
+    }
+    unreachable!();
+}
+
+/// Writes the effective run parameters (after presets, units and protocol) to the config.json of the run directory
+pub fn write_config(directory: &str, params: &Params) -> Result<()> {
+    let filename = Path::new(directory).join(CONFIG).to_string_lossy().to_string();
+    let json = ::serde_json::to_string_pretty(params).map_err(|e| Error::Serialization(e.to_string()))?;
+    return fs::write(&filename, json).map_err(|why| Error::FileNotFound(filename, why));
+}
+
+/// Index of the files of a run directory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Manifest {
+    /// Command line of the run, of the last restart for continued runs
+    pub command: Vec<String>,
+    /// UTC start time of the run (see format_timestamp)
+    pub created: String,
+    /// Status of the run: running, finished, stopped early (wall time limit or stop command) or aborted
+    pub status: String,
+    /// Names and sizes in bytes of the files, sorted by name
+    pub files: Vec<(String, u64)>,
+}
+
+impl Manifest {
+    pub fn new(command: &[String], created: &str) -> Manifest {
+        return Manifest { command: command.to_vec(), created: created.to_string(), status: "running".to_string(), files: vec![] };
+    }
+
+    /// Lists the files of the directory (except the manifest) and writes the manifest into it
+    pub fn write(&mut self, directory: &str) -> Result<()> {
+        let entries = fs::read_dir(directory).map_err(|why| Error::FileNotFound(directory.to_string(), why))?;
+        self.files.clear();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name != MANIFEST && entry.file_type()?.is_file() {
+                self.files.push((name, entry.metadata()?.len()));
+            }
+        }
+        self.files.sort();
+        let filename = Path::new(directory).join(MANIFEST).to_string_lossy().to_string();
+        let mut file = match fs::File::create(&filename) {
+            Err(why) => return Err(Error::FileNotFound(filename, why)),
+            Ok(file) => file,
+        };
+        let json = ::serde_json::to_string_pretty(self).map_err(|e| Error::Serialization(e.to_string()))?;
+        return Ok(file.write_all(json.as_bytes())?);
+    }
+
+    /// Writes the manifest with the final status of the run
+    pub fn finish(&mut self, status: &str, directory: &str) -> Result<()> {
+        self.status = status.to_string();
+        return self.write(directory);
+    }
+
+    pub fn load(directory: &str) -> Result<Manifest> {
+        let filename = Path::new(directory).join(MANIFEST).to_string_lossy().to_string();
+        let json = fs::read_to_string(&filename).map_err(|why| Error::FileNotFound(filename, why))?;
+        return ::serde_json::from_str(&json).map_err(|e| Error::Serialization(e.to_string()));
+    }
+}
+
+#[test]
+fn test_run_directory() {
+    let outdir = ::std::env::temp_dir().join("mclj_test_outdir").to_string_lossy().to_string();
+    let _ = fs::remove_dir_all(&outdir);
+    let first = create_run_directory(&outdir, "montecarlo", 1791991812).unwrap();
+    let second = create_run_directory(&outdir, "montecarlo", 1791991812).unwrap();
+    assert!(first.ends_with("montecarlo-20261014-153012"), "{}", first);
+    assert_eq!(format!("{}-2", first), second);
+
+    fs::write(Path::new(&first).join("montecarlo.xyz"), "12345").unwrap();
+    write_config(&first, &Params::default()).unwrap();
+    let config : Params = ::serde_json::from_str(&fs::read_to_string(Path::new(&first).join(CONFIG)).unwrap()).unwrap();
+    assert_eq!(Params::default(), config);
+    let config_size = fs::metadata(Path::new(&first).join(CONFIG)).unwrap().len();
+    let mut manifest = Manifest::new(&["mc".to_string(), "--outdir".to_string(), outdir.clone()], &format_timestamp(1791991812));
+    manifest.write(&first).unwrap();
+    // the second write lists the files again, without the manifest itself
+    manifest.finish("finished", &first).unwrap();
+    let loaded = Manifest::load(&first).unwrap();
+    assert_eq!(manifest, loaded);
+    assert_eq!(vec![(CONFIG.to_string(), config_size), ("montecarlo.xyz".to_string(), 5)], loaded.files);
+    fs::remove_dir_all(&outdir).unwrap();
+}
@@ -0,0 +1,76 @@
+//! Summary figures of a run as svg files
+use plotters::prelude::*;
+use error::{Error, Result};
+
+const WIDTH : u32 = 800;
+const HEIGHT : u32 = 500;
+
+/// Line plot of the points (x, y) to the svg file filename, non finite points are left out
+pub fn line_plot(filename: &str, title: &str, x_label: &str, y_label: &str, points: &[(f64, f64)]) -> Result<()> {
+    let svg = line_plot_svg(title, x_label, y_label, points)?;
+    return Ok(::std::fs::write(filename, svg)?);
+}
+
+/// Line plot of the points (x, y) as svg document
+pub fn line_plot_svg(title: &str, x_label: &str, y_label: &str, points: &[(f64, f64)]) -> Result<String> {
+    let points : Vec<(f64, f64)> = points.iter().cloned().filter(|p| p.0.is_finite() && p.1.is_finite()).collect();
+    if points.is_empty() {
+        return Err(Error::Plot(format!("no data for {}", title)));
+    }
+    let (x_min, x_max) = get_range(points.iter().map(|p| p.0));
+    let (y_min, y_max) = get_range(points.iter().map(|p| p.1));
+
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).map_err(plot_error)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 24))
+            .margin(15)
+            .x_label_area_size(45)
+            .y_label_area_size(70)
+            .build_cartesian_2d(x_min..x_max, y_min..y_max)
+            .map_err(plot_error)?;
+        chart.configure_mesh().x_desc(x_label).y_desc(y_label).draw().map_err(plot_error)?;
+        chart.draw_series(LineSeries::new(points, &BLUE)).map_err(plot_error)?;
+        root.present().map_err(plot_error)?;
+    }
+    return Ok(svg);
+}
+
+// axis range with some padding, widened if all values are equal
+fn get_range<I: Iterator<Item = f64>>(values: I) -> (f64, f64) {
+    let (mut min, mut max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| (min.min(v), max.max(v)));
+    if max - min < 1e-12 {
+        min -= 0.5;
+        max += 0.5;
+    }
+    let padding = 0.05 * (max - min);
+    return (min - padding, max + padding);
+}
+
+fn plot_error<E: ::std::fmt::Display>(e: E) -> Error {
+    return Error::Plot(e.to_string());
+}
+
+#[test]
+fn test_get_range() {
+    let (min, max) = get_range(vec![0.0, 10.0, 5.0].into_iter());
+    assert!( (min - -0.5).abs() < 0.00001, "{}", min);
+    assert!( (max - 10.5).abs() < 0.00001, "{}", max);
+    let (min, max) = get_range(vec![2.0, 2.0].into_iter());
+    assert!(min < 2.0 && max > 2.0);
+}
+
+#[test]
+fn test_line_plot() {
+    let filename = ::std::env::temp_dir().join("mclj_test_plot.svg").to_str().unwrap().to_string();
+    let points : Vec<(f64, f64)> = (0..100).map(|i| (i as f64, (i as f64 / 10.0).sin())).collect();
+    line_plot(&filename, "Test", "x", "sin(x)", &points).unwrap();
+    let svg = ::std::fs::read_to_string(&filename).unwrap();
+    ::std::fs::remove_file(&filename).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("sin(x)"));
+
+    assert!(line_plot(&filename, "Test", "x", "y", &[(0.0, f64::NAN)]).is_err());
+}
@@ -0,0 +1,99 @@
+use outdir::get_utc_date;
+use serde_json::Value;
+use std::fs;
+use system::Params;
+
+/// Git revision of the build, -dirty with uncommitted changes and empty if not built from a git checkout (see build.rs)
+pub const GIT_HASH : &str = env!("MCLJ_GIT_HASH");
+
+/// How and where a run (or one of its restarts) was started. The outputs carry it next to the full parameters, so any
+/// of them can be traced back to the program and the command that produced it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Provenance {
+    /// Version of the crate
+    pub version: String,
+    /// Git revision of the build (see GIT_HASH)
+    pub git_hash: String,
+    pub hostname: String,
+    /// Start time in UTC (ISO 8601, see format_iso_time)
+    pub start_time: String,
+    pub command: Vec<String>,
+    /// Master seed of the random number streams, 0 if seeded from the os
+    pub seed: usize,
+}
+
+impl Provenance {
+    /// Provenance of a run started now (seconds since the unix epoch) on this host
+    pub fn new(command: &[String], seed: usize, start_time: u64) -> Provenance {
+        return Provenance {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: GIT_HASH.to_string(),
+            hostname: get_hostname(),
+            start_time: format_iso_time(start_time),
+            command: command.to_vec(),
+            seed: seed,
+        };
+    }
+
+    /// Single line summary, e.g. for the title of a netcdf trajectory or pdb remarks
+    pub fn summary(&self) -> String {
+        return format!("mclj {}{} on {} at {}, seed {}", self.version, if self.git_hash.is_empty() { String::new() } else { format!(" ({})", self.git_hash) },
+                       self.hostname, self.start_time, self.seed);
+    }
+
+    /// Extended xyz key=value pairs of the provenance and all parameters (as param_<name>) for the comment line of the
+    /// first frame of a run, values with spaces are quoted and double quotes in them replaced by single quotes
+    pub fn to_key_values(&self, params: &Params) -> String {
+        let quote = |value: String| {
+            let value = value.replace('"', "'");
+            if value.is_empty() || value.contains(char::is_whitespace) { format!("\"{}\"", value) } else { value }
+        };
+        let mut pairs = vec![
+            format!("mclj_version={}", quote(self.version.clone())),
+            format!("git_hash={}", quote(self.git_hash.clone())),
+            format!("hostname={}", quote(self.hostname.clone())),
+            format!("start_time={}", quote(self.start_time.clone())),
+            format!("command={}", quote(self.command.join(" "))),
+            format!("seed={}", self.seed),
+        ];
+        if let Ok(Value::Object(map)) = ::serde_json::to_value(params) {
+            for (name, value) in map {
+                let value = match value { Value::String(text) => text, other => other.to_string() };
+                pairs.push(format!("param_{}={}", name, quote(value)));
+            }
+        }
+        return pairs.join(" ");
+    }
+}
+
+/// Name of the host from the environment or the kernel, unknown if there is none (e.g. in the browser)
+pub fn get_hostname() -> String {
+    let names = [::std::env::var("HOSTNAME").ok(), ::std::env::var("COMPUTERNAME").ok(),
+                 fs::read_to_string("/proc/sys/kernel/hostname").ok(), fs::read_to_string("/etc/hostname").ok()];
+    return names.iter().flatten().map(|name| name.trim()).find(|name| !name.is_empty()).unwrap_or("unknown").to_string();
+}
+
+/// ISO 8601 UTC time of seconds since the unix epoch, e.g. 2026-10-14T15:30:12Z
+pub fn format_iso_time(seconds: u64) -> String {
+    let (year, month, day, hour, minute, second) = get_utc_date(seconds);
+    return format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second);
+}
+
+#[test]
+fn test_provenance() {
+    assert_eq!("2026-10-14T15:30:12Z", format_iso_time(1791991812));
+    let provenance = Provenance::new(&["mc".to_string(), "-t".to_string(), "0.8".to_string()], 42, 1791991812);
+    assert_eq!(env!("CARGO_PKG_VERSION"), provenance.version);
+    assert!(!provenance.hostname.is_empty());
+
+    let params = Params { schedule: "1000:temperature:0.8".to_string(), ..Params::default() };
+    let pairs = ::trajectory::parse_key_values(&provenance.to_key_values(&params));
+    let get = |key: &str| pairs.iter().find(|pair| pair.0 == key).map(|pair| pair.1.as_str());
+    assert_eq!(Some("mc -t 0.8"), get("command"));
+    assert_eq!(Some("42"), get("seed"));
+    assert_eq!(Some("2026-10-14T15:30:12Z"), get("start_time"));
+    assert_eq!(Some("1000:temperature:0.8"), get("param_schedule"));
+    assert_eq!(Some("0.9"), get("param_temperature"));
+    assert_eq!(Some(""), get("param_units"));
+    assert_eq!(Some("montecarlo"), get("param_output_prefix"));
+}
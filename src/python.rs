@@ -0,0 +1,132 @@
+//! Python bindings of the sampler, built with the python feature
+// the pymethods macro expands to conversions clippy flags as useless
+#![allow(clippy::useless_conversion)]
+use numpy::{PyArray1, PyArray2};
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyIOError, PyValueError};
+use rand::StdRng;
+use system::{Params, System};
+use {Coordinate, Step};
+
+fn new_rng() -> PyResult<StdRng> {
+    return StdRng::new().map_err(|e| PyIOError::new_err(e.to_string()));
+}
+
+/// LJ system that can be sampled step by step from python
+#[pyclass(name = "System")]
+pub struct PySystem {
+    system: System,
+    rng: StdRng,
+}
+
+#[pymethods]
+impl PySystem {
+    #[new]
+    #[pyo3(signature = (num_particles=512, density=0.7, temperature=0.9, cutoff=3.0, displacement=0.1, sweeps=false, tailcorr=true, shift=true, vacuum_slab=0.0))]
+    fn new(num_particles: usize, density: f64, temperature: f64, cutoff: f64, displacement: f64, sweeps: bool,
+           tailcorr: bool, shift: bool, vacuum_slab: f64) -> PyResult<Self> {
+        let params = Params {
+            num_particles: num_particles,
+            density: density,
+            temperature: temperature,
+            cutoff: cutoff,
+            displacement: displacement,
+            sweeps: sweeps,
+            tailcorr: tailcorr,
+            shift: shift,
+            vacuum_slab: vacuum_slab,
+            ..Params::default()
+        };
+        let errors = params.validate();
+        if !errors.is_empty() {
+            return Err(PyValueError::new_err(errors.join("; ")));
+        }
+        let mut rng = new_rng()?;
+        let system = System::new(params, &mut rng);
+        return Ok(PySystem { system: system, rng: rng });
+    }
+
+    /// Loads a system from a json checkpoint of mc
+    #[staticmethod]
+    fn load(filename: String) -> PyResult<Self> {
+        let system = System::load_checkpoint(&filename).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        return Ok(PySystem { system: system, rng: new_rng()? });
+    }
+
+    /// Writes the system as json checkpoint
+    fn save(&self, filename: String) -> PyResult<()> {
+        return self.system.save_checkpoint(&filename).map_err(|e| PyIOError::new_err(e.to_string()));
+    }
+
+    /// Runs steps steps (or sweeps) and adds them to the averages
+    fn run(&mut self, steps: usize) {
+        self.system.run(steps as Step, &mut self.rng);
+    }
+
+    /// Resets acceptance and averages, e.g. after equilibration
+    fn reset_averages(&mut self) {
+        self.system.reset_averages();
+    }
+
+    #[getter]
+    fn energy(&self) -> f64 { self.system.energy }
+
+    #[getter]
+    fn virial(&self) -> f64 { self.system.virial }
+
+    #[getter]
+    fn pressure(&self) -> f64 { self.system.pressure() }
+
+    #[getter]
+    fn steps(&self) -> Step { self.system.counters.step }
+
+    #[getter]
+    fn acceptance(&self) -> f64 {
+        let tries = self.system.counters.step_counter * self.system.moves_per_step();
+        return self.system.counters.accept_counter as f64 / tries as f64;
+    }
+
+    #[getter]
+    fn average_energy(&self) -> f64 {
+        return self.system.counters.energy.mean();
+    }
+
+    #[getter]
+    fn average_pressure(&self) -> f64 {
+        let counters = &self.system.counters;
+        return counters.virial.mean() / 3.0 / self.system.volume + self.system.density * self.system.params.temperature + self.system.p_corr;
+    }
+
+    #[getter]
+    fn displacement(&self) -> f64 { self.system.displacement }
+
+    #[setter]
+    fn set_displacement(&mut self, displacement: f64) {
+        self.system.displacement = displacement.min(self.system.max_displacement);
+    }
+
+    #[getter]
+    fn box_size<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        return PyArray1::from_vec_bound(py, vec![self.system.l_x, self.system.l_y, self.system.l_z]);
+    }
+
+    /// Copy of the particle positions as (num_particles, 3) array
+    #[getter]
+    fn positions<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        let rows : Vec<Vec<f64>> = (0..self.system.num_particles)
+            .map(|i| vec![self.system.rx[i].to_f64(), self.system.ry[i].to_f64(), self.system.rz[i].to_f64()])
+            .collect();
+        return PyArray2::from_vec2_bound(py, &rows).expect("rows have equal length");
+    }
+
+    /// Checkpoint of the complete state as json string
+    fn to_json(&self) -> PyResult<String> {
+        return ::serde_json::to_string(&self.system).map_err(|e| PyValueError::new_err(e.to_string()));
+    }
+}
+
+#[pymodule]
+fn mclj(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySystem>()?;
+    return Ok(());
+}
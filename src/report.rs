@@ -0,0 +1,218 @@
+//! Single file html report of a run
+use std::fs::File;
+use std::io::prelude::*;
+use error::{Error, Result};
+use serde_json::Value;
+use provenance::Provenance;
+use system::Params;
+
+/// Content of a run report, figures are inline svg documents
+#[derive(Default)]
+pub struct Report {
+    pub title: String,
+    pub parameters: Vec<(String, String)>,
+    pub results: Vec<(String, f64, f64)>, // name, mean and error (NaN if unknown)
+    pub diagnostics: Vec<(String, String)>,
+    pub figures: Vec<(String, String)>, // caption and svg
+}
+
+impl Report {
+    /// Report titled title listing all run parameters
+    pub fn new(title: &str, params: &Params) -> Report {
+        let mut parameters = vec![];
+        // the parameter names are the ones of the checkpoints
+        if let Ok(Value::Object(map)) = ::serde_json::to_value(params) {
+            for (name, value) in map {
+                let value = match value { Value::String(s) => s, v => v.to_string() };
+                parameters.push((name, value));
+            }
+        }
+        return Report { title: title.to_string(), parameters: parameters, ..Report::default() };
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>{}</title>\n", escape(&self.title)));
+        html.push_str("<style>body { font-family: sans-serif; max-width: 900px; margin: auto; } \
+                       table { border-collapse: collapse; } td, th { border: 1px solid #ccc; padding: 3px 10px; text-align: left; } \
+                       td.number { text-align: right; font-family: monospace; }</style>\n</head>\n<body>\n");
+        html.push_str(&format!("<h1>{}</h1>\n", escape(&self.title)));
+
+        html.push_str("<h2>Results</h2>\n<table>\n<tr><th>Observable</th><th>Average</th><th>Error</th></tr>\n");
+        for &(ref name, mean, error) in &self.results {
+            let error = if error.is_finite() { format!("{:.6}", error) } else { "-".to_string() };
+            html.push_str(&format!("<tr><td>{}</td><td class=\"number\">{:.6}</td><td class=\"number\">{}</td></tr>\n", escape(name), mean, error));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Convergence diagnostics</h2>\n");
+        html.push_str(&table(&self.diagnostics));
+        for (caption, svg) in &self.figures {
+            html.push_str(&format!("<figure>\n{}\n<figcaption>{}</figcaption>\n</figure>\n", svg, escape(caption)));
+        }
+
+        html.push_str("<h2>Parameters</h2>\n");
+        html.push_str(&table(&self.parameters));
+        html.push_str("</body>\n</html>\n");
+        return html;
+    }
+
+    pub fn write(&self, filename: &String) -> Result<()> {
+        let mut file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        return Ok(file.write_all(self.to_html().as_bytes())?);
+    }
+}
+
+// two column table of names and values
+fn table(rows: &[(String, String)]) -> String {
+    let mut html = String::from("<table>\n");
+    for (name, value) in rows {
+        html.push_str(&format!("<tr><td>{}</td><td class=\"number\">{}</td></tr>\n", escape(name), escape(value)));
+    }
+    html.push_str("</table>\n");
+    return html;
+}
+
+fn escape(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+}
+
+#[test]
+fn test_report() {
+    let mut report = Report::new("Run <1>", &Params::default());
+    assert!(report.parameters.contains(&("num_particles".to_string(), "512".to_string())));
+    assert!(report.parameters.contains(&("output_prefix".to_string(), "montecarlo".to_string())));
+
+    report.results.push(("Energy".to_string(), -4.5, 0.01));
+    report.results.push(("Acceptance".to_string(), 0.33, f64::NAN));
+    report.figures.push(("Energy trace".to_string(), "<svg></svg>".to_string()));
+    let html = report.to_html();
+    assert!(html.contains("<title>Run &lt;1&gt;</title>"));
+    assert!(html.contains("<td>Energy</td><td class=\"number\">-4.500000</td><td class=\"number\">0.010000</td>"), "{}", html);
+    assert!(html.contains("<td class=\"number\">-</td>"));
+    assert!(html.contains("<svg></svg>\n<figcaption>Energy trace</figcaption>"));
+}
+
+/// Runs an observable of the results is meaningful for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    All,
+    Bulk, // homogeneous systems, e.g. the scalar pressure
+    Slab, // slabs with two interfaces (vacuum_slab > 0), e.g. the surface tension
+}
+
+/// Registry of the averaged observables of a run, the results block and the report show the ones of the run
+#[derive(Default)]
+pub struct Results {
+    pub observables: Vec<(String, f64, f64, Scope)>, // name, mean, error (NaN if unknown) and scope
+}
+
+impl Results {
+    pub fn add(&mut self, name: &str, mean: f64, error: f64, scope: Scope) {
+        self.observables.push((name.to_string(), mean, error, scope));
+    }
+
+    /// Observables of a slab or a bulk run, the ones that were not sampled (NaN mean) left out
+    pub fn select(&self, slab: bool) -> Vec<(String, f64, f64)> {
+        return self.observables.iter()
+            .filter(|o| o.1.is_finite() && (o.3 == Scope::All || o.3 == if slab { Scope::Slab } else { Scope::Bulk }))
+            .map(|o| (o.0.clone(), o.1, o.2)).collect();
+    }
+
+    /// Lines "name: mean +/- error" of the selected observables
+    pub fn to_text(&self, slab: bool) -> String {
+        return self.select(slab).iter().map(|&(ref name, mean, error)| {
+            if error.is_finite() { format!("{}: {} +/- {:.3e}", name, mean, error) } else { format!("{}: {}", name, mean) }
+        }).collect::<Vec<String>>().join("\n");
+    }
+}
+
+#[test]
+fn test_results() {
+    let mut results = Results::default();
+    results.add("Energy", -4.5, 0.01, Scope::All);
+    results.add("Pressure", 0.2, f64::NAN, Scope::Bulk);
+    results.add("Surface tension", 0.5, 0.1, Scope::Slab);
+    results.add("Unsampled", f64::NAN, f64::NAN, Scope::All);
+    assert_eq!(vec!["Energy", "Pressure"], results.select(false).iter().map(|o| o.0.as_str()).collect::<Vec<&str>>());
+    assert_eq!(vec!["Energy", "Surface tension"], results.select(true).iter().map(|o| o.0.as_str()).collect::<Vec<&str>>());
+    assert_eq!("Energy: -4.5 +/- 1.000e-2\nPressure: 0.2", results.to_text(false));
+}
+
+/// Averages of a run (name, mean and error if known) with its parameters, written to <output>.results.json for compare
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RunResults {
+    pub params: Params,
+    pub observables: Vec<(String, f64, Option<f64>)>,
+    // the start of the run and its restarts
+    #[serde(default)]
+    pub provenance: Vec<Provenance>,
+}
+
+impl RunResults {
+    pub fn new(params: &Params, observables: &[(String, f64, f64)]) -> RunResults {
+        let observables = observables.iter().map(|&(ref name, mean, error)| (name.clone(), mean, if error.is_finite() { Some(error) } else { None })).collect();
+        return RunResults { params: params.clone(), observables: observables, provenance: vec![] };
+    }
+
+    pub fn save(&self, filename: &String) -> Result<()> {
+        let mut file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let json = ::serde_json::to_string_pretty(self).map_err(|e| Error::Serialization(e.to_string()))?;
+        return Ok(file.write_all(json.as_bytes())?);
+    }
+
+    pub fn load(filename: &String) -> Result<RunResults> {
+        let mut file = match File::open(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let mut json = String::new();
+        file.read_to_string(&mut json)?;
+        return ::serde_json::from_str(&json).map_err(|e| Error::Serialization(e.to_string()));
+    }
+
+    /// Parameters of both runs that differ: name and the values of this and the other run
+    pub fn get_parameter_differences(&self, other: &RunResults) -> Vec<(String, String, String)> {
+        let to_map = |params: &Params| match ::serde_json::to_value(params) { Ok(Value::Object(map)) => map, _ => ::serde_json::Map::new() };
+        let (first, second) = (to_map(&self.params), to_map(&other.params));
+        return first.iter().filter(|&(name, value)| second.get(name) != Some(value))
+            .map(|(name, value)| (name.clone(), value.to_string(), second.get(name).map_or("-".to_string(), |v| v.to_string()))).collect();
+    }
+
+    /// Observables of both runs: name, the means of this and the other run and their difference in combined errors, NaN
+    /// without errors
+    pub fn compare(&self, other: &RunResults) -> Vec<(String, f64, f64, f64)> {
+        let mut comparison = vec![];
+        for &(ref name, mean, error) in &self.observables {
+            if let Some(&(_, other_mean, other_error)) = other.observables.iter().find(|o| o.0 == *name) {
+                let combined = match (error, other_error) { (Some(a), Some(b)) => (a * a + b * b).sqrt(), _ => f64::NAN };
+                comparison.push((name.clone(), mean, other_mean, (other_mean - mean) / combined));
+            }
+        }
+        return comparison;
+    }
+}
+
+#[test]
+fn test_run_results() {
+    let first = RunResults::new(&Params::default(), &[("Energy".to_string(), -4.5, 0.01), ("Pressure".to_string(), 0.2, 0.02), ("Acceptance".to_string(), 0.33, f64::NAN)]);
+    let second = RunResults::new(&Params { temperature: 1.0, ..Params::default() }, &[("Energy".to_string(), -4.4, 0.01), ("Pressure".to_string(), 0.2, 0.02)]);
+    assert_eq!(vec![("temperature".to_string(), "0.9".to_string(), "1.0".to_string())], first.get_parameter_differences(&second));
+
+    let comparison = first.compare(&second);
+    assert_eq!(2, comparison.len());
+    assert!( (comparison[0].3 - 0.1 / 0.0002f64.sqrt()).abs() < 1e-9, "{:?}", comparison);
+    assert_eq!(0.0, comparison[1].3);
+
+    let filename = ::std::env::temp_dir().join("mclj_test_results.json").to_str().unwrap().to_string();
+    first.save(&filename).unwrap();
+    assert_eq!(first, RunResults::load(&filename).unwrap());
+    ::std::fs::remove_file(&filename).unwrap();
+}
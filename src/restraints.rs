@@ -0,0 +1,139 @@
+//! Restraints: harmonic tethers of particles to points, distance restraints of pairs and a flat-bottom restraint of the slab center
+
+use std::f64::consts::PI;
+use energy::get_displacement_with_pbc;
+use {Coordinate, Real};
+
+/// One restraint energy term
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Restraint {
+    /// spring/2 |r - point|² of particle index
+    Tether { index: usize, point: [f64; 3], spring: f64 },
+    /// spring/2 (|r_first - r_second| - length)²
+    Distance { first: usize, second: usize, length: f64, spring: f64 },
+    /// spring/2 (|z_c - l_z/2| - width)² beyond width of the center z_c of all particles (circular mean along z)
+    Slab { width: f64, spring: f64 },
+}
+
+impl Restraint {
+    /// Parses a ; separated list of tether:index:x,y,z:spring, distance:first:second:length:spring and
+    /// slab:width:spring, empty for no restraints. Particle indices have to be below num_particles.
+    pub fn parse_list(list: &str, num_particles: usize) -> Option<Vec<Restraint>> {
+        let mut restraints = vec![];
+        for spec in list.split(';').map(|spec| spec.trim()).filter(|spec| !spec.is_empty()) {
+            let fields : Vec<&str> = spec.split(':').collect();
+            let number = |k: usize| fields.get(k).and_then(|f| f.parse::<f64>().ok()).filter(|v| v.is_finite() && *v >= 0.0);
+            let index = |k: usize| fields.get(k).and_then(|f| f.parse::<usize>().ok()).filter(|&i| i < num_particles);
+            restraints.push(match (fields[0], fields.len()) {
+                ("tether", 4) => {
+                    let point = fields[2].split(',').map(|c| c.parse::<f64>().ok()).collect::<Option<Vec<f64>>>().filter(|p| p.len() == 3)?;
+                    Restraint::Tether { index: index(1)?, point: [point[0], point[1], point[2]], spring: number(3)? }
+                },
+                ("distance", 5) => Restraint::Distance { first: index(1)?, second: index(2)?, length: number(3)?, spring: number(4)? },
+                ("slab", 3) => Restraint::Slab { width: number(1)?, spring: number(2)? },
+                _ => return None,
+            });
+        }
+        return Some(restraints);
+    }
+}
+
+#[test]
+fn test_parse_restraints() {
+    assert_eq!(Some(vec![Restraint::Tether { index: 3, point: [1.0, 2.0, 3.5], spring: 10.0 }, Restraint::Slab { width: 0.5, spring: 2.0 }]),
+               Restraint::parse_list("tether:3:1,2,3.5:10; slab:0.5:2", 10));
+    assert_eq!(Some(vec![Restraint::Distance { first: 1, second: 2, length: 1.5, spring: 20.0 }]), Restraint::parse_list("distance:1:2:1.5:20", 10));
+    assert_eq!(Some(vec![]), Restraint::parse_list("", 10));
+    assert_eq!(None, Restraint::parse_list("tether:12:1,2,3:10", 10));
+    assert_eq!(None, Restraint::parse_list("slab:0.5:-2", 10));
+    assert_eq!(None, Restraint::parse_list("wall:1", 10));
+}
+
+/// Restraints of a system with their current energy and the sums of cos and sin of the phases 2 pi z / l_z of all
+/// particles, the circular mean of the slab center
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Restraints {
+    pub restraints: Vec<Restraint>,
+    pub energy: f64,
+    pub phase_sums: [f64; 2],
+}
+
+fn get_phase(z: f64, l_z: f64) -> [f64; 2] {
+    let phase = 2.0 * PI * z / l_z;
+    return [phase.cos(), phase.sin()];
+}
+
+impl Restraints {
+    pub fn new(restraints: Vec<Restraint>, rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64) -> Restraints {
+        let phase_sums = Restraints::get_phase_sums(rz, num_particles, l_z);
+        let mut restraints = Restraints { restraints: restraints, energy: 0.0, phase_sums: phase_sums };
+        restraints.energy = restraints.get_energy(rx, ry, rz, l_x, l_y, l_z, phase_sums);
+        return restraints;
+    }
+
+    /// Sums of cos and sin of the phases of all particles from scratch
+    pub fn get_phase_sums(rz: &[Real], num_particles: usize, l_z: f64) -> [f64; 2] {
+        let mut phase_sums = [0.0; 2];
+        for z in rz.iter().take(num_particles) {
+            let phase = get_phase(z.to_f64(), l_z);
+            phase_sums[0] += phase[0];
+            phase_sums[1] += phase[1];
+        }
+        return phase_sums;
+    }
+
+    /// Center of the slab along z from the sums of the phases
+    pub fn get_slab_center(phase_sums: [f64; 2], l_z: f64) -> f64 {
+        return (phase_sums[1].atan2(phase_sums[0]) / (2.0 * PI) * l_z + l_z) % l_z;
+    }
+
+    /// Energy of all restraints for the positions and the phase sums of a configuration
+    pub fn get_energy(&self, rx: &[Real], ry: &[Real], rz: &[Real], l_x: f64, l_y: f64, l_z: f64, phase_sums: [f64; 2]) -> f64 {
+        let displacement = |i: usize, p: [f64; 3]| [get_displacement_with_pbc(rx[i].to_f64(), p[0], l_x, l_x / 2.0),
+                                                    get_displacement_with_pbc(ry[i].to_f64(), p[1], l_y, l_y / 2.0),
+                                                    get_displacement_with_pbc(rz[i].to_f64(), p[2], l_z, l_z / 2.0)];
+        let mut energy = 0.0;
+        for restraint in &self.restraints {
+            energy += match *restraint {
+                Restraint::Tether { index, point, spring } => 0.5 * spring * displacement(index, point).iter().map(|d| d * d).sum::<f64>(),
+                Restraint::Distance { first, second, length, spring } => {
+                    let d = displacement(first, [rx[second].to_f64(), ry[second].to_f64(), rz[second].to_f64()]);
+                    0.5 * spring * ((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() - length).powi(2)
+                },
+                Restraint::Slab { width, spring } => {
+                    let offset = ((Restraints::get_slab_center(phase_sums, l_z) - l_z / 2.0).abs() - width).max(0.0);
+                    0.5 * spring * offset * offset
+                },
+            };
+        }
+        return energy;
+    }
+
+    /// Energy and phase sums after particle index moved from old_z to the z of rz, the other coordinates only enter the energy
+    pub fn get_move_energy(&self, rx: &[Real], ry: &[Real], rz: &[Real], index: usize, old_z: f64, l_x: f64, l_y: f64, l_z: f64) -> (f64, [f64; 2]) {
+        let (old, new) = (get_phase(old_z, l_z), get_phase(rz[index].to_f64(), l_z));
+        let phase_sums = [self.phase_sums[0] - old[0] + new[0], self.phase_sums[1] - old[1] + new[1]];
+        return (self.get_energy(rx, ry, rz, l_x, l_y, l_z, phase_sums), phase_sums);
+    }
+}
+
+#[test]
+fn test_restraints() {
+    let (mut rx, ry, mut rz) = (vec![1.0, 2.0, 9.5], vec![1.0, 1.0, 1.0], vec![4.0, 5.0, 6.0]);
+    let list = Restraint::parse_list("tether:0:9.5,1,4:2;distance:0:1:0.5:4;slab:0.2:1", 3).unwrap();
+    let restraints = Restraints::new(list, &rx, &ry, &rz, 3, 10.0, 10.0, 10.0);
+    // tether 1.5 across the boundary, distance sqrt 2 of length 0.5, slab centered at 5
+    assert!( (Restraints::get_slab_center(restraints.phase_sums, 10.0) - 5.0).abs() < 0.00001);
+    assert!( (restraints.energy - (2.25 + 2.0 * (2f64.sqrt() - 0.5).powi(2))).abs() < 0.00001, "{}", restraints.energy);
+
+    // moving one particle from 6 to 8 pulls the center off by more than the width
+    rz[2] = 8.0;
+    let (energy, phase_sums) = restraints.get_move_energy(&rx, &ry, &rz, 2, 6.0, 10.0, 10.0, 10.0);
+    let moved = Restraints::new(restraints.restraints.clone(), &rx, &ry, &rz, 3, 10.0, 10.0, 10.0);
+    assert!( (energy - moved.energy).abs() < 0.00001 && energy > restraints.energy + 0.0001, "{} {}", energy, moved.energy);
+    assert!( (phase_sums[0] - moved.phase_sums[0]).abs() < 0.00001);
+    // the same pair distance at the tether point
+    rx[0] = 9.5;
+    rx[1] = 0.5;
+    assert!(Restraints::new(restraints.restraints.clone(), &rx, &ry, &rz, 3, 10.0, 10.0, 10.0).energy < moved.energy);
+}
@@ -0,0 +1,79 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store, StoreFalse};
+use mclj::error::exit_with;
+use mclj::reweighting::{get_reweighted_average, Potential};
+use mclj::statistics::get_mean;
+use mclj::trajectory::*;
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut skip : usize = 0;
+    let mut eps : f64 = 0.0;
+    let mut sig : f64 = 0.0;
+    let mut cutoff : f64 = 0.0;
+    let mut shift = true;
+    let mut tailcorr = true;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Energy and pressure of a modified lj potential from the frames of a trajectory by perturbation reweighting, without a new run.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Trajectory");
+        ap.refer(&mut skip)
+            .add_option(&["-s", "--skip"], Store, "Skip the first frames, e.g. the equilibration");
+        ap.refer(&mut eps)
+            .add_option(&["--eps"], Store, "Well depth of the modified potential (0=the one of the run)");
+        ap.refer(&mut sig)
+            .add_option(&["--sig"], Store, "Diameter of the modified potential (0=the one of the run)");
+        ap.refer(&mut cutoff)
+            .add_option(&["--cutoff"], Store, "Cutoff of the modified potential (0=the one of the run)");
+        ap.refer(&mut shift)
+            .add_option(&["--noshift"], StoreFalse, "The run did not shift the potential, neither does the modified one");
+        ap.refer(&mut tailcorr)
+            .add_option(&["--notailcorr"], StoreFalse, "The run had no tail corrections, neither has the modified one");
+        ap.parse_args_or_exit();
+    }
+    if eps.is_nan() || eps < 0.0 || sig.is_nan() || sig < 0.0 || cutoff.is_nan() || cutoff < 0.0 {
+        eprintln!("Error: eps, sig and cutoff can not be negative.");
+        std::process::exit(1);
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { reader.skip(skip).unwrap_or_else(exit_with); }
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+    let reference = Potential { eps: frame.lj_eps, sig: frame.lj_sig, cutoff: frame.lj_cutoff, shift: shift, tailcorr: tailcorr };
+    let modified = Potential { eps: if eps > 0.0 { eps } else { frame.lj_eps }, sig: if sig > 0.0 { sig } else { frame.lj_sig }, cutoff: if cutoff > 0.0 { cutoff } else { frame.lj_cutoff }, ..reference.clone() };
+    if modified.cutoff > frame.box_x.min(frame.box_y).min(frame.box_z) / 2.0 {
+        eprintln!("Error: cutoff {} is larger than half the box.", modified.cutoff);
+        std::process::exit(1);
+    }
+
+    // energies per particle and pressures of both potentials in every frame
+    let (mut energies, mut pressures, mut new_energies, mut new_pressures, mut d_u) = (vec![], vec![], vec![], vec![], vec![]);
+    loop {
+        let n = frame.num_particles;
+        let (energy, pressure) = reference.get_energy_and_pressure(&frame.rx, &frame.ry, &frame.rz, n, frame.box_x, frame.box_y, frame.box_z, frame.temperature);
+        let (new_energy, new_pressure) = modified.get_energy_and_pressure(&frame.rx, &frame.ry, &frame.rz, n, frame.box_x, frame.box_y, frame.box_z, frame.temperature);
+        energies.push(energy / n as f64);
+        pressures.push(pressure);
+        new_energies.push(new_energy / n as f64);
+        new_pressures.push(new_pressure);
+        d_u.push(new_energy - energy);
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+
+    let (new_energy, samples) = get_reweighted_average(&new_energies, &d_u, frame.temperature);
+    let (new_pressure, _) = get_reweighted_average(&new_pressures, &d_u, frame.temperature);
+    println!("# {} frames, T = {}", energies.len(), frame.temperature);
+    println!("# potential    eps    sig    cutoff    E/N    P");
+    println!("sampled\t{}\t{}\t{}\t{:.5}\t{:.5}", reference.eps, reference.sig, reference.cutoff, get_mean(&energies), get_mean(&pressures));
+    println!("reweighted\t{}\t{}\t{}\t{:.5}\t{:.5}", modified.eps, modified.sig, modified.cutoff, new_energy, new_pressure);
+    println!("# effective frames of the reweighting: {:.1}", samples);
+    if samples < 0.1 * energies.len() as f64 {
+        eprintln!("Warning: only {:.1} of {} frames carry the reweighted averages, the modified potential is too far from the sampled one.", samples, energies.len());
+    }
+}
@@ -0,0 +1,76 @@
+//! Perturbation reweighting: energies of stored configurations under a modified lj potential and the averages of the
+//! modified potential from the samples of the original one
+
+use energy::{eval_pair_energy, get_total_energy};
+use system::get_tail_corrections;
+use {Coordinate, Real};
+
+/// Lj potential of well depth eps and diameter sig, cut at cutoff and optionally shifted and tail corrected
+#[derive(Clone, Debug, PartialEq)]
+pub struct Potential {
+    pub eps: f64,
+    pub sig: f64,
+    pub cutoff: f64,
+    pub shift: bool,
+    pub tailcorr: bool,
+}
+
+impl Potential {
+    /// Energy and pressure of a configuration at temperature. The potential is the built-in one of the scaled
+    /// coordinates r sig_lj / sig times eps / eps_lj.
+    pub fn get_energy_and_pressure(&self, rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, temperature: f64) -> (f64, f64) {
+        let scale = ::LJ_SIG / self.sig;
+        let factor = self.eps / ::LJ_EPS;
+        let scaled = |r: &[Real]| -> Vec<Real> { r.iter().take(num_particles).map(|x| Real::from_f64(x.to_f64() * scale)).collect() };
+        let (cutoff, volume) = (self.cutoff * scale, l_x * l_y * l_z);
+        let e_shift = if self.shift { eval_pair_energy(cutoff * cutoff, 0.0).0 } else { 0.0 };
+        let (e_corr, p_corr) = if self.tailcorr { get_tail_corrections(cutoff, num_particles as f64 / (volume * scale.powi(3)), 0.0) } else { (0.0, 0.0) };
+        let (energy, virial) = get_total_energy(&scaled(rx), &scaled(ry), &scaled(rz), num_particles, l_x * scale, l_y * scale, l_z * scale, cutoff * cutoff, e_corr, e_shift, 0.0, 0.0);
+        let pressure = num_particles as f64 / volume * temperature + factor * virial / 3.0 / volume + factor * scale.powi(3) * p_corr;
+        return (factor * energy, pressure);
+    }
+}
+
+#[test]
+fn test_potential() {
+    let (rx, ry, rz) = (vec![1.0, 2.1, 1.0, 4.0], vec![1.0, 1.0, 2.2, 4.0], vec![1.0, 1.0, 1.0, 4.5]);
+    let reference = Potential { eps: ::LJ_EPS, sig: ::LJ_SIG, cutoff: 2.5, shift: true, tailcorr: false };
+    let (energy, _) = reference.get_energy_and_pressure(&rx, &ry, &rz, 4, 6.0, 6.0, 6.0, 1.0);
+    let e_shift = eval_pair_energy(6.25, 0.0).0;
+    assert!( (energy - get_total_energy(&rx, &ry, &rz, 4, 6.0, 6.0, 6.0, 6.25, 0.0, e_shift, 0.0, 0.0).0).abs() < 1e-12);
+
+    // twice the well depth doubles the energy and the excess pressure
+    let (energy_2, pressure_2) = Potential { eps: 2.0, ..reference.clone() }.get_energy_and_pressure(&rx, &ry, &rz, 4, 6.0, 6.0, 6.0, 0.0);
+    let (_, pressure) = reference.get_energy_and_pressure(&rx, &ry, &rz, 4, 6.0, 6.0, 6.0, 0.0);
+    assert!( (energy_2 - 2.0 * energy).abs() < 1e-12 && (pressure_2 - 2.0 * pressure).abs() < 1e-12);
+
+    // a larger diameter with the same reduced cutoff sees the scaled configuration like the reference the original one
+    let scale = |r: &[Real]| -> Vec<Real> { r.iter().map(|x| Real::from_f64(x.to_f64() * 1.1)).collect() };
+    let with_tails = Potential { tailcorr: true, ..reference.clone() };
+    let (energy_s, pressure_s) = Potential { sig: 1.1, cutoff: 2.75, ..with_tails.clone() }.get_energy_and_pressure(&scale(&rx), &scale(&ry), &scale(&rz), 4, 6.6, 6.6, 6.6, 1.0);
+    let (energy, pressure) = with_tails.get_energy_and_pressure(&rx, &ry, &rz, 4, 6.0, 6.0, 6.0, 1.0);
+    assert!( (energy_s - energy).abs() < 1e-9 && (pressure_s * 1.1f64.powi(3) - pressure).abs() < 1e-9, "{} {} {} {}", energy_s, energy, pressure_s, pressure);
+}
+
+/// Average of values sampled with energies u under the potential with energies u + d_u at temperature, weighted by
+/// exp(-d_u / T), and the Kish effective number of samples (sum w)² / sum w². Few effective samples mean the modified
+/// potential is too far from the sampled one.
+pub fn get_reweighted_average(values: &[f64], d_u: &[f64], temperature: f64) -> (f64, f64) {
+    // relative to the smallest difference, the weights do not overflow
+    let min = d_u.iter().cloned().fold(f64::INFINITY, f64::min);
+    let weights : Vec<f64> = d_u.iter().map(|d| (-(d - min) / temperature).exp()).collect();
+    let sum : f64 = weights.iter().sum();
+    let average = values.iter().zip(&weights).map(|(v, w)| v * w).sum::<f64>() / sum;
+    return (average, sum * sum / weights.iter().map(|w| w * w).sum::<f64>());
+}
+
+#[test]
+fn test_get_reweighted_average() {
+    let (average, samples) = get_reweighted_average(&[1.0, 2.0, 3.0], &[0.5, 0.5, 0.5], 1.0);
+    assert!( (average - 2.0).abs() < 1e-12 && (samples - 3.0).abs() < 1e-12);
+    // a difference of T ln 3 against the last value
+    let (average, samples) = get_reweighted_average(&[1.0, 3.0], &[0.0, 3f64.ln()], 1.0);
+    assert!( (average - 1.5).abs() < 1e-12 && (samples - 1.6).abs() < 1e-12, "{} {}", average, samples);
+    // no overflow for large differences
+    assert!(get_reweighted_average(&[1.0, 2.0], &[-5000.0, -5001.0], 0.5).0.is_finite());
+}
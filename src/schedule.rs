@@ -0,0 +1,134 @@
+//! Scheduled parameter changes of staged protocols, e.g. a quench to a lower temperature in the middle of the run,
+//! and protocol files of named stages that become a schedule
+
+use std::fmt;
+use system::System;
+use Step;
+
+/// A parameter change of the running system
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Temperature(f64),
+    /// scales box and positions uniformly
+    Density(f64),
+    /// opens a vacuum slab of this size relative to the system along z
+    Vacuum(f64),
+}
+
+impl Change {
+    /// Applies the change, beta, tail corrections, energy and virial follow
+    pub fn apply(&self, system: &mut System) -> Result<(), String> {
+        match *self {
+            Change::Temperature(temperature) => system.set_temperature(temperature),
+            Change::Density(density) => system.set_density(density)?,
+            Change::Vacuum(vacuum) => system.open_vacuum(vacuum)?,
+        }
+        return Ok(());
+    }
+
+    /// Entry of the change at step in a schedule, see parse_schedule
+    pub fn to_spec(&self, step: Step) -> String {
+        return match *self {
+            Change::Temperature(temperature) => format!("{}:temperature:{}", step, temperature),
+            Change::Density(density) => format!("{}:density:{}", step, density),
+            Change::Vacuum(vacuum) => format!("{}:vacuum:{}", step, vacuum),
+        };
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            Change::Temperature(temperature) => write!(f, "temperature {}", temperature),
+            Change::Density(density) => write!(f, "density {}", density),
+            Change::Vacuum(vacuum) => write!(f, "vacuum slab {}", vacuum),
+        };
+    }
+}
+
+/// Parses a ; separated list of step:temperature:value, step:density:value and step:vacuum:value, sorted by step,
+/// empty for no changes
+pub fn parse_schedule(list: &str) -> Option<Vec<(Step, Change)>> {
+    let mut schedule = vec![];
+    for spec in list.split(';').map(|spec| spec.trim()).filter(|spec| !spec.is_empty()) {
+        let fields : Vec<&str> = spec.split(':').map(|field| field.trim()).collect();
+        if fields.len() != 3 { return None; }
+        let step = fields[0].parse::<Step>().ok()?;
+        let value = fields[2].parse::<f64>().ok().filter(|v| v.is_finite() && *v > 0.0)?;
+        schedule.push((step, match fields[1] {
+            "temperature" | "T" => Change::Temperature(value),
+            "density" | "rho" => Change::Density(value),
+            "vacuum" => Change::Vacuum(value),
+            _ => return None,
+        }));
+    }
+    schedule.sort_by_key(|&(step, _)| step);
+    return Some(schedule);
+}
+
+#[test]
+fn test_parse_schedule() {
+    assert_eq!(Some(vec![(1000, Change::Density(0.7)), (2000, Change::Temperature(0.8))]), parse_schedule("2000:temperature:0.8; 1000:rho:0.7"));
+    assert_eq!(Some(vec![]), parse_schedule(""));
+    assert_eq!(None, parse_schedule("2000:temperature:-0.8"));
+    assert_eq!(None, parse_schedule("2000:pressure:1.0"));
+    assert_eq!(None, parse_schedule("temperature:0.8"));
+    // steps beyond 32 bits, the counters are 64 bits on every target
+    assert_eq!(Some(vec![(5000000000, Change::Density(0.7))]), parse_schedule("5000000000:density:0.7"));
+    assert_eq!("temperature 0.8", Change::Temperature(0.8).to_string());
+    assert_eq!(Some(vec![(5, Change::Vacuum(2.0))]), parse_schedule(&Change::Vacuum(2.0).to_spec(5)));
+}
+
+/// Named stage of a protocol: its length in steps and the changes at its start
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub steps: Step,
+    pub changes: Vec<Change>,
+}
+
+/// Parses a protocol file: one stage per line as name, steps and changes like temperature:0.7, density:0.8 or
+/// vacuum:2, with # comments. The stages run one after the other, the last is the sampling.
+pub fn parse_protocol(text: &str) -> Result<Vec<Stage>, String> {
+    let mut stages = vec![];
+    for (k, line) in text.lines().enumerate() {
+        let fields : Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+        if fields.is_empty() { continue; }
+        let steps = fields.get(1).and_then(|steps| steps.parse::<Step>().ok()).filter(|&steps| steps > 0)
+            .ok_or_else(|| format!("line {}: expected a name and a positive number of steps", k + 1))?;
+        let mut changes = vec![];
+        for field in &fields[2..] {
+            match parse_schedule(&format!("0:{}", field)) {
+                Some(ref schedule) if schedule.len() == 1 => changes.push(schedule[0].1),
+                _ => return Err(format!("line {}: invalid change {}, expected temperature:value, density:value or vacuum:value", k + 1, field)),
+            }
+        }
+        stages.push(Stage { name: fields[0].to_string(), steps: steps, changes: changes });
+    }
+    if stages.is_empty() { return Err("no stages".to_string()); }
+    return Ok(stages);
+}
+
+/// Steps at which the stages start
+pub fn get_stage_starts(stages: &[Stage]) -> Vec<Step> {
+    return stages.iter().scan(0, |start, stage| { let first = *start; *start += stage.steps; Some(first) }).collect();
+}
+
+/// Schedule of the changes of the stages, see parse_schedule
+pub fn get_protocol_schedule(stages: &[Stage]) -> String {
+    let starts = get_stage_starts(stages);
+    let specs : Vec<String> = stages.iter().zip(starts).flat_map(|(stage, start)| stage.changes.iter().map(move |change| change.to_spec(start))).collect();
+    return specs.join(";");
+}
+
+#[test]
+fn test_parse_protocol() {
+    let stages = parse_protocol("# melt, cool, open a slab and sample\nmelt 1000 temperature:2.0\ncool 500 T:0.7 # quench\n\nslab 500 vacuum:2\nsample 2000\n").unwrap();
+    assert_eq!(4, stages.len());
+    assert_eq!(Stage { name: "cool".to_string(), steps: 500, changes: vec![Change::Temperature(0.7)] }, stages[1]);
+    assert_eq!(vec![0, 1000, 1500, 2000], get_stage_starts(&stages));
+    assert_eq!("0:temperature:2;1000:temperature:0.7;1500:vacuum:2", get_protocol_schedule(&stages));
+    assert!(parse_protocol("melt temperature:2.0").is_err());
+    assert!(parse_protocol("melt 1000 pressure:2.0").unwrap_err().starts_with("line 1"));
+    assert!(parse_protocol("# nothing").is_err());
+}
@@ -0,0 +1,330 @@
+//! Minimal http control server and interactive console to monitor and steer a running simulation
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+use error::Result;
+use serde_json;
+use system::parse_output_interval;
+use trajectory::Frame;
+use Step;
+
+/// Observables published by the running simulation
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    pub step: Step,
+    pub total_steps: Step,
+    pub sampling: bool,
+    pub energy: f64,
+    pub virial: f64,
+    pub pressure: f64,
+    pub acceptance: f64,
+    pub displacement: f64,
+    /// Steps between the trajectory frames, None (null) for the last frame only
+    pub output_interval: Option<Step>,
+    pub paused: bool,
+}
+
+/// State shared between the simulation and the server threads
+#[derive(Debug, Default)]
+pub struct Control {
+    pub status: Status,
+    pub frame: Option<Frame>,
+    pub paused: bool,
+    pub stop: bool,
+    pub output_interval: Option<Step>,
+}
+
+/// Runtime metrics of a run for monitoring
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    pub steps: Step,
+    pub steps_per_second: f64,
+    pub acceptance: f64,
+    pub energy: f64,
+    pub pressure: f64,
+    pub displacement: f64,
+    pub frames_written: usize,
+}
+
+/// Metrics in the prometheus text exposition format, labeled with the run name
+pub fn format_metrics(metrics: &Metrics, run: &str) -> String {
+    // label values escape backslashes, double quotes and newlines
+    let run = run.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+    let values : [(&str, &str, &str, f64); 7] = [
+        ("mclj_steps_total", "counter", "Performed simulation steps", metrics.steps as f64),
+        ("mclj_steps_per_second", "gauge", "Simulation steps per second", metrics.steps_per_second),
+        ("mclj_acceptance_ratio", "gauge", "Acceptance ratio since the last displacement update", metrics.acceptance),
+        ("mclj_energy", "gauge", "Current total energy", metrics.energy),
+        ("mclj_pressure", "gauge", "Current pressure", metrics.pressure),
+        ("mclj_displacement", "gauge", "Current maximum displacement", metrics.displacement),
+        ("mclj_frames_written_total", "counter", "Frames written to the trajectory", metrics.frames_written as f64),
+    ];
+    let mut text = String::new();
+    for &(name, kind, help, value) in values.iter() {
+        // prometheus spells nan as NaN
+        let value = if value.is_nan() { "NaN".to_string() } else { value.to_string() };
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{}{{run=\"{}\"}} {}\n", name, help, name, kind, name, run, value));
+    }
+    return text;
+}
+
+/// Serves GET /metrics in a background thread, returns the bound address
+pub fn serve_metrics(address: &str, metrics: Arc<Mutex<Metrics>>, run: String) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = set_request_timeout(&stream).and_then(|_| read_request(&stream)).and_then(|(method, path)| {
+                if method == "GET" && path == "/metrics" {
+                    let text = format_metrics(&metrics.lock().unwrap(), &run);
+                    write_response(stream, 200, "text/plain; version=0.0.4", &text)
+                } else {
+                    write_response(stream, 404, "text/plain", "only GET /metrics\n")
+                }
+            });
+        }
+    });
+    return Ok(local_address);
+}
+
+/// Answers a request with status code and json body
+pub fn handle_request(method: &str, path: &str, control: &Mutex<Control>) -> (u16, String) {
+    let mut control = control.lock().unwrap();
+    let parts : Vec<&str> = path.trim_matches('/').split('/').collect();
+    return match (method, parts.as_slice()) {
+        ("GET", ["status"]) => (200, serde_json::to_string(&control.status).unwrap()),
+        ("GET", ["frame"]) => match control.frame {
+            Some(ref frame) => (200, serde_json::to_string(frame).unwrap()),
+            None => (404, error_json("no frame available yet")),
+        },
+        ("POST", ["pause"]) => { control.paused = true; (200, "{}".to_string()) },
+        ("POST", ["resume"]) => { control.paused = false; (200, "{}".to_string()) },
+        ("POST", ["stop"]) => { control.stop = true; control.paused = false; (200, "{}".to_string()) },
+        ("POST", ["output_interval", value]) => match parse_output_interval(value) {
+            Ok(interval) => { control.output_interval = interval; (200, "{}".to_string()) },
+            Err(message) => (400, error_json(&message)),
+        },
+        (_, ["status"]) | (_, ["frame"]) | (_, ["pause"]) | (_, ["resume"]) | (_, ["stop"]) | (_, ["output_interval", _]) =>
+            (405, error_json("method not allowed")),
+        _ => (404, error_json("unknown endpoint")),
+    };
+}
+
+fn error_json(message: &str) -> String {
+    return format!("{{\"error\": \"{}\"}}", message);
+}
+
+/// Time a client of the servers gets to send its request (and to take the response), so an idle or stalled
+/// connection cannot block the requests of the others
+pub const REQUEST_TIMEOUT : Duration = Duration::from_secs(2);
+
+/// Limits the reads and writes of a connection to REQUEST_TIMEOUT
+pub fn set_request_timeout(stream: &TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    return Ok(stream.set_write_timeout(Some(REQUEST_TIMEOUT))?);
+}
+
+/// Reads the request line of a http request and skips its headers
+pub fn read_request(stream: &TcpStream) -> Result<(String, String)> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+    return Ok((method, path));
+}
+
+/// Writes a complete http response and closes the connection
+pub fn write_response(mut stream: TcpStream, code: u16, content_type: &str, body: &str) -> Result<()> {
+    let reason = match code {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Error",
+    };
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           code, reason, content_type, body.len(), body)?;
+    return Ok(stream.flush()?);
+}
+
+/// Command of the interactive console
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Stop,
+    Status,
+    /// density profile along z with the number of bins if given
+    Profile(Option<usize>),
+    /// steps between the trajectory frames, None for the last frame only
+    OutputInterval(Option<Step>),
+    Temperature(f64),
+    Help,
+}
+
+/// Commands of the interactive console for its help
+pub const CONSOLE_HELP : &str = "pause | resume | status | profile [bins] | output <interval or -1> | temperature <T> | stop | help";
+
+/// Parses a line typed into the interactive console
+pub fn parse_command(line: &str) -> ::std::result::Result<Command, String> {
+    let parts : Vec<&str> = line.split_whitespace().collect();
+    return match parts.as_slice() {
+        ["pause"] => Ok(Command::Pause),
+        ["resume"] => Ok(Command::Resume),
+        ["stop"] => Ok(Command::Stop),
+        ["status"] => Ok(Command::Status),
+        ["help"] => Ok(Command::Help),
+        ["profile"] => Ok(Command::Profile(None)),
+        ["profile", bins] => match bins.parse::<usize>() {
+            Ok(bins) if bins > 0 => Ok(Command::Profile(Some(bins))),
+            _ => Err("the number of bins has to be positive".to_string()),
+        },
+        ["output", value] => parse_output_interval(value).map(Command::OutputInterval),
+        ["temperature", value] => match value.parse::<f64>() {
+            Ok(temperature) if temperature.is_finite() && temperature > 0.0 => Ok(Command::Temperature(temperature)),
+            _ => Err("temperature has to be positive".to_string()),
+        },
+        _ => Err(format!("unknown command {:?}, one of {}", line.trim(), CONSOLE_HELP)),
+    };
+}
+
+/// Reads console commands from stdin in a background thread, invalid lines are answered on stderr
+pub fn read_commands() -> Receiver<Command> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let stdin = ::std::io::stdin();
+        for line in stdin.lock().lines().map_while(|line| line.ok()) {
+            if line.trim().is_empty() { continue; }
+            match parse_command(&line) {
+                Ok(command) => if sender.send(command).is_err() { break; },
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+    });
+    return receiver;
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(Ok(Command::Pause), parse_command(" pause\n"));
+    assert_eq!(Ok(Command::Profile(None)), parse_command("profile"));
+    assert_eq!(Ok(Command::Profile(Some(20))), parse_command("profile 20"));
+    assert_eq!(Ok(Command::OutputInterval(None)), parse_command("output -1"));
+    assert_eq!(Ok(Command::OutputInterval(Some(250))), parse_command("output 250"));
+    assert_eq!(Ok(Command::Temperature(0.85)), parse_command("temperature 0.85"));
+    assert!(parse_command("temperature -1").is_err());
+    assert!(parse_command("output 0").is_err());
+    assert!(parse_command("profile 0").is_err());
+    assert!(parse_command("jump").is_err());
+}
+
+/// Binds to address and answers requests in a background thread, returns the bound address
+pub fn serve(address: &str, control: Arc<Mutex<Control>>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // a broken or idle connection only affects its client
+            let _ = set_request_timeout(&stream).and_then(|_| read_request(&stream)).and_then(|(method, path)| {
+                let (code, body) = handle_request(&method, &path, &control);
+                write_response(stream, code, "application/json", &body)
+            });
+        }
+    });
+    return Ok(local_address);
+}
+
+#[test]
+fn test_handle_request() {
+    let control = Mutex::new(Control::default());
+    assert_eq!(404, handle_request("GET", "/frame", &control).0);
+    assert_eq!(404, handle_request("GET", "/unknown", &control).0);
+    assert_eq!(405, handle_request("GET", "/pause", &control).0);
+
+    control.lock().unwrap().status.step = 42;
+    let (code, body) = handle_request("GET", "/status", &control);
+    assert_eq!(200, code);
+    assert!(body.contains("\"step\":42"), "{}", body);
+
+    assert_eq!(200, handle_request("POST", "/pause", &control).0);
+    assert!(control.lock().unwrap().paused);
+    assert_eq!(200, handle_request("POST", "/resume", &control).0);
+    assert!(!control.lock().unwrap().paused);
+
+    assert_eq!(200, handle_request("POST", "/output_interval/500", &control).0);
+    assert_eq!(Some(500), control.lock().unwrap().output_interval);
+    assert_eq!(200, handle_request("POST", "/output_interval/-1", &control).0);
+    assert_eq!(None, control.lock().unwrap().output_interval);
+    assert_eq!(400, handle_request("POST", "/output_interval/0", &control).0);
+    assert_eq!(400, handle_request("POST", "/output_interval/abc", &control).0);
+
+    assert_eq!(200, handle_request("POST", "/stop", &control).0);
+    assert!(control.lock().unwrap().stop);
+}
+
+#[test]
+fn test_serve() {
+    let control = Arc::new(Mutex::new(Control::default()));
+    control.lock().unwrap().status.energy = -1.5;
+    let address = serve("127.0.0.1:0", control.clone()).unwrap();
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains("\"energy\":-1.5"), "{}", response);
+
+    // a client that never sends its request times out instead of blocking the next one
+    let _idle = TcpStream::connect(address).unwrap();
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /status HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+}
+
+#[test]
+fn test_format_metrics() {
+    let metrics = Metrics { steps: 1000, energy: -2.5, acceptance: f64::NAN, frames_written: 3, ..Metrics::default() };
+    let text = format_metrics(&metrics, "slab");
+    assert!(text.contains("# TYPE mclj_steps_total counter\nmclj_steps_total{run=\"slab\"} 1000\n"), "{}", text);
+    assert!(text.contains("mclj_energy{run=\"slab\"} -2.5\n"), "{}", text);
+    assert!(text.contains("mclj_acceptance_ratio{run=\"slab\"} NaN\n"), "{}", text);
+    assert!(text.contains("mclj_frames_written_total{run=\"slab\"} 3\n"), "{}", text);
+    let text = format_metrics(&metrics, "runs\\a \"b\"\nc");
+    assert!(text.contains("mclj_steps_total{run=\"runs\\\\a \\\"b\\\"\\nc\"} 1000\n"), "{}", text);
+}
+
+#[test]
+fn test_serve_metrics() {
+    let metrics = Arc::new(Mutex::new(Metrics { steps: 7, ..Metrics::default() }));
+    let address = serve_metrics("127.0.0.1:0", metrics.clone(), "test".to_string()).unwrap();
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains("mclj_steps_total{run=\"test\"} 7"), "{}", response);
+
+    // an idle client does not block the scrapes
+    let _idle = TcpStream::connect(address).unwrap();
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+}
@@ -0,0 +1,94 @@
+//! Explicit AVX kernels of the pair energy, selected at runtime if the cpu supports AVX
+
+use std::arch::x86_64::*;
+
+// minimum image of coordinate differences, the box length is added or subtracted by masks
+#[inline]
+#[target_feature(enable = "avx")]
+unsafe fn minimum_image(d: __m256d, l: __m256d, hl: __m256d, nhl: __m256d) -> __m256d {
+    let d = _mm256_sub_pd(d, _mm256_and_pd(_mm256_cmp_pd(d, hl, _CMP_GT_OQ), l));
+    return _mm256_add_pd(d, _mm256_and_pd(_mm256_cmp_pd(d, nhl, _CMP_LT_OQ), l));
+}
+
+/// Particle energy and virial like ::energy::get_particle_energy with four neighbors per AVX register
+///
+/// # Safety
+/// The cpu has to support AVX.
+#[target_feature(enable = "avx")]
+pub unsafe fn get_particle_energy_avx(rx: &[f64], ry: &[f64], rz: &[f64], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, e_shift: f64) -> (f64, f64) {
+    assert!(rx.len() >= num_particles && ry.len() >= num_particles && rz.len() >= num_particles);
+    let (x, y, z) = (_mm256_set1_pd(rx[p_index]), _mm256_set1_pd(ry[p_index]), _mm256_set1_pd(rz[p_index]));
+    let (l_x4, l_y4, l_z4) = (_mm256_set1_pd(l_x), _mm256_set1_pd(l_y), _mm256_set1_pd(l_z));
+    let (hl_x, hl_y, hl_z) = (_mm256_set1_pd(l_x / 2.0), _mm256_set1_pd(l_y / 2.0), _mm256_set1_pd(l_z / 2.0));
+    let (nhl_x, nhl_y, nhl_z) = (_mm256_set1_pd(-l_x / 2.0), _mm256_set1_pd(-l_y / 2.0), _mm256_set1_pd(-l_z / 2.0));
+    let cutoff4 = _mm256_set1_pd(cutoff_squared);
+    let shift4 = _mm256_set1_pd(e_shift);
+    let one = _mm256_set1_pd(1.0);
+    let half = _mm256_set1_pd(0.5);
+    let four_eps = _mm256_set1_pd(4.0 * ::LJ_EPS);
+    let fortyeight_eps = _mm256_set1_pd(48.0 * ::LJ_EPS);
+    let sig6 = _mm256_set1_pd(::LJ_SIG);
+
+    let mut energy = _mm256_setzero_pd();
+    let mut virial = _mm256_setzero_pd();
+    let chunks = num_particles / 4;
+    for chunk in 0..chunks {
+        let j = chunk * 4;
+        let dx = minimum_image(_mm256_sub_pd(_mm256_loadu_pd(rx.as_ptr().add(j)), x), l_x4, hl_x, nhl_x);
+        let dy = minimum_image(_mm256_sub_pd(_mm256_loadu_pd(ry.as_ptr().add(j)), y), l_y4, hl_y, nhl_y);
+        let dz = minimum_image(_mm256_sub_pd(_mm256_loadu_pd(rz.as_ptr().add(j)), z), l_z4, hl_z, nhl_z);
+        let dist_squared = _mm256_add_pd(_mm256_add_pd(_mm256_mul_pd(dx, dx), _mm256_mul_pd(dy, dy)), _mm256_mul_pd(dz, dz));
+
+        let mut inside = _mm256_cmp_pd(dist_squared, cutoff4, _CMP_LT_OQ);
+        if p_index >= j && p_index < j + 4 {
+            let mut lanes = [-1i64; 4];
+            lanes[p_index - j] = 0;
+            inside = _mm256_and_pd(inside, _mm256_castsi256_pd(_mm256_loadu_si256(lanes.as_ptr() as *const __m256i)));
+        }
+
+        // the particle itself gives nan which is masked out like the pairs beyond the cutoff
+        let inv = _mm256_div_pd(one, dist_squared);
+        let r6 = _mm256_mul_pd(sig6, _mm256_mul_pd(_mm256_mul_pd(inv, inv), inv));
+        let r62 = _mm256_mul_pd(r6, r6);
+        let e = _mm256_sub_pd(_mm256_mul_pd(four_eps, _mm256_sub_pd(r62, r6)), shift4);
+        let v = _mm256_mul_pd(fortyeight_eps, _mm256_sub_pd(r62, _mm256_mul_pd(half, r6)));
+        energy = _mm256_add_pd(energy, _mm256_and_pd(e, inside));
+        virial = _mm256_add_pd(virial, _mm256_and_pd(v, inside));
+    }
+
+    let mut energy_lanes = [0.0; 4];
+    let mut virial_lanes = [0.0; 4];
+    _mm256_storeu_pd(energy_lanes.as_mut_ptr(), energy);
+    _mm256_storeu_pd(virial_lanes.as_mut_ptr(), virial);
+    let (mut energy, mut virial) : (f64, f64) = (energy_lanes.iter().sum(), virial_lanes.iter().sum());
+
+    // remaining neighbors
+    let (hl_x, hl_y, hl_z) = (l_x / 2.0, l_y / 2.0, l_z / 2.0);
+    for j in chunks * 4..num_particles {
+        if j == p_index { continue; }
+        let dist_squared = ::energy::get_particle_distance_squared(rx[j], ry[j], rz[j], rx[p_index], ry[p_index], rz[p_index], l_x, l_y, l_z, hl_x, hl_y, hl_z);
+        if dist_squared < cutoff_squared {
+            let (e, v) = ::energy::eval_pair_energy(dist_squared, e_shift);
+            energy += e;
+            virial += v;
+        }
+    }
+    return (energy, virial);
+}
+
+#[test]
+fn test_get_particle_energy_avx() {
+    if !is_x86_feature_detected!("avx") { return; }
+    let n = 41;
+    let l = 4.5;
+    let coordinate = |i: usize, k: usize| ((i * 7919 + k * 104729) % 1000) as f64 / 1000.0 * l;
+    let rx : Vec<f64> = (0..n).map(|i| coordinate(i, 1)).collect();
+    let ry : Vec<f64> = (0..n).map(|i| coordinate(i, 2)).collect();
+    let rz : Vec<f64> = (0..n).map(|i| coordinate(i, 3)).collect();
+    for p in 0..n {
+        let (energy, virial) = ::energy::get_particle_energy_chunked(&rx, &ry, &rz, p, n, l, l, l, 4.0, |d| ::energy::eval_pair_energy(d, 0.05));
+        let (e, v) = unsafe { get_particle_energy_avx(&rx, &ry, &rz, p, n, l, l, l, 4.0, 0.05) };
+        assert!( (e - energy).abs() < 1e-9 * energy.abs().max(1.0), "{} {}", e, energy);
+        assert!( (v - virial).abs() < 1e-9 * virial.abs().max(1.0), "{} {}", v, virial);
+    }
+}
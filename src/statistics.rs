@@ -0,0 +1,182 @@
+//! Averages with statistical errors from block averaging, and streaming means and variances
+
+use Step;
+
+/// Splits a series of samples into blocks of block_size samples, the error of the mean is estimated
+/// from the scatter of the block means which are uncorrelated for blocks longer than the correlation time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockAverage {
+    pub block_size: Step,
+    pub blocks: Vec<f64>, // means of the completed blocks
+    block_sum: f64,
+    block_count: Step,
+}
+
+impl BlockAverage {
+    pub fn new(block_size: Step) -> BlockAverage {
+        return BlockAverage { block_size: block_size.max(1), blocks: vec![], block_sum: 0.0, block_count: 0 };
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.block_sum += value;
+        self.block_count += 1;
+        if self.block_count == self.block_size {
+            self.blocks.push(self.block_sum / self.block_size as f64);
+            self.block_sum = 0.0;
+            self.block_count = 0;
+        }
+    }
+
+    /// Mean of the completed blocks
+    pub fn mean(&self) -> f64 {
+        return get_mean(&self.blocks);
+    }
+
+    /// Standard error of the mean, NaN for less than two blocks
+    pub fn error(&self) -> f64 {
+        return get_standard_error(&self.blocks);
+    }
+
+    /// Difference of the means of the second and the first half of the blocks in units of their
+    /// combined error, large values indicate that the run is not converged
+    pub fn drift(&self) -> f64 {
+        let half = self.blocks.len() / 2;
+        let (first, second) = (&self.blocks[..half], &self.blocks[self.blocks.len() - half..]);
+        let error = (get_standard_error(first).powi(2) + get_standard_error(second).powi(2)).sqrt();
+        return (get_mean(second) - get_mean(first)) / error;
+    }
+}
+
+/// Streaming mean and variance of a series (Welford's algorithm), without the cancellation of sums of the values or their
+/// squares over long runs
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Statistics {
+    pub count: Step,
+    mean: f64,
+    m2: f64, // sum of the squared deviations from the mean
+}
+
+impl Statistics {
+    /// Statistics of count values of the given sum with unknown scatter, counted as none
+    pub fn from_sum(sum: f64, count: Step) -> Statistics {
+        return Statistics { count: count, mean: if count > 0 { sum / count as f64 } else { 0.0 }, m2: 0.0 };
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Statistics::default();
+    }
+
+    /// Mean of the values, NaN without values
+    pub fn mean(&self) -> f64 {
+        return if self.count > 0 { self.mean } else { f64::NAN };
+    }
+
+    /// Sample variance of the values, NaN for less than two
+    pub fn variance(&self) -> f64 {
+        return if self.count > 1 { self.m2 / (self.count - 1) as f64 } else { f64::NAN };
+    }
+
+    pub fn standard_deviation(&self) -> f64 {
+        return self.variance().sqrt();
+    }
+
+    /// Standard error of the mean if the values are uncorrelated, a lower bound for correlated series
+    pub fn standard_error(&self) -> f64 {
+        return self.standard_deviation() / (self.count as f64).sqrt();
+    }
+}
+
+#[test]
+fn test_statistics() {
+    let mut statistics = Statistics::default();
+    assert!(statistics.mean().is_nan() && statistics.variance().is_nan());
+    for value in [1.0, 2.0, 3.0, 4.0] { statistics.push(value); }
+    assert_eq!(4, statistics.count);
+    assert!( (statistics.mean() - 2.5).abs() < 1e-12 && (statistics.variance() - 5.0 / 3.0).abs() < 1e-12);
+    assert!( (statistics.standard_error() - (5.0 / 12.0_f64).sqrt()).abs() < 1e-12);
+
+    // small fluctuations on a large offset, where the sums of the values and their squares cancel
+    let mut statistics = Statistics::default();
+    for i in 0..1000000 { statistics.push(-1e9 + if i % 2 == 0 { 0.001 } else { -0.001 }); }
+    assert!( (statistics.mean() + 1e9).abs() < 1e-6, "{}", statistics.mean());
+    assert!( (statistics.standard_deviation() - 0.001).abs() < 1e-6, "{}", statistics.standard_deviation());
+
+    statistics.clear();
+    assert_eq!(Statistics::default(), statistics);
+    assert_eq!(2.0, Statistics::from_sum(6.0, 3).mean());
+}
+
+pub fn get_mean(values: &[f64]) -> f64 {
+    return values.iter().sum::<f64>() / values.len() as f64;
+}
+
+/// Standard error of the mean of uncorrelated values
+pub fn get_standard_error(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    if values.len() < 2 {
+        return f64::NAN;
+    }
+    let mean = get_mean(values);
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    return (variance / n).sqrt();
+}
+
+#[test]
+fn test_get_standard_error() {
+    let result = get_standard_error(&[1.0, 2.0, 3.0, 4.0]);
+    assert!( (result - (5.0_f64 / 3.0 / 4.0).sqrt()).abs() < 0.00001, "{}", result);
+    assert!(get_standard_error(&[1.0]).is_nan());
+}
+
+#[test]
+fn test_block_average() {
+    let mut average = BlockAverage::new(10);
+    for i in 0..105 {
+        average.push((i % 10) as f64 + if i < 50 { 0.0 } else { 1.0 });
+    }
+    // the incomplete last block is ignored
+    assert_eq!(10, average.blocks.len());
+    assert!( (average.mean() - 5.0).abs() < 0.00001, "{}", average.mean());
+    assert!( (average.blocks[0] - 4.5).abs() < 0.00001, "{}", average.blocks[0]);
+    assert!(average.error() > 0.0);
+
+    // a step between the halves
+    assert!(average.drift().is_infinite() && average.drift() > 0.0);
+    assert!(BlockAverage::new(0).block_size == 1);
+}
+
+/// Weighted least squares fit of y = a + b x with the standard errors of y, returns a, b and their standard errors.
+/// Without errors (any not finite or zero) all points weigh the same and the errors come from the scatter.
+pub fn get_linear_fit(x: &[f64], y: &[f64], errors: &[f64]) -> (f64, f64, f64, f64) {
+    let weighted = errors.iter().all(|e| e.is_finite() && *e > 0.0);
+    let w : Vec<f64> = errors.iter().map(|e| if weighted { 1.0 / (e * e) } else { 1.0 }).collect();
+    let (s, s_x, s_y) = (w.iter().sum::<f64>(), (0..x.len()).map(|i| w[i] * x[i]).sum::<f64>(), (0..x.len()).map(|i| w[i] * y[i]).sum::<f64>());
+    let s_xx = (0..x.len()).map(|i| w[i] * x[i] * x[i]).sum::<f64>();
+    let s_xy = (0..x.len()).map(|i| w[i] * x[i] * y[i]).sum::<f64>();
+    let delta = s * s_xx - s_x * s_x;
+    let (a, b) = ((s_xx * s_y - s_x * s_xy) / delta, (s * s_xy - s_x * s_y) / delta);
+    // the unweighted errors from the residual variance, NaN for two points
+    let scale = if weighted { 1.0 } else { (0..x.len()).map(|i| (y[i] - a - b * x[i]).powi(2)).sum::<f64>() / (x.len() as f64 - 2.0) };
+    return (a, b, (scale * s_xx / delta).sqrt(), (scale * s / delta).sqrt());
+}
+
+#[test]
+fn test_get_linear_fit() {
+    let x = [0.0, 1.0, 2.0, 3.0];
+    let (a, b, a_error, b_error) = get_linear_fit(&x, &[1.0, 3.0, 5.0, 7.0], &[0.1; 4]);
+    assert!( (a - 1.0).abs() < 1e-12 && (b - 2.0).abs() < 1e-12);
+    // intercept error of equal weights: sigma sqrt(sum x² / (n sum x² - (sum x)²))
+    assert!( (a_error - 0.1 * (14.0f64 / 20.0).sqrt()).abs() < 1e-12 && (b_error - 0.1 * (4.0f64 / 20.0).sqrt()).abs() < 1e-12);
+    // a precise point pulls the fit
+    let (a, _, _, _) = get_linear_fit(&x, &[1.0, 3.0, 5.0, 8.0], &[0.1, 0.1, 0.1, 0.001]);
+    assert!(a < 1.0, "{}", a);
+    let (_, _, a_error, _) = get_linear_fit(&x, &[1.0, 3.1, 4.9, 7.0], &[f64::NAN; 4]);
+    assert!(a_error > 0.0 && a_error.is_finite());
+}
@@ -0,0 +1,88 @@
+//! Publishes frames and observables as json lines to all connected tcp clients
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use error::Result;
+use serde::Serialize;
+use serde_json;
+
+// clients that cannot take a message within this time are dropped
+const WRITE_TIMEOUT_MS : u64 = 1000;
+
+/// Tcp publisher, every message is one line {"<kind>": <json>}
+pub struct Publisher {
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+    address: SocketAddr,
+}
+
+impl Publisher {
+    /// Binds to address and accepts clients in a background thread
+    pub fn bind(address: &str) -> Result<Publisher> {
+        let listener = TcpListener::bind(address)?;
+        let local_address = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(vec![]));
+        let accepted = clients.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if stream.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT_MS))).is_ok() {
+                    let _ = stream.set_nodelay(true);
+                    accepted.lock().unwrap().push(stream);
+                }
+            }
+        });
+        return Ok(Publisher { clients: clients, address: local_address });
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        return self.address;
+    }
+
+    /// True if anybody listens, to skip building messages nobody reads
+    pub fn has_clients(&self) -> bool {
+        return !self.clients.lock().unwrap().is_empty();
+    }
+
+    /// Sends value as {"kind": value} line to every client and drops the ones that failed
+    pub fn publish<T: Serialize>(&self, kind: &str, value: &T) -> Result<()> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return Ok(());
+        }
+        let json = serde_json::to_string(value).map_err(|e| ::error::Error::Serialization(e.to_string()))?;
+        let line = format!("{{\"{}\":{}}}\n", kind, json);
+        clients.retain(|client| { let mut client = client; client.write_all(line.as_bytes()).is_ok() });
+        return Ok(());
+    }
+}
+
+#[test]
+fn test_publisher() {
+    use std::io::BufReader;
+    let publisher = Publisher::bind("127.0.0.1:0").unwrap();
+    assert!(!publisher.has_clients());
+    publisher.publish("energy", &1.0).unwrap();
+
+    let client = TcpStream::connect(publisher.local_addr()).unwrap();
+    while !publisher.has_clients() { thread::sleep(Duration::from_millis(10)); }
+    publisher.publish("energy", &-2.5).unwrap();
+    publisher.publish("step", &vec![1, 2]).unwrap();
+
+    let mut reader = BufReader::new(client);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!("{\"energy\":-2.5}\n", line);
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!("{\"step\":[1,2]}\n", line);
+
+    // closed clients are dropped
+    drop(reader);
+    for _ in 0..100 {
+        publisher.publish("energy", &0.0).unwrap();
+        if !publisher.has_clients() { break; }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!publisher.has_clients());
+}
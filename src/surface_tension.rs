@@ -1,11 +1,12 @@
-mod trajectory;
-use trajectory::*;
-mod energy;
-use energy::*;
+extern crate mclj;
+use mclj::trajectory::*;
+use mclj::error::exit_with;
+use mclj::energy::*;
 use std::env;
 
-const LJ_EPS : f64 = 1.0;
-const LJ_SIG : f64 = 1.0;
+use mclj::{LJ_EPS, LJ_SIG};
+use mclj::Coordinate;
+use mclj::statistics::Statistics;
 
 const AVG_OUTPUT_INTERVAL : usize = 10;
 
@@ -24,11 +25,11 @@ fn main() {
     }
 
     // open file and skip to requested position
-    let mut trj_reader = TrjReader::new(&filename);
-    if skip > 0 { trj_reader.skip(skip) };
+    let mut trj_reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    if skip > 0 { trj_reader.skip(skip).unwrap_or_else(exit_with) };
 
     // read first trajectory and system params
-    let mut frame = trj_reader.next_frame();
+    let mut frame = trj_reader.next_frame().unwrap_or_else(exit_with);
     let volume = frame.box_x * frame.box_y * frame.box_z;
     let density = frame.num_particles as f64 / volume;
     let num_particles = frame.num_particles;
@@ -43,8 +44,8 @@ fn main() {
 
     println!("Calculating surface tension");
     println!("~~~ THIS IS A RUNNING AVERAGE! ~~~");
-    let mut trace_xy_sum = 0.0;
-    let mut trace_z_sum = 0.0;
+    let mut trace_xy_statistics = Statistics::default();
+    let mut trace_z_statistics = Statistics::default();
     let mut frame_count = 0;
 
     loop {
@@ -55,23 +56,23 @@ fn main() {
         for i in 0..num_particles {
             for j in i+1..num_particles {
                 // this needs some optimization for speed
-                let dist_sqrt = get_particle_distance_squared(frame.rx[i], frame.ry[i], frame.rz[i], frame.rx[j], frame.ry[j], frame.rz[j], frame.box_x, frame.box_y, frame.box_z, box_half_x, box_half_y, box_half_z);
+                let dist_sqrt = get_particle_distance_squared(frame.rx[i].to_f64(), frame.ry[i].to_f64(), frame.rz[i].to_f64(), frame.rx[j].to_f64(), frame.ry[j].to_f64(), frame.rz[j].to_f64(), frame.box_x, frame.box_y, frame.box_z, box_half_x, box_half_y, box_half_z);
                 let dist = dist_sqrt.sqrt();
-                let dx = get_distance_with_pbc(frame.rx[i], frame.rx[j], frame.box_x, box_half_x);
-                let dy = get_distance_with_pbc(frame.ry[i], frame.ry[j], frame.box_y, box_half_y);
-                let dz = get_distance_with_pbc(frame.rz[i], frame.rz[j], frame.box_z, box_half_z);
+                let dx = get_distance_with_pbc(frame.rx[i].to_f64(), frame.rx[j].to_f64(), frame.box_x, box_half_x);
+                let dy = get_distance_with_pbc(frame.ry[i].to_f64(), frame.ry[j].to_f64(), frame.box_y, box_half_y);
+                let dz = get_distance_with_pbc(frame.rz[i].to_f64(), frame.rz[j].to_f64(), frame.box_z, box_half_z);
                 let virial = eval_virial(dist, LJ_EPS, LJ_SIG);
                 trace_xy += (dx * dx + dy * dy) / dist * virial;
                 trace_z += (dz * dz) / dist * virial;
             }
         }
-        trace_xy_sum += trace_xy;
-        trace_z_sum += trace_z;
+        trace_xy_statistics.push(trace_xy);
+        trace_z_statistics.push(trace_z);
 
         ///////////////////////////////////
         if frame_count % AVG_OUTPUT_INTERVAL == 0 {
-            let p_z_avg = variable_without_name - 1.0/volume*(trace_z_sum/frame_count as f64);
-            let p_xy_avg = variable_without_name - 1.0/2.0/volume*(trace_xy_sum/frame_count as f64);
+            let p_z_avg = variable_without_name - 1.0/volume*trace_z_statistics.mean();
+            let p_xy_avg = variable_without_name - 1.0/2.0/volume*trace_xy_statistics.mean();
 
             let surface_tension = eval_surface_tension(frame.box_z, p_z_avg, p_xy_avg);
             println!("Frame {}\t\tzz: {:.5}\txy: {:.5}\t\ttension: {:.5}", frame_count, p_z_avg, p_xy_avg, surface_tension);
@@ -79,7 +80,11 @@ fn main() {
         }
 
         // read next frame
-        if !trj_reader.update_with_next(&mut frame) { break }
+        match trj_reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
     }
 
 }
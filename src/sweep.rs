@@ -0,0 +1,196 @@
+extern crate mclj;
+extern crate argparse;
+extern crate serde_json;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use argparse::{ArgumentParser, Store};
+use serde_json::Value;
+use mclj::error::exit_with;
+use mclj::system::*;
+
+/// One state point of the sweep
+#[derive(Clone, Debug, PartialEq)]
+struct Job {
+    params: Params,
+    /// Master seed of the sweep and the stream of the run in it
+    seed: usize,
+    stream: u64,
+    directory: String,
+}
+
+/// Parses a config of "key = values" lines. temperature, density and particles take lists and span the
+/// grid, seed is the master seed of the sweep (every run draws from its own stream of it), all other keys are
+/// fixed run parameters.
+fn parse_config(config: &str, output_dir: &str) -> Result<Vec<Job>, String> {
+    let mut temperatures = vec![];
+    let mut densities = vec![];
+    let mut particles = vec![];
+    let mut seed = 1;
+    let mut params = serde_json::to_value(Params::default()).unwrap();
+    for (i, line) in config.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap().trim();
+        let values : Vec<&str> = match parts.next() {
+            Some(values) => values.split_whitespace().collect(),
+            None => return Err(format!("line {}: expected key = value", i + 1)),
+        };
+        let floats = || values.iter().map(|v| v.parse::<f64>()).collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("line {}: {}", i + 1, e));
+        match key {
+            "temperature" => temperatures = floats()?,
+            "density" => densities = floats()?,
+            "particles" => particles = values.iter().map(|v| v.parse::<usize>()).collect::<Result<Vec<usize>, _>>()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?,
+            "seed" => seed = values.first().and_then(|v| v.parse::<usize>().ok()).ok_or(format!("line {}: invalid seed", i + 1))?,
+            _ => {
+                if params.get(key).is_none() || values.len() != 1 {
+                    return Err(format!("line {}: unknown parameter or not a single value: {}", i + 1, key));
+                }
+                params[key] = parse_value(values[0]);
+            },
+        }
+    }
+    let params : Params = serde_json::from_value(params).map_err(|e| format!("invalid parameter: {}", e))?;
+    if temperatures.is_empty() { temperatures.push(params.temperature) }
+    if densities.is_empty() { densities.push(params.density) }
+    if particles.is_empty() { particles.push(params.num_particles) }
+
+    let mut jobs = vec![];
+    for &temperature in &temperatures {
+        for &density in &densities {
+            for &num_particles in &particles {
+                let directory = format!("{}/T{}_rho{}_N{}", output_dir, temperature, density, num_particles);
+                jobs.push(Job {
+                    params: Params { temperature: temperature, density: density, num_particles: num_particles,
+                                     output_prefix: format!("{}/run", directory), ..params.clone() },
+                    seed: seed,
+                    stream: jobs.len() as u64,
+                    directory: directory,
+                });
+            }
+        }
+    }
+    return Ok(jobs);
+}
+
+// integers, floats and booleans as json numbers and booleans, the rest as string
+fn parse_value(value: &str) -> Value {
+    if let Ok(i) = value.parse::<i64>() { return Value::from(i) }
+    if let Ok(f) = value.parse::<f64>() { return Value::from(f) }
+    if let Ok(b) = value.parse::<bool>() { return Value::from(b) }
+    return Value::from(value);
+}
+
+/// Equilibrates and samples one state point, writes its checkpoint and averages
+fn run_job(job: &Job) -> mclj::error::Result<(Averages, f64)> {
+    fs::create_dir_all(&job.directory)?;
+    let mut rng = get_stream_rng(job.seed, StreamDomain::Sweep, job.stream);
+    let mut system = System::new(job.params.clone(), &mut rng);
+    system.equilibrate(job.params.eq_steps, &mut rng);
+    let averages = system.sample(job.params.sample_steps, &mut rng);
+    system.save_checkpoint(&format!("{}.checkpoint.json", job.params.output_prefix))?;
+    let json = serde_json::to_string_pretty(&averages).map_err(|e| mclj::error::Error::Serialization(e.to_string()))?;
+    fs::write(format!("{}.averages.json", job.params.output_prefix), json)?;
+    return Ok((averages, system.displacement));
+}
+
+fn csv_line(job: &Job, averages: &Averages, displacement: f64) -> String {
+    let n = job.params.num_particles as f64;
+    return format!("{},{},{},{},{},{},{},{},{},{},{}", job.params.temperature, job.params.density, job.params.num_particles, job.seed, job.stream,
+                   averages.energy / n, averages.energy_error / n, averages.pressure, averages.pressure_error, averages.acceptance, displacement);
+}
+
+fn main() {
+    let mut config_file = "sweep.cfg".to_string();
+    let mut output_dir = "sweep".to_string();
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Runs a grid of state points in parallel and collects the averages in <output>/sweep.csv.");
+        ap.refer(&mut config_file)
+            .add_option(&["-c", "--config"], Store, "Config with temperature/density/particles lists and fixed run parameters (key = values)");
+        ap.refer(&mut output_dir)
+            .add_option(&["-o", "--output"], Store, "Output directory, one subdirectory per state point");
+        ap.refer(&mut threads)
+            .add_option(&["-j", "--threads"], Store, "Number of parallel runs");
+        ap.parse_args_or_exit();
+    }
+
+    let config = fs::read_to_string(&config_file).unwrap_or_else(|e| exit_with(mclj::error::Error::FileNotFound(config_file.clone(), e)));
+    let jobs = parse_config(&config, &output_dir).unwrap_or_else(|e| { eprintln!("Error: {}", e); std::process::exit(1) });
+    let mut invalid = false;
+    for job in &jobs {
+        for error in job.params.validate() {
+            eprintln!("Error in {}: {}", job.directory, error);
+            invalid = true;
+        }
+    }
+    if invalid { std::process::exit(1); }
+    fs::create_dir_all(&output_dir).unwrap_or_else(|e| exit_with(e.into()));
+    eprintln!("Running {} state points on {} threads", jobs.len(), threads);
+
+    let queue : Arc<Mutex<VecDeque<usize>>> = Arc::new(Mutex::new((0..jobs.len()).collect()));
+    let results : Arc<Mutex<Vec<Option<String>>>> = Arc::new(Mutex::new(vec![None; jobs.len()]));
+    let jobs = Arc::new(jobs);
+    let workers : Vec<thread::JoinHandle<()>> = (0..threads.max(1)).map(|_| {
+        let (queue, results, jobs) = (queue.clone(), results.clone(), jobs.clone());
+        thread::spawn(move || loop {
+            let index = match queue.lock().unwrap().pop_front() {
+                Some(index) => index,
+                None => break,
+            };
+            let job = &jobs[index];
+            match run_job(job) {
+                Ok((averages, displacement)) => {
+                    eprintln!("Done {}: energy/N {:.4} +- {:.4}, pressure {:.4} +- {:.4}", job.directory,
+                              averages.energy / job.params.num_particles as f64, averages.energy_error / job.params.num_particles as f64,
+                              averages.pressure, averages.pressure_error);
+                    results.lock().unwrap()[index] = Some(csv_line(job, &averages, displacement));
+                },
+                Err(e) => eprintln!("Failed {}: {}", job.directory, e),
+            }
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().expect("worker panicked");
+    }
+
+    let csv_file = format!("{}/sweep.csv", output_dir);
+    let mut csv = File::create(&csv_file).unwrap_or_else(|e| exit_with(mclj::error::Error::FileNotFound(csv_file.clone(), e)));
+    let mut text = String::from("temperature,density,particles,seed,stream,energy_per_particle,energy_error,pressure,pressure_error,acceptance,displacement\n");
+    for line in results.lock().unwrap().iter().flatten() {
+        text.push_str(line);
+        text.push('\n');
+    }
+    csv.write_all(text.as_bytes()).unwrap_or_else(|e| exit_with(e.into()));
+    eprintln!("Results written to {}", csv_file);
+}
+
+#[test]
+fn test_parse_config() {
+    let config = "# grid\ntemperature = 0.8 1.0\ndensity = 0.5 0.6 0.7\nsample_steps = 500 # per run\nsweeps = true\ncutoff = 2.5\nseed = 10\n";
+    let jobs = parse_config(config, "out").unwrap();
+    assert_eq!(6, jobs.len());
+    assert_eq!(0.8, jobs[0].params.temperature);
+    assert_eq!(0.6, jobs[1].params.density);
+    assert_eq!(512, jobs[5].params.num_particles);
+    assert_eq!(500, jobs[5].params.sample_steps);
+    assert!(jobs[5].params.sweeps);
+    assert_eq!(2.5, jobs[5].params.cutoff);
+    assert_eq!((10, 0), (jobs[0].seed, jobs[0].stream));
+    assert_eq!((10, 5), (jobs[5].seed, jobs[5].stream));
+    assert_eq!("out/T1_rho0.7_N512", jobs[5].directory);
+    assert_eq!("out/T1_rho0.7_N512/run", jobs[5].params.output_prefix);
+
+    assert!(parse_config("unknown = 1\n", "out").is_err());
+    assert!(parse_config("temperature = a\n", "out").is_err());
+    assert!(parse_config("sweeps = 0.5\n", "out").is_err());
+}
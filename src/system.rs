@@ -0,0 +1,2277 @@
+#![allow(dead_code)]
+
+use rand::{Rng, SeedableRng, StdRng};
+use std::fs::File;
+use std::io::prelude::*;
+use energy::*;
+use error::{Error, Result};
+use moves::*;
+use multicanonical::Multicanonical;
+use expanded::{get_soft_core_distance_squared, ExpandedEnsemble, SOLUTE};
+use tempering::SimulatedTempering;
+use observables::*;
+use orientation::*;
+use gay_berne::GayBerne;
+use three_body::*;
+use restraints::{Restraint, Restraints};
+use interface::get_slab_center;
+use cells::CellList;
+use checkerboard::{get_sweep_grid, move_color, PairSystem, COLORS};
+use move_log::{Outcome, TrialRecord};
+use units::Units;
+use provenance::Provenance;
+use schedule::{parse_schedule, Change};
+use trajectory::{Frame, XYZTrajectory};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as _;
+use serde_json;
+use statistics::{BlockAverage, Statistics};
+use {Coordinate, Real, Step};
+
+// intended acceptance rate = 33%
+pub const TRIES_INTENDED : f64 = 3.0;
+
+// gain and decay exponent of the robbins-monro displacement adaptation
+pub const RM_GAIN : f64 = 3.0;
+pub const RM_DECAY : f64 = 0.6;
+
+// steps (sweeps in sweep mode) between displacement updates
+pub const SCALE_INTERVAL : Step = 5000;
+pub const SWEEP_SCALE_INTERVAL : Step = 10;
+
+// blocks for the error estimates of a sampling run
+pub const SAMPLE_BLOCKS : Step = 20;
+
+// relative rounding error of the running energy per summed particle energy, the floor of the drift check
+pub const DRIFT_ROUNDING : f64 = 1e-13;
+
+// initial maximum angle of the rotation moves, adapted like the displacement up to a half turn
+const ROTATION_ANGLE : f64 = 0.5;
+
+// lowest pair energy at the overlap radius in kT of the highest temperature of the run, the hard core only removes
+// states of boltzmann factors below e^-20
+pub const OVERLAP_ENERGY_KT : f64 = 20.0;
+
+// width of the bins of the trial displacement lengths of the acceptance histogram
+pub const DISPLACEMENT_BIN_WIDTH : f64 = 0.025;
+
+/// Run parameters of a simulation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Params {
+    pub eq_steps: Step,
+    pub sample_steps: Step,
+    pub sweeps: bool, // one step is num_particles attempted moves
+
+    // master seed of the random number streams (0=seeded from the os)
+    #[serde(default)]
+    pub seed: usize,
+
+    pub num_particles: usize,
+    pub density: f64,
+    pub temperature: f64,
+    pub cutoff: f64,
+
+    // full energy recalculation every recompute_interval steps and/or recompute_time seconds (0=disabled)
+    pub recompute_interval: Step,
+    pub recompute_time: f64,
+
+    // exactness check at the recalculations: a relative discrepancy of the running energy or virial above
+    // drift_tolerance is reported, and aborts the run with drift_abort (0=disabled)
+    #[serde(default)]
+    pub drift_tolerance: f64,
+    #[serde(default)]
+    pub drift_abort: bool,
+
+    pub tailcorr: bool,
+    pub shift: bool,
+
+    // smooth truncation with a switching function from switch_radius to the cutoff instead of the shift (0=disabled)
+    #[serde(default)]
+    pub switch_radius: f64,
+
+    // force-shifted potential, energy and force go to zero at the cutoff. The tail corrections do not apply to it.
+    #[serde(default)]
+    pub force_shift: bool,
+
+    pub displacement: f64, // max particle displacement in one dimension
+    pub scale: bool, // switch for displacement scaling
+
+    // distribution of the trial displacements: cube, ball or gaussian (see Proposal, empty for the cube)
+    #[serde(default)]
+    pub proposal: String,
+
+    // trial moves stop summing the pairs once the energy is certainly above the pre-drawn metropolis threshold, for the
+    // plain lj pairs without force-bias moves or multicanonical sampling
+    #[serde(default)]
+    pub early_rejection: bool,
+
+    // trial moves only evaluate the energy, the virial and the pressure samples come from the full recalculations every
+    // recompute_interval steps, for the plain lj pairs
+    #[serde(default)]
+    pub lazy_virial: bool,
+
+    // checkerboard domain decomposition of the sweeps on checkerboard_threads threads, the particles move within the cells
+    // of a randomly shifted grid, the cells of a color in parallel (see checkerboard, 0=disabled)
+    #[serde(default)]
+    pub checkerboard_threads: usize,
+
+    // hard core: trial positions closer than overlap_radius to any particle are rejected by a cell list scan before
+    // the energy (0=disabled)
+    #[serde(default)]
+    pub overlap_radius: f64,
+
+    // force-bias moves displace particles preferentially along the acting force
+    pub force_bias: bool,
+    pub fb_lambda: f64,
+
+    // orientations of the particles, a rotation move follows every displacement move
+    #[serde(default)]
+    pub rotations: bool,
+
+    // gay-berne ellipsoids of the length to breadth ratio gb_kappa and the ratio gb_kappa_prime of the side by side to
+    // the end to end well depth instead of lj spheres, they need the rotations (0=lj)
+    #[serde(default)]
+    pub gb_kappa: f64,
+    #[serde(default)]
+    pub gb_kappa_prime: f64,
+
+    // axilrod-teller triple-dipole coefficient in epsilon sigma^9 of the triplets within three_body_cutoff (0=disabled)
+    #[serde(default)]
+    pub three_body_nu: f64,
+    #[serde(default)]
+    pub three_body_cutoff: f64,
+
+    // point dipoles of this moment along the particle axes in a uniform field of field_strength along field_direction
+    // (x,y,z, empty for z), the field energy is -mu E per particle (0=no field)
+    #[serde(default)]
+    pub dipole_moment: f64,
+    #[serde(default)]
+    pub field_strength: f64,
+    #[serde(default)]
+    pub field_direction: String,
+
+    // ; separated restraints added to the energy: tether:i:x,y,z:k of particle i to a point, distance:i:j:r0:k of a pair
+    // and slab:w:k keeping the slab center within w of the box center (see Restraint), empty for none
+    #[serde(default)]
+    pub restraints: String,
+
+    // scale factor in z for vaccuum space
+    pub vacuum_slab: f64,
+
+    // steps between translations of the liquid slab along z back to the box center (0=disabled)
+    #[serde(default)]
+    pub recenter_interval: Step,
+
+    // preferential selection of particles in the interfacial region of a slab
+    pub interface_factor: f64,
+    pub interface_width: f64,
+
+    // output config
+    pub output_prefix: String, // .xyz will be append
+    // steps between the trajectory frames, None for the last frame only
+    #[serde(deserialize_with = "deserialize_output_interval")]
+    pub output_interval: Option<Step>,
+
+    // run directory of --outdir the output prefix is in, its manifest is rewritten at the end of restarts (empty for
+    // none)
+    #[serde(default)]
+    pub output_directory: String,
+
+    // steps between the progress lines, the checkpoints and the on-the-fly analyses (slab profile printout and the
+    // series of the summary plots), 0 for the defaults: progress every 5000 steps or 10 sweeps, checkpoints at the end only
+    #[serde(default)]
+    pub log_interval: Step,
+    #[serde(default)]
+    pub checkpoint_interval: Step,
+    #[serde(default)]
+    pub analysis_interval: Step,
+    pub output_minim: bool,
+
+    // per-particle columns of the trajectory frames, e.g. energy,cluster,density (see ParticleColumn)
+    #[serde(default)]
+    pub particle_columns: String,
+
+    // lj parameters of the substance the results are also given in real units for, e.g. argon (see Units, empty for
+    // reduced units only)
+    #[serde(default)]
+    pub units: String,
+
+    // parameter changes during the run, e.g. 2000000:temperature:0.8;3000000:density:0.7 (see parse_schedule), the
+    // averages restart schedule_equilibration steps after each change, the displacement adapts in between
+    #[serde(default)]
+    pub schedule: String,
+    #[serde(default)]
+    pub schedule_equilibration: Step,
+
+    // umbrella bias spring/2 (n - center)² on the size n of the largest cluster of liquid-like particles, e.g. for
+    // nucleation barriers (umbrella_spring 0=disabled)
+    #[serde(default)]
+    pub umbrella_center: f64,
+    #[serde(default)]
+    pub umbrella_spring: f64,
+
+    // multicanonical sampling of the energies per particle between multicanonical_min and multicanonical_max in
+    // multicanonical_bins bins, the bias is learned by Wang-Landau during the equilibration (0 bins=disabled)
+    #[serde(default)]
+    pub multicanonical_min: f64,
+    #[serde(default)]
+    pub multicanonical_max: f64,
+    #[serde(default)]
+    pub multicanonical_bins: usize,
+
+    // expanded ensemble over expanded_states couplings of particle 0 between 0 and 1 for its solvation free energy,
+    // the state weights are learned by Wang-Landau during the equilibration (0=disabled)
+    #[serde(default)]
+    pub expanded_states: usize,
+
+    // simulated tempering over tempering_states temperatures between tempering_min and tempering_max, the weights are
+    // learned by Wang-Landau during the equilibration (0=disabled)
+    #[serde(default)]
+    pub tempering_min: f64,
+    #[serde(default)]
+    pub tempering_max: f64,
+    #[serde(default)]
+    pub tempering_states: usize,
+}
+
+/// Parses the output interval of the command line, the console and the control server: the steps between the
+/// trajectory frames or -1 for the last frame only
+pub fn parse_output_interval(text: &str) -> ::std::result::Result<Option<Step>, String> {
+    return match text.trim() {
+        "-1" => Ok(None),
+        text => match text.parse::<Step>() {
+            Ok(interval) if interval > 0 => Ok(Some(interval)),
+            _ => Err("output interval has to be positive or -1".to_string()),
+        },
+    };
+}
+
+// the output interval of the parameters, older checkpoints and configs write -1 for the last frame only
+fn deserialize_output_interval<'de, D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Option<Step>, D::Error> {
+    return match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::Number(ref number) if number.as_i64() == Some(-1) => Ok(None),
+        serde_json::Value::Number(ref number) if number.as_u64().is_some() => Ok(number.as_u64()),
+        other => Err(D::Error::custom(format!("invalid output interval {}", other))),
+    };
+}
+
+#[test]
+fn test_output_interval() {
+    assert_eq!(Ok(Some(500)), parse_output_interval("500"));
+    assert_eq!(Ok(None), parse_output_interval(" -1"));
+    assert!(parse_output_interval("0").is_err() && parse_output_interval("-2").is_err() && parse_output_interval("ten").is_err());
+
+    let mut value = serde_json::to_value(Params::default()).unwrap();
+    assert_eq!(Some(100), serde_json::from_value::<Params>(value.clone()).unwrap().output_interval);
+    let params = Params { output_interval: None, ..Params::default() };
+    assert_eq!(params, serde_json::from_value(serde_json::to_value(&params).unwrap()).unwrap());
+    value["output_interval"] = serde_json::json!(-1);
+    assert_eq!(None, serde_json::from_value::<Params>(value.clone()).unwrap().output_interval);
+    value["output_interval"] = serde_json::json!(-5);
+    assert!(serde_json::from_value::<Params>(value).is_err());
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            eq_steps: 1000000,
+            sample_steps: 100000,
+            sweeps: false,
+            seed: 0,
+            num_particles: 512,
+            density: 0.7,
+            temperature: 0.9,
+            cutoff: 3.0,
+            recompute_interval: 10000,
+            recompute_time: 0.0,
+            drift_tolerance: 0.0,
+            drift_abort: false,
+            tailcorr: true,
+            shift: true,
+            switch_radius: 0.0,
+            force_shift: false,
+            displacement: 0.1,
+            scale: true,
+            proposal: String::new(),
+            early_rejection: false,
+            lazy_virial: false,
+            checkerboard_threads: 0,
+            overlap_radius: 0.0,
+            force_bias: false,
+            fb_lambda: 0.5,
+            rotations: false,
+            gb_kappa: 0.0,
+            gb_kappa_prime: 0.0,
+            three_body_nu: 0.0,
+            three_body_cutoff: 0.0,
+            dipole_moment: 0.0,
+            field_strength: 0.0,
+            field_direction: String::new(),
+            restraints: String::new(),
+            vacuum_slab: 0.0,
+            recenter_interval: 0,
+            interface_factor: 1.0,
+            interface_width: 2.0,
+            output_prefix: "montecarlo".to_string(),
+            output_interval: Some(100),
+            output_directory: String::new(),
+            log_interval: 0,
+            checkpoint_interval: 0,
+            analysis_interval: 0,
+            output_minim: false,
+            particle_columns: String::new(),
+            units: String::new(),
+            schedule: String::new(),
+            schedule_equilibration: 0,
+            umbrella_center: 0.0,
+            umbrella_spring: 0.0,
+            multicanonical_min: 0.0,
+            multicanonical_max: 0.0,
+            multicanonical_bins: 0,
+            expanded_states: 0,
+            tempering_min: 0.0,
+            tempering_max: 0.0,
+            tempering_states: 0,
+        }
+    }
+}
+
+// true for positive numbers, false for zero, negative numbers and NaN
+pub(crate) fn is_positive(x: f64) -> bool {
+    return x > 0.0;
+}
+
+// names of the presets with a description
+pub const PRESETS : &[(&str, &str)] = &[
+    ("triple", "liquid at the triple point, T* = 0.694, rho* = 0.845"),
+    ("critical", "near the critical point of the full potential, T* = 1.312, rho* = 0.316 (Potoff and Panagiotopoulos 1998)"),
+    ("argon85", "liquid argon at 85 K and 1.41 g/cm3, T* = 0.7095, rho* = 0.839 in argon units"),
+    ("slab07", "liquid-vapor slab at T* = 0.7 with a cutoff of 3 for the surface tension"),
+];
+
+impl Params {
+    /// Parameters of a common state point (see PRESETS), 500 to 1000 particles with cutoff 3 and tail corrections in
+    /// the bulk, 2000 equilibration and 10000 sampling sweeps
+    pub fn preset(name: &str) -> Option<Params> {
+        let base = Params { num_particles: 500, cutoff: 3.0, sweeps: true, eq_steps: 2000, sample_steps: 10000, recompute_interval: 1000, ..Params::default() };
+        return match name {
+            "triple" => Some(Params { temperature: 0.694, density: 0.845, ..base }),
+            "critical" => Some(Params { num_particles: 1000, temperature: 1.312, density: 0.316, ..base }),
+            "argon85" => Some(Params { temperature: 0.7095, density: 0.839, units: "argon".to_string(), ..base }),
+            // the slab has the bulk liquid density between two vacuum regions of its width, no tail corrections
+            "slab07" => Some(Params { num_particles: 1000, temperature: 0.7, density: 0.8, vacuum_slab: 2.0, tailcorr: false, recenter_interval: 100, ..base }),
+            _ => None,
+        };
+    }
+
+    /// Checks the run parameters and returns a message for every nonsensical value
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if self.num_particles == 0 { errors.push("Number of particles has to be larger than 0.".to_string()); }
+        if !is_positive(self.density) { errors.push(format!("Density has to be positive (got {}).", self.density)); }
+        if !is_positive(self.temperature) { errors.push(format!("Temperature has to be positive (got {}).", self.temperature)); }
+        if !is_positive(self.cutoff) { errors.push(format!("Cutoff has to be positive (got {}).", self.cutoff)); }
+        if self.num_particles > 0 && self.density > 0.0 {
+            // no image sums, so the cutoff sphere has to fit in the (smallest) box dimension
+            let half_box = ((self.num_particles as f64) / self.density).cbrt() / 2.0;
+            if self.cutoff > half_box {
+                errors.push(format!("Cutoff {} is larger than half the box length {:.3}. Use more particles or a smaller cutoff.", self.cutoff, half_box));
+            }
+            if self.three_body_nu != 0.0 && self.three_body_cutoff > half_box / 2.0 {
+                errors.push(format!("Three-body cutoff {} is larger than a quarter of the box length {:.3}.", self.three_body_cutoff, half_box / 2.0));
+            }
+        }
+        if self.drift_tolerance.is_nan() || self.drift_tolerance < 0.0 { errors.push(format!("Drift tolerance can not be negative (got {}).", self.drift_tolerance)); }
+        if self.drift_tolerance > 0.0 && self.recompute_interval == 0 && self.recompute_time == 0.0 { errors.push("The drift check needs energy recalculations.".to_string()); }
+        if self.switch_radius.is_nan() || self.switch_radius < 0.0 || (self.switch_radius > 0.0 && self.switch_radius >= self.cutoff) {
+            errors.push(format!("Switching radius has to be between 0 and the cutoff (got {}).", self.switch_radius));
+        }
+        if self.force_shift && self.switch_radius > 0.0 { errors.push("Force shift and switching function can not be combined.".to_string()); }
+        if !is_positive(self.displacement) { errors.push(format!("Displacement has to be positive (got {}).", self.displacement)); }
+        if !self.units.is_empty() && Units::parse(&self.units).is_none() {
+            errors.push(format!("Invalid units {}, expected argon or e.g. eps=119.8K,sig=3.405A,mass=39.948.", self.units));
+        }
+        match parse_schedule(&self.schedule) {
+            None => errors.push(format!("Invalid schedule {}, expected a ; separated list of step:temperature:value, step:density:value and step:vacuum:value.", self.schedule)),
+            Some(ref schedule) if self.tempering_states > 0 && schedule.iter().any(|&(_, change)| matches!(change, Change::Temperature(_))) => {
+                errors.push("The temperature of simulated tempering can not be scheduled.".to_string());
+            },
+            Some(ref schedule) if schedule.iter().filter(|&&(_, change)| matches!(change, Change::Vacuum(_))).count() + (self.vacuum_slab > 0.0) as usize > 1 => {
+                errors.push("Only one vacuum slab can be opened.".to_string());
+            },
+            Some(ref schedule) if self.gb_kappa > 0.0 && schedule.iter().any(|&(_, change)| matches!(change, Change::Vacuum(_))) => {
+                errors.push("Gay-Berne particles can not be combined with slabs.".to_string());
+            },
+            _ => {},
+        }
+        match Proposal::parse(&self.proposal) {
+            None => errors.push(format!("Invalid proposal {}, expected cube, ball or gaussian.", self.proposal)),
+            Some(proposal) if proposal != Proposal::Cube && self.force_bias => errors.push("Force-bias moves have their own proposal.".to_string()),
+            _ => {},
+        }
+        if self.early_rejection && (self.force_bias || self.gb_kappa != 0.0 || self.three_body_nu != 0.0 || self.multicanonical_bins > 0) {
+            errors.push("Early rejection can not be combined with force-bias moves, Gay-Berne particles, the three-body term or multicanonical sampling.".to_string());
+        }
+        if self.lazy_virial && self.recompute_interval == 0 { errors.push("The lazy virial needs energy recalculations every recompute interval steps.".to_string()); }
+        if self.lazy_virial && (self.gb_kappa != 0.0 || self.three_body_nu != 0.0 || self.expanded_states > 0 || self.multicanonical_bins > 0 || self.tempering_states > 0) {
+            errors.push("The lazy virial can not be combined with Gay-Berne particles, the three-body term, the expanded ensemble, multicanonical sampling or simulated tempering.".to_string());
+        }
+        if self.checkerboard_threads > 0 && !self.sweeps { errors.push("The checkerboard decomposition needs sweeps.".to_string()); }
+        if self.checkerboard_threads > 0 && (self.gb_kappa != 0.0 || self.three_body_nu != 0.0 || self.expanded_states > 0 || self.multicanonical_bins > 0 || self.umbrella_spring > 0.0
+                                             || self.interface_factor != 1.0 || self.force_bias || self.early_rejection || self.overlap_radius > 0.0 || !self.restraints.is_empty()) {
+            errors.push("The checkerboard decomposition only moves plain lj pairs, without Gay-Berne particles, the three-body term, the expanded ensemble, multicanonical or umbrella sampling, preferential sampling, force-bias moves, early rejection, the overlap check or restraints.".to_string());
+        }
+        if self.overlap_radius.is_nan() || self.overlap_radius < 0.0 || self.overlap_radius >= ::LJ_SIG {
+            errors.push(format!("Overlap radius has to be between 0 and sigma (got {}).", self.overlap_radius));
+        } else if self.overlap_radius > 0.0 {
+            // the hard core has to stay far above kT at the highest temperature of the run
+            let scheduled = parse_schedule(&self.schedule).unwrap_or_default().iter().filter_map(|&(_, change)| match change { Change::Temperature(t) => Some(t), _ => None }).fold(0.0, f64::max);
+            let temperature = self.temperature.max(scheduled).max(if self.tempering_states > 0 { self.tempering_max } else { 0.0 });
+            let energy = eval_pair_energy(self.overlap_radius * self.overlap_radius, 0.0).0;
+            if energy < OVERLAP_ENERGY_KT * temperature {
+                errors.push(format!("The pair energy {:.1} at the overlap radius {} is below {} kT at the temperature {}, the hard core would remove states of the ensemble. Use a smaller radius.",
+                                    energy, self.overlap_radius, OVERLAP_ENERGY_KT, temperature));
+            }
+        }
+        if self.overlap_radius > 0.0 && (self.gb_kappa != 0.0 || self.expanded_states > 0) {
+            errors.push("The overlap check can not be combined with Gay-Berne particles or the soft core of the expanded ensemble.".to_string());
+        }
+        if self.output_interval == Some(0) {
+            errors.push("Output interval has to be positive or -1 for the last frame only (got 0).".to_string());
+        }
+        if ParticleColumn::parse_list(&self.particle_columns).is_none() {
+            errors.push(format!("Invalid particle columns {}, expected a list of energy, cluster, density, virial, stress and voronoi.", self.particle_columns));
+        }
+        if self.umbrella_spring.is_nan() || self.umbrella_spring < 0.0 { errors.push(format!("Umbrella spring constant can not be negative (got {}).", self.umbrella_spring)); }
+        if self.umbrella_center.is_nan() || self.umbrella_center < 0.0 || self.umbrella_center > self.num_particles as f64 {
+            errors.push(format!("Umbrella center has to be a cluster size between 0 and the number of particles (got {}).", self.umbrella_center));
+        }
+        if self.multicanonical_bins > 0 && !is_positive(self.multicanonical_max - self.multicanonical_min) {
+            errors.push(format!("Multicanonical energy range needs a minimum below the maximum (got {} to {}).", self.multicanonical_min, self.multicanonical_max));
+        }
+        if self.expanded_states == 1 { errors.push("The expanded ensemble needs at least 2 states.".to_string()); }
+        if self.expanded_states > 0 && self.multicanonical_bins > 0 { errors.push("Expanded ensemble and multicanonical sampling can not be combined.".to_string()); }
+        if self.tempering_states > 0 && (!is_positive(self.tempering_min) || !is_positive(self.tempering_max - self.tempering_min) || self.tempering_states < 2) {
+            errors.push(format!("Simulated tempering needs at least 2 temperatures between a positive minimum and a larger maximum (got {} from {} to {}).", self.tempering_states, self.tempering_min, self.tempering_max));
+        }
+        if self.tempering_states > 0 && (self.multicanonical_bins > 0 || self.expanded_states > 0) {
+            errors.push("Simulated tempering can not be combined with multicanonical sampling or the expanded ensemble.".to_string());
+        }
+        if self.gb_kappa != 0.0 {
+            if !is_positive(self.gb_kappa) || !is_positive(self.gb_kappa_prime) {
+                errors.push(format!("Gay-Berne aspect ratio and well depth ratio have to be positive (got {} and {}).", self.gb_kappa, self.gb_kappa_prime));
+            }
+            if !self.rotations { errors.push("Gay-Berne particles need the rotation moves.".to_string()); }
+            if self.force_bias || self.switch_radius > 0.0 || self.force_shift || self.vacuum_slab > 0.0 || self.expanded_states > 0 || self.multicanonical_bins > 0 {
+                errors.push("Gay-Berne particles can not be combined with force-bias moves, switching, force shifts, slabs, the expanded ensemble or multicanonical sampling.".to_string());
+            }
+        }
+        if self.three_body_nu != 0.0 && !is_positive(self.three_body_cutoff) { errors.push(format!("Three-body cutoff has to be positive (got {}).", self.three_body_cutoff)); }
+        if self.three_body_nu != 0.0 && (self.gb_kappa > 0.0 || self.expanded_states > 0) {
+            errors.push("The three-body term can not be combined with Gay-Berne particles or the expanded ensemble.".to_string());
+        }
+        if !self.field_direction.is_empty() && parse_direction(&self.field_direction).is_none() {
+            errors.push(format!("Invalid field direction {}, expected a vector like 0,0,1.", self.field_direction));
+        }
+        match Restraint::parse_list(&self.restraints, self.num_particles) {
+            None => errors.push(format!("Invalid restraints {}, expected a ; separated list of tether:i:x,y,z:k, distance:i:j:r0:k and slab:w:k with particle indices below {}.", self.restraints, self.num_particles)),
+            Some(ref list) if self.vacuum_slab == 0.0 && list.iter().any(|restraint| matches!(*restraint, Restraint::Slab { .. })) => errors.push("The slab restraint needs a vacuum slab.".to_string()),
+            _ => {},
+        }
+        if self.recenter_interval > 0 && self.vacuum_slab == 0.0 { errors.push("Recentering needs a vacuum slab.".to_string()); }
+        if self.recenter_interval > 0 && Restraint::parse_list(&self.restraints, self.num_particles).is_some_and(|list| list.iter().any(|restraint| matches!(*restraint, Restraint::Tether { .. }))) {
+            errors.push("Recentering moves the slab against the tethers, they can not be combined.".to_string());
+        }
+        if self.field_strength != 0.0 && (!self.rotations || self.dipole_moment == 0.0 || self.multicanonical_bins > 0) {
+            errors.push("The external field needs dipoles and the rotation moves and can not be combined with multicanonical sampling.".to_string());
+        }
+        if self.vacuum_slab.is_nan() || self.vacuum_slab < 0.0 { errors.push(format!("Vacuum slab can not be negative (got {}).", self.vacuum_slab)); }
+        if !(0.0..=1.0).contains(&self.fb_lambda) { errors.push(format!("Force-bias lambda has to be between 0 and 1 (got {}).", self.fb_lambda)); }
+        if !is_positive(self.interface_factor) { errors.push(format!("Interface sampling factor has to be positive (got {}).", self.interface_factor)); }
+        if !is_positive(self.interface_width) { errors.push(format!("Interface width has to be positive (got {}).", self.interface_width)); }
+        return errors;
+    }
+}
+
+#[test]
+fn test_presets() {
+    for &(name, _) in PRESETS {
+        let params = Params::preset(name).unwrap();
+        assert!(params.validate().is_empty(), "{} {:?}", name, params.validate());
+    }
+    assert_eq!(0.694, Params::preset("triple").unwrap().temperature);
+    assert_eq!(None, Params::preset("boiling"));
+}
+
+#[test]
+fn test_validate_params() {
+    let params = Params::default();
+    assert!(params.validate().is_empty());
+    assert!(Params { output_interval: None, vacuum_slab: 2.0, ..params.clone() }.validate().is_empty());
+
+    assert_eq!(1, Params { num_particles: 0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { density: -0.7, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { cutoff: 5.0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { output_interval: Some(0), ..params.clone() }.validate().len());
+    assert_eq!(1, Params { drift_tolerance: 1e-8, recompute_interval: 0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { switch_radius: 3.0, ..params.clone() }.validate().len());
+    assert!(Params { switch_radius: 2.5, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { switch_radius: 2.5, force_shift: true, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { proposal: "gaussian".to_string(), force_bias: true, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { proposal: "sphere".to_string(), ..params.clone() }.validate().len());
+    assert_eq!(1, Params { units: "krypton".to_string(), ..params.clone() }.validate().len());
+    assert_eq!(1, Params { schedule: "1000:pressure:1".to_string(), ..params.clone() }.validate().len());
+    assert_eq!(1, Params { schedule: "1000:vacuum:2".to_string(), vacuum_slab: 2.0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { early_rejection: true, force_bias: true, ..params.clone() }.validate().len());
+    assert!(Params { lazy_virial: true, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { lazy_virial: true, recompute_interval: 0, ..params.clone() }.validate().len());
+    assert!(Params { checkerboard_threads: 4, sweeps: true, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { checkerboard_threads: 4, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { checkerboard_threads: 4, sweeps: true, force_bias: true, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { lazy_virial: true, multicanonical_bins: 10, multicanonical_min: -6.0, multicanonical_max: -5.0, ..params.clone() }.validate().len());
+    // the virial of the last recalculation may be from another temperature of the ladder
+    assert_eq!(1, Params { lazy_virial: true, tempering_states: 4, tempering_min: 0.8, tempering_max: 1.2, ..params.clone() }.validate().len());
+    assert!(Params { overlap_radius: 0.8, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { overlap_radius: 1.2, ..params.clone() }.validate().len());
+    // 43 epsilon at 0.8 is just above 20 kT at temperature 2.1 and below it at 2.2
+    assert!(Params { overlap_radius: 0.8, temperature: 2.1, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { overlap_radius: 0.8, temperature: 2.2, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { overlap_radius: 0.8, schedule: "1000:temperature:2.2".to_string(), ..params.clone() }.validate().len());
+    assert_eq!(1, Params { overlap_radius: 0.95, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { particle_columns: "energy,spin".to_string(), ..params.clone() }.validate().len());
+    assert_eq!(2, Params { temperature: 0.0, fb_lambda: 1.5, ..params.clone() }.validate().len());
+    assert_eq!(2, Params { umbrella_center: 600.0, umbrella_spring: -1.0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { multicanonical_bins: 10, multicanonical_min: -5.0, multicanonical_max: -6.0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { expanded_states: 1, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { tempering_states: 4, tempering_min: 1.0, tempering_max: 0.8, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { tempering_states: 4, tempering_min: 0.8, tempering_max: 1.2, expanded_states: 4, ..params.clone() }.validate().len());
+    assert!(Params { gb_kappa: 3.0, gb_kappa_prime: 5.0, rotations: true, ..params.clone() }.validate().is_empty());
+    assert_eq!(2, Params { gb_kappa: 3.0, gb_kappa_prime: 0.0, force_bias: true, rotations: true, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { gb_kappa: 3.0, gb_kappa_prime: 5.0, ..params.clone() }.validate().len());
+    assert!(Params { three_body_nu: 0.073, three_body_cutoff: 2.0, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { three_body_nu: 0.073, three_body_cutoff: 3.0, ..params.clone() }.validate().len());
+    assert_eq!(1, Params { three_body_nu: 0.073, ..params.clone() }.validate().len());
+    assert!(Params { dipole_moment: 1.0, field_strength: 0.5, field_direction: "1,1,0".to_string(), rotations: true, ..params.clone() }.validate().is_empty());
+    assert_eq!(2, Params { field_strength: 0.5, field_direction: "1,1".to_string(), ..params.clone() }.validate().len());
+    assert!(Params { restraints: "tether:0:1,1,1:10;slab:1:5".to_string(), vacuum_slab: 2.0, ..params.clone() }.validate().is_empty());
+    assert_eq!(1, Params { restraints: "slab:1:5".to_string(), ..params.clone() }.validate().len());
+    assert_eq!(1, Params { restraints: "distance:0:512:1:5".to_string(), ..params.clone() }.validate().len());
+    assert!(Params { recenter_interval: 1000, vacuum_slab: 2.0, ..params.clone() }.validate().is_empty());
+    assert_eq!(2, Params { recenter_interval: 1000, restraints: "tether:0:1,1,1:10".to_string(), ..params.clone() }.validate().len());
+}
+
+/// Step and average counters of a run
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Counters {
+    pub step: Step, // next step to run
+    pub step_counter: Step,
+    pub accept_counter: Step,
+    // energies and virials of the steps, see load_checkpoint for the sums of older checkpoints
+    #[serde(default)]
+    pub energy: Statistics,
+    #[serde(default)]
+    pub virial: Statistics,
+    pub scale_updates: Step,
+
+    // drift between running and recomputed energy/virial
+    pub energy_drift_max: f64,
+    pub virial_drift_max: f64,
+    pub energy_drift_sum: f64,
+
+    // sum of the magnitudes of the particle energies and virials of the accepted moves since the last recalculation, the scale of
+    // the rounding errors of the running energy, and the recalculations above the drift tolerance
+    #[serde(default)]
+    pub energy_turnover: f64,
+    #[serde(default)]
+    pub drift_violations: Step,
+
+    // attempted and accepted moves by trial displacement length in bins of DISPLACEMENT_BIN_WIDTH, kept over the
+    // displacement updates of the equilibration
+    #[serde(default)]
+    pub displacement_histogram: Vec<[Step; 2]>,
+
+    // visits of the largest cluster sizes under the umbrella bias
+    #[serde(default)]
+    pub cluster_histogram: Vec<Step>,
+
+    #[serde(default)]
+    pub rotation_attempts: Step,
+    #[serde(default)]
+    pub rotation_accepted: Step,
+
+    // trial moves rejected before all pairs were summed, and by the overlap check
+    #[serde(default)]
+    pub early_rejections: Step,
+    #[serde(default)]
+    pub overlap_rejections: Step,
+
+    // accumulators of the errors and the diagnostics of the mc report, part of the checkpoints so a restart reports
+    // the errors of the whole sampling (None before the first run and in older checkpoints)
+    #[serde(default)]
+    pub sampling: Option<SamplingBlocks>,
+}
+
+/// Block averages of the sampling steps for the errors and drifts of the report, and the energy decomposition and the
+/// surface tension sampled at the full recalculations
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SamplingBlocks {
+    pub energy: BlockAverage,
+    pub pressure: BlockAverage,
+    pub nematic: BlockAverage,
+    pub polarization: BlockAverage,
+    pub restraint: BlockAverage,
+    pub tension: BlockAverage,
+    pub repulsive_energy: Statistics,
+    pub attractive_energy: Statistics,
+    pub three_body_energy: Statistics,
+}
+
+impl SamplingBlocks {
+    /// Accumulators of blocks of block_size steps, the pressure in blocks of pressure_block_size samples (see
+    /// pressure_interval) and the surface tension in blocks of tension_block_size recalculations
+    pub fn new(block_size: Step, pressure_block_size: Step, tension_block_size: Step) -> SamplingBlocks {
+        return SamplingBlocks {
+            energy: BlockAverage::new(block_size),
+            pressure: BlockAverage::new(pressure_block_size),
+            nematic: BlockAverage::new(block_size),
+            polarization: BlockAverage::new(block_size),
+            restraint: BlockAverage::new(block_size),
+            tension: BlockAverage::new(tension_block_size),
+            repulsive_energy: Statistics::default(),
+            attractive_energy: Statistics::default(),
+            three_body_energy: Statistics::default(),
+        };
+    }
+}
+
+/// Averages of a sampling run with errors from block averaging
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Averages {
+    pub steps: Step,
+    pub energy: f64,
+    pub energy_error: f64,
+    pub virial: f64,
+    pub pressure: f64,
+    pub pressure_error: f64,
+    pub acceptance: f64,
+}
+
+/// State of a LJ system: configuration, derived parameters, running energy and counters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct System {
+    pub params: Params,
+
+    pub rx: Vec<Real>,
+    pub ry: Vec<Real>,
+    pub rz: Vec<Real>,
+    pub num_particles: usize,
+    pub l_x: f64,
+    pub l_y: f64,
+    pub l_z: f64,
+    pub volume: f64,
+    pub density: f64,
+
+    pub beta: f64,
+    pub cutoff_squared: f64,
+    pub max_displacement: f64, // displacement wont be scaled over that
+    pub displacement: f64,
+    #[serde(default)]
+    pub proposal: Proposal,
+
+    // orientation of every particle, empty without rotations
+    #[serde(default)]
+    pub orientations: Vec<Quaternion>,
+    #[serde(default)]
+    pub max_angle: f64,
+
+    // pair potential of anisotropic particles instead of the lj spheres
+    #[serde(default)]
+    pub gay_berne: Option<GayBerne>,
+
+    // external field times the dipole moment
+    #[serde(default)]
+    pub dipole_field: [f64; 3],
+
+    // cells of the overlap check
+    #[serde(default)]
+    pub cells: Option<CellList>,
+
+    // cells of the three-body cutoff for the triplets of the moved particle, the particles are searched among all
+    // without (older checkpoints)
+    #[serde(default)]
+    pub three_body_cells: Option<CellList>,
+
+    // restraints with their running energy, part of the energy
+    #[serde(default)]
+    pub restraints: Option<Restraints>,
+
+    // shift and tailcorrections
+    pub e_shift: f64,
+    #[serde(default)]
+    pub f_shift: f64, // force shift, 0 without
+    #[serde(default)]
+    pub switch_squared: f64, // 0 for the sharp cutoff
+    pub e_corr: f64,
+    pub p_corr: f64,
+
+    pub energy: f64,
+    pub virial: f64,
+
+    // particles are selected with probability selection_weights[i] / selection_weight_sum
+    pub pref_sampling: bool,
+    pub interfaces: (f64, f64),
+
+    // sum of the translations along z by the recentering
+    #[serde(default)]
+    pub slab_shift: f64,
+    pub selection_weights: Vec<f64>,
+    pub selection_weight_sum: f64,
+
+    // particle selected in the last trial move
+    #[serde(default)]
+    pub last_index: usize,
+
+    // the last trial move for the move log
+    #[serde(default)]
+    pub last_trial: TrialRecord,
+
+    // size of the largest liquid cluster, only updated with the umbrella bias
+    #[serde(default)]
+    pub cluster_size: usize,
+
+    #[serde(default)]
+    pub multicanonical: Option<Multicanonical>,
+
+    #[serde(default)]
+    pub expanded: Option<ExpandedEnsemble>,
+
+    #[serde(default)]
+    pub tempering: Option<SimulatedTempering>,
+
+    pub counters: Counters,
+
+    // the start of the run and its restarts, empty for systems not run by mc
+    #[serde(default)]
+    pub provenance: Vec<Provenance>,
+}
+
+// mixing function of splitmix64, the counter based derivation of the stream seeds
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return z ^ (z >> 31);
+}
+
+/// Uses of the random number streams of a master seed, every one numbers its streams on its own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamDomain {
+    /// The run of mc, stream 0
+    Run,
+    /// A restart of mc, the stream of its first step
+    Restart,
+    /// The replicas of mc --replicas, the stream of the replica
+    Replica,
+    /// The state points of validate
+    Validate,
+    /// The system sizes of finite_size
+    FiniteSize,
+    /// The runs of a parameter sweep
+    Sweep,
+}
+
+/// Random number generator of stream number stream in a domain of a master seed, the same for the same seed, domain
+/// and stream independent of the threads and the order they run in. Seed 0 seeds it from the os instead.
+pub fn get_stream_rng(seed: usize, domain: StreamDomain, stream: u64) -> StdRng {
+    if seed == 0 { return StdRng::new().expect("no random source for the seed"); }
+    // the domain tag turns the master seed into a seed of the domain, so equal stream numbers of two domains differ
+    let domain_seed = mix(mix(seed as u64) ^ domain as u64);
+    let key : Vec<usize> = (0..4u64).map(|k| mix(domain_seed ^ mix((stream << 2) | k)) as usize).collect();
+    return StdRng::from_seed(&key[..]);
+}
+
+#[test]
+fn test_get_stream_rng() {
+    let draw_in = |seed: usize, domain: StreamDomain, stream: u64| -> Vec<u64> {
+        let mut rng = get_stream_rng(seed, domain, stream);
+        (0..4).map(|_| rng.gen::<u64>()).collect()
+    };
+    let draw = |seed: usize, stream: u64| draw_in(seed, StreamDomain::Replica, stream);
+    assert_eq!(draw(42, 3), draw(42, 3));
+    assert!(draw(42, 3) != draw(42, 4) && draw(42, 3) != draw(43, 3));
+    // the consecutive streams of a seed are unrelated, not shifted copies of each other
+    assert!(!draw(42, 3).iter().any(|x| draw(42, 4).contains(x)));
+    assert!(draw(0, 3) != draw(0, 3));
+    // a restart at step 3 does not repeat replica 3, the run not the first replica
+    assert!(!draw(42, 3).iter().any(|x| draw_in(42, StreamDomain::Restart, 3).contains(x)));
+    assert!(draw(42, 0) != draw_in(42, StreamDomain::Run, 0));
+    assert!(draw_in(42, StreamDomain::Validate, 1) != draw_in(42, StreamDomain::FiniteSize, 1));
+}
+
+// integration points of the switching region in the tail corrections
+const SWITCH_TAIL_POINTS : usize = 1000;
+
+/// Energy and pressure tail corrections for the given cutoff and density. With a switching radius > 0 the
+/// part of the potential removed by the switching function between the radius and the cutoff is added.
+pub fn get_tail_corrections(cutoff: f64, density: f64, switch_radius: f64) -> (f64, f64) {
+    let mut e_corr = 8.0/3.0*::std::f64::consts::PI*density*::LJ_EPS*::LJ_SIG.powi(3)*((1.0/3.0*(::LJ_SIG/cutoff).powi(9)) - (::LJ_SIG/cutoff).powi(3));
+    let mut p_corr = 16.0/3.0*::std::f64::consts::PI*density.powi(2)*::LJ_EPS*::LJ_SIG.powi(3)*((2.0/3.0*(::LJ_SIG/cutoff).powi(9)) - (::LJ_SIG/cutoff).powi(3));
+    if switch_radius > 0.0 {
+        // midpoint rule of 2 pi rho int r^2 (U - U S) dr and 2/3 pi rho^2 int r^2 (W - W_switched) dr
+        let dr = (cutoff - switch_radius) / SWITCH_TAIL_POINTS as f64;
+        for i in 0..SWITCH_TAIL_POINTS {
+            let r = switch_radius + (i as f64 + 0.5) * dr;
+            let (e, v) = eval_pair_energy(r * r, 0.0);
+            let (e_switched, v_switched) = eval_truncated_pair_energy(r * r, 0.0, 0.0, cutoff * cutoff, switch_radius * switch_radius);
+            e_corr += 2.0 * ::std::f64::consts::PI * density * r * r * (e - e_switched) * dr;
+            p_corr += 2.0/3.0 * ::std::f64::consts::PI * density.powi(2) * r * r * (v - v_switched) * dr;
+        }
+    }
+    return (e_corr, p_corr);
+}
+
+#[test]
+fn test_get_tail_corrections() {
+    // the energy of a switching function starting at the cutoff changes nothing, the full correction from the switching radius is larger than the difference
+    let (e_corr, _) = get_tail_corrections(3.0, 0.8, 0.0);
+    let (e_switched, _) = get_tail_corrections(3.0, 0.8, 3.0 - 1e-6);
+    assert!( (e_corr - e_switched).abs() < 0.00001, "{} {}", e_corr, e_switched);
+    let (e_switched, _) = get_tail_corrections(3.0, 0.8, 2.5);
+    let (e_from_switch, _) = get_tail_corrections(2.5, 0.8, 0.0);
+    assert!(e_switched < e_corr && e_switched > e_from_switch, "{} {} {}", e_corr, e_switched, e_from_switch);
+}
+
+impl System {
+    /// Creates a system with randomly placed particles for the given parameters
+    pub fn new<R: Rng>(params: Params, rng: &mut R) -> System {
+        let num_particles = params.num_particles;
+        let mut density = params.density;
+        let mut volume = (num_particles as f64)/ density;
+        let length  = volume.cbrt();
+        let (l_x, l_y, mut l_z) = (length, length, length);
+
+        // randomly place particles in the box
+        let mut rx : Vec<Real> = vec![];
+        let mut ry : Vec<Real> = vec![];
+        let mut rz : Vec<Real> = vec![];
+        while rx.len() < num_particles {
+            rx.push(Real::from_f64(l_x * rng.gen::<f64>()));
+            ry.push(Real::from_f64(l_y * rng.gen::<f64>()));
+            rz.push(Real::from_f64(l_z * rng.gen::<f64>()));
+        }
+
+        // scale box in z for vacuum space and move particles in the middle of the box
+        if params.vacuum_slab > 0.0 {
+            let scale = params.vacuum_slab + 1.0;
+            l_z *= scale;
+            volume *= scale;
+            density /= scale;
+            let move_z = l_z/scale*params.vacuum_slab/2.0;
+            for i in 0..num_particles {
+                rz[i] = Real::from_f64(rz[i].to_f64() + move_z);
+            }
+        }
+
+        // calculation of shift and tailcorrections
+        let cutoff = params.cutoff;
+        let switch_squared = params.switch_radius.powi(2);
+        // gay-berne pairs are cut without shifts or tail corrections
+        let gay_berne = if params.gb_kappa > 0.0 { Some(GayBerne::new(params.gb_kappa, params.gb_kappa_prime)) } else { None };
+        let (e_shift, f_shift) = if gay_berne.is_some() { (0.0, 0.0) }
+            else if params.force_shift { get_force_shifts(cutoff) }
+            else if params.shift && params.switch_radius == 0.0 { (4.0 * ::LJ_EPS * ( (::LJ_SIG/cutoff).powi(12) - (::LJ_SIG/cutoff).powi(6) ), 0.0) } else { (0.0, 0.0) };
+        let (e_corr, p_corr) = if params.tailcorr && !params.force_shift && gay_berne.is_none() { get_tail_corrections(cutoff, density, params.switch_radius) } else { (0.0, 0.0) };
+
+        let cutoff_squared = cutoff * cutoff;
+        let (energy, virial) = get_total_energy(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, cutoff_squared, e_corr, e_shift, f_shift, switch_squared);
+
+        let direction = parse_direction(&params.field_direction).unwrap_or([0.0, 0.0, 1.0]);
+        let dipole_field = [params.dipole_moment * params.field_strength * direction[0], params.dipole_moment * params.field_strength * direction[1], params.dipole_moment * params.field_strength * direction[2]];
+        let restraints = Restraint::parse_list(&params.restraints, num_particles).filter(|list| !list.is_empty()).map(|list| Restraints::new(list, &rx, &ry, &rz, num_particles, l_x, l_y, l_z));
+        let cells = if params.overlap_radius > 0.0 { Some(CellList::new(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, params.overlap_radius)) } else { None };
+        let three_body_cells = if params.three_body_nu != 0.0 { Some(CellList::new(&rx, &ry, &rz, num_particles, l_x, l_y, l_z, params.three_body_cutoff)) } else { None };
+        let orientations = if params.rotations { (0..num_particles).map(|_| random_orientation(rng)).collect() } else { vec![] };
+        let (params_min, params_max, params_bins) = (params.multicanonical_min, params.multicanonical_max, params.multicanonical_bins);
+        let params_states = params.expanded_states;
+        let tempering = if params.tempering_states > 0 { Some(SimulatedTempering::new(params.tempering_min, params.tempering_max, params.tempering_states, params.temperature)) } else { None };
+        let mut system = System {
+            pref_sampling: params.vacuum_slab > 0.0 && params.interface_factor != 1.0,
+            beta: 1.0/params.temperature,
+            max_displacement: length / 2.0,
+            displacement: params.displacement,
+            proposal: Proposal::parse(&params.proposal).unwrap_or_default(),
+            orientations: orientations,
+            max_angle: ROTATION_ANGLE,
+            gay_berne: gay_berne,
+            dipole_field: dipole_field,
+            restraints: restraints,
+            cells: cells,
+            three_body_cells: three_body_cells,
+            params: params,
+            rx: rx,
+            ry: ry,
+            rz: rz,
+            num_particles: num_particles,
+            l_x: l_x,
+            l_y: l_y,
+            l_z: l_z,
+            volume: volume,
+            density: density,
+            cutoff_squared: cutoff_squared,
+            e_shift: e_shift,
+            f_shift: f_shift,
+            switch_squared: switch_squared,
+            e_corr: e_corr,
+            p_corr: p_corr,
+            energy: energy,
+            virial: virial,
+            interfaces: (0.0, 0.0),
+            slab_shift: 0.0,
+            selection_weights: vec![1.0; num_particles],
+            selection_weight_sum: num_particles as f64,
+            last_index: 0,
+            last_trial: TrialRecord::default(),
+            cluster_size: 0,
+            multicanonical: if params_bins > 0 { Some(Multicanonical::new(params_min * num_particles as f64, params_max * num_particles as f64, params_bins)) } else { None },
+            expanded: if params_states > 0 { Some(ExpandedEnsemble::new(params_states)) } else { None },
+            tempering: tempering,
+            counters: Counters::default(),
+            provenance: vec![],
+        };
+        if system.gay_berne.is_some() || system.params.three_body_nu != 0.0 || system.params.field_strength != 0.0 || system.restraints.is_some() {
+            let (energy, virial) = system.total_energy();
+            system.energy = energy;
+            system.virial = virial;
+        }
+        if let Some(temperature) = system.tempering.as_ref().map(|tempering| tempering.temperature()) { system.set_temperature(temperature); }
+        if system.params.umbrella_spring > 0.0 { system.cluster_size = system.get_largest_cluster_size(); }
+        return system;
+    }
+
+    /// Size of the largest cluster of liquid-like particles of the configuration
+    pub fn get_largest_cluster_size(&self) -> usize {
+        return get_largest_liquid_cluster(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, NEIGHBOR_RADIUS, LIQUID_NEIGHBORS);
+    }
+
+    /// Umbrella bias energy of a largest cluster size
+    pub fn get_umbrella_bias(&self, size: usize) -> f64 {
+        return 0.5 * self.params.umbrella_spring * (size as f64 - self.params.umbrella_center).powi(2);
+    }
+
+    /// Counts the current largest cluster size in the histogram of the umbrella bias
+    pub fn record_cluster_size(&mut self) {
+        if self.params.umbrella_spring == 0.0 { return; }
+        let histogram = &mut self.counters.cluster_histogram;
+        if histogram.len() <= self.cluster_size { histogram.resize(self.cluster_size + 1, 0); }
+        histogram[self.cluster_size] += 1;
+    }
+
+    /// Free energy in kT of every largest cluster size from the biased histogram, -ln P(n) - W(n)/kT relative to its
+    /// minimum. NaN for sizes that were not visited.
+    pub fn get_cluster_free_energy(&self) -> Vec<f64> {
+        let histogram = &self.counters.cluster_histogram;
+        let free_energy : Vec<f64> = (0..histogram.len()).map(|n| if histogram[n] > 0 { -(histogram[n] as f64).ln() - self.beta * self.get_umbrella_bias(n) } else { f64::NAN }).collect();
+        let minimum = free_energy.iter().cloned().filter(|f| f.is_finite()).fold(f64::INFINITY, f64::min);
+        return free_energy.iter().map(|f| f - minimum).collect();
+    }
+
+    /// Counts the last trial move by its displacement length in the acceptance histogram
+    pub fn record_trial_displacement(&mut self) {
+        let d = self.last_trial.displacement;
+        let bin = ((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() / DISPLACEMENT_BIN_WIDTH) as usize;
+        let histogram = &mut self.counters.displacement_histogram;
+        if histogram.len() <= bin { histogram.resize(bin + 1, [0, 0]); }
+        histogram[bin][0] += 1;
+        if self.last_trial.outcome == Outcome::Accepted { histogram[bin][1] += 1; }
+    }
+
+    /// Center of every displacement length bin, the attempted moves and their acceptance ratio (NaN without attempts)
+    pub fn get_displacement_acceptance(&self) -> Vec<(f64, Step, f64)> {
+        return self.counters.displacement_histogram.iter().enumerate()
+            .map(|(k, &[attempts, accepted])| ((k as f64 + 0.5) * DISPLACEMENT_BIN_WIDTH, attempts, accepted as f64 / attempts as f64)).collect();
+    }
+
+    /// attempted single particle moves per reported step (one sweep = num_particles moves)
+    pub fn moves_per_step(&self) -> Step {
+        return if self.params.sweeps { self.num_particles as Step } else { 1 };
+    }
+
+    /// steps between displacement updates during equilibration
+    pub fn scale_interval(&self) -> Step {
+        return if self.params.sweeps { SWEEP_SCALE_INTERVAL } else { SCALE_INTERVAL };
+    }
+
+    /// Moves the displacement towards the intended acceptance with a robbins-monro step based on the
+    /// acceptance since the last update and resets the averages
+    pub fn adapt_displacement(&mut self) {
+        if self.counters.step_counter > 0 {
+            let acceptance = self.counters.accept_counter as f64 / (self.counters.step_counter * self.moves_per_step()) as f64;
+            self.displacement = robbins_monro_update(self.displacement, acceptance, 1.0/TRIES_INTENDED, self.counters.scale_updates, RM_GAIN, RM_DECAY).min(self.max_displacement);
+            if self.counters.rotation_attempts > 0 {
+                let acceptance = self.counters.rotation_accepted as f64 / self.counters.rotation_attempts as f64;
+                self.max_angle = robbins_monro_update(self.max_angle, acceptance, 1.0/TRIES_INTENDED, self.counters.scale_updates, RM_GAIN, RM_DECAY).min(::std::f64::consts::PI);
+            }
+            self.counters.scale_updates += 1;
+        }
+        self.reset_averages();
+    }
+
+    /// Equilibration without output: interface updates and displacement adaptation as configured
+    pub fn equilibrate<R: Rng>(&mut self, steps: Step, rng: &mut R) {
+        for step in 0..steps {
+            if self.pref_sampling && step % self.scale_interval() == 0 {
+                self.update_interfaces();
+            }
+            self.run(1, rng);
+            if self.params.scale && step % self.scale_interval() == 0 {
+                self.adapt_displacement();
+            }
+            if step % self.scale_interval() == 0 {
+                self.update_weights();
+            }
+        }
+        self.freeze_weights();
+        // the overlaps of a random start leave rounding errors in the running energy
+        let (energy, virial) = self.total_energy();
+        self.apply_recomputed_energy(energy, virial);
+        self.reset_averages();
+    }
+
+    /// Samples steps steps without output and returns the averages
+    pub fn sample<R: Rng>(&mut self, steps: Step, rng: &mut R) -> Averages {
+        self.reset_averages();
+        let mut energy_blocks = BlockAverage::new(steps / SAMPLE_BLOCKS);
+        let mut pressure_blocks = BlockAverage::new(steps / self.pressure_interval() / SAMPLE_BLOCKS);
+        for _ in 0..steps {
+            let sampled = self.samples_pressure();
+            self.run(1, rng);
+            energy_blocks.push(self.energy);
+            if sampled { pressure_blocks.push(self.pressure()); }
+        }
+        let counters = &self.counters;
+        return Averages {
+            steps: steps,
+            energy: counters.energy.mean(),
+            energy_error: energy_blocks.error(),
+            virial: counters.virial.mean(),
+            pressure: counters.virial.mean() / 3.0 / self.volume + self.density * self.params.temperature + self.p_corr,
+            pressure_error: pressure_blocks.error(),
+            acceptance: counters.accept_counter as f64 / (counters.step_counter * self.moves_per_step()) as f64,
+        };
+    }
+
+    /// Locates the slab interfaces and updates the particle selection weights
+    pub fn update_interfaces(&mut self) {
+        self.interfaces = find_slab_interfaces(&self.rz, self.num_particles, self.l_z, 20);
+        self.selection_weight_sum = 0.0;
+        for i in 0..self.num_particles {
+            self.selection_weights[i] = get_interface_weight(self.rz[i].to_f64(), self.interfaces, self.l_z, self.params.interface_width, self.params.interface_factor);
+            self.selection_weight_sum += self.selection_weights[i];
+        }
+    }
+
+    /// Translates all particles along z so that the liquid slab is centered in the box and returns the shift. The
+    /// energy does not change, apart from the slab restraint, and the interfaces of the particle selection move along.
+    pub fn recenter_slab(&mut self) -> f64 {
+        let l_z = self.l_z;
+        let shift = l_z / 2.0 - get_slab_center(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, l_z);
+        for z in self.rz.iter_mut() {
+            *z = Real::from_f64((z.to_f64() + shift + l_z) % l_z);
+        }
+        self.interfaces = ((self.interfaces.0 + shift + l_z) % l_z, (self.interfaces.1 + shift + l_z) % l_z);
+        self.slab_shift += shift;
+        self.rebuild_cells();
+        if self.restraints.is_some() {
+            let old = self.get_restraint_energy();
+            self.refresh_restraints();
+            self.energy += self.get_restraint_energy() - old;
+        }
+        return shift;
+    }
+
+    /// One metropolis trial move of a randomly selected particle, returns true if the move was accepted
+    pub fn trial_move<R: Rng>(&mut self, rng: &mut R) -> bool {
+        let num_particles = self.num_particles;
+        let (l_x, l_y, l_z) = (self.l_x, self.l_y, self.l_z);
+        let beta = self.beta;
+        let displacement = self.displacement;
+        let fb_lambda = self.params.fb_lambda;
+
+        // select rnd particle
+        let rnd_index = if self.pref_sampling {
+            let mut u = rng.gen::<f64>() * self.selection_weight_sum;
+            let mut index = num_particles - 1;
+            for i in 0..num_particles {
+                u -= self.selection_weights[i];
+                if u < 0.0 { index = i; break; }
+            }
+            index
+        } else {
+            rng.gen_range(0, num_particles)
+        };
+        self.last_index = rnd_index;
+
+        // store old position
+        let old_x = self.rx[rnd_index];
+        let old_y = self.ry[rnd_index];
+        let old_z = self.rz[rnd_index];
+
+        // old particle energy, the lazy virial leaves the virial to the full recalculations
+        let lazy_virial = self.params.lazy_virial;
+        let (old_particle_energy, old_particle_virial) = if lazy_virial { (self.particle_energy_only(rnd_index), 0.0) } else { self.particle_energy(rnd_index) };
+        let lambda = self.expanded.as_ref().map_or(1.0, |expanded| expanded.lambda());
+
+        // rnd displacement (uniform or biased along the force) and PBC
+        let mut old_force = [0.0; 3];
+        let mut trial_displacement = [0.0; 3];
+        if self.params.force_bias {
+            let (f_x, f_y, f_z) = get_particle_forces(&self.rx, &self.ry, &self.rz, rnd_index, num_particles, l_x, l_y, l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+            old_force = [f_x, f_y, f_z];
+            for k in 0..3 {
+                trial_displacement[k] = sample_force_bias_displacement(old_force[k], beta, fb_lambda, displacement, rng.gen::<f64>());
+            }
+        } else {
+            trial_displacement = self.proposal.sample(displacement, rng);
+        }
+        let (mut x, mut y, mut z) = (old_x.to_f64() + trial_displacement[0], old_y.to_f64() + trial_displacement[1], old_z.to_f64() + trial_displacement[2]);
+        if x < 0.0 { x += l_x }
+        if x >= l_x { x -= l_x }
+        if y < 0.0 { y += l_y }
+        if y >= l_y { y -= l_y }
+        if z < 0.0 { z += l_z }
+        if z >= l_z { z -= l_z }
+        self.rx[rnd_index] = Real::from_f64(x);
+        self.ry[rnd_index] = Real::from_f64(y);
+        self.rz[rnd_index] = Real::from_f64(z);
+        self.last_trial = TrialRecord { step: self.counters.step, index: rnd_index, displacement: trial_displacement, ..TrialRecord::default() };
+
+        // hard core, moves into an overlap are rejected without the energy. Particles of the overlaps of a random start
+        // move by their energy until they are out.
+        if self.cells.as_ref().is_some_and(|cells| cells.has_overlap(&self.rx, &self.ry, &self.rz, rnd_index, x, y, z, l_x, l_y, l_z)
+                                                  && !cells.has_overlap(&self.rx, &self.ry, &self.rz, rnd_index, old_x.to_f64(), old_y.to_f64(), old_z.to_f64(), l_x, l_y, l_z)) {
+            self.counters.overlap_rejections += 1;
+            self.last_trial.outcome = Outcome::OverlapRejected;
+            self.rx[rnd_index] = old_x;
+            self.ry[rnd_index] = old_y;
+            self.rz[rnd_index] = old_z;
+            return false;
+        }
+
+        // correction for the position dependent particle selection
+        let mut log_correction = 0.0;
+        let new_weight = if self.pref_sampling { get_interface_weight(z, self.interfaces, l_z, self.params.interface_width, self.params.interface_factor) } else { 1.0 };
+        let new_weight_sum = self.selection_weight_sum - self.selection_weights[rnd_index] + new_weight;
+        if self.pref_sampling {
+            log_correction += (new_weight / new_weight_sum).ln() - (self.selection_weights[rnd_index] / self.selection_weight_sum).ln();
+        }
+
+        // restraints depend on the positions only and add nothing to the virial
+        let new_restraints = self.restraints.as_ref().map(|restraints| (restraints.get_move_energy(&self.rx, &self.ry, &self.rz, rnd_index, old_z.to_f64(), l_x, l_y, l_z), restraints.energy));
+        let d_restraints = new_restraints.map_or(0.0, |((energy, _), old_energy)| energy - old_energy);
+
+        // calculate energy difference, with early rejection against the pre-drawn metropolis threshold
+        let early = self.params.early_rejection && lambda == 1.0;
+        let u = if early { rng.gen::<f64>() } else { 0.0 };
+        let (new_particle_energy, new_particle_virial) = if early {
+            let limit = old_particle_energy - d_restraints + (log_correction - u.ln()) / beta;
+            match get_particle_energy_bounded(&self.rx, &self.ry, &self.rz, rnd_index, num_particles, l_x, l_y, l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared, limit) {
+                Some((energy, virial)) => (energy, if lazy_virial { 0.0 } else { virial }),
+                None => {
+                    self.counters.early_rejections += 1;
+                    self.last_trial.outcome = Outcome::EarlyRejected;
+                    self.last_trial.random = Some(u);
+                    self.rx[rnd_index] = old_x;
+                    self.ry[rnd_index] = old_y;
+                    self.rz[rnd_index] = old_z;
+                    return false;
+                },
+            }
+        } else if lazy_virial { (self.particle_energy_only(rnd_index), 0.0) } else { self.particle_energy(rnd_index) };
+
+        let d_e = new_particle_energy - old_particle_energy + d_restraints;
+        let d_v = new_particle_virial - old_particle_virial;
+
+        // non-symmetric proposal correction of force-bias moves
+        if self.params.force_bias {
+            let (f_x, f_y, f_z) = get_particle_forces(&self.rx, &self.ry, &self.rz, rnd_index, num_particles, l_x, l_y, l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+            log_correction += force_bias_log_correction(trial_displacement, old_force, [f_x, f_y, f_z], beta, fb_lambda, displacement);
+        }
+
+        // acceptance rule, the multicanonical weights replace the boltzmann factor once the energy is in their range
+        let mut random = None;
+        let mut accepted = match self.multicanonical {
+            Some(ref multicanonical) if multicanonical.get_bin(self.energy).is_some() => match multicanonical.get_bin(self.energy + d_e) {
+                Some(new_bin) => {
+                    let d_bias = multicanonical.ln_g[new_bin] - multicanonical.ln_g[multicanonical.get_bin(self.energy).unwrap()];
+                    (d_bias <= 0.0 && log_correction >= 0.0) || { let u = rng.gen::<f64>(); random = Some(u); u < (-d_bias + log_correction).exp() }
+                },
+                None => false,
+            },
+            _ => (d_e < 0.0 && log_correction >= 0.0) || { let u = if early { u } else { rng.gen::<f64>() }; random = Some(u); u < (-beta * d_e + log_correction).exp() },
+        };
+        self.last_trial.d_e = Some(d_e);
+        self.last_trial.random = random;
+
+        // the umbrella bias is a second metropolis step, so the clusters are only evaluated after moves the energy accepts
+        let mut cluster_size = self.cluster_size;
+        if accepted && self.params.umbrella_spring > 0.0 {
+            cluster_size = self.get_largest_cluster_size();
+            let d_w = self.get_umbrella_bias(cluster_size) - self.get_umbrella_bias(self.cluster_size);
+            accepted = d_w <= 0.0 || rng.gen::<f64>() < (-beta * d_w).exp();
+        }
+
+        if accepted {
+            self.counters.accept_counter += 1;
+            self.last_trial.outcome = Outcome::Accepted;
+            self.cluster_size = cluster_size;
+            self.energy += d_e;
+            self.virial += d_v;
+            self.counters.energy_turnover += old_particle_energy.abs() + new_particle_energy.abs() + old_particle_virial.abs() + new_particle_virial.abs();
+            self.selection_weights[rnd_index] = new_weight;
+            self.selection_weight_sum = new_weight_sum;
+            if let Some(ref mut cells) = self.cells { cells.update(rnd_index, x, y, z); }
+            if let Some(ref mut cells) = self.three_body_cells { cells.update(rnd_index, x, y, z); }
+            if let (Some(restraints), Some(((energy, phase_sums), _))) = (self.restraints.as_mut(), new_restraints) {
+                restraints.energy = energy;
+                restraints.phase_sums = phase_sums;
+            }
+        } else {
+            // restore old positions if move is rejected
+            self.rx[rnd_index] = old_x;
+            self.ry[rnd_index] = old_y;
+            self.rz[rnd_index] = old_z;
+        }
+        if let Some(ref mut multicanonical) = self.multicanonical { multicanonical.visit(self.energy); }
+        return accepted;
+    }
+
+    /// One sweep of trial moves by the checkerboard decomposition on checkerboard_threads threads: the colors of the
+    /// cells of a randomly shifted grid in random order, as many moves as there are particles. Returns the number of
+    /// accepted moves.
+    pub fn checkerboard_sweep<R: Rng>(&mut self, rng: &mut R) -> Step {
+        let pair = PairSystem { l_x: self.l_x, l_y: self.l_y, l_z: self.l_z, cutoff_squared: self.cutoff_squared, e_shift: self.e_shift, f_shift: self.f_shift,
+                                switch_squared: self.switch_squared, beta: self.beta, displacement: self.displacement };
+        let grid = get_sweep_grid(&self.rx, &self.ry, &self.rz, self.num_particles, &pair, rng);
+        let mut colors : Vec<usize> = (0..COLORS).collect();
+        rng.shuffle(&mut colors);
+        let mut accepted = 0;
+        for color in colors {
+            let moves = move_color(&self.rx, &self.ry, &self.rz, &grid, color, &pair, &self.proposal, self.params.checkerboard_threads, rng);
+            for &(i, x, y, z) in &moves.positions {
+                self.rx[i] = x;
+                self.ry[i] = y;
+                self.rz[i] = z;
+            }
+            self.energy += moves.d_energy;
+            self.virial += moves.d_virial;
+            self.counters.accept_counter += moves.accepted;
+            self.counters.energy_turnover += moves.turnover;
+            accepted += moves.accepted;
+        }
+        return accepted;
+    }
+
+    /// Runs steps reported steps: trial moves, energy recomputation every recompute_interval steps and the averages
+    pub fn run<R: Rng>(&mut self, steps: Step, rng: &mut R) {
+        for _ in 0..steps {
+            if self.params.checkerboard_threads > 0 {
+                self.checkerboard_sweep(rng);
+                for _ in 0..self.moves_per_step() { self.rotation_move(rng); }
+            } else {
+                for _ in 0..self.moves_per_step() {
+                    self.trial_move(rng);
+                    self.rotation_move(rng);
+                }
+            }
+            self.lambda_move(rng);
+            self.tempering_move(rng);
+            if self.params.recenter_interval > 0 && self.counters.step.is_multiple_of(self.params.recenter_interval) { self.recenter_slab(); }
+            let sampled = self.samples_pressure();
+            if self.params.recompute_interval > 0 && self.counters.step.is_multiple_of(self.params.recompute_interval) {
+                let (energy, virial) = self.total_energy();
+                self.apply_recomputed_energy(energy, virial);
+            }
+            self.counters.step += 1;
+            self.counters.step_counter += 1;
+            self.counters.energy.push(self.energy);
+            if sampled { self.counters.virial.push(self.virial); }
+            self.record_cluster_size();
+            self.record_multicanonical();
+            self.record_tempering();
+        }
+    }
+
+    /// True if the virial of the current step is sampled: every step, with the lazy virial only at the full
+    /// recalculations every recompute_interval steps
+    pub fn samples_pressure(&self) -> bool {
+        return !self.params.lazy_virial || self.counters.step.is_multiple_of(self.params.recompute_interval.max(1));
+    }
+
+    /// Steps between the samples of the virial and the pressure
+    pub fn pressure_interval(&self) -> Step {
+        return if self.params.lazy_virial { self.params.recompute_interval.max(1) } else { 1 };
+    }
+
+    /// Attempts a rotation of a random particle by a random angle up to max_angle, returns true if it was accepted. The
+    /// lj pair energies do not depend on the orientations, so all rotations of spheres without a field are accepted.
+    pub fn rotation_move<R: Rng>(&mut self, rng: &mut R) -> bool {
+        if self.orientations.is_empty() { return false; }
+        let index = rng.gen_range(0, self.num_particles);
+        let old = self.orientations[index];
+        self.counters.rotation_attempts += 1;
+        if self.gay_berne.is_none() && self.params.field_strength == 0.0 {
+            self.orientations[index] = normalize(multiply(random_rotation(rng, self.max_angle), old));
+            self.counters.rotation_accepted += 1;
+            return true;
+        }
+        let (old_energy, old_virial) = self.get_orientation_energy(index);
+        self.orientations[index] = normalize(multiply(random_rotation(rng, self.max_angle), old));
+        let (new_energy, new_virial) = self.get_orientation_energy(index);
+        let d_e = new_energy - old_energy;
+        if d_e > 0.0 && rng.gen::<f64>() >= (-self.beta * d_e).exp() {
+            self.orientations[index] = old;
+            return false;
+        }
+        self.energy += d_e;
+        self.virial += new_virial - old_virial;
+        self.counters.rotation_accepted += 1;
+        return true;
+    }
+
+    // energy and virial of particle index that depend on its orientation: the gay-berne pairs and the dipole in the field
+    fn get_orientation_energy(&self, index: usize) -> (f64, f64) {
+        let (energy, virial) = if self.gay_berne.is_some() { self.particle_energy(index) } else { (0.0, 0.0) };
+        return (energy + self.get_field_energy(index), virial);
+    }
+
+    /// Energy -mu E of the dipole of particle index in the external field
+    pub fn get_field_energy(&self, index: usize) -> f64 {
+        let axis = get_axis(self.orientations[index]);
+        return -(0..3).map(|k| self.dipole_field[k] * axis[k]).sum::<f64>();
+    }
+
+    /// Dipole moment per volume of the configuration
+    pub fn get_polarization(&self) -> [f64; 3] {
+        let mut polarization = [0.0; 3];
+        for &orientation in &self.orientations {
+            let axis = get_axis(orientation);
+            for k in 0..3 {
+                polarization[k] += self.params.dipole_moment * axis[k] / self.volume;
+            }
+        }
+        return polarization;
+    }
+
+    /// Wang-Landau step of the learned weights: halves the modification factors of flat histograms
+    pub fn update_weights(&mut self) {
+        if let Some(ref mut multicanonical) = self.multicanonical { multicanonical.update_modification_factor(); }
+        if let Some(ref mut expanded) = self.expanded { expanded.ladder.update_modification_factor(); }
+        if let Some(ref mut tempering) = self.tempering { tempering.ladder.update_modification_factor(); }
+    }
+
+    /// Fixes the learned weights for the sampling
+    pub fn freeze_weights(&mut self) {
+        if let Some(ref mut multicanonical) = self.multicanonical { multicanonical.freeze(); }
+        if let Some(ref mut expanded) = self.expanded { expanded.freeze(); }
+        if let Some(ref mut tempering) = self.tempering { tempering.freeze(); }
+    }
+
+    // pair energy and virial of the solute and particle j at coupling lambda, the virial -r du/dr of lambda U(s) with
+    // the soft-core distance s is lambda W(s) r^6 / s^6
+    fn get_coupled_pair_energy(&self, j: usize, lambda: f64) -> (f64, f64) {
+        let dist_squared = get_particle_distance_squared(self.rx[j].to_f64(), self.ry[j].to_f64(), self.rz[j].to_f64(), self.rx[SOLUTE].to_f64(), self.ry[SOLUTE].to_f64(), self.rz[SOLUTE].to_f64(),
+                                                         self.l_x, self.l_y, self.l_z, self.l_x / 2.0, self.l_y / 2.0, self.l_z / 2.0);
+        let soft_squared = get_soft_core_distance_squared(dist_squared, lambda);
+        if dist_squared >= self.cutoff_squared || soft_squared >= self.cutoff_squared { return (0.0, 0.0); }
+        let (energy, virial) = eval_truncated_pair_energy(soft_squared, self.e_shift, self.f_shift, self.cutoff_squared, self.switch_squared);
+        return (lambda * energy, lambda * virial * (dist_squared / soft_squared).powi(3));
+    }
+
+    /// Energy and virial of the solute at coupling lambda
+    pub fn get_solute_energy(&self, lambda: f64) -> (f64, f64) {
+        let (mut energy, mut virial) = (0.0, 0.0);
+        for j in (0..self.num_particles).filter(|&j| j != SOLUTE) {
+            let (e, v) = self.get_coupled_pair_energy(j, lambda);
+            energy += e;
+            virial += v;
+        }
+        return (energy, virial);
+    }
+
+    /// Attempts a move of the expanded ensemble to a neighboring coupling state, returns true if it was accepted. The
+    /// full solute energy of the decoupled state is also a widom insertion.
+    pub fn lambda_move<R: Rng>(&mut self, rng: &mut R) -> bool {
+        let (old, new, lambdas) = match self.expanded {
+            Some(ref expanded) => (expanded.ladder.state, expanded.ladder.propose(rng), expanded.lambdas.clone()),
+            None => return false,
+        };
+        let old_energy = self.get_solute_energy(lambdas[old]);
+        let (d_e, d_v) = new.map_or((0.0, 0.0), |new| { let e = self.get_solute_energy(lambdas[new]); (e.0 - old_energy.0, e.1 - old_energy.1) });
+        let widom = if old == 0 { (-self.beta * self.get_solute_energy(1.0).0).exp() } else { 0.0 };
+        let beta = self.beta;
+        let expanded = self.expanded.as_mut().unwrap();
+        if old == 0 && !expanded.ladder.learning {
+            expanded.widom.push(widom);
+        }
+        let accepted = expanded.ladder.step(new, beta * d_e, rng);
+        if accepted {
+            self.energy += d_e;
+            self.virial += d_v;
+        }
+        return accepted;
+    }
+
+    /// Attempts a move of simulated tempering to a neighboring temperature, returns true if it was accepted
+    pub fn tempering_move<R: Rng>(&mut self, rng: &mut R) -> bool {
+        let (energy, old_beta) = (self.energy, self.beta);
+        let temperature = match self.tempering {
+            Some(ref mut tempering) => {
+                let new = tempering.ladder.propose(rng);
+                let d_beta = new.map_or(0.0, |new| 1.0 / tempering.temperatures[new] - old_beta);
+                if !tempering.ladder.step(new, d_beta * energy, rng) { return false; }
+                tempering.temperature()
+            },
+            None => return false,
+        };
+        self.set_temperature(temperature);
+        return true;
+    }
+
+    /// Adds the current energy and virial to the sums of the current temperature of simulated tempering
+    pub fn record_tempering(&mut self) {
+        let (energy, virial) = (self.energy, self.virial);
+        if let Some(ref mut tempering) = self.tempering {
+            if !tempering.ladder.learning { tempering.record(energy, virial); }
+        }
+    }
+
+    /// Adds the current energy and virial to the reweighting sums of the multicanonical sampling
+    pub fn record_multicanonical(&mut self) {
+        let (energy, virial, beta) = (self.energy, self.virial, self.beta);
+        if let Some(ref mut multicanonical) = self.multicanonical {
+            if !multicanonical.learning { multicanonical.record(energy, virial, beta); }
+        }
+    }
+
+    /// Canonical energy and pressure at the temperature of the system reweighted from the multicanonical sampling
+    pub fn get_multicanonical_averages(&self) -> Option<(f64, f64)> {
+        let multicanonical = self.multicanonical.as_ref()?;
+        let (energy, virial) = multicanonical.get_canonical_averages(self.beta);
+        return Some((energy, virial / 3.0 / self.volume + self.density * self.params.temperature + self.p_corr));
+    }
+
+    /// Resets the acceptance and average sums, e.g. after equilibration
+    pub fn reset_averages(&mut self) {
+        self.counters.step_counter = 0;
+        self.counters.accept_counter = 0;
+        self.counters.energy.clear();
+        self.counters.virial.clear();
+        self.counters.cluster_histogram.clear();
+        self.counters.rotation_attempts = 0;
+        self.counters.rotation_accepted = 0;
+        self.counters.early_rejections = 0;
+        self.counters.overlap_rejections = 0;
+    }
+
+    /// Changes the temperature of the system
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.params.temperature = temperature;
+        self.beta = 1.0 / temperature;
+    }
+
+    /// Changes the density by scaling box and positions uniformly, fails if the cutoff would exceed half the box
+    pub fn set_density(&mut self, density: f64) -> ::std::result::Result<(), String> {
+        let scale = (self.density / density).cbrt();
+        if self.params.cutoff > self.l_x.min(self.l_y).min(self.l_z) * scale / 2.0 {
+            return Err(format!("Cutoff {} is larger than half the box length at density {}.", self.params.cutoff, density));
+        }
+        if self.params.three_body_nu != 0.0 && self.params.three_body_cutoff > self.l_x.min(self.l_y).min(self.l_z) * scale / 4.0 {
+            return Err(format!("Three-body cutoff {} is larger than a quarter of the box length at density {}.", self.params.three_body_cutoff, density));
+        }
+        for i in 0..self.num_particles {
+            self.rx[i] = Real::from_f64(self.rx[i].to_f64() * scale);
+            self.ry[i] = Real::from_f64(self.ry[i].to_f64() * scale);
+            self.rz[i] = Real::from_f64(self.rz[i].to_f64() * scale);
+        }
+        self.l_x *= scale;
+        self.l_y *= scale;
+        self.l_z *= scale;
+        self.params.density *= density / self.density;
+        self.volume *= scale.powi(3);
+        self.density = density;
+        self.max_displacement = self.l_x / 2.0;
+        self.displacement = self.displacement.min(self.max_displacement);
+        if self.params.tailcorr && !self.params.force_shift && self.gay_berne.is_none() {
+            let (e_corr, p_corr) = get_tail_corrections(self.params.cutoff, density, self.params.switch_radius);
+            self.e_corr = e_corr;
+            self.p_corr = p_corr;
+        }
+        self.refresh_restraints();
+        self.rebuild_cells();
+        let (energy, virial) = self.total_energy();
+        self.energy = energy;
+        self.virial = virial;
+        if self.params.umbrella_spring > 0.0 { self.cluster_size = self.get_largest_cluster_size(); }
+        return Ok(());
+    }
+
+    /// Opens a vacuum slab like the one of the start: extends the box along z by vacuum times its length with the
+    /// particles in the middle, fails for a system with a slab already
+    pub fn open_vacuum(&mut self, vacuum: f64) -> ::std::result::Result<(), String> {
+        if self.params.vacuum_slab > 0.0 { return Err("The system has a vacuum slab already.".to_string()); }
+        let move_z = self.l_z * vacuum / 2.0;
+        for i in 0..self.num_particles {
+            self.rz[i] = Real::from_f64(self.rz[i].to_f64() + move_z);
+        }
+        let scale = vacuum + 1.0;
+        self.l_z *= scale;
+        self.volume *= scale;
+        self.density /= scale;
+        self.params.vacuum_slab = vacuum;
+        if self.params.tailcorr && !self.params.force_shift && self.gay_berne.is_none() {
+            let (e_corr, p_corr) = get_tail_corrections(self.params.cutoff, self.density, self.params.switch_radius);
+            self.e_corr = e_corr;
+            self.p_corr = p_corr;
+        }
+        self.refresh_restraints();
+        self.rebuild_cells();
+        let (energy, virial) = self.total_energy();
+        self.energy = energy;
+        self.virial = virial;
+        return Ok(());
+    }
+
+    /// Pressure of the current configuration including the tail correction
+    pub fn pressure(&self) -> f64 {
+        return self.virial / 3.0 / self.volume + self.density * self.params.temperature + self.p_corr;
+    }
+
+    /// total energy and virial recalculated from scratch
+    pub fn total_energy(&self) -> (f64, f64) {
+        let field_energy : f64 = if self.params.field_strength != 0.0 { (0..self.num_particles).map(|i| self.get_field_energy(i)).sum() } else { 0.0 };
+        let field_energy = field_energy + self.restraints.as_ref().map_or(0.0, |restraints| restraints.get_energy(&self.rx, &self.ry, &self.rz, self.l_x, self.l_y, self.l_z, Restraints::get_phase_sums(&self.rz, self.num_particles, self.l_z)));
+        if let Some(ref gay_berne) = self.gay_berne {
+            let (energy, virial) = gay_berne.get_total_energy(&self.rx, &self.ry, &self.rz, &self.orientations, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared);
+            return (energy + field_energy, virial);
+        }
+        let lambda = self.expanded.as_ref().map_or(1.0, |expanded| expanded.lambda());
+        let (mut energy, mut virial) = if lambda < 1.0 {
+            // the solvent after the solute (particle 0) and the solute at its coupling, the full pairs of the overlaps
+            // the soft core allows would cancel out all precision
+            let (e, v) = get_total_energy(&self.rx[1..], &self.ry[1..], &self.rz[1..], self.num_particles - 1, self.l_x, self.l_y, self.l_z, self.cutoff_squared, 0.0, self.e_shift, self.f_shift, self.switch_squared);
+            let (e_solute, v_solute) = self.get_solute_energy(lambda);
+            (e + e_solute + self.num_particles as f64 * self.e_corr, v + v_solute)
+        } else {
+            get_total_energy(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared, self.e_corr, self.e_shift, self.f_shift, self.switch_squared)
+        };
+        if self.params.three_body_nu != 0.0 {
+            let (e, v) = self.get_three_body_energy();
+            energy += e;
+            virial += v;
+        }
+        return (energy + field_energy, virial);
+    }
+
+    /// Energy and virial of particle index with all others, the pairs of the solute at its coupling
+    pub fn particle_energy(&self, index: usize) -> (f64, f64) {
+        match self.expanded {
+            Some(ref expanded) if expanded.lambda() < 1.0 => {
+                if index == SOLUTE { return self.get_solute_energy(expanded.lambda()); }
+                let (energy, virial) = get_particle_energy(&self.rx[1..], &self.ry[1..], &self.rz[1..], index - 1, self.num_particles - 1, self.l_x, self.l_y, self.l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+                let (e, v) = self.get_coupled_pair_energy(index, expanded.lambda());
+                return (energy + e, virial + v);
+            },
+            _ => {},
+        }
+        if let Some(ref gay_berne) = self.gay_berne {
+            return gay_berne.get_particle_energy(&self.rx, &self.ry, &self.rz, &self.orientations, index, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared);
+        }
+        let (energy, virial) = get_particle_energy(&self.rx, &self.ry, &self.rz, index, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+        if self.params.three_body_nu == 0.0 { return (energy, virial); }
+        let (e, v) = get_particle_three_body_energy(&self.rx, &self.ry, &self.rz, index, self.num_particles, self.l_x, self.l_y, self.l_z, self.params.three_body_cutoff.powi(2), self.params.three_body_nu, self.three_body_cells.as_ref());
+        return (energy + e, virial + v);
+    }
+
+    /// Energy of particle index with all others without the virial, for the plain lj pairs of the lazy virial
+    pub fn particle_energy_only(&self, index: usize) -> f64 {
+        return get_particle_energy_only(&self.rx, &self.ry, &self.rz, index, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+    }
+
+    /// Axilrod-Teller energy and virial of all triplets within the three-body cutoff
+    pub fn get_three_body_energy(&self) -> (f64, f64) {
+        if self.params.three_body_nu == 0.0 { return (0.0, 0.0); }
+        return get_total_three_body_energy(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.params.three_body_cutoff.powi(2), self.params.three_body_nu);
+    }
+
+    /// Running energy of the restraints, 0 without
+    pub fn get_restraint_energy(&self) -> f64 {
+        return self.restraints.as_ref().map_or(0.0, |restraints| restraints.energy);
+    }
+
+    // cells of the overlap check and the three-body triplets from scratch
+    fn rebuild_cells(&mut self) {
+        if self.cells.is_some() { self.cells = Some(CellList::new(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.params.overlap_radius)); }
+        if self.three_body_cells.is_some() { self.three_body_cells = Some(CellList::new(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.params.three_body_cutoff)); }
+    }
+
+    // restraint energy and phase sums from scratch
+    fn refresh_restraints(&mut self) {
+        if let Some(ref mut restraints) = self.restraints {
+            restraints.phase_sums = Restraints::get_phase_sums(&self.rz, self.num_particles, self.l_z);
+            restraints.energy = restraints.get_energy(&self.rx, &self.ry, &self.rz, self.l_x, self.l_y, self.l_z, restraints.phase_sums);
+        }
+    }
+
+    /// Message if the running energy or virial differ from the recomputed values by more than the drift tolerance
+    /// relative to the recomputed ones, on top of the rounding the accepted moves can leave
+    pub fn check_drift(&self, energy: f64, virial: f64) -> Option<String> {
+        let tolerance = self.params.drift_tolerance;
+        if tolerance == 0.0 { return None; }
+        let rounding = DRIFT_ROUNDING * self.counters.energy_turnover;
+        // the lazy virial only holds between the recalculations
+        let (d_e, d_v) = ((self.energy - energy).abs(), if self.params.lazy_virial { 0.0 } else { (self.virial - virial).abs() });
+        if d_e <= tolerance * energy.abs().max(1.0) + rounding && d_v <= tolerance * virial.abs().max(1.0) + rounding { return None; }
+        return Some(format!("Energy drift above the tolerance {:e} at step {}: running energy {} against {} recomputed, running virial {} against {}",
+                            tolerance, self.counters.step, self.energy, energy, self.virial, virial));
+    }
+
+    /// Replaces the running energy and virial by recomputed values and tracks the drift between both
+    pub fn apply_recomputed_energy(&mut self, energy: f64, virial: f64) {
+        if self.check_drift(energy, virial).is_some() { self.counters.drift_violations += 1; }
+        self.counters.energy_turnover = 0.0;
+        self.refresh_restraints();
+        self.counters.energy_drift_max = self.counters.energy_drift_max.max((self.energy - energy).abs());
+        if !self.params.lazy_virial { self.counters.virial_drift_max = self.counters.virial_drift_max.max((self.virial - virial).abs()); }
+        self.counters.energy_drift_sum += self.energy - energy;
+        self.energy = energy;
+        self.virial = virial;
+    }
+
+    /// Copy of the current configuration after step completed MC steps as trajectory frame
+    pub fn frame(&self, step: Step) -> Frame {
+        return Frame {
+            rx: self.rx.clone(),
+            ry: self.ry.clone(),
+            rz: self.rz.clone(),
+            num_particles: self.num_particles,
+            box_x: self.l_x,
+            box_y: self.l_y,
+            box_z: self.l_z,
+            temperature: self.params.temperature,
+            lj_eps: ::LJ_EPS,
+            lj_sig: ::LJ_SIG,
+            lj_cutoff: self.params.cutoff,
+            ensemble: ::trajectory::ENSEMBLE.to_string(),
+            step: Some(step),
+            sweep: Some(self.get_sweeps(step)),
+        };
+    }
+
+    /// Writes the current configuration after step completed MC steps as frame to the trajectory with the per-particle
+    /// columns of the parameters and the orientations of rotated particles
+    pub fn write_frame(&self, trajectory: &mut XYZTrajectory, step: Step) -> Result<()> {
+        let columns = ParticleColumn::parse_list(&self.params.particle_columns).unwrap_or_default();
+        let mut values = self.get_particle_columns(&columns);
+        if !self.orientations.is_empty() {
+            values.push(("orientation:R:4", self.orientations.iter().map(|q| q.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" ")).collect()));
+        }
+        return trajectory.write_with_columns(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.params.temperature, ::LJ_EPS, ::LJ_SIG, self.params.cutoff,
+                                             Some(step), Some(self.get_sweeps(step)), &values, false);
+    }
+
+    /// Values of per-particle columns for the trajectory
+    pub fn get_particle_columns(&self, columns: &[ParticleColumn]) -> Vec<(&'static str, Vec<String>)> {
+        let n = self.num_particles;
+        return columns.iter().map(|column| {
+            let values : Vec<String> = match *column {
+                ParticleColumn::Energy => self.get_particle_energies().iter().map(|e| e.to_string()).collect(),
+                ParticleColumn::Cluster => get_cluster_ids(&self.rx, &self.ry, &self.rz, n, self.l_x, self.l_y, self.l_z, NEIGHBOR_RADIUS).iter().map(|c| c.to_string()).collect(),
+                ParticleColumn::LocalDensity => get_local_densities(&self.rx, &self.ry, &self.rz, n, self.l_x, self.l_y, self.l_z, NEIGHBOR_RADIUS).iter().map(|d| d.to_string()).collect(),
+                ParticleColumn::Virial => self.get_particle_virials().iter().map(|w| (w[0] + w[1] + w[2]).to_string()).collect(),
+                ParticleColumn::VoronoiVolume => get_voronoi_volumes(&self.rx, &self.ry, &self.rz, n, self.l_x, self.l_y, self.l_z).iter().map(|v| v.to_string()).collect(),
+                ParticleColumn::Stress => self.get_particle_virials().iter().map(|w| w.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" ")).collect(),
+            };
+            (column.property(), values)
+        }).collect();
+    }
+
+    /// Energy of every particle, they add up to the total energy without tail corrections
+    pub fn get_particle_energies(&self) -> Vec<f64> {
+        return get_particle_energies(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+    }
+
+    /// Virial tensor of every particle as xx yy zz xy xz yz, the traces add up to the virial
+    pub fn get_particle_virials(&self) -> Vec<[f64; 6]> {
+        return get_particle_virials(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared, self.e_shift, self.f_shift, self.switch_squared);
+    }
+
+    /// Surface tension of a slab with two interfaces normal to z, Lz/2 (p_zz - (p_xx + p_yy)/2) of the current
+    /// configuration without tail corrections
+    pub fn surface_tension(&self) -> f64 {
+        let (mut w_xx, mut w_yy, mut w_zz) = (0.0, 0.0, 0.0);
+        for w in self.get_particle_virials() {
+            w_xx += w[0];
+            w_yy += w[1];
+            w_zz += w[2];
+        }
+        return self.l_z / 2.0 * (w_zz - (w_xx + w_yy) / 2.0) / self.volume;
+    }
+
+    /// Repulsive and attractive parts of the lj energy within the cutoff of the current configuration
+    pub fn get_energy_decomposition(&self) -> (f64, f64) {
+        return get_energy_decomposition(&self.rx, &self.ry, &self.rz, self.num_particles, self.l_x, self.l_y, self.l_z, self.cutoff_squared);
+    }
+
+    /// attempted moves per particle of a number of MC steps
+    pub fn get_sweeps(&self, steps: Step) -> f64 {
+        return (steps * self.moves_per_step()) as f64 / self.num_particles as f64;
+    }
+
+    /// Writes the complete system state as json checkpoint
+    pub fn save_checkpoint(&self, filename: &String) -> Result<()> {
+        let mut file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let json = serde_json::to_string_pretty(self).map_err(|e| Error::Serialization(e.to_string()))?;
+        file.write_all(json.as_bytes())?;
+        return Ok(());
+    }
+
+    /// Reads a system state from a json checkpoint
+    pub fn load_checkpoint(filename: &String) -> Result<System> {
+        let file = match File::open(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let mut value : serde_json::Value = serde_json::from_reader(file).map_err(|e| Error::Serialization(e.to_string()))?;
+        // older checkpoints kept the sums of the energies, virials and widom factors instead of their statistics
+        let migrate = |object: Option<&mut serde_json::Value>, names: &[&str], count: &str| {
+            if let Some(object) = object.and_then(|object| object.as_object_mut()) {
+                let count = object.get(count).and_then(|count| count.as_u64()).unwrap_or(0);
+                for name in names {
+                    if let Some(sum) = object.remove(&format!("{}_sum", name)).and_then(|sum| sum.as_f64()) {
+                        object.insert(name.to_string(), serde_json::json!(Statistics::from_sum(sum, count)));
+                    }
+                }
+            }
+        };
+        migrate(value.get_mut("counters"), &["energy", "virial"], "step_counter");
+        migrate(value.get_mut("expanded"), &["widom"], "widom_count");
+        // and the sums of the tempering and multicanonical samples, per bin without the counts, taken as single samples
+        if let Some(tempering) = value.get_mut("tempering").and_then(|tempering| tempering.as_object_mut()) {
+            if let Some(sums) = tempering.remove("sums").and_then(|sums| serde_json::from_value::<Vec<[f64; 3]>>(sums).ok()) {
+                let count = |sum: &[f64; 3]| sum[0] as Step;
+                tempering.insert("energies".to_string(), serde_json::json!(sums.iter().map(|sum| Statistics::from_sum(sum[1], count(sum))).collect::<Vec<_>>()));
+                tempering.insert("virials".to_string(), serde_json::json!(sums.iter().map(|sum| Statistics::from_sum(sum[2], count(sum))).collect::<Vec<_>>()));
+            }
+        }
+        if let Some(multicanonical) = value.get_mut("multicanonical").and_then(|multicanonical| multicanonical.as_object_mut()) {
+            if let Some(sums) = multicanonical.remove("sums").and_then(|sums| serde_json::from_value::<Vec<[f64; 3]>>(sums).ok()) {
+                let weights : Vec<[Statistics; 3]> = sums.iter().map(|sum| [0, 1, 2].map(|k| Statistics::from_sum(sum[k], if sum[0] > 0.0 { 1 } else { 0 }))).collect();
+                multicanonical.insert("weights".to_string(), serde_json::json!(weights));
+            }
+        }
+        // json has no NaN and infinity, the non-finite values of an emergency checkpoint were written as null
+        return serde_json::from_value(value).map_err(|e| match e.to_string() {
+            ref message if message.contains("null, expected f") => Error::Serialization(format!("{}, the checkpoint holds non-finite values (an emergency checkpoint is only for inspection)", message)),
+            message => Error::Serialization(message),
+        });
+    }
+}
+
+#[test]
+fn test_umbrella_bias() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 64, density: 0.3, temperature: 0.7, cutoff: 2.0, umbrella_center: 20.0, umbrella_spring: 0.5, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    assert_eq!(system.get_largest_cluster_size(), system.cluster_size);
+    system.run(2000, &mut rng);
+    assert_eq!(system.get_largest_cluster_size(), system.cluster_size);
+    assert_eq!(2000, system.counters.cluster_histogram.iter().sum::<Step>());
+    assert!( (system.get_umbrella_bias(22) - 1.0).abs() < 0.00001);
+    let free_energy = system.get_cluster_free_energy();
+    assert_eq!(0.0, free_energy.iter().cloned().filter(|f| f.is_finite()).fold(f64::INFINITY, f64::min));
+    system.reset_averages();
+    assert!(system.counters.cluster_histogram.is_empty());
+}
+
+#[test]
+fn test_displacement_acceptance() {
+    let mut rng = ::rand::thread_rng();
+    let mut system = System::new(Params { num_particles: 64, density: 0.8, temperature: 1.0, cutoff: 2.0, displacement: 0.3, ..Params::default() }, &mut rng);
+    let mut accepted = 0;
+    for _ in 0..5000 {
+        if system.trial_move(&mut rng) { accepted += 1; }
+        system.record_trial_displacement();
+    }
+    let acceptance = system.get_displacement_acceptance();
+    assert_eq!(5000, acceptance.iter().map(|a| a.1).sum::<Step>());
+    assert_eq!(accepted, system.counters.displacement_histogram.iter().map(|h| h[1]).sum::<Step>());
+    // the cube of edge 2 * 0.3 has no longer displacements than its half diagonal
+    assert!(acceptance.last().unwrap().0 < 0.3 * 3f64.sqrt() + DISPLACEMENT_BIN_WIDTH);
+    // short moves are accepted more often than long ones, pooled as the sparse outer bins scatter
+    let pooled = |range: ::std::ops::Range<f64>| {
+        let bins : Vec<&(f64, Step, f64)> = acceptance.iter().filter(|a| range.contains(&a.0)).collect();
+        bins.iter().map(|a| a.1 as f64 * a.2).sum::<f64>() / bins.iter().map(|a| a.1 as f64).sum::<f64>()
+    };
+    assert!(pooled(0.0..0.1) > pooled(0.2..1.0), "{:?}", acceptance);
+}
+
+#[test]
+fn test_multicanonical_sampling() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.5, cutoff: 2.0, multicanonical_min: -4.0, multicanonical_max: 0.0, multicanonical_bins: 20, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(20000, &mut rng);
+    {
+        let multicanonical = system.multicanonical.as_ref().unwrap();
+        assert!(!multicanonical.learning);
+        assert!(multicanonical.ln_g.iter().any(|&g| g > 0.0));
+    }
+    let in_range = system.multicanonical.as_ref().unwrap().get_bin(system.energy).is_some();
+    system.run(2000, &mut rng);
+    // once inside the range the energy stays there
+    assert!(!in_range || system.multicanonical.as_ref().unwrap().get_bin(system.energy).is_some(), "{}", system.energy);
+    let (energy, pressure) = system.get_multicanonical_averages().unwrap();
+    assert!(!in_range || (energy.is_finite() && pressure.is_finite()), "{} {}", energy, pressure);
+}
+
+#[test]
+fn test_expanded_ensemble_sampling() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.3, cutoff: 2.0, expanded_states: 5, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(20000, &mut rng);
+    system.run(5000, &mut rng);
+    // the running energy follows the coupling of the solute
+    let (energy, _) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs().max(1.0), "{} {}", energy, system.energy);
+    let ladder = &system.expanded.as_ref().unwrap().ladder;
+    assert!(!ladder.learning && ladder.accepted > 0);
+    assert_eq!(5000, ladder.histogram.iter().sum::<Step>());
+}
+
+#[test]
+fn test_simulated_tempering_sampling() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.3, cutoff: 2.0, temperature: 1.0, tempering_min: 1.0, tempering_max: 1.5, tempering_states: 4, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    assert_eq!(1.0, system.params.temperature);
+    system.equilibrate(20000, &mut rng);
+    system.run(5000, &mut rng);
+    let tempering = system.tempering.as_ref().unwrap();
+    assert!(!tempering.ladder.learning && tempering.ladder.accepted > 0);
+    assert_eq!(tempering.temperature(), system.params.temperature);
+    assert_eq!(5000, tempering.energies.iter().map(|s| s.count).sum::<Step>());
+    let most = (0..4).max_by_key(|&k| tempering.energies[k].count).unwrap();
+    assert!(tempering.get_errors(most).0 > 0.0);
+}
+
+#[test]
+fn test_system_checkpoint() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 64, density: 0.5, cutoff: 2.0, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    for _ in 0..100 { system.trial_move(&mut rng); }
+    system.provenance.push(Provenance::new(&["mc".to_string()], 42, 1791991812));
+    // the block averages of the report continue after a restart
+    let mut sampling = SamplingBlocks::new(10, 10, 2);
+    for k in 0..25 { sampling.energy.push(-3.0 + 0.01 * k as f64); sampling.repulsive_energy.push(k as f64); }
+    system.counters.sampling = Some(sampling);
+
+    let filename = ::std::env::temp_dir().join("mclj_test_checkpoint.json").to_str().unwrap().to_string();
+    system.save_checkpoint(&filename).unwrap();
+    let loaded = System::load_checkpoint(&filename).unwrap();
+    assert_eq!(system, loaded);
+
+    // the sums of older checkpoints become the means
+    system.run(10, &mut rng);
+    let mut value = serde_json::to_value(&system).unwrap();
+    let counters = value["counters"].as_object_mut().unwrap();
+    counters.remove("energy");
+    counters.insert("energy_sum".to_string(), serde_json::json!(-25.0));
+    value.as_object_mut().unwrap().remove("provenance");
+    ::std::fs::write(&filename, value.to_string()).unwrap();
+    let loaded = System::load_checkpoint(&filename).unwrap();
+    ::std::fs::remove_file(&filename).unwrap();
+    assert_eq!((10, -2.5), (loaded.counters.energy.count, loaded.counters.energy.mean()));
+
+    // and so do the sums of the tempering temperatures and the multicanonical bins
+    let mut value = serde_json::to_value(&system).unwrap();
+    let mut tempering = serde_json::to_value(SimulatedTempering::new(1.0, 2.0, 2, 1.0)).unwrap();
+    tempering.as_object_mut().unwrap().remove("energies");
+    tempering.as_object_mut().unwrap().remove("virials");
+    tempering["sums"] = serde_json::json!([[4.0, -8.0, 2.0], [0.0, 0.0, 0.0]]);
+    let mut multicanonical = serde_json::to_value(Multicanonical::new(-10.0, 0.0, 2)).unwrap();
+    multicanonical.as_object_mut().unwrap().remove("weights");
+    multicanonical["sums"] = serde_json::json!([[0.5, -4.0, 1.0], [0.0, 0.0, 0.0]]);
+    value["tempering"] = tempering;
+    value["multicanonical"] = multicanonical;
+    ::std::fs::write(&filename, value.to_string()).unwrap();
+    let loaded = System::load_checkpoint(&filename).unwrap();
+    ::std::fs::remove_file(&filename).unwrap();
+    let tempering = loaded.tempering.unwrap();
+    assert_eq!((4, -2.0, 0.5), (tempering.energies[0].count, tempering.get_averages(0).0, tempering.get_averages(0).1));
+    assert!(tempering.get_averages(1).0.is_nan());
+    assert_eq!((-8.0, 2.0), loaded.multicanonical.unwrap().get_canonical_averages(1.0));
+
+    // the emergency checkpoint of a non-finite state can be read but not loaded
+    system.energy = f64::NAN;
+    system.rx[3] = Real::from_f64(f64::INFINITY);
+    system.save_checkpoint(&filename).unwrap();
+    let value : serde_json::Value = serde_json::from_str(&::std::fs::read_to_string(&filename).unwrap()).unwrap();
+    assert!(value["energy"].is_null() && value["rx"][3].is_null());
+    match System::load_checkpoint(&filename) {
+        Err(Error::Serialization(message)) => assert!(message.contains("non-finite"), "{}", message),
+        other => panic!("expected a serialization error, got {:?}", other.map(|system| system.energy)),
+    }
+    ::std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_system_trial_move() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 64, density: 0.5, cutoff: 2.0, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    let mut accepted = 0;
+    for _ in 0..2000 {
+        if system.trial_move(&mut rng) { accepted += 1; }
+    }
+    assert_eq!(accepted, system.counters.accept_counter);
+
+    // get rid of the rounding errors from the overlaps in the random start
+    let (energy, virial) = system.total_energy();
+    system.apply_recomputed_energy(energy, virial);
+    for _ in 0..1000 { system.trial_move(&mut rng); }
+
+    // running energy matches recalculation
+    let (energy, virial) = system.total_energy();
+    assert!( ((system.energy - energy) / energy).abs() < 1e-6, "{} {}", system.energy, energy);
+    assert!( ((system.virial - virial) / virial).abs() < 1e-6, "{} {}", system.virial, virial);
+}
+
+#[test]
+fn test_lazy_virial() {
+    let params = Params { num_particles: 64, density: 0.5, cutoff: 2.0, recompute_interval: 10, ..Params::default() };
+    let mut rng = get_stream_rng(42, StreamDomain::Run, 0);
+    let mut system = System::new(params.clone(), &mut rng);
+    let mut lazy_rng = get_stream_rng(42, StreamDomain::Run, 0);
+    let mut lazy = System::new(Params { lazy_virial: true, ..params }, &mut lazy_rng);
+
+    // the same moves are accepted, only the virial stays at its last recalculation
+    let virial = lazy.virial;
+    for _ in 0..500 { assert_eq!(system.trial_move(&mut rng), lazy.trial_move(&mut lazy_rng)); }
+    assert_eq!((&system.rx, system.energy), (&lazy.rx, lazy.energy));
+    assert_eq!(virial, lazy.virial);
+
+    // the pressure is sampled at the recalculations only
+    system.reset_averages();
+    lazy.reset_averages();
+    system.run(100, &mut rng);
+    lazy.run(100, &mut lazy_rng);
+    assert_eq!((100, 10), (system.counters.virial.count, lazy.counters.virial.count));
+}
+
+#[test]
+fn test_checkerboard_sweep() {
+    // 256 particles at 0.5 fill a 4x4x4 grid of the cutoff 2
+    let params = Params { num_particles: 256, density: 0.5, cutoff: 2.0, sweeps: true, recompute_interval: 0, checkerboard_threads: 4, ..Params::default() };
+    let mut rng = get_stream_rng(42, StreamDomain::Run, 0);
+    let mut system = System::new(params.clone(), &mut rng);
+    let mut single_rng = get_stream_rng(42, StreamDomain::Run, 0);
+    let mut single = System::new(Params { checkerboard_threads: 1, ..params }, &mut single_rng);
+
+    // the running energy and virial follow the moves, and the moves do not depend on the number of threads
+    system.run(20, &mut rng);
+    single.run(20, &mut single_rng);
+    assert_eq!((&system.rx, &system.ry, &system.rz, system.energy), (&single.rx, &single.ry, &single.rz, single.energy));
+    assert!(system.counters.accept_counter > 0 && system.counters.accept_counter < 20 * 256);
+    let (energy, virial) = system.total_energy();
+    assert!( (system.energy - energy).abs() < 1e-4 * energy.abs(), "{} {}", system.energy, energy);
+    assert!( (system.virial - virial).abs() < 1e-4 * virial.abs(), "{} {}", system.virial, virial);
+}
+
+#[test]
+fn test_rotation_moves() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.3, cutoff: 2.0, rotations: true, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    assert_eq!(32, system.orientations.len());
+    let old = system.orientations.clone();
+    system.run(100, &mut rng);
+    assert_eq!(100, system.counters.rotation_attempts);
+    assert!(system.orientations != old);
+    assert!(system.orientations.iter().all(|q| (q.iter().map(|c| c * c).sum::<f64>() - 1.0).abs() < 0.00001));
+    // always accepted rotations open the angle
+    system.adapt_displacement();
+    assert!(system.max_angle > ROTATION_ANGLE);
+    assert_eq!(0, system.counters.rotation_attempts);
+    assert!(System::new(Params::default(), &mut rng).orientations.is_empty());
+}
+
+#[test]
+fn test_gay_berne_sampling() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.1, temperature: 2.0, cutoff: 4.0, rotations: true, gb_kappa: 3.0, gb_kappa_prime: 5.0, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    assert_eq!((0.0, 0.0), (system.e_corr, system.e_shift));
+    system.equilibrate(2000, &mut rng);
+    system.run(500, &mut rng);
+    assert!(system.counters.rotation_accepted > 0 && system.counters.rotation_accepted < system.counters.rotation_attempts);
+    // translations and rotations keep the running energy
+    let (energy, virial) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs().max(1.0), "{} {}", energy, system.energy);
+    assert!( (virial - system.virial).abs() < 1e-6 * virial.abs().max(1.0), "{} {}", virial, system.virial);
+}
+
+#[test]
+fn test_three_body_sampling() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 216, density: 0.7, cutoff: 2.0, three_body_nu: 0.073, three_body_cutoff: 1.5, sweeps: true, ..Params::default() };
+    assert!(params.validate().is_empty());
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(30, &mut rng);
+    system.run(10, &mut rng);
+    // the triplets of the liquid are mostly repulsive and part of the running energy and virial
+    let (e3, w3) = system.get_three_body_energy();
+    assert!(e3 > 0.0 && (w3 - 9.0 * e3).abs() < 1e-9 * w3);
+    let (energy, virial) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs().max(1.0), "{} {}", energy, system.energy);
+    assert!( (virial - system.virial).abs() < 1e-6 * virial.abs().max(1.0), "{} {}", virial, system.virial);
+}
+
+#[test]
+fn test_dipoles_in_field() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.3, cutoff: 2.0, temperature: 1.0, rotations: true, dipole_moment: 1.0, field_strength: 2.0, field_direction: "0,2,0".to_string(), sweeps: true, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(500, &mut rng);
+    let mut alignment = 0.0;
+    for _ in 0..4000 {
+        system.run(1, &mut rng);
+        alignment += system.get_polarization()[1] * system.volume / 32.0 / 4000.0;
+    }
+    // the orientations of lj spheres are free dipoles, <cos> follows the langevin function coth(x) - 1/x of x = beta mu E
+    assert!( (alignment - (1.0 / 2f64.tanh() - 0.5)).abs() < 0.05, "{}", alignment);
+    let (energy, _) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs().max(1.0), "{} {}", energy, system.energy);
+}
+
+#[test]
+fn test_early_rejection() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 108, density: 0.9, temperature: 1.0, cutoff: 2.0, early_rejection: true, sweeps: true, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(100, &mut rng);
+    system.run(100, &mut rng);
+    // only rejections are cut short, so the running energy stays exact
+    assert!(system.counters.early_rejections > 0 && system.counters.early_rejections < 108 * 200);
+    let (energy, _) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs(), "{} {}", energy, system.energy);
+}
+
+#[test]
+fn test_last_trial() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 108, density: 0.8, temperature: 1.0, cutoff: 2.0, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(10, &mut rng);
+    for _ in 0..2000 {
+        let (rx, ry, rz) = (system.rx.clone(), system.ry.clone(), system.rz.clone());
+        let energy = system.energy;
+        let accepted = system.trial_move(&mut rng);
+        let trial = &system.last_trial;
+        assert_eq!(accepted, trial.outcome == Outcome::Accepted);
+        // the record replays the move and its metropolis decision
+        let i = trial.index;
+        let d_e = trial.d_e.unwrap();
+        if accepted {
+            assert!( (system.energy - energy - d_e).abs() < 1e-9 * energy.abs().max(1.0));
+            let moved = get_particle_distance_squared(rx[i].to_f64() + trial.displacement[0], ry[i].to_f64() + trial.displacement[1], rz[i].to_f64() + trial.displacement[2],
+                                                      system.rx[i].to_f64(), system.ry[i].to_f64(), system.rz[i].to_f64(), system.l_x, system.l_y, system.l_z, system.l_x / 2.0, system.l_y / 2.0, system.l_z / 2.0);
+            assert!(moved < 1e-12);
+        } else {
+            assert_eq!((rx[i], ry[i], rz[i]), (system.rx[i], system.ry[i], system.rz[i]));
+        }
+        assert_eq!(accepted, d_e < 0.0 || trial.random.unwrap() < (-d_e / system.params.temperature).exp());
+    }
+}
+
+#[test]
+fn test_check_drift() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 108, density: 0.8, temperature: 1.0, cutoff: 2.0, drift_tolerance: 1e-9, sweeps: true, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(20, &mut rng);
+    let (energy, virial) = system.total_energy();
+    system.apply_recomputed_energy(energy, virial);
+    system.run(20, &mut rng);
+    let (energy, virial) = system.total_energy();
+    assert_eq!(None, system.check_drift(energy, virial));
+    // a bookkeeping error of a move
+    system.energy += 1e-3;
+    assert!(system.check_drift(energy, virial).is_some());
+    system.apply_recomputed_energy(energy, virial);
+    assert_eq!(1, system.counters.drift_violations);
+    assert_eq!(None, system.check_drift(energy, virial));
+}
+
+#[test]
+fn test_overlap_check() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 108, density: 0.9, temperature: 1.0, cutoff: 2.0, overlap_radius: 0.8, sweeps: true, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(100, &mut rng);
+    system.run(100, &mut rng);
+    assert!(system.counters.overlap_rejections > 0);
+    let (energy, _) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs(), "{} {}", energy, system.energy);
+    // the cells follow the moves
+    let cells = system.cells.as_ref().unwrap();
+    for i in 0..108 {
+        let (x, y, z) = (system.rx[i].to_f64(), system.ry[i].to_f64(), system.rz[i].to_f64());
+        let nearest = (0..108).filter(|&j| j != i).map(|j| get_particle_distance_squared(system.rx[j].to_f64(), system.ry[j].to_f64(), system.rz[j].to_f64(), x, y, z,
+                                                                                        system.l_x, system.l_y, system.l_z, system.l_x / 2.0, system.l_y / 2.0, system.l_z / 2.0)).fold(f64::INFINITY, f64::min);
+        assert_eq!(nearest < 0.64, cells.has_overlap(&system.rx, &system.ry, &system.rz, i, x, y, z, system.l_x, system.l_y, system.l_z));
+    }
+}
+
+#[test]
+fn test_recenter_slab() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 128, density: 0.7, cutoff: 2.0, vacuum_slab: 2.0, recenter_interval: 10, ..Params::default() };
+    assert!(params.validate().is_empty());
+    // moves out of the overlaps of the random start (without recentering), whose energies would amplify the rounding
+    // of single precision coordinates by the shift
+    let mut system = System::new(Params { recenter_interval: 0, ..params }, &mut rng);
+    system.run(20 * system.num_particles as Step, &mut rng);
+    // slab moved across the boundary
+    let l_z = system.l_z;
+    for z in system.rz.iter_mut() {
+        *z = Real::from_f64((z.to_f64() + 0.4 * l_z) % l_z);
+    }
+    let (energy, _) = system.total_energy();
+    let shift = system.recenter_slab();
+    assert!( (shift + 0.4 * l_z).abs() < 0.5, "{} {}", shift, l_z);
+    assert_eq!(shift, system.slab_shift);
+    assert!( (get_slab_center(&system.rx, &system.ry, &system.rz, 128, system.l_x, system.l_y, l_z) - l_z / 2.0).abs() < 1e-6);
+    // single precision rounding of the shifted coordinates moves pairs across the cutoff
+    let tolerance = 1e-6_f64.max(1e4 * Real::EPSILON.to_f64());
+    assert!( (system.total_energy().0 - energy).abs() < tolerance * energy.abs());
+    assert!(system.rz.iter().all(|z| z.to_f64() >= 0.0 && z.to_f64() < l_z));
+}
+
+#[test]
+fn test_restraint_sampling() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 32, density: 0.3, cutoff: 2.0, temperature: 1.0, vacuum_slab: 2.0, sweeps: true, restraints: "tether:0:2,2,7:10;distance:1:2:1.5:20;slab:0.5:10".to_string(), ..Params::default() };
+    assert!(params.validate().is_empty());
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(1000, &mut rng);
+    let mut tether = 0.0;
+    for _ in 0..5000 {
+        system.run(1, &mut rng);
+        let d = [system.rx[0].to_f64() - 2.0, system.ry[0].to_f64() - 2.0, system.rz[0].to_f64() - 7.0];
+        tether += (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]) / 5000.0;
+    }
+    // equipartition of the tether 3 T / k, perturbed by the neighbors
+    assert!(tether > 0.15 && tether < 0.6, "{}", tether);
+    let center = Restraints::get_slab_center(system.restraints.as_ref().unwrap().phase_sums, system.l_z);
+    assert!( (center - system.l_z / 2.0).abs() < 1.5, "{} {}", center, system.l_z);
+    let restraint_energy = system.get_restraint_energy();
+    let (energy, _) = system.total_energy();
+    assert!( (energy - system.energy).abs() < 1e-6 * energy.abs().max(1.0), "{} {}", energy, system.energy);
+    system.refresh_restraints();
+    assert!( (system.get_restraint_energy() - restraint_energy).abs() < 1e-6 && restraint_energy > 0.0);
+}
+
+#[test]
+fn test_system_particle_columns() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 64, density: 0.5, cutoff: 2.0, particle_columns: "energy,cluster".to_string(), ..Params::default() };
+    let system = System::new(params, &mut rng);
+    let (energy, _) = system.total_energy();
+    let sum : f64 = system.get_particle_energies().iter().sum();
+    assert!( (sum + 64.0 * system.e_corr - energy).abs() < 1e-6 * energy.abs(), "{} {}", sum, energy);
+
+    let columns = system.get_particle_columns(&[ParticleColumn::Energy, ParticleColumn::Cluster, ParticleColumn::Stress]);
+    assert_eq!(vec!["energy:R:1", "cluster:I:1", "stress:R:6"], columns.iter().map(|c| c.0).collect::<Vec<&str>>());
+    assert!(columns.iter().all(|c| c.1.len() == 64));
+    assert_eq!(6, columns[2].1[0].split_whitespace().count());
+
+    // no interfaces in the homogeneous system
+    let tension = system.surface_tension();
+    assert!(tension.is_finite());
+}
+
+#[test]
+fn test_system_run() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 64, density: 0.5, cutoff: 2.0, sweeps: true, recompute_interval: 10, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.run(20, &mut rng);
+    assert_eq!(20, system.counters.step);
+    assert_eq!(20, system.counters.step_counter);
+    assert!(system.counters.accept_counter <= 20 * 64);
+
+    system.reset_averages();
+    assert_eq!(20, system.counters.step);
+    assert_eq!(0, system.counters.step_counter);
+    assert_eq!(0, system.counters.energy.count);
+}
+
+#[test]
+fn test_system_set_density() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 200, density: 0.5, cutoff: 2.0, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.set_density(0.7).unwrap();
+    assert!( (system.num_particles as f64 / system.volume - 0.7).abs() < 0.00001, "{}", system.volume);
+    assert!( (system.l_x * system.l_y * system.l_z - system.volume).abs() < 0.00001);
+
+    // same state as a system created at that density
+    let reference = System::new(Params { density: 0.7, ..system.params.clone() }, &mut rng);
+    assert!( (system.e_corr - reference.e_corr).abs() < 0.00001, "{}", system.e_corr);
+    assert!( (system.l_x - reference.l_x).abs() < 0.00001, "{}", system.l_x);
+
+    // cutoff has to fit in the box
+    assert!(system.set_density(30.0).is_err());
+}
+
+#[test]
+fn test_system_open_vacuum() {
+    let mut rng = ::rand::thread_rng();
+    let mut system = System::new(Params { num_particles: 200, density: 0.8, cutoff: 2.0, ..Params::default() }, &mut rng);
+    let l_z = system.l_z;
+    system.open_vacuum(2.0).unwrap();
+    // same box and tail corrections as a system created with the slab, the particles in the middle third
+    let reference = System::new(Params { num_particles: 200, density: 0.8, cutoff: 2.0, vacuum_slab: 2.0, ..Params::default() }, &mut rng);
+    assert!( (system.l_z - reference.l_z).abs() < 0.00001 && (system.density - reference.density).abs() < 0.00001);
+    assert!( (system.e_corr - reference.e_corr).abs() < 0.00001, "{}", system.e_corr);
+    assert!(system.rz.iter().all(|&z| z.to_f64() >= l_z && z.to_f64() < 2.0 * l_z));
+    assert!( (system.energy - system.total_energy().0).abs() < 0.00001);
+    assert!(system.open_vacuum(1.0).is_err());
+}
+
+#[test]
+fn test_system_equilibrate_sample() {
+    let mut rng = ::rand::thread_rng();
+    let params = Params { num_particles: 100, density: 0.5, cutoff: 2.0, sweeps: true, ..Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.equilibrate(200, &mut rng);
+    assert_eq!(20, system.counters.scale_updates);
+    assert_eq!(0, system.counters.step_counter);
+    // running energy is recomputed at the end of the equilibration
+    let (energy, virial) = system.total_energy();
+    assert_eq!((energy, virial), (system.energy, system.virial));
+
+    let averages = system.sample(100, &mut rng);
+    assert_eq!(100, averages.steps);
+    assert!(averages.energy < 0.0, "{}", averages.energy);
+    assert!(averages.energy_error > 0.0 && averages.energy_error.is_finite());
+    assert!(averages.pressure_error > 0.0 && averages.pressure_error.is_finite());
+    // adapted to roughly the intended acceptance
+    assert!( (averages.acceptance - 1.0/TRIES_INTENDED).abs() < 0.1, "{}", averages.acceptance);
+}
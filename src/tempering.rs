@@ -0,0 +1,70 @@
+//! Simulated tempering: the temperature as a dynamical variable on a ladder with learned weights
+
+use expanded::StateLadder;
+use statistics::Statistics;
+
+/// Temperatures of simulated tempering with the weights of their ladder. Moves to a neighboring temperature are
+/// accepted with min(1, exp(-(beta_new - beta_old) U + w_new - w_old)), the weights are learned so that all temperatures
+/// are visited evenly, a single process alternative to replica exchange.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SimulatedTempering {
+    pub temperatures: Vec<f64>,
+    pub ladder: StateLadder,
+    // per temperature of the sampling: the statistics of the energy and the virial
+    pub energies: Vec<Statistics>,
+    pub virials: Vec<Statistics>,
+}
+
+impl SimulatedTempering {
+    /// Geometric ladder between min and max, starting from the temperature closest to the given one
+    pub fn new(min: f64, max: f64, states: usize, temperature: f64) -> SimulatedTempering {
+        let temperatures : Vec<f64> = (0..states).map(|k| min * (max / min).powf(k as f64 / (states - 1) as f64)).collect();
+        let mut state = 0;
+        for k in 0..states {
+            if (temperatures[k] - temperature).abs() < (temperatures[state] - temperature).abs() { state = k; }
+        }
+        return SimulatedTempering { temperatures: temperatures, ladder: StateLadder::new(states, state),
+                                   energies: vec![Statistics::default(); states], virials: vec![Statistics::default(); states] };
+    }
+
+    pub fn temperature(&self) -> f64 {
+        return self.temperatures[self.ladder.state];
+    }
+
+    /// Adds a sample of the energy and virial at the current temperature
+    pub fn record(&mut self, energy: f64, virial: f64) {
+        self.energies[self.ladder.state].push(energy);
+        self.virials[self.ladder.state].push(virial);
+    }
+
+    /// Ends the learning stage, the statistics start over for the sampling
+    pub fn freeze(&mut self) {
+        self.ladder.freeze();
+        for statistics in self.energies.iter_mut().chain(self.virials.iter_mut()) { statistics.clear(); }
+    }
+
+    /// Mean energy and virial at temperature k, NaN without samples
+    pub fn get_averages(&self, k: usize) -> (f64, f64) {
+        return (self.energies[k].mean(), self.virials[k].mean());
+    }
+
+    /// Standard errors of the mean energy and virial at temperature k for uncorrelated samples, NaN for less than two
+    pub fn get_errors(&self, k: usize) -> (f64, f64) {
+        return (self.energies[k].standard_error(), self.virials[k].standard_error());
+    }
+}
+
+#[test]
+fn test_simulated_tempering() {
+    let mut tempering = SimulatedTempering::new(1.0, 4.0, 3, 1.8);
+    assert!( (tempering.temperatures[1] - 2.0).abs() < 0.00001, "{:?}", tempering.temperatures);
+    assert_eq!(4.0, tempering.temperatures[2]);
+    assert_eq!(2.0, tempering.temperature().round());
+    tempering.record(1.0, 2.0);
+    tempering.freeze();
+    tempering.record(-1.0, 4.0);
+    tempering.record(-3.0, 2.0);
+    assert_eq!((-2.0, 3.0), tempering.get_averages(1));
+    assert_eq!((1.0, 1.0), tempering.get_errors(1));
+    assert!(tempering.get_averages(0).0.is_nan());
+}
@@ -0,0 +1,92 @@
+//! Axilrod-Teller triple-dipole energies of particle triplets within a short three-body cutoff
+
+use cells::CellList;
+use energy::{dot, get_displacement_with_pbc, CompensatedSum};
+use {Coordinate, Real};
+
+/// Triple-dipole energy nu (1 + 3 cos g1 cos g2 cos g3) / (r12 r13 r23)^3 of a triplet from the separations of the
+/// second and third particle from the first, g are the inner angles of the triangle
+pub fn eval_triplet_energy(r12: [f64; 3], r13: [f64; 3], nu: f64) -> f64 {
+    let r23 = [r13[0] - r12[0], r13[1] - r12[1], r13[2] - r12[2]];
+    let (d12, d13, d23) = (dot(r12, r12), dot(r13, r13), dot(r23, r23));
+    // products of the cosines at the three corners
+    let cosines = dot(r12, r13) * -dot(r12, r23) * dot(r13, r23) / (d12 * d13 * d23);
+    return nu * (1.0 + 3.0 * cosines) / (d12 * d13 * d23).powf(1.5);
+}
+
+#[test]
+fn test_eval_triplet_energy() {
+    // equilateral triangle: cosines 1/2, nu (1 + 3/8) / r^9
+    let energy = eval_triplet_energy([1.0, 0.0, 0.0], [0.5, 0.75f64.sqrt(), 0.0], 1.0);
+    assert!( (energy - 1.375).abs() < 0.00001, "{}", energy);
+    // linear chain of distances 1 2 1: cosines 1 -1 1, attractive
+    let energy = eval_triplet_energy([1.0, 0.0, 0.0], [2.0, 0.0, 0.0], 1.0);
+    assert!( (energy - -0.25).abs() < 0.00001, "{}", energy);
+}
+
+// separations from particle p of all particles within the cutoff, searched in the cells around p of a cell list of the
+// cutoff or among all particles without one
+fn get_neighbors(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, cells: Option<&CellList>) -> Vec<(usize, [f64; 3])> {
+    let candidates = match cells {
+        Some(cells) => cells.get_candidates(rx[p_index].to_f64(), ry[p_index].to_f64(), rz[p_index].to_f64()),
+        None => (0..num_particles).collect(),
+    };
+    let mut neighbors = vec![];
+    for j in candidates.into_iter().filter(|&j| j != p_index) {
+        let r = [get_displacement_with_pbc(rx[j].to_f64(), rx[p_index].to_f64(), l_x, l_x / 2.0),
+                 get_displacement_with_pbc(ry[j].to_f64(), ry[p_index].to_f64(), l_y, l_y / 2.0),
+                 get_displacement_with_pbc(rz[j].to_f64(), rz[p_index].to_f64(), l_z, l_z / 2.0)];
+        if dot(r, r) < cutoff_squared { neighbors.push((j, r)); }
+    }
+    return neighbors;
+}
+
+/// Three-body energy and virial of all triplets of particle p_index in which every pair is within the cutoff. The
+/// cutoff may be at most a quarter of the box, so the separation of two neighbors is their minimum image. The energy is
+/// homogeneous of degree -9 in the distances, so the virial is nine times the energy. The neighbors are searched in
+/// the cell list of the cutoff if given, which may still have particle p_index in the cell of its old position.
+pub fn get_particle_three_body_energy(rx: &[Real], ry: &[Real], rz: &[Real], p_index: usize, num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, nu: f64, cells: Option<&CellList>) -> (f64, f64) {
+    let neighbors = get_neighbors(rx, ry, rz, p_index, num_particles, l_x, l_y, l_z, cutoff_squared, cells);
+    let mut energy = 0.0;
+    for (a, &(_, r1)) in neighbors.iter().enumerate() {
+        for &(_, r2) in &neighbors[a+1..] {
+            let r12 = [r2[0] - r1[0], r2[1] - r1[1], r2[2] - r1[2]];
+            if dot(r12, r12) < cutoff_squared { energy += eval_triplet_energy(r1, r2, nu); }
+        }
+    }
+    return (energy, 9.0 * energy);
+}
+
+/// Three-body energy and virial of all triplets within the cutoff, the neighbors from a cell list of the cutoff
+pub fn get_total_three_body_energy(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, l_x: f64, l_y: f64, l_z: f64, cutoff_squared: f64, nu: f64) -> (f64, f64) {
+    let cells = CellList::new(rx, ry, rz, num_particles, l_x, l_y, l_z, cutoff_squared.sqrt());
+    let mut energy = CompensatedSum::default();
+    for i in 0..num_particles {
+        // every triplet once, from its particle of the lowest index
+        let neighbors : Vec<(usize, [f64; 3])> = get_neighbors(rx, ry, rz, i, num_particles, l_x, l_y, l_z, cutoff_squared, Some(&cells)).into_iter().filter(|&(j, _)| j > i).collect();
+        for (a, &(_, r1)) in neighbors.iter().enumerate() {
+            for &(_, r2) in &neighbors[a+1..] {
+                let r12 = [r2[0] - r1[0], r2[1] - r1[1], r2[2] - r1[2]];
+                if dot(r12, r12) < cutoff_squared { energy.add(eval_triplet_energy(r1, r2, nu)); }
+            }
+        }
+    }
+    return (energy.value(), 9.0 * energy.value());
+}
+
+#[test]
+fn test_three_body_energy() {
+    // an equilateral triangle across the boundary and a particle beyond the cutoff
+    let to_real = |values: [f64; 4]| -> Vec<Real> { values.iter().map(|&v| Real::from_f64(v)).collect() };
+    let (rx, ry, rz) = (to_real([9.5, 0.5, 0.0, 5.0]), to_real([1.0, 1.0, 1.0 + 0.75f64.sqrt(), 5.0]), to_real([1.0, 1.0, 1.0, 5.0]));
+    let (total, virial) = get_total_three_body_energy(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 2.25, 1.0);
+    assert!( (total - 1.375).abs() < 0.0001, "{}", total);
+    assert!( (virial - 9.0 * total).abs() < 0.00001);
+    let cells = CellList::new(&rx, &ry, &rz, 4, 10.0, 10.0, 10.0, 1.5);
+    for p in 0..3 {
+        let (energy, _) = get_particle_three_body_energy(&rx, &ry, &rz, p, 4, 10.0, 10.0, 10.0, 2.25, 1.0, None);
+        assert!( (energy - total).abs() < 0.00001, "{} {}", p, energy);
+        assert_eq!(energy, get_particle_three_body_energy(&rx, &ry, &rz, p, 4, 10.0, 10.0, 10.0, 2.25, 1.0, Some(&cells)).0);
+    }
+    assert_eq!(0.0, get_particle_three_body_energy(&rx, &ry, &rz, 3, 4, 10.0, 10.0, 10.0, 2.25, 1.0, Some(&cells)).0);
+}
@@ -0,0 +1,232 @@
+//! Tagged particles: unwrapped coordinates and move statistics for the analysis of the MC pseudo-dynamics
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use energy::get_displacement_with_pbc;
+use error::{Error, Result};
+use system::System;
+use trajectory::Frame;
+use {Coordinate, Step};
+
+/// Writes the unwrapped coordinates and the move statistics of the tagged particles once per sweep. Lines are the sweep
+/// followed by x y z attempts accepted of every tagged particle.
+pub struct TaggedParticles {
+    pub indices: Vec<usize>,
+    pub unwrapped: Vec<[f64; 3]>,
+    pub attempts: Vec<Step>,
+    pub accepted: Vec<Step>,
+    wrapped: Vec<[f64; 3]>,
+    // position in indices of every particle, usize::MAX if not tagged
+    tags: Vec<usize>,
+    file: BufWriter<File>,
+}
+
+impl TaggedParticles {
+    /// Starts tracking the particles at indices from their current positions
+    pub fn new(filename: &String, indices: Vec<usize>, system: &System) -> Result<TaggedParticles> {
+        let file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        let mut tags = vec![usize::MAX; system.num_particles];
+        for (tag, &i) in indices.iter().enumerate() {
+            tags[i] = tag;
+        }
+        let wrapped : Vec<[f64; 3]> = indices.iter().map(|&i| [system.rx[i].to_f64(), system.ry[i].to_f64(), system.rz[i].to_f64()]).collect();
+        let mut file = BufWriter::new(file);
+        writeln!(file, "# tagged particles: {}", indices.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(" "))?;
+        writeln!(file, "# sweep, then x y z attempts accepted of every particle (unwrapped coordinates)")?;
+        return Ok(TaggedParticles {
+            unwrapped: wrapped.clone(),
+            attempts: vec![0; indices.len()],
+            accepted: vec![0; indices.len()],
+            indices: indices,
+            wrapped: wrapped,
+            tags: tags,
+            file: file,
+        });
+    }
+
+    /// Counts the last trial move of the system and follows the moved particle across the boundaries
+    pub fn record_move(&mut self, system: &System, accepted: bool) {
+        let tag = self.tags[system.last_index];
+        if tag == usize::MAX { return; }
+        self.attempts[tag] += 1;
+        if !accepted { return; }
+        self.accepted[tag] += 1;
+        let i = system.last_index;
+        let position = [system.rx[i].to_f64(), system.ry[i].to_f64(), system.rz[i].to_f64()];
+        let lengths = [system.l_x, system.l_y, system.l_z];
+        for k in 0..3 {
+            self.unwrapped[tag][k] += get_displacement_with_pbc(position[k], self.wrapped[tag][k], lengths[k], lengths[k] / 2.0);
+        }
+        self.wrapped[tag] = position;
+    }
+
+    /// Writes the line of a sweep
+    pub fn write(&mut self, sweep: Step) -> Result<()> {
+        let mut line = sweep.to_string();
+        for tag in 0..self.indices.len() {
+            let r = self.unwrapped[tag];
+            line += &format!(" {} {} {} {} {}", r[0], r[1], r[2], self.attempts[tag], self.accepted[tag]);
+        }
+        writeln!(self.file, "{}", line)?;
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        return Ok(());
+    }
+}
+
+/// Updates the image flags of all particles from their wrapped positions in two consecutive frames, a particle that moved
+/// by more than half the box crossed the boundary. The unwrapped position is the wrapped one plus the flags times the box.
+pub fn update_image_flags(images: &mut [[i64; 3]], previous: &Frame, frame: &Frame) {
+    let lengths = [frame.box_x, frame.box_y, frame.box_z];
+    for i in 0..frame.num_particles.min(previous.num_particles) {
+        let old = [previous.rx[i].to_f64(), previous.ry[i].to_f64(), previous.rz[i].to_f64()];
+        let new = [frame.rx[i].to_f64(), frame.ry[i].to_f64(), frame.rz[i].to_f64()];
+        for k in 0..3 {
+            let d = new[k] - old[k];
+            if d > lengths[k] / 2.0 { images[i][k] -= 1; }
+            else if d < -lengths[k] / 2.0 { images[i][k] += 1; }
+        }
+    }
+}
+
+#[test]
+fn test_update_image_flags() {
+    let frame = |x: f64, y: f64| Frame { rx: vec![::Real::from_f64(x), 5.0], ry: vec![::Real::from_f64(y), 5.0], rz: vec![5.0, 5.0], num_particles: 2, box_x: 10.0, box_y: 10.0, box_z: 10.0,
+                                        temperature: 1.0, lj_eps: 1.0, lj_sig: 1.0, lj_cutoff: 3.0, ensemble: String::new(), step: None, sweep: None };
+    let mut images = vec![[0; 3]; 2];
+    // out through the upper x boundary and the lower y boundary, then back in x
+    update_image_flags(&mut images, &frame(9.8, 0.1), &frame(0.2, 9.9));
+    assert_eq!(vec![[1, -1, 0], [0, 0, 0]], images);
+    update_image_flags(&mut images, &frame(0.2, 9.9), &frame(9.7, 9.5));
+    assert_eq!(vec![[0, -1, 0], [0, 0, 0]], images);
+}
+
+/// Parses a list of particle indices and ranges, e.g. 0-9,42, all below num_particles
+pub fn parse_indices(list: &str, num_particles: usize) -> Option<Vec<usize>> {
+    let mut indices = vec![];
+    for part in list.split(',') {
+        let bounds : Vec<&str> = part.trim().splitn(2, '-').collect();
+        let first = bounds[0].parse::<usize>().ok()?;
+        let last = if bounds.len() == 2 { bounds[1].parse::<usize>().ok()? } else { first };
+        if last < first || last >= num_particles { return None; }
+        indices.extend(first..last+1);
+    }
+    indices.sort();
+    indices.dedup();
+    return Some(indices);
+}
+
+#[test]
+fn test_parse_indices() {
+    assert_eq!(Some(vec![0, 1, 2, 7]), parse_indices("0-2,7", 10));
+    assert_eq!(Some(vec![3]), parse_indices("3,3", 10));
+    assert_eq!(None, parse_indices("5-12", 10));
+    assert_eq!(None, parse_indices("4-2", 10));
+    assert_eq!(None, parse_indices("a", 10));
+}
+
+/// Records of a tagged particle file: the sweeps and per sweep the unwrapped position, attempts and accepted moves of every particle
+pub struct TaggedRecords {
+    pub indices: Vec<usize>,
+    pub sweeps: Vec<Step>,
+    pub positions: Vec<Vec<[f64; 3]>>,
+    pub attempts: Vec<Vec<Step>>,
+    pub accepted: Vec<Vec<Step>>,
+}
+
+/// Reads a file written by TaggedParticles
+pub fn read_tagged_records(filename: &String) -> Result<TaggedRecords> {
+    let file = match File::open(filename) {
+        Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+        Ok(file) => file,
+    };
+    let mut records = TaggedRecords { indices: vec![], sweeps: vec![], positions: vec![], attempts: vec![], accepted: vec![] };
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let malformed = |reason: &str| Error::MalformedFrame(number + 1, reason.to_string());
+        if let Some(indices) = line.strip_prefix("# tagged particles:") {
+            records.indices = indices.split_whitespace().map(|i| i.parse::<usize>().map_err(|_| malformed("invalid particle index"))).collect::<Result<Vec<usize>>>()?;
+            continue;
+        }
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let values : Vec<&str> = line.split_whitespace().collect();
+        if values.len() != 1 + 5 * records.indices.len() { return Err(malformed("wrong number of columns")); }
+        records.sweeps.push(values[0].parse().map_err(|_| malformed("invalid sweep"))?);
+        let (mut positions, mut attempts, mut accepted) = (vec![], vec![], vec![]);
+        for particle in values[1..].chunks(5) {
+            let coordinate = |k: usize| particle[k].parse::<f64>().map_err(|_| malformed("invalid coordinate"));
+            positions.push([coordinate(0)?, coordinate(1)?, coordinate(2)?]);
+            attempts.push(particle[3].parse().map_err(|_| malformed("invalid attempts"))?);
+            accepted.push(particle[4].parse().map_err(|_| malformed("invalid accepted moves"))?);
+        }
+        records.positions.push(positions);
+        records.attempts.push(attempts);
+        records.accepted.push(accepted);
+    }
+    return Ok(records);
+}
+
+/// Mean squared displacement for lags of 0 to max_lag records, averaged over all time origins and particles
+pub fn get_mean_squared_displacement(positions: &[Vec<[f64; 3]>], max_lag: usize) -> Vec<f64> {
+    let mut msd = vec![];
+    for lag in 0..max_lag.min(positions.len().saturating_sub(1)) + 1 {
+        if positions.is_empty() { break; }
+        let (mut sum, mut count) = (0.0, 0);
+        for origin in 0..positions.len() - lag {
+            for (a, b) in positions[origin].iter().zip(positions[origin + lag].iter()) {
+                sum += (0..3).map(|k| (b[k] - a[k]).powi(2)).sum::<f64>();
+                count += 1;
+            }
+        }
+        msd.push(if count > 0 { sum / count as f64 } else { 0.0 });
+    }
+    return msd;
+}
+
+#[test]
+fn test_get_mean_squared_displacement() {
+    // one particle moving with constant velocity, one resting
+    let positions : Vec<Vec<[f64; 3]>> = (0..5).map(|t| vec![[t as f64, 0.0, 0.0], [1.0, 1.0, 1.0]]).collect();
+    let msd = get_mean_squared_displacement(&positions, 10);
+    assert_eq!(5, msd.len());
+    for lag in 0..5 {
+        assert!( (msd[lag] - (lag * lag) as f64 / 2.0).abs() < 0.00001, "{}", msd[lag]);
+    }
+}
+
+#[test]
+fn test_tagged_particles() {
+    let mut rng = ::rand::thread_rng();
+    let params = ::system::Params { num_particles: 64, density: 0.5, cutoff: 2.0, ..::system::Params::default() };
+    let mut system = System::new(params, &mut rng);
+    system.displacement = 1.0;
+    let filename = ::std::env::temp_dir().join("mclj_test_tagged.dat").to_str().unwrap().to_string();
+    let mut tagged = TaggedParticles::new(&filename, vec![3, 10], &system).unwrap();
+    for sweep in 0..20 {
+        for _ in 0..64 {
+            let accepted = system.trial_move(&mut rng);
+            tagged.record_move(&system, accepted);
+        }
+        tagged.write(sweep).unwrap();
+    }
+    tagged.flush().unwrap();
+
+    // unwrapped and wrapped positions agree up to whole boxes
+    for (tag, &i) in tagged.indices.iter().enumerate() {
+        let periods = (tagged.unwrapped[tag][0] - system.rx[i].to_f64()) / system.l_x;
+        assert!( (periods - periods.round()).abs() < 0.00001, "{}", periods);
+    }
+    let records = read_tagged_records(&filename).unwrap();
+    ::std::fs::remove_file(&filename).unwrap();
+    assert_eq!(vec![3, 10], records.indices);
+    assert_eq!(20, records.sweeps.len());
+    assert_eq!(tagged.attempts, records.attempts[19]);
+    assert!(tagged.attempts.iter().zip(tagged.accepted.iter()).all(|(a, b)| b <= a));
+}
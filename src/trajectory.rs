@@ -1,53 +1,135 @@
 #![allow(dead_code)]
-#![allow(unused_must_use)]
-#![allow(unused_variables)]
 
-use std::error::Error;
 use std::io::prelude::*;
-use std::fs::File;
-use std::path::Path;
+use std::fs::{File, OpenOptions};
 use std::fmt;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
+use std::time::Instant;
+use error::{Error, Result};
+use {Coordinate, Real, Step};
 
-pub struct XYZTrajectory {
-    file: File,
+/// Version of the frame header written by XYZTrajectory. 1 is the single line "N ## Box: x y z Temp: T LJ: eps/sig/cutoff",
+/// 2 are extended xyz headers of the particle count and a comment line of key=value pairs (see write_with_columns).
+pub const FRAME_FORMAT : u32 = 2;
+
+/// Ensemble written to the frames
+pub const ENSEMBLE : &str = "NVT";
 
+/// Buffered xyz trajectory writer, flushed at most flush_interval seconds after a frame was written
+pub struct XYZTrajectory {
+    file: BufWriter<File>,
+    pub flush_interval: f64,
+    last_flush: Instant,
+    // key=value pairs appended to the comment line of the next frame only, e.g. the provenance of the run
+    pub header: String,
 }
 
 impl XYZTrajectory {
-    pub fn new(filename: &String) -> XYZTrajectory {
-        let path = Path::new(filename);
-        let display = path.display();
-        // Open a file in write-only mode, returns `io::Result<File>`
-        let traj_file = match File::create(&path) {
-            Err(why) => panic!("couldn't create {}: {}",
-                               display,
-                               why.description()),
+    pub fn new(filename: &String) -> Result<XYZTrajectory> {
+        // Open a file in write-only mode
+        let traj_file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
             Ok(file) => file,
         };
 
-        XYZTrajectory { file: traj_file }
+        Ok(XYZTrajectory::with_file(traj_file))
+
+    }
+
+    /// Opens a trajectory to continue writing at its end, e.g. for restarted runs
+    pub fn append(filename: &String) -> Result<XYZTrajectory> {
+        let traj_file = match OpenOptions::new().create(true).append(true).open(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        Ok(XYZTrajectory::with_file(traj_file))
+    }
 
+    /// Opens a trajectory to continue a restart from the checkpoint of step: keeps the frames up to step as long as
+    /// their steps advance and cuts off the rest, i.e. the frames written after the checkpoint before a crash and a
+    /// truncated last frame, so the restarted frames do not repeat them. Returns the number of dropped frames.
+    pub fn resume(filename: &String, step: Step) -> Result<(XYZTrajectory, usize)> {
+        let mut keep = 0;
+        let mut dropped = 0;
+        if let Ok(mut reader) = TrjReader::new(filename) {
+            let mut last_step = None;
+            while let Ok(frame) = reader.next_frame() {
+                if frame.step.is_some_and(|s| s > step || last_step.is_some_and(|last| s <= last)) { dropped += 1; break; }
+                last_step = frame.step.or(last_step);
+                keep = reader.offset;
+            }
+            // the rest, a truncated frame counts as well
+            let mut end = reader.offset;
+            while reader.next_frame().is_ok() { dropped += 1; end = reader.offset; }
+            if reader.offset > end || (dropped == 0 && end > keep) { dropped += 1; }
+        }
+        let traj_file = match OpenOptions::new().create(true).append(true).open(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        traj_file.set_len(keep)?;
+        Ok((XYZTrajectory::with_file(traj_file), dropped))
     }
 
-    pub fn write(&mut self, rx: &[f64], ry: &[f64], rz: &[f64], num_particles: usize, box_x : f64, box_y : f64, box_z : f64, temp: f64 ,lj_eps : f64, lj_sig : f64, lj_cutoff : f64, flush: bool) {
-        self.file.write(format!("{} ## Box: {} {} {} Temp: {} LJ: {}/{}/{}\n", num_particles, box_x,box_y,box_z,temp, lj_eps, lj_sig, lj_cutoff).as_bytes());
+    fn with_file(file: File) -> XYZTrajectory {
+        return XYZTrajectory { file: BufWriter::new(file), flush_interval: 0.0, last_flush: Instant::now(), header: String::new() };
+    }
+
+    pub fn write(&mut self, rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, box_x : f64, box_y : f64, box_z : f64, temp: f64 ,lj_eps : f64, lj_sig : f64, lj_cutoff : f64, step: Option<Step>, sweep: Option<f64>, flush: bool) -> Result<()> {
+        return self.write_with_columns(rx, ry, rz, num_particles, box_x, box_y, box_z, temp, lj_eps, lj_sig, lj_cutoff, step, sweep, &[], flush);
+    }
+
+    /// Like write with additional columns after the coordinates. Every column is the extended xyz property of its name,
+    /// type and width (e.g. energy:R:1) with a value per particle.
+    ///
+    /// The comment line holds mclj_format (FRAME_FORMAT), Lattice (the box vectors), Properties, temperature, lj_eps,
+    /// lj_sig, cutoff and ensemble, and the completed MC steps and sweeps (moves per particle) of the frame if known,
+    /// followed by the header if there is one. Readers ignore keys they do not know.
+    pub fn write_with_columns(&mut self, rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, box_x : f64, box_y : f64, box_z : f64, temp: f64 ,lj_eps : f64, lj_sig : f64, lj_cutoff : f64, step: Option<Step>, sweep: Option<f64>, columns: &[(&str, Vec<String>)], flush: bool) -> Result<()> {
+        let properties : String = columns.iter().map(|column| format!(":{}", column.0)).collect();
+        let mut stamp = String::new();
+        if let Some(step) = step { stamp += &format!(" step={}", step); }
+        if let Some(sweep) = sweep { stamp += &format!(" sweep={}", sweep); }
+        if !self.header.is_empty() { stamp += &format!(" {}", self.header); }
+        self.header.clear();
+        self.file.write_all(format!("{}\nmclj_format={} Lattice=\"{} 0 0 0 {} 0 0 0 {}\" Properties=species:S:1:pos:R:3{} temperature={} lj_eps={} lj_sig={} cutoff={} ensemble={}{}\n",
+                                    num_particles, FRAME_FORMAT, box_x, box_y, box_z, properties, temp, lj_eps, lj_sig, lj_cutoff, ENSEMBLE, stamp).as_bytes())?;
         for i  in 0..num_particles {
-            let formatted = format!("atom{} {} {} {}\n",
+            let mut formatted = format!("atom{} {} {} {}",
                                     i+1, rx[i], ry[i], rz[i]
             );
-            self.file.write(formatted.as_bytes());
+            for column in columns {
+                formatted.push(' ');
+                formatted.push_str(&column.1[i]);
+            }
+            formatted.push('\n');
+            self.file.write_all(formatted.as_bytes())?;
         }
 
-        if flush { self.file.flush(); }
+        if flush || self.last_flush.elapsed().as_secs_f64() >= self.flush_interval { self.flush()?; }
+        Ok(())
+    }
 
+    /// Writes all buffered frames to the file
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        self.last_flush = Instant::now();
+        Ok(())
     }
 }
 
+impl Drop for XYZTrajectory {
+    // also runs when unwinding from a panic, errors can not be reported anymore
+    fn drop(&mut self) {
+        let _ = self.file.flush();
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Frame {
-    pub rx : Vec<f64>,
-    pub ry : Vec<f64>,
-    pub rz : Vec<f64>,
+    pub rx : Vec<Real>,
+    pub ry : Vec<Real>,
+    pub rz : Vec<Real>,
     pub num_particles: usize,
     pub box_x: f64,
     pub box_y: f64,
@@ -56,6 +138,13 @@ pub struct Frame {
     pub lj_eps: f64,
     pub lj_sig: f64,
     pub lj_cutoff: f64,
+    #[serde(default)]
+    pub ensemble: String,
+    // completed MC steps and sweeps, None for frames without them (older trajectories or other programs)
+    #[serde(default)]
+    pub step: Option<Step>,
+    #[serde(default)]
+    pub sweep: Option<f64>,
 }
 
 impl fmt::Debug for Frame {
@@ -69,137 +158,496 @@ impl fmt::Debug for Frame {
             .field("lj_eps", &self.lj_eps)
             .field("lj_sig", &self.lj_sig)
             .field("lj_cutoff", &self.lj_cutoff)
+            .field("ensemble", &self.ensemble)
+            .field("step", &self.step)
+            .field("sweep", &self.sweep)
             .finish()
     }
 }
 
+/// Splits an extended xyz comment line into key=value pairs, values may be quoted to contain spaces and bare keys
+/// (flags) get empty values
+pub fn parse_key_values(line: &str) -> Vec<(String, String)> {
+    let mut pairs = vec![];
+    let mut chars = line.trim().chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) { chars.next(); }
+        if chars.peek().is_none() { break; }
+        // the key ends before the = or the whitespace after a bare flag, which gets an empty value
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) { key.push(c); }
+        if chars.next_if_eq(&'=').is_none() {
+            pairs.push((key, String::new()));
+            continue;
+        }
+        let value : String = if chars.peek() == Some(&'"') {
+            chars.next();
+            chars.by_ref().take_while(|&c| c != '"').collect()
+        } else {
+            chars.by_ref().take_while(|c| !c.is_whitespace()).collect()
+        };
+        pairs.push((key, value));
+    }
+    return pairs;
+}
+
+#[test]
+fn test_parse_key_values() {
+    let pairs = parse_key_values("mclj_format=2 Lattice=\"1 0 0 0 2 0 0 0 3\" temperature=0.9 flag \n");
+    assert_eq!(("mclj_format".to_string(), "2".to_string()), pairs[0]);
+    assert_eq!(("Lattice".to_string(), "1 0 0 0 2 0 0 0 3".to_string()), pairs[1]);
+    assert_eq!(("temperature".to_string(), "0.9".to_string()), pairs[2]);
+    assert_eq!(("flag".to_string(), "".to_string()), pairs[3]);
+
+    // bare flags in the middle of the line do not take the next pair as their value
+    let pairs = parse_key_values("a flag b=2 pbc empty= d=\"x y\"");
+    let expected = [("a", ""), ("flag", ""), ("b", "2"), ("pbc", ""), ("empty", ""), ("d", "x y")];
+    assert_eq!(expected.iter().map(|&(key, value)| (key.to_string(), value.to_string())).collect::<Vec<_>>(), pairs);
+}
+
+/// Read-only memory map of a whole file, unmapped on drop. Truncating the file while it is mapped crashes the reader.
+#[cfg(all(feature = "mmap", unix))]
+struct Mapping {
+    data: *const u8,
+    len: usize,
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Mapping {
+    // None for empty files or if the file can not be mapped
+    fn new(file: &File) -> Option<Mapping> {
+        use std::os::unix::io::AsRawFd;
+        let len = file.metadata().ok()?.len() as usize;
+        if len == 0 { return None; }
+        let data = unsafe { ::libc::mmap(::std::ptr::null_mut(), len, ::libc::PROT_READ, ::libc::MAP_PRIVATE, file.as_raw_fd(), 0) };
+        if data == ::libc::MAP_FAILED { return None; }
+        unsafe { ::libc::madvise(data, len, ::libc::MADV_SEQUENTIAL); }
+        return Some(Mapping { data: data as *const u8, len: len });
+    }
+
+    fn bytes(&self) -> &[u8] {
+        return unsafe { ::std::slice::from_raw_parts(self.data, self.len) };
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { ::libc::munmap(self.data as *mut ::libc::c_void, self.len); }
+    }
+}
+
+// lines of the trajectory, buffered reads or a mapping of the file
+enum Source {
+    File(BufReader<File>),
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(Mapping),
+}
+
+/// Reader of xyz trajectories. With the mmap feature the file is memory mapped on unix and the lines are parsed in
+/// place without copies, otherwise read through a buffer.
 pub struct TrjReader {
-    pub reader: BufReader<File>,
+    source: Source,
+    // current line, in the buffer or the bytes between start and offset in the mapping
+    buffer: String,
+    start: usize,
+    line: usize,
+    /// bytes read so far, the end of the last frame read
+    pub offset: u64,
 }
 impl TrjReader {
-    pub fn new(filename: &String) -> TrjReader {
-        let file = File::open(filename).expect("Failed to open file.");
-        let reader : BufReader<File> = BufReader::new(file);
-        return TrjReader { reader:reader };
+    pub fn new(filename: &String) -> Result<TrjReader> {
+        let file = match File::open(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        #[cfg(all(feature = "mmap", unix))]
+        let source = match Mapping::new(&file) {
+            Some(mapping) => Source::Mapped(mapping),
+            None => Source::File(BufReader::new(file)),
+        };
+        #[cfg(not(all(feature = "mmap", unix)))]
+        let source = Source::File(BufReader::new(file));
+        return Ok(TrjReader { source: source, buffer: String::new(), start: 0, line: 0, offset: 0 });
     }
 
-    // get next frame
-    pub fn next_frame(&mut self) -> Frame {
-        let buffer_string = &mut String::new();
-        match self.reader.read_line(buffer_string) {
-            Ok(size) => {
-                if size == 0 {
-                    panic!("no new frame!")
+    // advance to the next line, false at the end of the file
+    fn next_line(&mut self) -> Result<bool> {
+        match self.source {
+            Source::File(ref mut reader) => {
+                self.buffer.clear();
+                let size = reader.read_line(&mut self.buffer)?;
+                if size == 0 { return Ok(false); }
+                self.offset += size as u64;
+            },
+            #[cfg(all(feature = "mmap", unix))]
+            Source::Mapped(ref mapping) => {
+                let bytes = mapping.bytes();
+                let start = self.offset as usize;
+                if start >= bytes.len() { return Ok(false); }
+                let end = bytes[start..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |k| start + k + 1);
+                if ::std::str::from_utf8(&bytes[start..end]).is_err() {
+                    return Err(Error::MalformedFrame(self.line + 1, "invalid utf-8".to_string()));
                 }
+                self.start = start;
+                self.offset = end as u64;
             },
-            Err(e) => panic!("no new frame!")
         }
-        let first_line_vec : Vec<&str> = buffer_string.split_whitespace().collect();
-        let num_particles = first_line_vec[0].parse::<usize>().unwrap();
-        let box_x = first_line_vec[3].parse::<f64>().unwrap();
-        let box_y = first_line_vec[4].parse::<f64>().unwrap();
-        let box_z = first_line_vec[5].parse::<f64>().unwrap();
-        let temp = first_line_vec[7].parse::<f64>().unwrap();
-        let lj : Vec<&str> = first_line_vec[9].split("/").collect();
-        let lj_eps = lj[0].parse::<f64>().unwrap();
-        let lj_sig = lj[1].parse::<f64>().unwrap();
-        let lj_cutoff = lj[2].parse::<f64>().unwrap();
-        let mut rx = Vec::new();
-        let mut ry = Vec::new();
-        let mut rz = Vec::new();
-        for i in 0..num_particles{
-            let atom_line = &mut String::new();
-            match self.reader.read_line(atom_line) {
-                Ok(size) => {
-                    let atom_vec : Vec<&str> = atom_line.split_whitespace().collect();
-                    rx.push(atom_vec[1].parse::<f64>().unwrap());
-                    ry.push(atom_vec[2].parse::<f64>().unwrap());
-                    rz.push(atom_vec[3].parse::<f64>().unwrap());
-                },
-                Err(e) => return panic!("no new frame!")
-            };
+        self.line += 1;
+        return Ok(true);
+    }
+
+    // the current line
+    fn line_text(&self) -> &str {
+        return match self.source {
+            Source::File(_) => &self.buffer,
+            // validated by next_line
+            #[cfg(all(feature = "mmap", unix))]
+            Source::Mapped(ref mapping) => unsafe { ::std::str::from_utf8_unchecked(&mapping.bytes()[self.start..self.offset as usize]) },
+        };
+    }
+
+    // parse a single value of a frame
+    fn parse<T: ::std::str::FromStr>(&self, value: Option<&str>, name: &str) -> Result<T> {
+        match value {
+            Some(v) => v.parse::<T>().map_err(|_| Error::MalformedFrame(self.line, format!("invalid {} '{}'", name, v))),
+            None => Err(Error::MalformedFrame(self.line, format!("missing {}", name))),
         }
-        let frame = Frame {
-            rx : rx,
-            ry : ry,
-            rz : rz,
-            num_particles : num_particles,
-            box_x : box_x,
-            box_y : box_y,
-            box_z : box_z,
-            temperature : temp,
-            lj_eps : lj_eps,
-            lj_sig : lj_sig,
-            lj_cutoff : lj_cutoff,
+    }
+
+    // get next frame
+    pub fn next_frame(&mut self) -> Result<Frame> {
+        let mut frame = Frame {
+            rx : Vec::new(),
+            ry : Vec::new(),
+            rz : Vec::new(),
+            num_particles : 0,
+            box_x : 0.0,
+            box_y : 0.0,
+            box_z : 0.0,
+            temperature : 0.0,
+            lj_eps : 0.0,
+            lj_sig : 0.0,
+            lj_cutoff : 0.0,
+            ensemble : String::new(),
+            step : None,
+            sweep : None,
         };
-        return frame;
+        if !self.update_with_next(&mut frame)? {
+            return Err(Error::EndOfTrajectory);
+        }
+        return Ok(frame);
     }
 
-    // read next frame data into the frame
-    pub fn update_with_next(&mut self, frame: &mut Frame) -> bool {
-        let buffer_string = &mut String::new();
-        match self.reader.read_line(buffer_string) {
-            Ok(size) => {
-                if size == 0 {
-                    return false;
-                }
-            },
-            Err(e) => { return false; }
+    // read next frame data into the frame, false if the trajectory has no further frame
+    pub fn update_with_next(&mut self, frame: &mut Frame) -> Result<bool> {
+        if !self.next_line()? { return Ok(false); }
+        let first_line_vec : Vec<&str> = self.line_text().split_whitespace().collect();
+        frame.num_particles = self.parse(first_line_vec.first().cloned(), "number of particles")?;
+        if first_line_vec.len() > 1 {
+            // format 1: everything in the first line
+            frame.box_x = self.parse(first_line_vec.get(3).cloned(), "box x")?;
+            frame.box_y = self.parse(first_line_vec.get(4).cloned(), "box y")?;
+            frame.box_z = self.parse(first_line_vec.get(5).cloned(), "box z")?;
+            frame.temperature = self.parse(first_line_vec.get(7).cloned(), "temperature")?;
+            let lj : Vec<&str> = first_line_vec.get(9).cloned().unwrap_or("").split('/').collect();
+            frame.lj_eps = self.parse(lj.first().cloned(), "lj epsilon")?;
+            frame.lj_sig = self.parse(lj.get(1).cloned(), "lj sigma")?;
+            frame.lj_cutoff = self.parse(lj.get(2).cloned(), "lj cutoff")?;
+            frame.ensemble = ENSEMBLE.to_string();
+            frame.step = None;
+            frame.sweep = None;
+        } else {
+            if !self.next_line()? {
+                return Err(Error::MalformedFrame(self.line, "missing comment line".to_string()));
+            }
+            self.parse_comment_line(self.line_text(), frame)?;
         }
-        let first_line_vec : Vec<&str> = buffer_string.split_whitespace().collect();
-        frame.num_particles = first_line_vec[0].parse::<usize>().unwrap();
-        frame.box_x = first_line_vec[3].parse::<f64>().unwrap();
-        frame.box_y = first_line_vec[4].parse::<f64>().unwrap();
-        frame.box_z = first_line_vec[5].parse::<f64>().unwrap();
-        frame.temperature = first_line_vec[7].parse::<f64>().unwrap();
-        let lj : Vec<&str> = first_line_vec[9].split("/").collect();
-        frame.lj_eps = lj[0].parse::<f64>().unwrap();
-        frame.lj_sig = lj[1].parse::<f64>().unwrap();
-        frame.lj_cutoff = lj[2].parse::<f64>().unwrap();
+        frame.rx.resize(frame.num_particles, 0.0);
+        frame.ry.resize(frame.num_particles, 0.0);
+        frame.rz.resize(frame.num_particles, 0.0);
         for i in 0..frame.num_particles {
-            let atom_line = &mut String::new();
-            match self.reader.read_line(atom_line) {
-                Ok(size) => {
-                    if size == 0 {
-                        return false;
-                    } else {
-                        let atom_vec : Vec<&str> = atom_line.split_whitespace().collect();
-                        frame.rx[i] = atom_vec[1].parse::<f64>().unwrap();
-                        frame.ry[i]= atom_vec[2].parse::<f64>().unwrap();
-                        frame.rz[i] = atom_vec[3].parse::<f64>().unwrap();
-                    }
-                },
-                Err(e) => {return false;}
+            if !self.next_line()? {
+                return Err(Error::MalformedFrame(self.line, format!("frame ends after {} of {} particles", i, frame.num_particles)));
             }
+            let mut atom = self.line_text().split_whitespace().skip(1);
+            frame.rx[i] = self.parse(atom.next(), "x coordinate")?;
+            frame.ry[i] = self.parse(atom.next(), "y coordinate")?;
+            frame.rz[i] = self.parse(atom.next(), "z coordinate")?;
         }
 
-        return true;
+        return Ok(true);
+    }
+
+    // box and parameters from the key=value pairs of an extended xyz comment line, missing keys get defaults (the
+    // temperature NaN, the lj parameters of the crate and a cutoff of 3) except the box
+    fn parse_comment_line(&self, comment: &str, frame: &mut Frame) -> Result<()> {
+        let values = parse_key_values(comment);
+        let get = |key: &str| values.iter().find(|v| v.0 == key).map(|v| v.1.as_str());
+        let lattice : Vec<&str> = get("Lattice").unwrap_or("").split_whitespace().collect();
+        if lattice.len() != 9 {
+            return Err(Error::MalformedFrame(self.line, "missing or invalid Lattice".to_string()));
+        }
+        frame.box_x = self.parse(lattice.first().cloned(), "box x")?;
+        frame.box_y = self.parse(lattice.get(4).cloned(), "box y")?;
+        frame.box_z = self.parse(lattice.get(8).cloned(), "box z")?;
+        frame.temperature = match get("temperature") { Some(v) => self.parse(Some(v), "temperature")?, None => f64::NAN };
+        frame.lj_eps = match get("lj_eps") { Some(v) => self.parse(Some(v), "lj epsilon")?, None => ::LJ_EPS };
+        frame.lj_sig = match get("lj_sig") { Some(v) => self.parse(Some(v), "lj sigma")?, None => ::LJ_SIG };
+        frame.lj_cutoff = match get("cutoff") { Some(v) => self.parse(Some(v), "lj cutoff")?, None => 3.0 };
+        frame.ensemble = get("ensemble").unwrap_or(ENSEMBLE).to_string();
+        frame.step = match get("step") { Some(v) => Some(self.parse(Some(v), "step")?), None => None };
+        frame.sweep = match get("sweep") { Some(v) => Some(self.parse(Some(v), "sweep")?), None => None };
+        return Ok(());
     }
 
     // skip x frames
-    pub fn skip(&mut self, skip: usize) {
-        if skip < 1 { return };
+    pub fn skip(&mut self, skip: usize) -> Result<()> {
+        if skip < 1 { return Ok(()) };
 
         // find number of particles
-        let buffer_string = &mut String::new();
-        match self.reader.read_line(buffer_string) {
-            Ok(size) => {
-                if size == 0 {
-                    panic!("no new frame!")
-                }
-            },
-            Err(e) => panic!("no new frame!")
+        if !self.next_line()? { return Err(Error::EndOfTrajectory); }
+        let first_line_vec : Vec<&str> = self.line_text().split_whitespace().collect();
+        let num_particles : usize = self.parse(first_line_vec.first().cloned(), "number of particles")?;
+
+        // format 2 frames have the comment line on their own
+        let header_lines = if first_line_vec.len() > 1 { 1 } else { 2 };
+        let lines_to_skip = (num_particles + header_lines) * skip - 1;
+        for _ in 0..lines_to_skip {
+            if !self.next_line()? { return Err(Error::EndOfTrajectory); }
+        }
+        return Ok(());
+    }
+
+}
+
+#[test]
+fn test_trj_reader() {
+    let filename = ::std::env::temp_dir().join("mclj_test_trj_reader.xyz").to_str().unwrap().to_string();
+    {
+        let mut trajectory = XYZTrajectory::new(&filename).unwrap();
+        trajectory.write(&[1.0, 2.0], &[3.0, 4.0], &[5.0, 6.0], 2, 10.0, 10.0, 20.0, 0.9, 1.0, 1.0, 3.0, Some(0), Some(0.0), true).unwrap();
+        trajectory.write(&[1.5, 2.5], &[3.5, 4.5], &[5.5, 6.5], 2, 10.0, 10.0, 20.0, 0.9, 1.0, 1.0, 3.0, Some(500), Some(2.5), true).unwrap();
+        // only the box, and a frame of the first format
+        trajectory.file.write_all(b"1\nLattice=\"8 0 0 0 8 0 0 0 9\" comment=\"no parameters\"\natom0 1 2 3\n").unwrap();
+        trajectory.file.write_all(b"1 ## Box: 10 10 20 Temp: 0.7 LJ: 1/1/2.5\natom0 1 2 4\n").unwrap();
+        trajectory.file.write_all(b"2 ## Box: 10 10 20 Temp: 0.9 LJ: 1/1/3\natom1 1 2 3\n").unwrap();
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap();
+    let mut frame = reader.next_frame().unwrap();
+    assert_eq!(2, frame.num_particles);
+    assert_eq!(20.0, frame.box_z);
+    assert_eq!(6.0, frame.rz[1]);
+    assert_eq!(0.9, frame.temperature);
+    assert_eq!(3.0, frame.lj_cutoff);
+    assert_eq!("NVT", frame.ensemble);
+    assert_eq!((Some(0), Some(0.0)), (frame.step, frame.sweep));
+    assert!(reader.update_with_next(&mut frame).unwrap());
+    assert_eq!(6.5, frame.rz[1]);
+    assert_eq!((Some(500), Some(2.5)), (frame.step, frame.sweep));
+
+    let frame = reader.next_frame().unwrap();
+    assert_eq!((8.0, 9.0), (frame.box_y, frame.box_z));
+    assert!(frame.temperature.is_nan());
+    assert_eq!(None, frame.step);
+    assert_eq!((::LJ_EPS, ::LJ_SIG, 3.0), (frame.lj_eps, frame.lj_sig, frame.lj_cutoff));
+    let mut frame = reader.next_frame().unwrap();
+    assert_eq!((20.0, 0.7, 2.5), (frame.box_z, frame.temperature, frame.lj_cutoff));
+    assert_eq!(4.0, frame.rz[0]);
+
+    // truncated last frame
+    match reader.update_with_next(&mut frame) {
+        Err(Error::MalformedFrame(line, _)) => assert_eq!(15, line),
+        _ => panic!("expected malformed frame"),
+    }
+    assert!(!reader.update_with_next(&mut frame).unwrap());
+
+    let mut reader = TrjReader::new(&filename).unwrap();
+    reader.skip(1).unwrap();
+    assert_eq!(6.5, reader.next_frame().unwrap().rz[1]);
+
+    ::std::fs::remove_file(&filename).unwrap();
+    match TrjReader::new(&filename) {
+        Err(Error::FileNotFound(_, _)) => {},
+        _ => panic!("expected missing file"),
+    }
+}
+
+#[test]
+fn test_xyz_trajectory_flush_interval() {
+    let filename = ::std::env::temp_dir().join("mclj_test_flush_interval.xyz").to_str().unwrap().to_string();
+    let read = || ::std::fs::read_to_string(&filename).unwrap().lines().count();
+    {
+        let mut trajectory = XYZTrajectory::new(&filename).unwrap();
+        trajectory.flush_interval = 3600.0;
+        trajectory.write(&[1.0], &[2.0], &[3.0], 1, 10.0, 10.0, 10.0, 0.9, 1.0, 1.0, 3.0, None, None, false).unwrap();
+        assert_eq!(0, read());
+        trajectory.flush().unwrap();
+        assert_eq!(3, read());
+        trajectory.write(&[1.0], &[2.0], &[3.0], 1, 10.0, 10.0, 10.0, 0.9, 1.0, 1.0, 3.0, None, None, false).unwrap();
+    }
+    // dropping the trajectory writes the rest
+    assert_eq!(6, read());
+
+    // the header only goes into the next frame
+    {
+        let mut trajectory = XYZTrajectory::new(&filename).unwrap();
+        trajectory.header = "command=\"mc -t 0.8\"".to_string();
+        for _ in 0..2 { trajectory.write(&[1.0], &[2.0], &[3.0], 1, 10.0, 10.0, 10.0, 0.9, 1.0, 1.0, 3.0, None, None, false).unwrap(); }
+    }
+    let comments : Vec<String> = ::std::fs::read_to_string(&filename).unwrap().lines().skip(1).step_by(3).map(|line| line.to_string()).collect();
+    assert!(parse_key_values(&comments[0]).contains(&("command".to_string(), "mc -t 0.8".to_string())), "{}", comments[0]);
+    assert!(!comments[1].contains("command"));
+    assert_eq!(2, {
+        let mut reader = TrjReader::new(&filename).unwrap();
+        (0..3).filter(|_| reader.next_frame().is_ok()).count()
+    });
+
+    // appending continues the trajectory
+    XYZTrajectory::append(&filename).unwrap().write(&[1.0], &[2.0], &[3.0], 1, 10.0, 10.0, 10.0, 0.9, 1.0, 1.0, 3.0, None, None, true).unwrap();
+    assert_eq!(9, read());
+    ::std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_xyz_trajectory_resume() {
+    let filename = ::std::env::temp_dir().join("mclj_test_resume.xyz").to_str().unwrap().to_string();
+    let write = |trajectory: &mut XYZTrajectory, step: Step| trajectory.write(&[1.0], &[2.0], &[step as Real], 1, 10.0, 10.0, 10.0, 0.9, 1.0, 1.0, 3.0, Some(step), None, true).unwrap();
+    let steps = || {
+        let mut reader = TrjReader::new(&filename).unwrap();
+        let mut steps = vec![];
+        while let Ok(frame) = reader.next_frame() { steps.push(frame.step.unwrap()); }
+        steps
+    };
+    {
+        let mut trajectory = XYZTrajectory::new(&filename).unwrap();
+        for step in [0, 100, 200, 300] { write(&mut trajectory, step); }
+        // the final frame on top of the last one and a frame cut off by the crash
+        write(&mut trajectory, 300);
+        trajectory.file.write_all(b"1\nLattice=\"10 0 0 0 10 0 0 0 10\" step=400\natom0 1").unwrap();
+    }
+
+    // a restart from the checkpoint of step 200 drops the frames after it
+    let (mut trajectory, dropped) = XYZTrajectory::resume(&filename, 200).unwrap();
+    assert_eq!(3, dropped);
+    write(&mut trajectory, 300);
+    drop(trajectory);
+    assert_eq!(vec![0, 100, 200, 300], steps());
+
+    // from the end, and a new file for a missing one
+    assert_eq!(0, XYZTrajectory::resume(&filename, 300).unwrap().1);
+    assert_eq!(vec![0, 100, 200, 300], steps());
+    ::std::fs::remove_file(&filename).unwrap();
+    assert_eq!(0, XYZTrajectory::resume(&filename, 300).unwrap().1);
+    assert!(steps().is_empty());
+    ::std::fs::remove_file(&filename).unwrap();
+}
+
+/// Writes a frame as gromacs .gro file with the lengths in nm, length_unit nm per sigma (1 for reduced units). The
+/// coordinates are rounded to the 3 decimals of the format, the box to 5.
+pub fn write_gro(filename: &String, frame: &Frame, length_unit: f64) -> Result<()> {
+    let file = match File::create(filename) {
+        Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+        Ok(file) => file,
+    };
+    let mut file = BufWriter::new(file);
+    let stamp = frame.step.map_or(String::new(), |step| format!(" step={}", step));
+    writeln!(file, "lj particles, temperature={} cutoff={} ensemble={}{}\n{}", frame.temperature, frame.lj_cutoff, frame.ensemble, stamp, frame.num_particles)?;
+    for i in 0..frame.num_particles {
+        // residue and atom numbers wrap around in the fixed columns
+        writeln!(file, "{:>5}{:<5}{:>5}{:>5}{:8.3}{:8.3}{:8.3}", (i + 1) % 100000, "LJ", "LJ", (i + 1) % 100000,
+               frame.rx[i].to_f64() * length_unit, frame.ry[i].to_f64() * length_unit, frame.rz[i].to_f64() * length_unit)?;
+    }
+    writeln!(file, "{:10.5}{:10.5}{:10.5}", frame.box_x * length_unit, frame.box_y * length_unit, frame.box_z * length_unit)?;
+    file.flush()?;
+    return Ok(());
+}
+
+#[test]
+fn test_write_gro() {
+    let filename = ::std::env::temp_dir().join("mclj_test_write_gro.gro").to_str().unwrap().to_string();
+    let frame = Frame { rx: vec![1.0, 2.5], ry: vec![3.0, 4.0], rz: vec![5.0, 6.0], num_particles: 2, box_x: 10.0, box_y: 10.0, box_z: 20.0, temperature: 0.9,
+                        lj_eps: 1.0, lj_sig: 1.0, lj_cutoff: 3.0, ensemble: "NVT".to_string(), step: Some(100), sweep: None };
+    write_gro(&filename, &frame, 0.5).unwrap();
+    let lines : Vec<String> = ::std::fs::read_to_string(&filename).unwrap().lines().map(|l| l.to_string()).collect();
+    assert_eq!(5, lines.len());
+    assert!(lines[0].ends_with("step=100"), "{}", lines[0]);
+    assert_eq!("2", lines[1]);
+    assert_eq!("    2LJ      LJ    2   1.250   2.000   3.000", lines[3]);
+    assert_eq!("   5.00000   5.00000  10.00000", lines[4]);
+    ::std::fs::remove_file(&filename).unwrap();
+}
+
+/// Pdb writer of frames as models of minimal CRYST1, HETATM and ENDMDL records (no bonds), lengths in Å with
+/// length_unit Å per sigma and the element symbol of all particles
+pub struct PdbWriter {
+    file: BufWriter<File>,
+    models: usize,
+    length_unit: f64,
+    element: String,
+}
+
+impl PdbWriter {
+    pub fn new(filename: &String, length_unit: f64, element: &str) -> Result<PdbWriter> {
+        let file = match File::create(filename) {
+            Err(why) => return Err(Error::FileNotFound(filename.clone(), why)),
+            Ok(file) => file,
+        };
+        return Ok(PdbWriter { file: BufWriter::new(file), models: 0, length_unit: length_unit, element: element.to_uppercase() });
+    }
+
+    /// Writes a REMARK record before the models, e.g. of the provenance of the run, long remarks are split over lines
+    pub fn remark(&mut self, text: &str) -> Result<()> {
+        let chars : Vec<char> = text.chars().collect();
+        for line in chars.chunks(68) {
+            writeln!(self.file, "REMARK   1 {}", line.iter().collect::<String>())?;
         }
-        let first_line_vec : Vec<&str> = buffer_string.split_whitespace().collect();
-        let num_particles = first_line_vec[0].parse::<usize>().unwrap();
-
-        let lines_to_skip = (num_particles + 1) * skip - 1;
-        let mut skipped = 0;
-        loop {
-            self.reader.read_line(&mut String::new());
-            skipped += 1;
-            if skipped == lines_to_skip { break; }
+        return Ok(());
+    }
+
+    pub fn write(&mut self, frame: &Frame) -> Result<()> {
+        let unit = self.length_unit;
+        self.models += 1;
+        writeln!(self.file, "CRYST1{:9.3}{:9.3}{:9.3}{:7.2}{:7.2}{:7.2} P 1           1", frame.box_x * unit, frame.box_y * unit, frame.box_z * unit, 90.0, 90.0, 90.0)?;
+        writeln!(self.file, "MODEL     {:>4}", self.models % 10000)?;
+        // one letter symbols start in the second column of the atom name
+        let name = if self.element.len() == 1 { format!(" {:<3}", self.element) } else { format!("{:<4}", self.element) };
+        for i in 0..frame.num_particles {
+            writeln!(self.file, "HETATM{:>5} {} {:>3}  {:>4}    {:8.3}{:8.3}{:8.3}{:6.2}{:6.2}          {:>2}", (i + 1) % 100000, name, "LJ", (i + 1) % 10000,
+                     frame.rx[i].to_f64() * unit, frame.ry[i].to_f64() * unit, frame.rz[i].to_f64() * unit, 1.0, 0.0, self.element)?;
         }
+        writeln!(self.file, "ENDMDL")?;
+        return Ok(());
     }
 
+    /// Ends the file and writes it out
+    pub fn finish(&mut self) -> Result<()> {
+        writeln!(self.file, "END")?;
+        self.file.flush()?;
+        return Ok(());
+    }
+}
+
+#[test]
+fn test_pdb_writer() {
+    let filename = ::std::env::temp_dir().join("mclj_test_pdb_writer.pdb").to_str().unwrap().to_string();
+    let frame = Frame { rx: vec![1.0, 2.5], ry: vec![3.0, 4.0], rz: vec![5.0, 6.0], num_particles: 2, box_x: 10.0, box_y: 10.0, box_z: 20.0, temperature: 0.9,
+                        lj_eps: 1.0, lj_sig: 1.0, lj_cutoff: 3.0, ensemble: "NVT".to_string(), step: None, sweep: None };
+    let mut pdb = PdbWriter::new(&filename, 3.405, "Ar").unwrap();
+    pdb.remark(&"x".repeat(100)).unwrap();
+    pdb.write(&frame).unwrap();
+    pdb.write(&frame).unwrap();
+    pdb.finish().unwrap();
+    let text = ::std::fs::read_to_string(&filename).unwrap();
+    let lines : Vec<&str> = text.lines().collect();
+    assert_eq!(13, lines.len());
+    assert_eq!(format!("REMARK   1 {}", "x".repeat(68)), lines[0]);
+    assert_eq!(format!("REMARK   1 {}", "x".repeat(32)), lines[1]);
+    assert_eq!("CRYST1   34.050   34.050   68.100  90.00  90.00  90.00 P 1           1", lines[2]);
+    assert_eq!("MODEL        1", lines[3]);
+    assert_eq!("HETATM    2 AR    LJ     2       8.512  13.620  20.430  1.00  0.00          AR", lines[5]);
+    assert!(lines.iter().all(|line| line.len() <= 80));
+    assert_eq!(("ENDMDL", "END"), (lines[11], lines[12]));
+    ::std::fs::remove_file(&filename).unwrap();
 }
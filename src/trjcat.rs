@@ -0,0 +1,71 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, List, Store};
+use mclj::error::exit_with;
+use mclj::trajectory::*;
+
+fn main() {
+    let mut files : Vec<String> = vec![];
+    let mut output : String = "trjcat.xyz".to_string();
+    let mut begin : usize = 0;
+    let mut end : usize = usize::MAX;
+    let mut stride : usize = 1;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Concatenates trajectory chunks, e.g. of restarted runs, drops repeated frames at the joins and trims or strides the result.");
+        ap.refer(&mut files)
+            .add_argument("files", List, "Trajectories in order").required();
+        ap.refer(&mut output)
+            .add_option(&["-o", "--output"], Store, "Output trajectory");
+        ap.refer(&mut begin)
+            .add_option(&["-b", "--begin"], Store, "First frame of the concatenated trajectory to write (counted from 0)");
+        ap.refer(&mut end)
+            .add_option(&["-e", "--end"], Store, "Stop before this frame of the concatenated trajectory");
+        ap.refer(&mut stride)
+            .add_option(&["--stride"], Store, "Write every n-th frame");
+        ap.parse_args_or_exit();
+    }
+    if stride == 0 {
+        eprintln!("Error: stride has to be at least 1.");
+        std::process::exit(1);
+    }
+
+    let mut trajectory = XYZTrajectory::new(&output).unwrap_or_else(exit_with);
+    let mut previous : Option<Frame> = None;
+    let (mut read, mut duplicates, mut index, mut written) = (0, 0, 0, 0);
+    'files: for filename in &files {
+        let mut reader = TrjReader::new(filename).unwrap_or_else(exit_with);
+        let mut frame = match reader.next_frame() {
+            Ok(frame) => frame,
+            Err(e) => { eprintln!("Skipping {}: {}", filename, e); continue }
+        };
+        loop {
+            read += 1;
+            // a restart or the final frame of a run repeats the last written configuration, a restart from an older
+            // checkpoint repeats the steps after it
+            let repeated = match previous {
+                Some(ref previous) => previous == &frame || (frame.step.is_some() && previous.step.is_some() && frame.step <= previous.step),
+                None => false,
+            };
+            if repeated {
+                duplicates += 1;
+            } else {
+                if index >= end { break 'files; }
+                if index >= begin && (index - begin).is_multiple_of(stride) {
+                    trajectory.write(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, frame.box_x, frame.box_y, frame.box_z,
+                                     frame.temperature, frame.lj_eps, frame.lj_sig, frame.lj_cutoff, frame.step, frame.sweep, false).unwrap_or_else(exit_with);
+                    written += 1;
+                }
+                index += 1;
+                previous = Some(frame.clone());
+            }
+            match reader.update_with_next(&mut frame) {
+                Ok(true) => {},
+                Ok(false) => break,
+                Err(e) => { eprintln!("Stopping {} early: {}", filename, e); break }
+            }
+        }
+    }
+    trajectory.flush().unwrap_or_else(exit_with);
+    eprintln!("Read {} frames of {} files, dropped {} repeated frames, wrote {} frames to {}", read, files.len(), duplicates, written, output);
+}
@@ -0,0 +1,122 @@
+//! Text rendering of traces and profiles for the terminal dashboard
+use std::collections::VecDeque;
+use observables::get_density_profile;
+use system::System;
+use Step;
+
+const SPARKS : [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const EIGHTHS : [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+// layout of the dashboard
+const TRACE_LENGTH : usize = 60;
+const PROFILE_BINS : usize = 20;
+const BAR_WIDTH : usize = 50;
+
+/// One character per value scaled between the minimum and maximum, non finite values are blank
+pub fn sparkline(values: &[f64]) -> String {
+    let finite : Vec<f64> = values.iter().cloned().filter(|v| v.is_finite()).collect();
+    let min = finite.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = finite.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    return values.iter().map(|&v| {
+        if !v.is_finite() {
+            ' '
+        } else if max > min {
+            SPARKS[(((v - min) / (max - min)) * 7.0).round() as usize]
+        } else {
+            SPARKS[3]
+        }
+    }).collect();
+}
+
+/// Horizontal bar of value/max * width characters with eighth block resolution
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    if !(value.is_finite() && max > 0.0) || value <= 0.0 {
+        return String::new();
+    }
+    let eighths = ((value / max).min(1.0) * (width * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    let rest = eighths % 8;
+    if rest > 0 {
+        bar.push(EIGHTHS[rest]);
+    }
+    return bar;
+}
+
+/// Bar chart of a density profile along z, one line per bin
+pub fn render_profile(profile: &[f64], l_z: f64, width: usize) -> String {
+    let max = profile.iter().cloned().fold(0.0, f64::max);
+    let bin_height = l_z / profile.len() as f64;
+    let mut text = String::new();
+    // top of the box first
+    for (i, &density) in profile.iter().enumerate().rev() {
+        text.push_str(&format!("{:8.2} |{:<width$}| {:.3}\n", (i as f64 + 0.5) * bin_height, bar(density, max, width), density, width = width));
+    }
+    return text;
+}
+
+/// Rolling traces of the observables shown in the terminal dashboard
+#[derive(Default)]
+pub struct Dashboard {
+    energy: VecDeque<f64>,
+    pressure: VecDeque<f64>,
+    acceptance: VecDeque<f64>,
+}
+
+impl Dashboard {
+    /// Adds the current observables to the traces
+    pub fn push(&mut self, system: &System, acceptance: f64) {
+        for &mut (ref mut trace, value) in [(&mut self.energy, system.energy / system.num_particles as f64),
+                                            (&mut self.pressure, system.pressure()),
+                                            (&mut self.acceptance, acceptance)].iter_mut() {
+            trace.push_back(value);
+            if trace.len() > TRACE_LENGTH {
+                trace.pop_front();
+            }
+        }
+    }
+
+    /// Complete screen including the escape sequences to redraw the terminal
+    pub fn render(&self, system: &System, step: Step, total_steps: Step, phase: &str) -> String {
+        let last = |trace: &VecDeque<f64>| *trace.back().unwrap_or(&f64::NAN);
+        let line = |trace: &VecDeque<f64>| sparkline(&trace.iter().cloned().collect::<Vec<f64>>());
+        let mut text = String::from("\x1b[H\x1b[2J");
+        text.push_str(&format!(" LJ Monte Carlo   {}   step {} / {} ({:.1}%)\n\n", phase, step, total_steps, 100.0 * step as f64 / total_steps as f64));
+        text.push_str(&format!(" Particles {}   Density {:.3}   Temperature {:.3}   Box {:.2}/{:.2}/{:.2}\n\n",
+                               system.num_particles, system.density, system.params.temperature, system.l_x, system.l_y, system.l_z));
+        text.push_str(&format!(" Energy/N   {:>10.4}  {}\n", last(&self.energy), line(&self.energy)));
+        text.push_str(&format!(" Pressure   {:>10.4}  {}\n", last(&self.pressure), line(&self.pressure)));
+        text.push_str(&format!(" Acceptance {:>9.1}%  {}\n", 100.0 * last(&self.acceptance), line(&self.acceptance)));
+        text.push_str(&format!(" Displacement {:>8.4}\n\n", system.displacement));
+        text.push_str("  density profile rho(z)\n");
+        let profile = get_density_profile(&system.rz, system.num_particles, system.l_x, system.l_y, system.l_z, PROFILE_BINS);
+        text.push_str(&render_profile(&profile, system.l_z, BAR_WIDTH));
+        return text;
+    }
+}
+
+#[test]
+fn test_sparkline() {
+    assert_eq!("▁▅█", sparkline(&[0.0, 0.5, 1.0]));
+    assert_eq!("▄▄", sparkline(&[2.0, 2.0]));
+    assert_eq!("▁ █", sparkline(&[0.0, f64::NAN, 1.0]));
+    assert_eq!("", sparkline(&[]));
+}
+
+#[test]
+fn test_bar() {
+    assert_eq!("██████████", bar(1.0, 1.0, 10));
+    assert_eq!("█████", bar(0.5, 1.0, 10));
+    assert_eq!("█▌", bar(0.15, 1.0, 10));
+    assert_eq!("", bar(0.0, 1.0, 10));
+    assert_eq!("", bar(1.0, 0.0, 10));
+}
+
+#[test]
+fn test_render_profile() {
+    let text = render_profile(&[0.0, 0.8, 0.4], 3.0, 4);
+    let lines : Vec<&str> = text.lines().collect();
+    assert_eq!(3, lines.len());
+    assert_eq!("    2.50 |██  | 0.400", lines[0]);
+    assert_eq!("    1.50 |████| 0.800", lines[1]);
+    assert_eq!("    0.50 |    | 0.000", lines[2]);
+}
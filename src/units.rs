@@ -0,0 +1,99 @@
+//! Real units: the lj parameters of a substance and the conversion of the reduced units to kelvin, kJ/mol, nm and bar
+
+// boltzmann constant (J/K), avogadro constant (1/mol) and molar gas constant (J/(mol K))
+const BOLTZMANN : f64 = 1.380649e-23;
+const AVOGADRO : f64 = 6.02214076e23;
+const GAS_CONSTANT : f64 = BOLTZMANN * AVOGADRO;
+
+/// Well depth eps/k_B in kelvin, diameter sigma in nm and molar mass in g/mol (0 if unknown) of the lj particles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Units {
+    pub eps: f64,
+    pub sig: f64,
+    pub mass: f64,
+}
+
+/// Argon (eps/k_B = 119.8 K, sigma = 0.3405 nm, 39.948 g/mol)
+pub const ARGON : Units = Units { eps: 119.8, sig: 0.3405, mass: 39.948 };
+
+impl Units {
+    /// Parses argon or a , separated list of eps=<value>K or kJ/mol, sig=<value>nm or A and optionally mass=<g/mol>,
+    /// e.g. eps=0.996kJ/mol,sig=3.405A,mass=39.948
+    pub fn parse(spec: &str) -> Option<Units> {
+        if spec.trim() == "argon" { return Some(ARGON); }
+        let (mut eps, mut sig, mut mass) = (None, None, 0.0);
+        for field in spec.split(',') {
+            let (key, value) = field.trim().split_once('=')?;
+            let number = |unit: &str| value.strip_suffix(unit).and_then(|v| v.trim().parse::<f64>().ok()).filter(|v| v.is_finite() && *v > 0.0);
+            match key.trim() {
+                "eps" => eps = Some(number("K").or_else(|| number("kJ/mol").map(|e| e * 1000.0 / GAS_CONSTANT))?),
+                "sig" => sig = Some(number("nm").or_else(|| number("A").or_else(|| number("Å")).map(|s| s / 10.0))?),
+                "mass" => mass = value.trim().parse::<f64>().ok().filter(|m| m.is_finite() && *m > 0.0)?,
+                _ => return None,
+            }
+        }
+        return Some(Units { eps: eps?, sig: sig?, mass: mass });
+    }
+
+    /// Temperature in kelvin of a reduced temperature
+    pub fn temperature(&self, temperature: f64) -> f64 {
+        return temperature * self.eps;
+    }
+
+    /// Reduced temperature of a temperature in kelvin
+    pub fn reduced_temperature(&self, kelvin: f64) -> f64 {
+        return kelvin / self.eps;
+    }
+
+    /// Energy in kJ/mol of a reduced energy
+    pub fn energy(&self, energy: f64) -> f64 {
+        return energy * self.eps * GAS_CONSTANT / 1000.0;
+    }
+
+    /// Length in nm of a reduced length
+    pub fn length(&self, length: f64) -> f64 {
+        return length * self.sig;
+    }
+
+    /// Pressure in bar of a reduced pressure eps/sigma³
+    pub fn pressure(&self, pressure: f64) -> f64 {
+        return pressure * self.eps * BOLTZMANN / (self.sig * 1e-9).powi(3) / 1e5;
+    }
+
+    /// Surface tension in mN/m of a reduced tension eps/sigma²
+    pub fn tension(&self, tension: f64) -> f64 {
+        return tension * self.eps * BOLTZMANN / (self.sig * 1e-9).powi(2) * 1e3;
+    }
+
+    /// Number density in 1/nm³ of a reduced density
+    pub fn number_density(&self, density: f64) -> f64 {
+        return density / self.sig.powi(3);
+    }
+
+    /// Mass density in kg/m³ of a reduced density, NaN without the molar mass
+    pub fn mass_density(&self, density: f64) -> f64 {
+        if self.mass == 0.0 { return f64::NAN; }
+        return density * self.mass * 1e-3 / AVOGADRO / (self.sig * 1e-9).powi(3);
+    }
+}
+
+#[test]
+fn test_units() {
+    assert_eq!(Some(ARGON), Units::parse("argon"));
+    let units = Units::parse("eps=0.99607kJ/mol, sig=3.405A, mass=39.948").unwrap();
+    assert!( (units.eps - 119.8).abs() < 0.01 && (units.sig - 0.3405).abs() < 1e-12 && units.mass == 39.948, "{:?}", units);
+    assert_eq!(Some(Units { eps: 120.0, sig: 0.34, mass: 0.0 }), Units::parse("eps=120K,sig=0.34nm"));
+    assert_eq!(None, Units::parse("eps=120K"));
+    assert_eq!(None, Units::parse("eps=120,sig=0.34nm"));
+    assert_eq!(None, Units::parse("eps=-120K,sig=0.34nm"));
+
+    // the argon units of temperature, energy, pressure, tension and density
+    assert!( (ARGON.temperature(0.75) - 89.85).abs() < 1e-9);
+    assert!( (ARGON.reduced_temperature(119.8) - 1.0).abs() < 1e-12);
+    assert!( (ARGON.energy(1.0) - 0.99607).abs() < 1e-4, "{}", ARGON.energy(1.0));
+    assert!( (ARGON.pressure(1.0) - 419.0).abs() < 1.0, "{}", ARGON.pressure(1.0));
+    assert!( (ARGON.tension(1.0) - 14.27).abs() < 0.01, "{}", ARGON.tension(1.0));
+    assert!( (ARGON.mass_density(1.0) - 1680.0).abs() < 1.0, "{}", ARGON.mass_density(1.0));
+    assert!( (ARGON.number_density(1.0) * 0.3405f64.powi(3) - 1.0).abs() < 1e-12);
+    assert!(Units { mass: 0.0, ..ARGON }.mass_density(1.0).is_nan());
+}
@@ -0,0 +1,58 @@
+extern crate mclj;
+extern crate argparse;
+use argparse::{ArgumentParser, Store, StoreTrue};
+use mclj::error::exit_with;
+use mclj::tracking::update_image_flags;
+use mclj::trajectory::*;
+use mclj::{Coordinate, Real};
+
+fn main() {
+    let mut filename : String = "montecarlo.xyz".to_string();
+    let mut output : String = "unwrapped.xyz".to_string();
+    let mut images_only : bool = false;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Removes the periodic jumps of the particles from a trajectory by tracking their image flags across the frames.");
+        ap.refer(&mut filename)
+            .add_option(&["-f", "--file"], Store, "Input trajectory");
+        ap.refer(&mut output)
+            .add_option(&["-o", "--output"], Store, "Output trajectory");
+        ap.refer(&mut images_only)
+            .add_option(&["--images"], StoreTrue, "Keep the wrapped coordinates and write the image flags ix iy iz as extra columns instead");
+        ap.parse_args_or_exit();
+    }
+
+    let mut reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
+    let mut trajectory = XYZTrajectory::new(&output).unwrap_or_else(exit_with);
+    let mut frame = reader.next_frame().unwrap_or_else(exit_with);
+    let mut previous = frame.clone();
+    let mut images = vec![[0i64; 3]; frame.num_particles];
+    let mut frames = 0;
+    loop {
+        if frame.num_particles != images.len() {
+            eprintln!("Error: frame {} has {} instead of {} particles.", frames + 1, frame.num_particles, images.len());
+            std::process::exit(1);
+        }
+        update_image_flags(&mut images, &previous, &frame);
+        if images_only {
+            let columns : Vec<(&str, Vec<String>)> = ["ix:I:1", "iy:I:1", "iz:I:1"].iter().enumerate()
+                .map(|(k, &name)| (name, images.iter().map(|image| image[k].to_string()).collect())).collect();
+            trajectory.write_with_columns(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, frame.box_x, frame.box_y, frame.box_z,
+                                          frame.temperature, frame.lj_eps, frame.lj_sig, frame.lj_cutoff, frame.step, frame.sweep, &columns, false).unwrap_or_else(exit_with);
+        } else {
+            let unwrap = |r: &[Real], k: usize, length: f64| -> Vec<Real> { r.iter().zip(images.iter()).map(|(x, image)| Real::from_f64(x.to_f64() + image[k] as f64 * length)).collect() };
+            trajectory.write(&unwrap(&frame.rx, 0, frame.box_x), &unwrap(&frame.ry, 1, frame.box_y), &unwrap(&frame.rz, 2, frame.box_z), frame.num_particles,
+                             frame.box_x, frame.box_y, frame.box_z, frame.temperature, frame.lj_eps, frame.lj_sig, frame.lj_cutoff, frame.step, frame.sweep, false).unwrap_or_else(exit_with);
+        }
+        frames += 1;
+
+        previous.clone_from(&frame);
+        match reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
+        }
+    }
+    trajectory.flush().unwrap_or_else(exit_with);
+    eprintln!("Unwrapped {} frames into {}", frames, output);
+}
@@ -0,0 +1,117 @@
+extern crate rand;
+extern crate mclj;
+extern crate argparse;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use argparse::{ArgumentParser, Store};
+use mclj::system::*;
+use mclj::Step;
+
+/// NIST Standard Reference Simulation Website results for the LJ fluid with N = 500, cutoff 3 sigma and
+/// long range corrections (no shift) of the vapor and the liquid close to coexistence: temperature, density,
+/// energy per particle and pressure with errors
+const NIST_REFERENCE : [(f64, f64, f64, f64, f64, f64); 14] = [
+    (0.85, 0.001, -1.0317e-02, 2.4e-05, 8.4402e-04, 4.66e-08),
+    (0.85, 0.003, -3.1019e-02, 1.8e-05, 2.4965e-03, 2.11e-07),
+    (0.85, 0.005, -5.1901e-02, 7.1e-05, 4.1003e-03, 6.20e-07),
+    (0.85, 0.007, -7.2834e-02, 1.3e-04, 5.6565e-03, 1.08e-06),
+    (0.85, 0.009, -9.3973e-02, 1.2e-04, 7.1641e-03, 1.16e-06),
+    (0.85, 0.776, -5.5121e+00, 4.7e-04, 6.7714e-03, 1.25e-03),
+    (0.85, 0.780, -5.5386e+00, 7.6e-04, 4.7924e-02, 2.53e-03),
+    (0.90, 0.001, -9.9165e-03, 1.9e-05, 8.9429e-04, 2.48e-08),
+    (0.90, 0.003, -2.9787e-02, 3.1e-05, 2.6485e-03, 2.52e-07),
+    (0.90, 0.005, -4.9771e-02, 3.8e-05, 4.3569e-03, 2.14e-07),
+    (0.90, 0.007, -6.9805e-02, 7.7e-05, 6.0193e-03, 5.54e-07),
+    (0.90, 0.009, -8.9936e-02, 5.3e-05, 7.6363e-03, 1.66e-06),
+    (0.90, 0.776, -5.4689e+00, 4.4e-04, 2.4056e-01, 2.98e-03),
+    (0.90, 0.780, -5.4956e+00, 7.4e-04, 2.7851e-01, 1.89e-03),
+];
+
+const NIST_PARTICLES : usize = 500;
+const NIST_CUTOFF : f64 = 3.0;
+
+/// Deviation between a result and the reference in units of their combined error
+fn get_deviation(value: f64, error: f64, reference: f64, reference_error: f64) -> f64 {
+    return (value - reference).abs() / (error.powi(2) + reference_error.powi(2)).sqrt();
+}
+
+#[test]
+fn test_get_deviation() {
+    let result = get_deviation(1.5, 0.3, 1.0, 0.4);
+    assert!( (result - 1.0).abs() < 0.00001, "{}", result);
+    assert_eq!(0.0, get_deviation(1.0, 0.1, 1.0, 0.0));
+}
+
+fn main() {
+    let mut eq_sweeps : Step = 2000;
+    let mut sample_sweeps : Step = 10000;
+    let mut tolerance : f64 = 3.0;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut seed : usize = 0;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Runs the NIST reference state points of the LJ fluid and compares energy and pressure with the published values.");
+        ap.refer(&mut eq_sweeps)
+            .add_option(&["-m", "--nminimsweeps"], Store, "Equilibration sweeps per state point");
+        ap.refer(&mut sample_sweeps)
+            .add_option(&["-n", "--nsweeps"], Store, "Sampling sweeps per state point");
+        ap.refer(&mut tolerance)
+            .add_option(&["--tolerance"], Store, "Allowed deviation in units of the combined error");
+        ap.refer(&mut threads)
+            .add_option(&["-j", "--threads"], Store, "Number of parallel runs");
+        ap.refer(&mut seed)
+            .add_option(&["--seed"], Store, "Master seed, every state point runs on its own stream of it (0=seeded from the os)");
+        ap.parse_args_or_exit();
+    }
+    if sample_sweeps < SAMPLE_BLOCKS {
+        eprintln!("Error: at least {} sampling sweeps are needed for the error estimates.", SAMPLE_BLOCKS);
+        std::process::exit(1);
+    }
+    eprintln!("Running {} NIST state points with {} particles on {} threads", NIST_REFERENCE.len(), NIST_PARTICLES, threads);
+
+    let queue : Arc<Mutex<VecDeque<usize>>> = Arc::new(Mutex::new((0..NIST_REFERENCE.len()).collect()));
+    let results : Arc<Mutex<Vec<Option<Averages>>>> = Arc::new(Mutex::new(vec![None; NIST_REFERENCE.len()]));
+    let workers : Vec<thread::JoinHandle<()>> = (0..threads.max(1)).map(|_| {
+        let (queue, results) = (queue.clone(), results.clone());
+        thread::spawn(move || loop {
+            let index = match queue.lock().unwrap().pop_front() {
+                Some(index) => index,
+                None => break,
+            };
+            let (temperature, density, _, _, _, _) = NIST_REFERENCE[index];
+            let params = Params {
+                num_particles: NIST_PARTICLES, density: density, temperature: temperature, cutoff: NIST_CUTOFF,
+                shift: false, tailcorr: true, sweeps: true, eq_steps: eq_sweeps, sample_steps: sample_sweeps,
+                ..Params::default()
+            };
+            let mut rng = get_stream_rng(seed, StreamDomain::Validate, index as u64);
+            let mut system = System::new(params, &mut rng);
+            system.equilibrate(eq_sweeps, &mut rng);
+            let averages = system.sample(sample_sweeps, &mut rng);
+            eprintln!("Done T = {}, rho = {}", temperature, density);
+            results.lock().unwrap()[index] = Some(averages);
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().expect("worker panicked");
+    }
+
+    let n = NIST_PARTICLES as f64;
+    let mut failed = 0;
+    println!("{:>5} {:>6} {:>24} {:>24} {:>6} {:>24} {:>24} {:>6} result", "T", "rho", "U/N", "NIST U/N", "dev", "p", "NIST p", "dev");
+    for (reference, averages) in NIST_REFERENCE.iter().zip(results.lock().unwrap().iter()) {
+        let &(temperature, density, energy, energy_error, pressure, pressure_error) = reference;
+        let averages = averages.as_ref().expect("missing state point");
+        let energy_deviation = get_deviation(averages.energy / n, averages.energy_error / n, energy, energy_error);
+        let pressure_deviation = get_deviation(averages.pressure, averages.pressure_error, pressure, pressure_error);
+        let passed = energy_deviation <= tolerance && pressure_deviation <= tolerance;
+        if !passed { failed += 1; }
+        println!("{:>5} {:>6} {:>24} {:>24} {:>6.2} {:>24} {:>24} {:>6.2} {}", temperature, density,
+                 format!("{:.5} +- {:.5}", averages.energy / n, averages.energy_error / n), format!("{:.5} +- {:.5}", energy, energy_error), energy_deviation,
+                 format!("{:.5} +- {:.5}", averages.pressure, averages.pressure_error), format!("{:.5} +- {:.5}", pressure, pressure_error), pressure_deviation,
+                 if passed { "pass" } else { "FAIL" });
+    }
+    println!("{} of {} state points within {} combined errors", NIST_REFERENCE.len() - failed, NIST_REFERENCE.len(), tolerance);
+    if failed > 0 { std::process::exit(1); }
+}
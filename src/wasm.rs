@@ -0,0 +1,87 @@
+//! WebAssembly interface for the in-browser demo in www/, built with the wasm feature
+use rand::{SeedableRng, XorShiftRng};
+use wasm_bindgen::prelude::*;
+use system::{is_positive, Params, System};
+use {Coordinate, Step};
+
+/// LJ system driven from javascript
+#[wasm_bindgen]
+pub struct Simulation {
+    system: System,
+    rng: XorShiftRng,
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    /// Creates a system with randomly placed particles, one step is a sweep over all particles
+    #[wasm_bindgen(constructor)]
+    pub fn new(num_particles: usize, density: f64, temperature: f64, cutoff: f64, seed: u32) -> Result<Simulation, JsValue> {
+        let params = Params { num_particles: num_particles, density: density, temperature: temperature, cutoff: cutoff, sweeps: true, ..Params::default() };
+        let errors = params.validate();
+        if !errors.is_empty() {
+            return Err(JsValue::from_str(&errors.join(" ")));
+        }
+        // the os random source is not available in the browser
+        let mut rng = XorShiftRng::from_seed([seed | 1, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]);
+        let system = System::new(params, &mut rng);
+        return Ok(Simulation { system: system, rng: rng });
+    }
+
+    /// Runs steps sweeps
+    pub fn run(&mut self, steps: usize) {
+        self.system.run(steps as Step, &mut self.rng);
+    }
+
+    pub fn set_temperature(&mut self, temperature: f64) -> Result<(), JsValue> {
+        if !is_positive(temperature) {
+            return Err(JsValue::from_str("Temperature has to be positive."));
+        }
+        self.system.set_temperature(temperature);
+        self.system.reset_averages();
+        return Ok(());
+    }
+
+    pub fn set_density(&mut self, density: f64) -> Result<(), JsValue> {
+        if !is_positive(density) {
+            return Err(JsValue::from_str("Density has to be positive."));
+        }
+        self.system.set_density(density).map_err(|e| JsValue::from_str(&e))?;
+        self.system.reset_averages();
+        return Ok(());
+    }
+
+    pub fn set_displacement(&mut self, displacement: f64) {
+        self.system.displacement = displacement.min(self.system.max_displacement);
+    }
+
+    pub fn energy(&self) -> f64 { self.system.energy }
+
+    pub fn pressure(&self) -> f64 { self.system.pressure() }
+
+    /// Steps done, a Number is exact up to 2^53 where a u64 would turn into a BigInt
+    pub fn steps(&self) -> f64 { self.system.counters.step as f64 }
+
+    /// Acceptance ratio since the last parameter change
+    pub fn acceptance(&self) -> f64 {
+        let tries = self.system.counters.step_counter * self.system.moves_per_step();
+        return self.system.counters.accept_counter as f64 / tries as f64;
+    }
+
+    pub fn num_particles(&self) -> usize { self.system.num_particles }
+
+    /// Box lengths x, y, z
+    pub fn box_size(&self) -> Vec<f64> {
+        return vec![self.system.l_x, self.system.l_y, self.system.l_z];
+    }
+
+    /// Snapshot of the positions as x0 y0 z0 x1 ... (a Float64Array in javascript)
+    pub fn positions(&self) -> Vec<f64> {
+        let mut positions = Vec::with_capacity(3 * self.system.num_particles);
+        for i in 0..self.system.num_particles {
+            positions.push(self.system.rx[i].to_f64());
+            positions.push(self.system.ry[i].to_f64());
+            positions.push(self.system.rz[i].to_f64());
+        }
+        return positions;
+    }
+}
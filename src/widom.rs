@@ -1,20 +1,21 @@
 #![allow(unused_variables)]
 
-mod trajectory;
-use trajectory::*;
-mod energy;
-use energy::*;
+extern crate mclj;
+use mclj::trajectory::*;
+use mclj::error::exit_with;
+use mclj::energy::*;
 extern crate rand;
 use rand::Rng;
 use std::env;
 
-const LJ_EPS : f64 = 1.0;
-const LJ_SIG : f64 = 1.0;
+use mclj::{LJ_EPS, LJ_SIG};
+use mclj::{Coordinate, Real, Step};
+use mclj::statistics::Statistics;
 
 static MKSA_PLANCKS_CONSTANT_H : f64 = 1.0;
 static MASS : f64 = 1.0;
 
-const RUN_AVG_SIZE : usize = 1;
+const RUN_AVG_SIZE : Step = 1;
 
 fn main() {
     // default values
@@ -46,15 +47,15 @@ fn main() {
     }
 
     // open file and skip to requested position
-    let mut trj_reader = TrjReader::new(&filename);
+    let mut trj_reader = TrjReader::new(&filename).unwrap_or_else(exit_with);
     if skip > 0 {
         println!("Skipping {} frames.", skip);
-        trj_reader.skip(skip);
+        trj_reader.skip(skip).unwrap_or_else(exit_with);
         println!("Done.");
     };
 
     // Get first frame and read system configuration
-    let mut frame = trj_reader.next_frame();
+    let mut frame = trj_reader.next_frame().unwrap_or_else(exit_with);
     let volume = frame.box_x * frame.box_y * frame.box_z;
     let beta = 1.0/frame.temperature;
     let cutoff_sqr = frame.lj_cutoff * frame.lj_cutoff;
@@ -80,11 +81,10 @@ fn main() {
 
     // average counters
     let mut frame_count = 0;
-    let mut widom_sum_gas = 0.0;
-    let mut ideal_pot_gas_sum = 0.0;
-    let mut widom_sum_liquid = 0.0;
-    let mut ideal_pot_liquid_sum = 0.0;
-    let mut avg_count = 0;
+    let mut widom_gas = Statistics::default();
+    let mut ideal_pot_gas = Statistics::default();
+    let mut widom_liquid = Statistics::default();
+    let mut ideal_pot_liquid = Statistics::default();
 
     loop  {
         frame_count += 1;
@@ -93,20 +93,18 @@ fn main() {
         let mut liquid_count = 0.0;
         let mut gas_count = 0.0;
         for i in 0..frame.num_particles {
-            if frame.rz[i] > liquid_start && frame.rz[i] < liquid_end { liquid_count += 1.0; }
+            if frame.rz[i].to_f64() > liquid_start && frame.rz[i].to_f64() < liquid_end { liquid_count += 1.0; }
             else { gas_count += 1.0; }
         }
 
         // test particle insertion
         for i in 0..insertions {
-            avg_count += 1;
-
             // liquid test partcile
             let lx = frame.box_x * rng.gen::<f64>();
             let ly = frame.box_y * rng.gen::<f64>();
             let lz = liquid_start + (liquid_height * rng.gen::<f64>());
             let widom_e_liquid = get_particle_insertion_energy(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, lx, ly, lz, frame.box_x, frame.box_y, frame.box_z, cutoff_sqr, e_shift);
-            widom_sum_liquid += (-beta*widom_e_liquid).exp();
+            widom_liquid.push((-beta*widom_e_liquid).exp());
 
             // gas test particle
             let gx = frame.box_x * rng.gen::<f64>();
@@ -116,20 +114,20 @@ fn main() {
                 gz= frame.box_z * rng.gen::<f64>();
             }
             let widom_e_gas = get_particle_insertion_energy(&frame.rx, &frame.ry, &frame.rz, frame.num_particles, gx, gy, gz, frame.box_x, frame.box_y, frame.box_z, cutoff_sqr, e_shift);
-            widom_sum_gas += (-beta*widom_e_gas).exp();
+            widom_gas.push((-beta*widom_e_gas).exp());
 
             // ideal gas potentials
-            ideal_pot_gas_sum += eval_ideal_potential(frame.temperature, frame.lj_eps, gas_volume, gas_count, tw3);
-            ideal_pot_liquid_sum += eval_ideal_potential(frame.temperature, frame.lj_eps, liquid_volume, liquid_count, tw3);
+            ideal_pot_gas.push(eval_ideal_potential(frame.temperature, frame.lj_eps, gas_volume, gas_count, tw3));
+            ideal_pot_liquid.push(eval_ideal_potential(frame.temperature, frame.lj_eps, liquid_volume, liquid_count, tw3));
 
         }
 
         // print running averages
-        if avg_count / insertions > RUN_AVG_SIZE {
-            let ideal_gas_potential = ideal_pot_gas_sum / avg_count as f64;
-            let ideal_liquid_potential = ideal_pot_liquid_sum / avg_count as f64;
-            let excess_gas_potential = -(widom_sum_gas/avg_count as f64).ln()/beta;
-            let excess_liquid_potential = -(widom_sum_liquid/avg_count as f64).ln()/beta;
+        if widom_gas.count / insertions as Step > RUN_AVG_SIZE {
+            let ideal_gas_potential = ideal_pot_gas.mean();
+            let ideal_liquid_potential = ideal_pot_liquid.mean();
+            let excess_gas_potential = -widom_gas.mean().ln()/beta;
+            let excess_liquid_potential = -widom_liquid.mean().ln()/beta;
             let gas_total = ideal_gas_potential + excess_gas_potential;
             let liquid_total = ideal_liquid_potential + excess_liquid_potential;
             println!("Frame {}\tg_ex: {:5}\tl_ex: {:5}\tg_tot: {:5}\tl_tot: {:5}\t\tnparticles: {}/{}",
@@ -138,30 +136,31 @@ fn main() {
                 liquid_total, gas_count, liquid_count);
 
             // reset averages for next round
-            avg_count = 0;
-            ideal_pot_gas_sum = 0.0;
-            ideal_pot_liquid_sum = 0.0;
-            widom_sum_gas = 0.0;
-            widom_sum_liquid = 0.0;
+            ideal_pot_gas.clear();
+            ideal_pot_liquid.clear();
+            widom_gas.clear();
+            widom_liquid.clear();
         }
 
         // jump to next frame
-        if !trj_reader.update_with_next(&mut frame) {
-            break;
+        match trj_reader.update_with_next(&mut frame) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => { eprintln!("Stopping early: {}", e); break }
         }
     }
 
 }
 
 /// calculates the energy for a hypothetic particle inserted at x,y,z
-fn get_particle_insertion_energy(rx: &[f64], ry: &[f64], rz: &[f64], num_particles: usize, x: f64, y: f64, z: f64, l_x: f64,l_y: f64, l_z: f64, cutoff_sqr: f64, e_shift: f64) -> f64 {
+fn get_particle_insertion_energy(rx: &[Real], ry: &[Real], rz: &[Real], num_particles: usize, x: f64, y: f64, z: f64, l_x: f64,l_y: f64, l_z: f64, cutoff_sqr: f64, e_shift: f64) -> f64 {
     let mut energy = 0.0;
     let half_l_x = l_x/2.0;
     let half_l_y = l_y/2.0;
     let half_l_z = l_z/2.0;
     for i in 0..num_particles {
 
-        let dist_squared = get_particle_distance_squared(x, y, z, rx[i],ry[i],rz[i], l_x, l_y, l_z, half_l_x, half_l_y, half_l_z);
+        let dist_squared = get_particle_distance_squared(x, y, z, rx[i].to_f64(), ry[i].to_f64(), rz[i].to_f64(), l_x, l_y, l_z, half_l_x, half_l_y, half_l_z);
         if dist_squared < cutoff_sqr {
             energy += eval_pair_energy(dist_squared, e_shift).0;
         }
@@ -38,14 +38,19 @@ max_z = 0
 with open(inp, "r") as infile:
     for line in infile:
 
+        # box z of the old "N ## Box: x y z" headers and of the Lattice key of extended xyz comment lines
+        if line.startswith("mclj_format") and max_z == 0:
+            max_z = float(line.split('Lattice="')[1].split('"')[0].split()[8])
+
         # get num particles from first line
-        if not line.startswith("atom") and num_particles == 0:
+        if not line.startswith("atom") and not line.startswith("mclj_format") and num_particles == 0:
             num_particles = int(line.split()[0])
-            max_z = float(line.split()[5])
+            if len(line.split()) > 1:
+                max_z = float(line.split()[5])
             print "Particles: %s" % num_particles
             print ""
 
-        if not line.startswith("atom"):
+        if not line.startswith("atom") and not line.startswith("mclj_format"):
             step_counter += 1
 
         if step_counter > skip and (step_counter == 0 or (step_counter % step_size) == 0):
@@ -0,0 +1,89 @@
+// Browser demo of the mclj sampler. Build the module with
+//   cargo build --lib --release --features wasm --target wasm32-unknown-unknown
+//   wasm-bindgen --target web --out-dir www/pkg target/wasm32-unknown-unknown/release/mclj.wasm
+// and serve this directory, e.g. with python3 -m http.server -d www
+import init, { Simulation } from "./pkg/mclj.js";
+
+const NUM_PARTICLES = 256;
+const CUTOFF = 2.5;
+
+const canvas = document.getElementById("view");
+const context = canvas.getContext("2d");
+const slider = (name) => document.getElementById(name);
+
+let simulation = null;
+
+function showError(e) {
+    document.getElementById("error").textContent = e ? String(e) : "";
+}
+
+function updateLabels() {
+    for (const name of ["temperature", "density", "displacement", "speed"]) {
+        document.getElementById(name + "_value").textContent = slider(name).value;
+    }
+}
+
+function restart() {
+    try {
+        simulation = new Simulation(NUM_PARTICLES, parseFloat(slider("density").value),
+                                    parseFloat(slider("temperature").value), CUTOFF,
+                                    Math.floor(Math.random() * 4294967295));
+        simulation.set_displacement(parseFloat(slider("displacement").value));
+        showError(null);
+    } catch (e) {
+        showError(e);
+    }
+}
+
+// projection along z, particles further back are drawn darker
+function draw() {
+    const [l_x, l_y, l_z] = simulation.box_size();
+    const positions = simulation.positions();
+    const scale = canvas.width / Math.max(l_x, l_y);
+    context.fillStyle = "white";
+    context.fillRect(0, 0, canvas.width, canvas.height);
+    for (let i = 0; i < positions.length; i += 3) {
+        const depth = positions[i + 2] / l_z;
+        const shade = Math.floor(200 * (1.0 - depth));
+        context.fillStyle = `rgb(${shade}, ${shade}, 255)`;
+        context.beginPath();
+        context.arc(positions[i] * scale, positions[i + 1] * scale, 0.5 * scale, 0, 2 * Math.PI);
+        context.fill();
+    }
+}
+
+function frame() {
+    if (simulation) {
+        simulation.run(parseInt(slider("speed").value));
+        draw();
+        document.getElementById("observables").textContent =
+            `Sweeps:      ${simulation.steps()}\n` +
+            `Energy/N:    ${(simulation.energy() / simulation.num_particles()).toFixed(3)}\n` +
+            `Pressure:    ${simulation.pressure().toFixed(3)}\n` +
+            `Acceptance:  ${(100 * simulation.acceptance()).toFixed(1)}%`;
+    }
+    requestAnimationFrame(frame);
+}
+
+async function main() {
+    await init();
+    updateLabels();
+    slider("temperature").oninput = () => {
+        updateLabels();
+        try { simulation.set_temperature(parseFloat(slider("temperature").value)); showError(null); } catch (e) { showError(e); }
+    };
+    slider("density").oninput = () => {
+        updateLabels();
+        try { simulation.set_density(parseFloat(slider("density").value)); showError(null); } catch (e) { showError(e); }
+    };
+    slider("displacement").oninput = () => {
+        updateLabels();
+        simulation.set_displacement(parseFloat(slider("displacement").value));
+    };
+    slider("speed").oninput = updateLabels;
+    document.getElementById("restart").onclick = restart;
+    restart();
+    requestAnimationFrame(frame);
+}
+
+main();